@@ -0,0 +1,565 @@
+/*
+This file builds circuits for the standard textbook quantum algorithms.
+
+Key concepts:
+1. Quantum Fourier Transform:
+   - QFT|x⟩ = 1/√N Σ_y e^{2πi·xy/N} |y⟩ with N = 2^n, the discrete Fourier
+     transform applied to amplitudes
+   - Built from n Hadamards, n(n-1)/2 controlled phases and a final reversal
+     of the qubit order by SWAPs
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_Fourier_transform
+
+2. Grover Search:
+   - A phase oracle flips the sign of the M marked states among N; each
+     iteration (oracle, then inversion about the mean) rotates the state by
+     2θ towards them, where sin θ = √(M/N)
+   - After k iterations the success probability is sin²((2k+1)θ), highest
+     near k = π/(4θ) - 1/2, i.e. about (π/4)√(N/M) oracle calls
+   - Reference: https://arxiv.org/abs/quant-ph/9605043
+
+3. Oracle Problems:
+   - A bit oracle maps |x⟩|y⟩ → |x⟩|y ⊕ f(x)⟩; with the output qubit in |−⟩
+     it kicks the phase (-1)^{f(x)} back onto |x⟩
+   - Deutsch–Jozsa decides with one query whether f is constant or balanced:
+     the input register reads all zeros exactly when f is constant
+   - Bernstein–Vazirani recovers s from f(x) = s·x mod 2 with one query
+   - Simon finds the hidden period s of a two-to-one f(x) = f(x ⊕ s); every
+     outcome y satisfies y·s = 0 mod 2, and about n runs pin s down
+   - Learn more: https://en.wikipedia.org/wiki/Deutsch%E2%80%93Jozsa_algorithm
+
+4. Phase Estimation:
+   - For U|ψ⟩ = e^{2πiφ}|ψ⟩, controlled U^{2^k} from counting qubit k write
+     Σ_x e^{2πiφx}|x⟩ onto the counting register, and the inverse QFT turns
+     it into |round(φ·2^t)⟩
+   - When φ·2^t is not an integer the nearest value is still read with
+     probability at least 4/π²
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_phase_estimation_algorithm
+
+The circuits carry no measurements, so their states can be inspected
+directly; call `measure_all()` (or measure the listed qubits) to sample them.
+Registers follow the usual convention: qubit k is bit k of a register value.
+*/
+
+use crate::ir::{Circuit, Gate, Instruction};
+use crate::transpiler::remap;
+use std::f64::consts::PI;
+
+/// Builds the QFT on `n_qubits` qubits
+pub fn qft(n_qubits: usize) -> Result<Circuit, String> {
+    if n_qubits == 0 {
+        return Err("The QFT needs at least one qubit".to_string());
+    }
+    let mut circuit = Circuit::new(n_qubits)?;
+    // The most significant qubit collects the phases of all the others
+    for j in (0..n_qubits).rev() {
+        circuit.h(j)?;
+        for k in (0..j).rev() {
+            circuit.cp(PI / (1u64 << (j - k)) as f64, k, j)?;
+        }
+    }
+    for q in 0..n_qubits / 2 {
+        circuit.swap(q, n_qubits - 1 - q)?;
+    }
+    Ok(circuit)
+}
+
+/// Builds the inverse QFT on `n_qubits` qubits
+pub fn inverse_qft(n_qubits: usize) -> Result<Circuit, String> {
+    qft(n_qubits)?.inverse()
+}
+
+/// Number of Grover iterations maximizing the chance of finding one of
+/// `n_marked` marked states among 2^`n_qubits`
+pub fn optimal_grover_iterations(n_qubits: usize, n_marked: usize) -> Result<usize, String> {
+    if n_qubits == 0 || n_qubits >= 64 {
+        return Err(format!("Cannot search over {} qubits", n_qubits));
+    }
+    let size = 1u64 << n_qubits;
+    if n_marked == 0 || n_marked as u64 > size {
+        return Err(format!(
+            "Between 1 and {} states can be marked, got {}",
+            size, n_marked
+        ));
+    }
+    let theta = (n_marked as f64 / size as f64).sqrt().asin();
+    Ok((PI / (4.0 * theta) - 0.5).round().max(0.0) as usize)
+}
+
+/// Builds a phase oracle flipping the sign of each `marked` basis state
+pub fn phase_oracle(n_qubits: usize, marked: &[usize]) -> Result<Circuit, String> {
+    if n_qubits == 0 || n_qubits > 64 {
+        return Err(format!("Cannot build an oracle on {} qubits", n_qubits));
+    }
+    let mut circuit = Circuit::new(n_qubits)?;
+    let controls: Vec<usize> = (1..n_qubits).collect();
+    for &state in marked {
+        if n_qubits < usize::BITS as usize && state >> n_qubits != 0 {
+            return Err(format!(
+                "Marked state {} does not fit in {} qubits",
+                state, n_qubits
+            ));
+        }
+        // Z on qubit 0 when the others match, with qubit 0 flipped for a 0 bit
+        let flip = state & 1 == 0;
+        if flip {
+            circuit.x(0)?;
+        }
+        if controls.is_empty() {
+            circuit.z(0)?;
+        } else {
+            circuit.multi_controlled_on(Gate::Z, &controls, (state >> 1) as u64, &[0])?;
+        }
+        if flip {
+            circuit.x(0)?;
+        }
+    }
+    Ok(circuit)
+}
+
+/// Builds Grover search over qubits 0..`n_qubits` with the optimal number
+/// of iterations for `n_marked` marked states
+///
+/// `oracle` must flip the sign of the marked states; it may be wider than
+/// the search register if it needs ancillas, which must start and end in |0⟩.
+pub fn grover(n_qubits: usize, oracle: &Circuit, n_marked: usize) -> Result<Circuit, String> {
+    let iterations = optimal_grover_iterations(n_qubits, n_marked)?;
+    grover_with_iterations(n_qubits, oracle, iterations)
+}
+
+/// Builds Grover search with a fixed number of iterations
+pub fn grover_with_iterations(
+    n_qubits: usize,
+    oracle: &Circuit,
+    iterations: usize,
+) -> Result<Circuit, String> {
+    if n_qubits == 0 || oracle.n_qubits() < n_qubits {
+        return Err(format!(
+            "A {}-qubit oracle cannot search over {} qubits",
+            oracle.n_qubits(),
+            n_qubits
+        ));
+    }
+    let register: Vec<usize> = (0..n_qubits).collect();
+    let mut circuit = Circuit::new(oracle.n_qubits())?;
+    for &q in &register {
+        circuit.h(q)?;
+    }
+    for _ in 0..iterations {
+        circuit.append(oracle)?;
+        // Inversion about the mean: H^n (I - 2|0⟩⟨0|) H^n
+        for &q in &register {
+            circuit.h(q)?.x(q)?;
+        }
+        let (&target, controls) = register.split_last().unwrap();
+        if controls.is_empty() {
+            circuit.z(target)?;
+        } else {
+            circuit.multi_controlled(Gate::Z, controls, &[target])?;
+        }
+        for &q in &register {
+            circuit.x(q)?.h(q)?;
+        }
+    }
+    Ok(circuit)
+}
+
+/// Builds the bit oracle |x⟩|y⟩ → |x⟩|y ⊕ f(x)⟩ of a function on `n_inputs`
+/// bits from its truth table, with the output on qubit `n_inputs`
+///
+/// The oracle has one multi-controlled X per input with f(x) = 1, which is
+/// fine for teaching-sized inputs.
+pub fn boolean_oracle<F: Fn(usize) -> bool>(n_inputs: usize, f: F) -> Result<Circuit, String> {
+    if n_inputs == 0 || n_inputs > 20 {
+        return Err(format!(
+            "Truth-table oracles need between 1 and 20 inputs, got {}",
+            n_inputs
+        ));
+    }
+    let mut circuit = Circuit::new(n_inputs + 1)?;
+    let inputs: Vec<usize> = (0..n_inputs).collect();
+    for x in (0..1usize << n_inputs).filter(|&x| f(x)) {
+        circuit.multi_controlled_on(Gate::X, &inputs, x as u64, &[n_inputs])?;
+    }
+    Ok(circuit)
+}
+
+/// Wraps a bit oracle on `n_inputs` inputs in a single phase-kickback query:
+/// H on the inputs, the oracle with its output qubit in |−⟩, H again
+fn kickback_query(n_inputs: usize, oracle: &Circuit) -> Result<Circuit, String> {
+    let output = n_inputs;
+    let mut circuit = Circuit::new(oracle.n_qubits())?;
+    circuit.x(output)?.h(output)?;
+    for q in 0..n_inputs {
+        circuit.h(q)?;
+    }
+    circuit.append(oracle)?;
+    for q in 0..n_inputs {
+        circuit.h(q)?;
+    }
+    // Return the output qubit to |0⟩
+    circuit.h(output)?.x(output)?;
+    Ok(circuit)
+}
+
+/// Builds Deutsch–Jozsa for a bit oracle on `n_inputs` inputs with its
+/// output on qubit `n_inputs` (see `boolean_oracle`)
+///
+/// Qubits 0..`n_inputs` end in |0...0⟩ when f is constant and never do when f
+/// is balanced.
+pub fn deutsch_jozsa(n_inputs: usize, oracle: &Circuit) -> Result<Circuit, String> {
+    if n_inputs == 0 || oracle.n_qubits() <= n_inputs {
+        return Err(format!(
+            "A {}-qubit oracle has no output qubit after {} inputs",
+            oracle.n_qubits(),
+            n_inputs
+        ));
+    }
+    kickback_query(n_inputs, oracle)
+}
+
+/// Builds Bernstein–Vazirani for f(x) = `secret`·x mod 2 on `n_inputs` bits
+///
+/// Qubits 0..`n_inputs` end in |secret⟩.
+pub fn bernstein_vazirani(n_inputs: usize, secret: u64) -> Result<Circuit, String> {
+    check_secret(n_inputs, secret)?;
+    let mut oracle = Circuit::new(n_inputs + 1)?;
+    for q in (0..n_inputs).filter(|q| secret >> q & 1 == 1) {
+        oracle.cx(q, n_inputs)?;
+    }
+    kickback_query(n_inputs, &oracle)
+}
+
+/// Builds one query of Simon's algorithm for a two-to-one function with
+/// period `secret` on `n_inputs` bits
+///
+/// The function's value goes to qubits `n_inputs..2·n_inputs`; measuring
+/// qubits 0..`n_inputs` gives a uniformly random y with y·secret = 0 mod 2.
+pub fn simon(n_inputs: usize, secret: u64) -> Result<Circuit, String> {
+    check_secret(n_inputs, secret)?;
+    let mut circuit = Circuit::new(2 * n_inputs)?;
+    for q in 0..n_inputs {
+        circuit.h(q)?;
+    }
+    // f(x) = x, with the lowest set bit of s deciding whether s is added;
+    // then f(x) = f(x ⊕ s)
+    for q in 0..n_inputs {
+        circuit.cx(q, n_inputs + q)?;
+    }
+    if secret != 0 {
+        let pivot = secret.trailing_zeros() as usize;
+        for q in (0..n_inputs).filter(|q| secret >> q & 1 == 1) {
+            circuit.cx(pivot, n_inputs + q)?;
+        }
+    }
+    for q in 0..n_inputs {
+        circuit.h(q)?;
+    }
+    Ok(circuit)
+}
+
+fn check_secret(n_inputs: usize, secret: u64) -> Result<(), String> {
+    if n_inputs == 0 || n_inputs > 32 {
+        return Err(format!(
+            "Oracle problems need between 1 and 32 input bits, got {}",
+            n_inputs
+        ));
+    }
+    if secret >> n_inputs != 0 {
+        return Err(format!(
+            "Secret {} does not fit in {} bits",
+            secret, n_inputs
+        ));
+    }
+    Ok(())
+}
+
+/// Returns `instruction` with `control` added as an extra control on |1⟩
+fn add_control(instruction: &Instruction, control: usize) -> Result<Instruction, String> {
+    Ok(match instruction {
+        Instruction::Gate { gate, qubits } => Instruction::MultiControlled {
+            gate: gate.clone(),
+            controls: vec![control],
+            control_state: 1,
+            targets: qubits.clone(),
+        },
+        Instruction::Controlled {
+            gate,
+            control: inner,
+            target,
+        } => Instruction::MultiControlled {
+            gate: gate.clone(),
+            controls: vec![control, *inner],
+            control_state: 0b11,
+            targets: vec![*target],
+        },
+        Instruction::MultiControlled {
+            gate,
+            controls,
+            control_state,
+            targets,
+        } => {
+            if controls.len() >= 64 {
+                return Err("Cannot add a control to a gate with 64 controls".to_string());
+            }
+            Instruction::MultiControlled {
+                gate: gate.clone(),
+                controls: std::iter::once(control)
+                    .chain(controls.iter().copied())
+                    .collect(),
+                control_state: control_state << 1 | 1,
+                targets: targets.clone(),
+            }
+        }
+        Instruction::Barrier { .. } => instruction.clone(),
+        _ => {
+            return Err(
+                "Phase estimation needs a unitary without measurements, resets or conditions"
+                    .to_string(),
+            )
+        }
+    })
+}
+
+/// Builds phase estimation of `unitary` with `n_counting` counting qubits
+///
+/// The counting register is qubits 0..`n_counting` and `unitary` acts on the
+/// qubits after it, which `preparation` first puts into an eigenstate. For
+/// U|ψ⟩ = e^{2πiφ}|ψ⟩ the counting register then reads round(φ·2^t).
+/// Controlled powers are built by repetition, so t should stay small.
+pub fn phase_estimation(
+    n_counting: usize,
+    unitary: &Circuit,
+    preparation: &Circuit,
+) -> Result<Circuit, String> {
+    if n_counting == 0 || n_counting > 16 {
+        return Err(format!(
+            "Phase estimation needs between 1 and 16 counting qubits, got {}",
+            n_counting
+        ));
+    }
+    if preparation.n_qubits() > unitary.n_qubits() {
+        return Err(format!(
+            "A {}-qubit preparation does not fit the {}-qubit unitary",
+            preparation.n_qubits(),
+            unitary.n_qubits()
+        ));
+    }
+
+    let shift = |q: usize| q + n_counting;
+    let mut circuit = Circuit::new(n_counting + unitary.n_qubits())?;
+    for instruction in preparation.instructions() {
+        circuit.push(remap(instruction, &shift))?;
+    }
+    for q in 0..n_counting {
+        circuit.h(q)?;
+    }
+    for k in 0..n_counting {
+        let controlled = unitary
+            .instructions()
+            .iter()
+            .map(|instruction| add_control(&remap(instruction, &shift), k))
+            .collect::<Result<Vec<_>, _>>()?;
+        for _ in 0..1u64 << k {
+            for instruction in &controlled {
+                circuit.push(instruction.clone())?;
+            }
+        }
+    }
+    circuit.append(&inverse_qft(n_counting)?)?;
+    Ok(circuit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::circuit::QuantumCircuit;
+    use crate::ir::equivalent;
+    use approx::assert_relative_eq;
+    use nalgebra::Complex;
+
+    /// Probability that qubits 0..`width` hold `value`
+    fn register_probability(circuit: &Circuit, width: usize, value: usize) -> f64 {
+        let mut backend = QuantumCircuit::new(circuit.n_qubits()).unwrap();
+        backend.run(circuit).unwrap();
+        let mask = (1 << width) - 1;
+        backend
+            .get_state()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| index & mask == value)
+            .map(|(_, amplitude)| amplitude.norm_sqr())
+            .sum()
+    }
+
+    #[test]
+    fn test_qft_matches_dft() {
+        let n = 3;
+        let dim = 1 << n;
+        let unitary = qft(n).unwrap().unitary().unwrap();
+        for y in 0..dim {
+            for x in 0..dim {
+                let phase = 2.0 * PI * (x * y) as f64 / dim as f64;
+                let expected = Complex::new(phase.cos(), phase.sin()) / (dim as f64).sqrt();
+                assert_relative_eq!(
+                    (unitary[(y, x)] - expected).norm_sqr(),
+                    0.0,
+                    epsilon = 1e-24
+                );
+            }
+        }
+
+        let mut round_trip = qft(4).unwrap();
+        round_trip.append(&inverse_qft(4).unwrap()).unwrap();
+        assert!(equivalent(&round_trip, &Circuit::new(4).unwrap(), 1e-10).unwrap());
+        assert!(qft(0).is_err());
+    }
+
+    #[test]
+    fn test_grover_single_marked_state() {
+        let n = 5;
+        let marked = 0b10110;
+        let iterations = optimal_grover_iterations(n, 1).unwrap();
+        assert_eq!(iterations, 4);
+
+        let oracle = phase_oracle(n, &[marked]).unwrap();
+        let circuit = grover(n, &oracle, 1).unwrap();
+        let theta = (1.0 / 32.0_f64).sqrt().asin();
+        let expected = ((2 * iterations + 1) as f64 * theta).sin().powi(2);
+        let probability = register_probability(&circuit, n, marked);
+        assert_relative_eq!(probability, expected, epsilon = 1e-10);
+        assert!(probability > 0.99);
+
+        // Too many iterations overshoot the marked state
+        let overshoot = grover_with_iterations(n, &oracle, 2 * iterations).unwrap();
+        assert!(register_probability(&overshoot, n, marked) < 0.5);
+    }
+
+    #[test]
+    fn test_grover_multiple_marked_states() {
+        let n = 4;
+        let marked = [0b0011, 0b1100, 0b0101];
+        let oracle = phase_oracle(n, &marked).unwrap();
+        let circuit = grover(n, &oracle, marked.len()).unwrap();
+        let success: f64 = marked
+            .iter()
+            .map(|&m| register_probability(&circuit, n, m))
+            .sum();
+        let theta = (3.0 / 16.0_f64).sqrt().asin();
+        let iterations = optimal_grover_iterations(n, 3).unwrap();
+        assert_eq!(iterations, 1);
+        assert_relative_eq!(
+            success,
+            ((2 * iterations + 1) as f64 * theta).sin().powi(2),
+            epsilon = 1e-10
+        );
+        assert!(success > 0.9);
+
+        // A one-qubit search marks |1⟩ with a bare Z
+        let circuit = grover(1, &phase_oracle(1, &[1]).unwrap(), 1).unwrap();
+        assert_relative_eq!(register_probability(&circuit, 1, 1), 0.5, epsilon = 1e-10);
+
+        assert!(optimal_grover_iterations(3, 0).is_err());
+        assert!(optimal_grover_iterations(3, 9).is_err());
+        assert!(phase_oracle(2, &[4]).is_err());
+        assert!(grover(4, &phase_oracle(3, &[1]).unwrap(), 1).is_err());
+    }
+
+    #[test]
+    fn test_deutsch_jozsa() {
+        let n = 4;
+        let constant = [
+            boolean_oracle(n, |_| false).unwrap(),
+            boolean_oracle(n, |_| true).unwrap(),
+        ];
+        for oracle in &constant {
+            let circuit = deutsch_jozsa(n, oracle).unwrap();
+            assert_relative_eq!(register_probability(&circuit, n, 0), 1.0, epsilon = 1e-10);
+        }
+
+        let balanced = [
+            boolean_oracle(n, |x| x.count_ones() % 2 == 1).unwrap(),
+            boolean_oracle(n, |x| x >= 8).unwrap(),
+            boolean_oracle(n, |x| [0, 3, 5, 6, 9, 10, 12, 15].contains(&x)).unwrap(),
+        ];
+        for oracle in &balanced {
+            let circuit = deutsch_jozsa(n, oracle).unwrap();
+            assert_relative_eq!(register_probability(&circuit, n, 0), 0.0, epsilon = 1e-10);
+        }
+
+        assert!(deutsch_jozsa(4, &Circuit::new(4).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_bernstein_vazirani() {
+        for secret in [0, 0b1, 0b1011, 0b11111] {
+            let circuit = bernstein_vazirani(5, secret).unwrap();
+            // The output qubit is returned to |0⟩ too
+            assert_relative_eq!(
+                register_probability(&circuit, 6, secret as usize),
+                1.0,
+                epsilon = 1e-10
+            );
+        }
+        assert!(bernstein_vazirani(3, 0b1000).is_err());
+    }
+
+    #[test]
+    fn test_simon() {
+        let n = 4;
+        let secret = 0b0110;
+        let circuit = simon(n, secret).unwrap();
+        for y in 0..1 << n {
+            let probability = register_probability(&circuit, n, y);
+            if (y as u64 & secret).count_ones() & 1 == 0 {
+                assert_relative_eq!(probability, 1.0 / 8.0, epsilon = 1e-10);
+            } else {
+                assert_relative_eq!(probability, 0.0, epsilon = 1e-10);
+            }
+        }
+
+        // With s = 0 the function is one-to-one and every y is equally likely
+        let circuit = simon(3, 0).unwrap();
+        assert_relative_eq!(
+            register_probability(&circuit, 3, 5),
+            1.0 / 8.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn test_phase_estimation() {
+        // P(2π·5/16)|1⟩ = e^{2πi·5/16}|1⟩ is read exactly with 4 qubits
+        let mut unitary = Circuit::new(1).unwrap();
+        unitary.p(2.0 * PI * 5.0 / 16.0, 0).unwrap();
+        let mut one = Circuit::new(1).unwrap();
+        one.x(0).unwrap();
+        let circuit = phase_estimation(4, &unitary, &one).unwrap();
+        assert_relative_eq!(register_probability(&circuit, 4, 5), 1.0, epsilon = 1e-10);
+
+        // A phase of 1/3 is not a 5-bit fraction; 11/32 is the nearest
+        unitary = Circuit::new(1).unwrap();
+        unitary.p(2.0 * PI / 3.0, 0).unwrap();
+        let circuit = phase_estimation(5, &unitary, &one).unwrap();
+        assert!(register_probability(&circuit, 5, 11) > 4.0 / (PI * PI));
+
+        // Controlled-phase on |11⟩ with a multi-instruction unitary
+        let mut unitary = Circuit::new(2).unwrap();
+        unitary
+            .cp(2.0 * PI * 3.0 / 8.0, 0, 1)
+            .unwrap()
+            .h(0)
+            .unwrap()
+            .h(0)
+            .unwrap();
+        let mut both = Circuit::new(2).unwrap();
+        both.x(0).unwrap().x(1).unwrap();
+        let circuit = phase_estimation(3, &unitary, &both).unwrap();
+        assert_relative_eq!(register_probability(&circuit, 3, 3), 1.0, epsilon = 1e-10);
+
+        let mut measured = Circuit::new(1).unwrap();
+        measured.measure(0).unwrap();
+        assert!(phase_estimation(3, &measured, &Circuit::new(1).unwrap()).is_err());
+        assert!(phase_estimation(3, &unitary, &Circuit::new(3).unwrap()).is_err());
+    }
+}
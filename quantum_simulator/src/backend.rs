@@ -0,0 +1,687 @@
+/*
+This file defines how recorded circuits are executed.
+
+Key concepts:
+1. Backends:
+   - A `Backend` owns a quantum state and knows how to apply one
+     `Instruction` to it
+   - The same `Circuit` can be run on any backend, and re-run as often as
+     needed, because the circuit itself is never modified
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_circuit
+
+2. State-vector backend:
+   - `QuantumCircuit` (circuit.rs) stores the full 2^n amplitude vector and is
+     the first backend
+   - Gates from the `Gate` enum are handed to its generic `apply_*` methods
+     through their matrices
+
+3. Classical memory:
+   - `execute()` keeps one bit per classical bit of the circuit, written by
+     `Measure` and read by `Conditional` instructions
+   - `run()` returns the final classical bits, with `result[i]` holding
+     classical bit i
+   - Named registers (see ir.rs) are views of these bits, so conditions on a
+     register are conditions on its bits
+
+4. Shots and randomness:
+   - Every measurement draws from a random number generator that callers can
+     supply through `execute_with_rng()` / `run_with_rng()`
+   - `run_shots()` seeds its own generator, so the same seed always gives the
+     same counts
+   - Circuits that only measure at the end are simulated once and sampled
+     from the final probabilities instead of being re-run for every shot
+*/
+
+use crate::circuit::QuantumCircuit;
+use crate::error::QuantumError;
+use crate::gates::{MultiQubitGate, QuantumGate};
+use crate::ir::{Circuit, Instruction};
+use nalgebra::{Complex, DMatrix, DVector, Matrix2, Vector2};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::collections::BTreeMap;
+
+/// Measurement counts keyed by bitstring, with classical bit 0 as the
+/// rightmost character
+pub type Counts = BTreeMap<String, usize>;
+
+/// A simulator that can execute circuit instructions
+pub trait Backend {
+    /// Number of qubits held by the backend
+    fn n_qubits(&self) -> usize;
+
+    /// Returns the backend to |00...0⟩
+    fn reset(&mut self);
+
+    /// Applies one instruction, returning the outcome if it was a measurement
+    ///
+    /// Measurements draw their randomness from `rng`.
+    fn apply(
+        &mut self,
+        instruction: &Instruction,
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<bool>, QuantumError>;
+
+    /// Probability of each basis state, if the backend can compute them
+    ///
+    /// `run_shots` uses this to sample circuits whose measurements all come
+    /// at the end without simulating every shot.
+    fn probabilities(&self) -> Option<Vec<f64>> {
+        None
+    }
+
+    /// Applies every instruction of `circuit` to the current state
+    fn execute(&mut self, circuit: &Circuit) -> Result<Vec<bool>, QuantumError> {
+        self.execute_with_rng(circuit, &mut rand::thread_rng())
+    }
+
+    /// Like `execute`, drawing measurement outcomes from `rng`
+    fn execute_with_rng(
+        &mut self,
+        circuit: &Circuit,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<bool>, QuantumError> {
+        execute_with(self, circuit, rng, |_, _, outcome, _| Ok(outcome))
+    }
+
+    /// Resets the backend and executes `circuit` from |00...0⟩
+    fn run(&mut self, circuit: &Circuit) -> Result<Vec<bool>, QuantumError> {
+        self.reset();
+        self.execute(circuit)
+    }
+
+    /// Like `run`, drawing measurement outcomes from `rng`
+    fn run_with_rng(
+        &mut self,
+        circuit: &Circuit,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<bool>, QuantumError> {
+        self.reset();
+        self.execute_with_rng(circuit, rng)
+    }
+
+    /// Runs `circuit` `shots` times and counts the classical outcomes
+    ///
+    /// Results are reproducible for a given `seed`. When every measurement
+    /// comes after the last gate, the circuit is simulated once and the
+    /// shots are sampled from the final state.
+    fn run_shots(
+        &mut self,
+        circuit: &Circuit,
+        shots: usize,
+        seed: u64,
+    ) -> Result<Counts, QuantumError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut counts = Counts::new();
+
+        let instructions = circuit.instructions();
+        let first_measure = instructions
+            .iter()
+            .position(|i| matches!(i, Instruction::Measure { .. }))
+            .unwrap_or(instructions.len());
+        let (body, tail) = instructions.split_at(first_measure);
+        // Resets and conditional measurements collapse the state, so one
+        // simulated run is not representative of every shot
+        let terminal = tail
+            .iter()
+            .all(|i| matches!(i, Instruction::Measure { .. } | Instruction::Barrier { .. }))
+            && !body.iter().any(collapses);
+
+        if terminal {
+            let mut prefix = circuit.empty_like();
+            for instruction in body {
+                prefix
+                    .push(instruction.clone())
+                    .expect("a prefix of a valid circuit is valid");
+            }
+            self.run_with_rng(&prefix, &mut rng)?;
+
+            if let Some(probabilities) = self.probabilities() {
+                let cumulative: Vec<f64> = probabilities
+                    .iter()
+                    .scan(0.0, |sum, p| {
+                        *sum += p;
+                        Some(*sum)
+                    })
+                    .collect();
+                let total = cumulative.last().copied().unwrap_or(0.0);
+
+                for _ in 0..shots {
+                    let random = rng.gen::<f64>() * total;
+                    let index = cumulative
+                        .partition_point(|&c| c <= random)
+                        .min(cumulative.len() - 1);
+                    let mut clbits = vec![false; circuit.n_clbits()];
+                    for instruction in tail {
+                        if let Instruction::Measure { qubit, clbit } = instruction {
+                            clbits[*clbit] = index & (1 << qubit) != 0;
+                        }
+                    }
+                    *counts.entry(bitstring(&clbits)).or_insert(0) += 1;
+                }
+                return Ok(counts);
+            }
+        }
+
+        for _ in 0..shots {
+            let clbits = self.run_with_rng(circuit, &mut rng)?;
+            *counts.entry(bitstring(&clbits)).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+}
+
+/// True for instructions that may measure or reset a qubit
+fn collapses(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Measure { .. } | Instruction::Reset { .. } => true,
+        Instruction::Conditional { instruction, .. } => collapses(instruction),
+        _ => false,
+    }
+}
+
+/// Formats classical bits with bit 0 as the rightmost character
+fn bitstring(clbits: &[bool]) -> String {
+    clbits
+        .iter()
+        .rev()
+        .map(|&bit| if bit { '1' } else { '0' })
+        .collect()
+}
+
+/// Runs `circuit` on `backend`, calling `after` once every executed
+/// instruction has been applied
+///
+/// `after` receives the instruction (with any condition already resolved), its
+/// measurement outcome and the random number generator, and returns the
+/// outcome to store in classical memory. This is the hook noisy execution
+/// uses to add errors, so `after` may report errors of its own type `E`.
+pub(crate) fn execute_with<B, F, E>(
+    backend: &mut B,
+    circuit: &Circuit,
+    rng: &mut dyn RngCore,
+    mut after: F,
+) -> Result<Vec<bool>, E>
+where
+    B: Backend + ?Sized,
+    F: FnMut(&mut B, &Instruction, Option<bool>, &mut dyn RngCore) -> Result<Option<bool>, E>,
+    E: From<QuantumError>,
+{
+    if circuit.n_qubits() != backend.n_qubits() {
+        return Err(QuantumError::DimensionMismatch {
+            what: "qubits of the circuit and the backend".to_string(),
+            expected: backend.n_qubits(),
+            found: circuit.n_qubits(),
+        }
+        .into());
+    }
+    if !circuit.is_bound() {
+        let names = circuit
+            .parameters()
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        return Err(QuantumError::UnboundParameters(names).into());
+    }
+
+    let mut clbits = vec![false; circuit.n_clbits()];
+    for instruction in circuit.instructions() {
+        let instruction = match instruction {
+            Instruction::Conditional {
+                clbits: condition,
+                value,
+                instruction,
+            } => {
+                let register = condition
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &c)| clbits[c])
+                    .fold(0u64, |acc, (i, _)| acc | (1 << i));
+                if register != *value {
+                    continue;
+                }
+                instruction.as_ref()
+            }
+            other => other,
+        };
+
+        let outcome = backend.apply(instruction, rng)?;
+        if let Some(outcome) = after(backend, instruction, outcome, rng)? {
+            if let Instruction::Measure { clbit, .. } = instruction {
+                clbits[*clbit] = outcome;
+            }
+        }
+    }
+    Ok(clbits)
+}
+
+/// Single-qubit gate known only by its matrix
+struct Matrix2Gate(Matrix2<Complex<f64>>);
+
+impl QuantumGate for Matrix2Gate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let result = self.0 * Vector2::new(state[0], state[1]);
+        state[0] = result[0];
+        state[1] = result[1];
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        self.0
+    }
+
+    fn name(&self) -> &'static str {
+        "Matrix"
+    }
+}
+
+/// Multi-qubit gate known only by its matrix
+pub(crate) struct MatrixGate(pub(crate) DMatrix<Complex<f64>>);
+
+impl MultiQubitGate for MatrixGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        self.0.clone()
+    }
+
+    fn n_qubits(&self) -> usize {
+        self.0.nrows().trailing_zeros() as usize
+    }
+
+    fn name(&self) -> &'static str {
+        "Matrix"
+    }
+}
+
+impl Backend for QuantumCircuit {
+    fn n_qubits(&self) -> usize {
+        QuantumCircuit::n_qubits(self)
+    }
+
+    fn reset(&mut self) {
+        QuantumCircuit::reset(self)
+    }
+
+    fn apply(
+        &mut self,
+        instruction: &Instruction,
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<bool>, QuantumError> {
+        match instruction {
+            Instruction::Gate { gate, qubits } => {
+                if gate.n_qubits() == 1 {
+                    self.apply_gate(Matrix2Gate(gate.matrix2()?), qubits[0])?;
+                } else {
+                    self.apply_multi_qubit_gate(MatrixGate(gate.matrix()?), qubits)?;
+                }
+                Ok(None)
+            }
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => {
+                self.apply_controlled_gate(Matrix2Gate(gate.matrix2()?), *control, *target)?;
+                Ok(None)
+            }
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                targets,
+            } => {
+                self.apply_controlled_matrix(&gate.matrix()?, controls, *control_state, targets)?;
+                Ok(None)
+            }
+            Instruction::Measure { qubit, .. } => Ok(Some(self.measure_with(*qubit, rng)?)),
+            Instruction::Reset { qubit } => {
+                self.reset_qubit_with(*qubit, rng)?;
+                Ok(None)
+            }
+            Instruction::Barrier { .. } => Ok(None),
+            Instruction::Conditional { .. } => Err(QuantumError::Unsupported(
+                "applying a conditional outside Backend::execute".to_string(),
+            )),
+        }
+    }
+
+    fn probabilities(&self) -> Option<Vec<f64>> {
+        Some(self.get_state().iter().map(|x| x.norm_sqr()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_run_bell_circuit() {
+        let mut bell = Circuit::new(2).unwrap();
+        bell.h(0).unwrap().cx(0, 1).unwrap().measure_all().unwrap();
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        for _ in 0..10 {
+            let outcomes = backend.run(&bell).unwrap();
+            assert_eq!(outcomes.len(), 2);
+            assert_eq!(outcomes[0], outcomes[1]);
+        }
+    }
+
+    #[test]
+    fn test_controlled_instruction() {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit
+            .x(1)
+            .unwrap()
+            .controlled(crate::ir::Gate::X, 1, 0)
+            .unwrap();
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        backend.run(&circuit).unwrap();
+        assert_relative_eq!(backend.get_probability(0b11).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_conditional_on_measured_bit() {
+        // Measure |1⟩ into c0, then flip q1 only if c0 == 1
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.x(0).unwrap().measure(0).unwrap();
+        circuit
+            .conditional(
+                &[0],
+                1,
+                Instruction::Gate {
+                    gate: crate::ir::Gate::X,
+                    qubits: vec![1],
+                },
+            )
+            .unwrap()
+            .measure(1)
+            .unwrap();
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert_eq!(backend.run(&circuit).unwrap(), vec![true, true]);
+
+        let mut skipped = Circuit::new(2).unwrap();
+        skipped
+            .conditional(
+                &[0],
+                1,
+                Instruction::Gate {
+                    gate: crate::ir::Gate::X,
+                    qubits: vec![1],
+                },
+            )
+            .unwrap()
+            .measure(1)
+            .unwrap();
+        assert_eq!(backend.run(&skipped).unwrap(), vec![false, false]);
+    }
+
+    #[test]
+    fn test_execute_continues_from_current_state() {
+        let mut flip = Circuit::new(1).unwrap();
+        flip.x(0).unwrap();
+
+        let mut backend = QuantumCircuit::new(1).unwrap();
+        backend.execute(&flip).unwrap();
+        backend.execute(&flip).unwrap();
+        assert_relative_eq!(backend.get_probability(0).unwrap(), 1.0);
+        assert!(backend.run(&Circuit::new(2).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_run_shots_counts_bell_outcomes() {
+        let mut bell = Circuit::new(2).unwrap();
+        bell.h(0).unwrap().cx(0, 1).unwrap().measure_all().unwrap();
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        let counts = backend.run_shots(&bell, 1000, 7).unwrap();
+        assert_eq!(counts.values().sum::<usize>(), 1000);
+        assert!(counts.keys().all(|k| k == "00" || k == "11"));
+        assert!(counts["00"] > 400 && counts["11"] > 400);
+        assert_eq!(backend.run_shots(&bell, 1000, 7).unwrap(), counts);
+    }
+
+    #[test]
+    fn test_run_shots_bitstring_order() {
+        // Only classical bit 0 is set, so it appears as the rightmost character
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit.x(0).unwrap().measure_all().unwrap();
+
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        let counts = backend.run_shots(&circuit, 10, 0).unwrap();
+        assert_eq!(counts.get("001"), Some(&10));
+    }
+
+    #[test]
+    fn test_run_shots_with_mid_circuit_measurement() {
+        // The conditional depends on a measurement, so every shot is simulated
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.h(0).unwrap().measure(0).unwrap();
+        circuit
+            .conditional(
+                &[0],
+                1,
+                Instruction::Gate {
+                    gate: crate::ir::Gate::X,
+                    qubits: vec![1],
+                },
+            )
+            .unwrap()
+            .measure(1)
+            .unwrap();
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        let counts = backend.run_shots(&circuit, 200, 3).unwrap();
+        assert!(counts.keys().all(|k| k == "00" || k == "11"));
+        assert_eq!(backend.run_shots(&circuit, 200, 3).unwrap(), counts);
+    }
+
+    #[test]
+    fn test_injected_rng_is_reproducible() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .h(2)
+            .unwrap()
+            .measure_all()
+            .unwrap();
+
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        let mut first = StdRng::seed_from_u64(42);
+        let mut second = StdRng::seed_from_u64(42);
+        for _ in 0..10 {
+            assert_eq!(
+                backend.run_with_rng(&circuit, &mut first).unwrap(),
+                backend.run_with_rng(&circuit, &mut second).unwrap()
+            );
+        }
+    }
+
+    /// Teleports RY(θ)|0⟩ from qubit 0 to qubit 2 with feed-forward corrections
+    fn teleportation(theta: f64) -> Circuit {
+        let mut circuit = Circuit::with_clbits(3, 0).unwrap();
+        circuit
+            .add_register("m0", 1)
+            .unwrap()
+            .add_register("m1", 1)
+            .unwrap()
+            .add_register("out", 1)
+            .unwrap();
+        circuit
+            .ry(theta, 0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .cx(1, 2)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .h(0)
+            .unwrap()
+            .measure_register(&[0], "m0")
+            .unwrap()
+            .measure_register(&[1], "m1")
+            .unwrap()
+            .c_if(
+                "m1",
+                1,
+                Instruction::Gate {
+                    gate: crate::ir::Gate::X,
+                    qubits: vec![2],
+                },
+            )
+            .unwrap()
+            .c_if(
+                "m0",
+                1,
+                Instruction::Gate {
+                    gate: crate::ir::Gate::Z,
+                    qubits: vec![2],
+                },
+            )
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_teleportation() {
+        let theta = 2.0 * std::f64::consts::PI / 3.0;
+        let circuit = teleportation(theta);
+
+        // Whatever the measurements, qubit 2 ends in RY(θ)|0⟩
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..20 {
+            backend.run_with_rng(&circuit, &mut rng).unwrap();
+            let p_one: f64 = (0..8)
+                .filter(|i| i & 0b100 != 0)
+                .map(|i| backend.get_probability(i).unwrap())
+                .sum();
+            assert_relative_eq!(p_one, 0.75, epsilon = 1e-10);
+        }
+
+        let mut measured = circuit.clone();
+        measured.measure_register(&[2], "out").unwrap();
+        let counts = backend.run_shots(&measured, 2000, 9).unwrap();
+        let ones: usize = counts
+            .iter()
+            .filter(|(bits, _)| bits.starts_with('1'))
+            .map(|(_, n)| n)
+            .sum();
+        assert!((ones as f64 / 2000.0 - 0.75).abs() < 0.05);
+        // The corrections make the output independent of the measured bits
+        assert_eq!(counts.len(), 8);
+    }
+
+    #[test]
+    fn test_repeat_until_success() {
+        // Retry a coin flip on qubit 0 until it lands on 1, then flip qubit 1
+        let mut circuit = Circuit::with_clbits(2, 0).unwrap();
+        circuit
+            .add_register("flag", 1)
+            .unwrap()
+            .add_register("out", 1)
+            .unwrap();
+        for _ in 0..6 {
+            circuit
+                .c_if("flag", 0, Instruction::Reset { qubit: 0 })
+                .unwrap()
+                .c_if(
+                    "flag",
+                    0,
+                    Instruction::Gate {
+                        gate: crate::ir::Gate::H,
+                        qubits: vec![0],
+                    },
+                )
+                .unwrap()
+                .c_if("flag", 0, Instruction::Measure { qubit: 0, clbit: 0 })
+                .unwrap();
+        }
+        circuit
+            .c_if(
+                "flag",
+                1,
+                Instruction::Gate {
+                    gate: crate::ir::Gate::X,
+                    qubits: vec![1],
+                },
+            )
+            .unwrap()
+            .measure_register(&[1], "out")
+            .unwrap();
+
+        let counts = QuantumCircuit::new(2)
+            .unwrap()
+            .run_shots(&circuit, 500, 2)
+            .unwrap();
+        // Every shot fails six times in a row with probability 1/64
+        assert!(counts.get("11").copied().unwrap_or(0) > 450);
+        assert!(counts.keys().all(|k| k == "11" || k == "00"));
+    }
+
+    #[test]
+    fn test_reset_on_every_backend() {
+        // Resetting half of a Bell pair leaves the other half random
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .reset(0)
+            .unwrap()
+            .measure_all()
+            .unwrap();
+
+        let backends: Vec<Box<dyn Backend>> = vec![
+            Box::new(QuantumCircuit::new(2).unwrap()),
+            Box::new(crate::density::DensityMatrixCircuit::new(2)),
+            Box::new(crate::stabilizer::StabilizerCircuit::new(2)),
+            Box::new(crate::mps::MpsCircuit::new(2)),
+        ];
+        for mut backend in backends {
+            let counts = backend.run_shots(&circuit, 200, 4).unwrap();
+            assert_eq!(counts.keys().collect::<Vec<_>>(), vec!["00", "10"]);
+        }
+    }
+
+    #[test]
+    fn test_apply_leaves_conditionals_to_execute() {
+        let conditional = Instruction::Conditional {
+            clbits: vec![0],
+            value: 1,
+            instruction: Box::new(Instruction::Reset { qubit: 0 }),
+        };
+        let mut backend = QuantumCircuit::new(1).unwrap();
+        let error = backend
+            .apply(&conditional, &mut rand::thread_rng())
+            .unwrap_err();
+        assert!(matches!(error, QuantumError::Unsupported(_)));
+    }
+
+    #[test]
+    fn test_execute_reports_typed_errors() {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit
+            .rx(crate::parameter::Parameter::new("t"), 0)
+            .unwrap();
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert_eq!(
+            backend.run(&circuit).unwrap_err(),
+            QuantumError::UnboundParameters(vec!["t".to_string()])
+        );
+        assert!(matches!(
+            QuantumCircuit::new(3).unwrap().run(&circuit).unwrap_err(),
+            QuantumError::DimensionMismatch {
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+    }
+}
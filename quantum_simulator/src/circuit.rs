@@ -22,7 +22,10 @@ Key concepts and implementations:
 4. Circuit Operations:
    - apply_gate(): Applies single-qubit gates
    - apply_controlled_gate(): Implements controlled operations like CNOT
+   - apply_multi_qubit_gate(): Applies a 2^k × 2^k unitary to any k qubits
+   - apply_multi_controlled_gate(): Any number of controls, each on |1⟩ or |0⟩
    - measure(): Performs quantum measurements
+   - reset_qubit(): Measures a qubit and flips it back to |0⟩
    - verify_state(): Ensures quantum state normalization
 
 5. State Management:
@@ -30,33 +33,63 @@ Key concepts and implementations:
    - Maintains state vector normalization
    - Implements state reset functionality
 
+6. Errors:
+   - Every fallible operation returns a `QuantumError` (error.rs) naming what
+     went wrong: an out-of-range or repeated qubit, a non-unitary gate, a
+     mismatched gate size, a bad control state, ...
+   - Gate matrices are checked for unitarity (U†U = I) before they touch the
+     state, so a bad custom gate cannot silently denormalise it
+
 Further Reading:
 - Quantum Computing Basics: https://quantum.country/qcvc
 - Circuit Model: https://en.wikipedia.org/wiki/Quantum_circuit
 
 The implementation uses:
 - nalgebra for linear algebra operations
+- In-place, rayon-parallel kernels from kernels.rs for gates and measurement
 - Complex numbers for quantum amplitudes
 - Random number generation for measurement outcomes
 */
 
-use crate::gates::QuantumGate;
-use nalgebra::{Complex, DVector};
+use crate::error::QuantumError;
+use crate::gates::{is_unitary, MultiQubitGate, QuantumGate, XGate};
+use crate::kernels;
+use nalgebra::{Complex, DMatrix, DVector, Matrix2};
 use rand::Rng;
 use std::f64;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QuantumCircuit {
     state: DVector<Complex<f64>>,
     n_qubits: usize,
 }
 
+/// Checks that U†U = I for a gate's matrix
+fn check_unitary(matrix: &DMatrix<Complex<f64>>, name: &str) -> Result<(), QuantumError> {
+    if !is_unitary(matrix) {
+        return Err(QuantumError::NonUnitary(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Returns a single-qubit gate's matrix after checking that it is unitary
+fn single_qubit_matrix<G: QuantumGate>(gate: &G) -> Result<Matrix2<Complex<f64>>, QuantumError> {
+    let matrix = gate.matrix();
+    check_unitary(
+        &DMatrix::from_iterator(2, 2, matrix.iter().cloned()),
+        gate.name(),
+    )?;
+    Ok(matrix)
+}
+
 impl QuantumCircuit {
     /// Creates a new quantum circuit with the specified number of qubits
     /// All qubits are initialized to |0⟩ state
-    pub fn new(n_qubits: usize) -> Self {
-        if n_qubits == 0 {
-            panic!("Number of qubits must be greater than 0");
+    ///
+    /// Fails for zero qubits or more than the state vector can index.
+    pub fn new(n_qubits: usize) -> Result<Self, QuantumError> {
+        if n_qubits == 0 || n_qubits >= usize::BITS as usize - 1 {
+            return Err(QuantumError::InvalidQubitCount(n_qubits));
         }
 
         // Create a zero vector of size 2^n_qubits
@@ -64,40 +97,35 @@ impl QuantumCircuit {
         // Initialize to |00...0⟩ state
         state[0] = Complex::new(1.0, 0.0);
 
-        QuantumCircuit { state, n_qubits }
+        Ok(QuantumCircuit { state, n_qubits })
     }
 
-    /// Applies a quantum gate to the specified target qubit
-    pub fn apply_gate<G: QuantumGate>(&mut self, gate: G, target: usize) -> Result<(), String> {
-        if target >= self.n_qubits {
-            return Err(format!(
-                "Target qubit {} is out of range for circuit with {} qubits",
-                target, self.n_qubits
-            ));
-        }
-
-        let n = self.state.len();
-        let mut new_state = DVector::from_element(n, Complex::new(0.0, 0.0));
-
-        for i in 0..n {
-            if (i & (1 << target)) != 0 {
-                continue;
+    /// Checks that `qubits` are in range and pairwise different
+    fn check_qubits(&self, qubits: &[usize]) -> Result<(), QuantumError> {
+        for (i, &qubit) in qubits.iter().enumerate() {
+            if qubit >= self.n_qubits {
+                return Err(QuantumError::QubitOutOfRange {
+                    qubit,
+                    n_qubits: self.n_qubits,
+                });
+            }
+            if qubits[..i].contains(&qubit) {
+                return Err(QuantumError::DuplicateQubit(qubit));
             }
-
-            let i1 = i | (1 << target);
-
-            // Create the 2D state vector for the target qubit
-            let mut target_state = DVector::from_vec(vec![self.state[i], self.state[i1]]);
-
-            // Apply the gate
-            gate.apply(&mut target_state);
-
-            // Update the new state
-            new_state[i] = target_state[0];
-            new_state[i1] = target_state[1];
         }
+        Ok(())
+    }
 
-        self.state = new_state;
+    /// Applies a quantum gate to the specified target qubit
+    pub fn apply_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        self.check_qubits(&[target])?;
+        let matrix = single_qubit_matrix(&gate)?;
+
+        kernels::apply_single_qubit(self.state.as_mut_slice(), &matrix, target, 0, 0);
         Ok(())
     }
 
@@ -107,78 +135,173 @@ impl QuantumCircuit {
         gate: G,
         control: usize,
         target: usize,
-    ) -> Result<(), String> {
-        if control >= self.n_qubits || target >= self.n_qubits {
-            return Err(format!(
-                "Qubit indices out of range for circuit with {} qubits",
-                self.n_qubits
-            ));
-        }
-        if control == target {
-            return Err("Control and target qubits must be different".to_string());
+    ) -> Result<(), QuantumError> {
+        self.check_qubits(&[control, target])?;
+        let matrix = single_qubit_matrix(&gate)?;
+
+        let control_mask = 1 << control;
+        kernels::apply_single_qubit(
+            self.state.as_mut_slice(),
+            &matrix,
+            target,
+            control_mask,
+            control_mask,
+        );
+        Ok(())
+    }
+
+    /// Applies a k-qubit gate to the listed target qubits
+    ///
+    /// `targets[0]` is mapped to the most significant bit of the gate matrix,
+    /// so `apply_multi_qubit_gate(CNOTGate, &[control, target])` behaves like
+    /// the textbook CNOT.
+    pub fn apply_multi_qubit_gate<G: MultiQubitGate>(
+        &mut self,
+        gate: G,
+        targets: &[usize],
+    ) -> Result<(), QuantumError> {
+        if targets.len() != gate.n_qubits() {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("{} gate targets", gate.name()),
+                expected: gate.n_qubits(),
+                found: targets.len(),
+            });
         }
+        self.check_qubits(targets)?;
+        let matrix = gate.matrix();
+        check_unitary(&matrix, gate.name())?;
 
-        let n = self.state.len();
-        let mut new_state = self.state.clone();
+        self.apply_controlled_matrix(&matrix, &[], 0, targets)
+    }
 
-        for i in 0..n {
-            if (i & (1 << control)) != 0 {
-                let mut target_state = DVector::from_vec(vec![
-                    self.state[i & !(1 << target)],
-                    self.state[i | (1 << target)],
-                ]);
+    /// Applies a single-qubit gate to `target` when every control qubit is |1⟩
+    pub fn apply_multi_controlled_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        controls: &[usize],
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        let all_ones = if controls.len() >= 64 {
+            u64::MAX
+        } else {
+            (1 << controls.len()) - 1
+        };
+        self.apply_multi_controlled_gate_with_state(gate, controls, all_ones, target)
+    }
 
-                gate.apply(&mut target_state);
+    /// Applies a single-qubit gate to `target` when the control qubits hold
+    /// `control_state`
+    ///
+    /// Bit i of `control_state` is the value required of `controls[i]`, so a
+    /// 0 bit makes that control a negative (control-on-|0⟩) control.
+    pub fn apply_multi_controlled_gate_with_state<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        controls: &[usize],
+        control_state: u64,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        let matrix = single_qubit_matrix(&gate)?;
+        let matrix = DMatrix::from_iterator(2, 2, matrix.iter().cloned());
+        self.apply_controlled_matrix(&matrix, controls, control_state, &[target])
+    }
 
-                new_state[i & !(1 << target)] = target_state[0];
-                new_state[i | (1 << target)] = target_state[1];
-            }
+    /// Applies `matrix` to `targets` on the basis states where `controls` hold
+    /// `control_state`, without building the full controlled matrix
+    pub(crate) fn apply_controlled_matrix(
+        &mut self,
+        matrix: &DMatrix<Complex<f64>>,
+        controls: &[usize],
+        control_state: u64,
+        targets: &[usize],
+    ) -> Result<(), QuantumError> {
+        let all: Vec<usize> = controls.iter().chain(targets).copied().collect();
+        self.check_qubits(&all)?;
+        if controls.len() > 64 || (controls.len() < 64 && control_state >= 1 << controls.len()) {
+            return Err(QuantumError::InvalidControlState {
+                control_state,
+                n_controls: controls.len(),
+            });
+        }
+
+        let k = targets.len();
+        let dim = 1 << k;
+        if matrix.nrows() != dim || matrix.ncols() != dim {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("rows and columns of a {}-qubit matrix", k),
+                expected: dim,
+                found: if matrix.nrows() != dim {
+                    matrix.nrows()
+                } else {
+                    matrix.ncols()
+                },
+            });
+        }
+
+        let control_mask = controls.iter().fold(0, |acc, &c| acc | (1 << c));
+        let control_value = controls
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| control_state & (1 << i) != 0)
+            .fold(0, |acc, (_, &c)| acc | (1 << c));
+
+        let state = self.state.as_mut_slice();
+        if k == 1 {
+            let matrix = Matrix2::from_iterator(matrix.iter().cloned());
+            kernels::apply_single_qubit(state, &matrix, targets[0], control_mask, control_value);
+        } else {
+            kernels::apply_multi_qubit(state, matrix, targets, control_mask, control_value);
         }
 
-        self.state = new_state;
         Ok(())
     }
 
     /// Measures the specified qubit and returns the result (0 or 1)
-    pub fn measure(&mut self, target: usize) -> Result<bool, String> {
-        if target >= self.n_qubits {
-            return Err(format!(
-                "Target qubit {} is out of range for circuit with {} qubits",
-                target, self.n_qubits
-            ));
-        }
+    pub fn measure(&mut self, target: usize) -> Result<bool, QuantumError> {
+        self.measure_with(target, &mut rand::thread_rng())
+    }
 
-        let n = self.state.len();
-        let mut prob_one = 0.0;
+    /// Measures the specified qubit, drawing the outcome from `rng`
+    pub fn measure_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<bool, QuantumError> {
+        self.check_qubits(&[target])?;
 
-        // Calculate probability of measuring |1⟩
-        for i in 0..n {
-            if (i & (1 << target)) != 0 {
-                prob_one += self.state[i].norm_sqr();
-            }
-        }
+        let prob_one = kernels::probability_of_one(self.state.as_slice(), target);
 
         // Generate random number and collapse the state
-        let mut rng = rand::thread_rng();
         let random: f64 = rng.gen();
         let result = random < prob_one;
 
-        // Collapse the state vector
-        let mut new_state = DVector::from_element(n, Complex::new(0.0, 0.0));
         let norm = if result {
             prob_one.sqrt()
         } else {
             (1.0 - prob_one).sqrt()
         };
+        kernels::collapse(self.state.as_mut_slice(), target, result, norm);
+        Ok(result)
+    }
 
-        for i in 0..n {
-            if (i & (1 << target) != 0) == result {
-                new_state[i] = self.state[i] / Complex::new(norm, 0.0);
-            }
-        }
+    /// Resets one qubit to |0⟩
+    ///
+    /// The qubit is measured and flipped when the outcome is 1, so a qubit
+    /// entangled with others leaves them in the matching collapsed state.
+    pub fn reset_qubit(&mut self, target: usize) -> Result<(), QuantumError> {
+        self.reset_qubit_with(target, &mut rand::thread_rng())
+    }
 
-        self.state = new_state;
-        Ok(result)
+    /// Like `reset_qubit`, drawing the discarded outcome from `rng`
+    pub fn reset_qubit_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<(), QuantumError> {
+        if self.measure_with(target, rng)? {
+            self.apply_gate(XGate, target)?;
+        }
+        Ok(())
     }
 
     /// Returns the current state vector
@@ -186,6 +309,12 @@ impl QuantumCircuit {
         &self.state
     }
 
+    /// Mutable access to the amplitudes, for crate-internal simulators that
+    /// update the state directly
+    pub(crate) fn state_mut(&mut self) -> &mut DVector<Complex<f64>> {
+        &mut self.state
+    }
+
     /// Verifies that the state vector is normalized
     pub fn verify_state(&self) -> bool {
         let sum: f64 = self.state.iter().map(|x| x.norm_sqr()).sum();
@@ -204,9 +333,12 @@ impl QuantumCircuit {
     }
 
     /// Returns the probability of measuring a specific basis state
-    pub fn get_probability(&self, basis_state: usize) -> Result<f64, String> {
+    pub fn get_probability(&self, basis_state: usize) -> Result<f64, QuantumError> {
         if basis_state >= self.state.len() {
-            return Err(format!("Basis state {} is out of range", basis_state));
+            return Err(QuantumError::BasisStateOutOfRange {
+                state: basis_state,
+                dimension: self.state.len(),
+            });
         }
         Ok(self.state[basis_state].norm_sqr())
     }
@@ -215,12 +347,14 @@ impl QuantumCircuit {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gates::{HadamardGate, XGate};
+    use crate::gates::{
+        CNOTGate, CPhaseGate, FredkinGate, HadamardGate, SwapGate, ToffoliGate, XGate,
+    };
     use approx::assert_relative_eq;
 
     #[test]
     fn test_new_circuit() {
-        let circuit = QuantumCircuit::new(2);
+        let circuit = QuantumCircuit::new(2).unwrap();
         assert_eq!(circuit.state.len(), 4);
         assert_eq!(circuit.state[0], Complex::new(1.0, 0.0));
         assert!(circuit.verify_state());
@@ -228,7 +362,7 @@ mod tests {
 
     #[test]
     fn test_apply_x_gate() {
-        let mut circuit = QuantumCircuit::new(1);
+        let mut circuit = QuantumCircuit::new(1).unwrap();
         circuit.apply_gate(XGate, 0).unwrap();
         assert_eq!(circuit.state[0], Complex::new(0.0, 0.0));
         assert_eq!(circuit.state[1], Complex::new(1.0, 0.0));
@@ -236,7 +370,7 @@ mod tests {
 
     #[test]
     fn test_apply_hadamard() {
-        let mut circuit = QuantumCircuit::new(1);
+        let mut circuit = QuantumCircuit::new(1).unwrap();
         circuit.apply_gate(HadamardGate, 0).unwrap();
         let sqrt_2_inv = 1.0 / (2.0_f64.sqrt());
         assert_relative_eq!(circuit.state[0].re, sqrt_2_inv, epsilon = 1e-10);
@@ -245,25 +379,202 @@ mod tests {
 
     #[test]
     fn test_measurement() {
-        let mut circuit = QuantumCircuit::new(1);
+        let mut circuit = QuantumCircuit::new(1).unwrap();
         circuit.apply_gate(HadamardGate, 0).unwrap();
         let result = circuit.measure(0).unwrap();
         assert!(circuit.verify_state());
-        assert!(result == true || result == false);
+        let collapsed = if result { 1 } else { 0 };
+        assert_relative_eq!(
+            circuit.get_probability(collapsed).unwrap(),
+            1.0,
+            epsilon = 1e-10
+        );
     }
 
     #[test]
-    #[should_panic]
     fn test_invalid_qubit_count() {
-        QuantumCircuit::new(0);
+        assert_eq!(
+            QuantumCircuit::new(0).unwrap_err(),
+            QuantumError::InvalidQubitCount(0)
+        );
+        assert!(matches!(
+            QuantumCircuit::new(usize::BITS as usize),
+            Err(QuantumError::InvalidQubitCount(_))
+        ));
+    }
+
+    /// A "gate" that scales |0⟩ by 2
+    struct Stretch;
+
+    impl QuantumGate for Stretch {
+        fn apply(&self, _state: &mut DVector<Complex<f64>>) {}
+        fn matrix(&self) -> Matrix2<Complex<f64>> {
+            Matrix2::new(
+                Complex::new(2.0, 0.0),
+                Complex::new(0.0, 0.0),
+                Complex::new(0.0, 0.0),
+                Complex::new(1.0, 0.0),
+            )
+        }
+        fn name(&self) -> &'static str {
+            "Stretch"
+        }
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let mut circuit = QuantumCircuit::new(2).unwrap();
+
+        assert_eq!(
+            circuit.apply_gate(XGate, 2).unwrap_err(),
+            QuantumError::QubitOutOfRange {
+                qubit: 2,
+                n_qubits: 2
+            }
+        );
+        assert_eq!(
+            circuit.apply_controlled_gate(XGate, 1, 1).unwrap_err(),
+            QuantumError::DuplicateQubit(1)
+        );
+        assert_eq!(
+            circuit.apply_multi_qubit_gate(CNOTGate, &[0]).unwrap_err(),
+            QuantumError::DimensionMismatch {
+                what: "CNOT gate targets".to_string(),
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            circuit.apply_gate(Stretch, 0).unwrap_err(),
+            QuantumError::NonUnitary("Stretch".to_string())
+        );
+        assert_eq!(
+            circuit.get_probability(4).unwrap_err(),
+            QuantumError::BasisStateOutOfRange {
+                state: 4,
+                dimension: 4
+            }
+        );
+        assert_eq!(
+            circuit
+                .apply_multi_controlled_gate_with_state(XGate, &[0], 0b10, 1)
+                .unwrap_err(),
+            QuantumError::InvalidControlState {
+                control_state: 0b10,
+                n_controls: 1
+            }
+        );
+
+        // Rejected operations leave the state untouched
+        assert_relative_eq!(circuit.get_probability(0).unwrap(), 1.0);
+        assert_eq!(
+            circuit.measure(3).unwrap_err().to_string(),
+            "qubit 3 is out of range for circuit with 2 qubits"
+        );
     }
 
     #[test]
     fn test_reset() {
-        let mut circuit = QuantumCircuit::new(1);
+        let mut circuit = QuantumCircuit::new(1).unwrap();
         circuit.apply_gate(XGate, 0).unwrap();
         circuit.reset();
         assert_eq!(circuit.state[0], Complex::new(1.0, 0.0));
         assert_eq!(circuit.state[1], Complex::new(0.0, 0.0));
     }
+
+    #[test]
+    fn test_cnot_with_any_control_and_target() {
+        let mut circuit = QuantumCircuit::new(3).unwrap();
+        circuit.apply_gate(XGate, 2).unwrap();
+        circuit.apply_multi_qubit_gate(CNOTGate, &[2, 0]).unwrap();
+        assert_relative_eq!(circuit.get_probability(0b101).unwrap(), 1.0);
+
+        // Reversed roles: control q0 is now |1⟩, so q1 flips
+        circuit.apply_multi_qubit_gate(CNOTGate, &[0, 1]).unwrap();
+        assert_relative_eq!(circuit.get_probability(0b111).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_swap_gate() {
+        let mut circuit = QuantumCircuit::new(3).unwrap();
+        circuit.apply_gate(XGate, 0).unwrap();
+        circuit.apply_multi_qubit_gate(SwapGate, &[0, 2]).unwrap();
+        assert_relative_eq!(circuit.get_probability(0b100).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_cphase_gate() {
+        let mut circuit = QuantumCircuit::new(2).unwrap();
+        circuit.apply_gate(XGate, 0).unwrap();
+        circuit.apply_gate(XGate, 1).unwrap();
+        circuit
+            .apply_multi_qubit_gate(CPhaseGate::new(f64::consts::PI / 2.0), &[0, 1])
+            .unwrap();
+        assert_relative_eq!(circuit.state[3].im, 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_multi_qubit_gate_rejects_bad_targets() {
+        let mut circuit = QuantumCircuit::new(2).unwrap();
+        assert!(circuit.apply_multi_qubit_gate(CNOTGate, &[0]).is_err());
+        assert!(circuit.apply_multi_qubit_gate(CNOTGate, &[0, 0]).is_err());
+        assert!(circuit.apply_multi_qubit_gate(CNOTGate, &[0, 2]).is_err());
+    }
+
+    #[test]
+    fn test_multi_controlled_x() {
+        // Flip q3 only when q0, q1 and q2 are all |1⟩
+        let mut circuit = QuantumCircuit::new(4).unwrap();
+        for q in 0..2 {
+            circuit.apply_gate(XGate, q).unwrap();
+        }
+        circuit
+            .apply_multi_controlled_gate(XGate, &[0, 1, 2], 3)
+            .unwrap();
+        assert_relative_eq!(circuit.get_probability(0b0011).unwrap(), 1.0);
+
+        circuit.apply_gate(XGate, 2).unwrap();
+        circuit
+            .apply_multi_controlled_gate(XGate, &[0, 1, 2], 3)
+            .unwrap();
+        assert_relative_eq!(circuit.get_probability(0b1111).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_negative_controls() {
+        // Control on q0 = |1⟩ and q1 = |0⟩
+        let mut circuit = QuantumCircuit::new(3).unwrap();
+        circuit.apply_gate(XGate, 0).unwrap();
+        circuit
+            .apply_multi_controlled_gate_with_state(XGate, &[0, 1], 0b01, 2)
+            .unwrap();
+        assert_relative_eq!(circuit.get_probability(0b101).unwrap(), 1.0);
+
+        assert!(circuit
+            .apply_multi_controlled_gate_with_state(XGate, &[0, 1], 0b100, 2)
+            .is_err());
+        assert!(circuit
+            .apply_multi_controlled_gate(XGate, &[0, 2], 2)
+            .is_err());
+    }
+
+    #[test]
+    fn test_toffoli_and_fredkin() {
+        let mut circuit = QuantumCircuit::new(3).unwrap();
+        circuit.apply_gate(XGate, 0).unwrap();
+        circuit.apply_gate(XGate, 2).unwrap();
+        circuit
+            .apply_multi_qubit_gate(ToffoliGate, &[0, 2, 1])
+            .unwrap();
+        assert_relative_eq!(circuit.get_probability(0b111).unwrap(), 1.0);
+
+        // Undo the Toffoli, then swap q0 and q1 under control q2: |101⟩ → |110⟩
+        circuit
+            .apply_multi_qubit_gate(ToffoliGate, &[0, 2, 1])
+            .unwrap();
+        circuit
+            .apply_multi_qubit_gate(FredkinGate, &[2, 0, 1])
+            .unwrap();
+        assert_relative_eq!(circuit.get_probability(0b110).unwrap(), 1.0);
+    }
 }
@@ -0,0 +1,467 @@
+/*
+This file implements a density-matrix simulator for mixed quantum states.
+
+Key concepts:
+1. Density Matrices:
+   - A state is described by ρ = Σ p_i |ψ_i⟩⟨ψ_i|, a 2^n × 2^n Hermitian,
+     positive matrix with trace 1
+   - Pure states are the special case ρ = |ψ⟩⟨ψ|
+   - Learn more: https://en.wikipedia.org/wiki/Density_matrix
+
+2. Gates:
+   - A unitary U acts as ρ → U ρ U†
+   - The same gate types as `QuantumCircuit` are supported
+
+3. Measurement and Reset:
+   - Measuring a qubit picks an outcome with probability Tr(P ρ) and
+     collapses ρ → P ρ P / Tr(P ρ) (Born rule)
+   - Resetting a qubit maps it to |0⟩ without looking at the outcome, which
+     generally leaves the rest of the register mixed
+
+4. Noise:
+   - Kraus channels from noise.rs are applied exactly as ρ → Σ K_i ρ K_i†
+
+5. Purity:
+   - Tr(ρ²) is 1 for pure states and 1/2^n for the maximally mixed state
+   - Reference: https://en.wikipedia.org/wiki/Purity_(quantum_mechanics)
+
+Qubit indices follow the same convention as `QuantumCircuit`: qubit k is bit
+k of the basis-state index.
+*/
+
+use crate::backend::Backend;
+use crate::error::QuantumError;
+use crate::gates::{check_size, controlled_matrix, MultiQubitGate, QuantumGate};
+use crate::ir::Instruction;
+use crate::noise::{KrausChannel, NoisyBackend};
+use nalgebra::{Complex, DMatrix, DVector, Matrix2};
+use rand::{Rng, RngCore};
+
+#[derive(Debug, Clone)]
+pub struct DensityMatrixCircuit {
+    rho: DMatrix<Complex<f64>>,
+    n_qubits: usize,
+}
+
+impl DensityMatrixCircuit {
+    /// Creates a density matrix for `n_qubits` qubits in the |00...0⟩ state
+    pub fn new(n_qubits: usize) -> Self {
+        if n_qubits == 0 {
+            panic!("Number of qubits must be greater than 0");
+        }
+
+        let dim = 1 << n_qubits;
+        let mut rho = DMatrix::from_element(dim, dim, Complex::new(0.0, 0.0));
+        rho[(0, 0)] = Complex::new(1.0, 0.0);
+
+        DensityMatrixCircuit { rho, n_qubits }
+    }
+
+    /// Creates the pure density matrix |ψ⟩⟨ψ| from a normalized state vector
+    pub fn from_state_vector(state: &DVector<Complex<f64>>) -> Result<Self, String> {
+        let dim = state.len();
+        if dim < 2 || !dim.is_power_of_two() {
+            return Err(format!("State vector length {} is not a power of two", dim));
+        }
+        let norm: f64 = state.iter().map(|x| x.norm_sqr()).sum();
+        if (norm - 1.0).abs() > 1e-10 {
+            return Err("State vector is not normalized".to_string());
+        }
+
+        Ok(DensityMatrixCircuit {
+            rho: state * state.adjoint(),
+            n_qubits: dim.trailing_zeros() as usize,
+        })
+    }
+
+    /// Checks that the targets are in range and distinct
+    fn check_targets(&self, targets: &[usize]) -> Result<(), QuantumError> {
+        for (i, &target) in targets.iter().enumerate() {
+            if target >= self.n_qubits {
+                return Err(QuantumError::QubitOutOfRange {
+                    qubit: target,
+                    n_qubits: self.n_qubits,
+                });
+            }
+            if targets[..i].contains(&target) {
+                return Err(QuantumError::DuplicateQubit(target));
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes M · ρ where M acts on `targets` (targets[0] is the most
+    /// significant bit of M's index, as in `MultiQubitGate`)
+    fn left_multiply(
+        rho: &DMatrix<Complex<f64>>,
+        matrix: &DMatrix<Complex<f64>>,
+        targets: &[usize],
+    ) -> DMatrix<Complex<f64>> {
+        let k = targets.len();
+        let dim = 1 << k;
+        let offsets: Vec<usize> = (0..dim)
+            .map(|local| {
+                targets
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| local & (1 << (k - 1 - j)) != 0)
+                    .fold(0, |acc, (_, &t)| acc | (1 << t))
+            })
+            .collect();
+        let target_mask = offsets[dim - 1];
+
+        let mut result = DMatrix::from_element(rho.nrows(), rho.ncols(), Complex::new(0.0, 0.0));
+        for col in 0..rho.ncols() {
+            for base in (0..rho.nrows()).filter(|b| b & target_mask == 0) {
+                for (row_local, row_offset) in offsets.iter().enumerate() {
+                    let mut sum = Complex::new(0.0, 0.0);
+                    for (k_local, k_offset) in offsets.iter().enumerate() {
+                        sum += matrix[(row_local, k_local)] * rho[(base | k_offset, col)];
+                    }
+                    result[(base | row_offset, col)] = sum;
+                }
+            }
+        }
+        result
+    }
+
+    /// Applies ρ → M ρ M† for an operator M on `targets`
+    fn conjugate_by(&mut self, matrix: &DMatrix<Complex<f64>>, targets: &[usize]) {
+        let left = Self::left_multiply(&self.rho, matrix, targets);
+        self.rho = Self::left_multiply(&left.adjoint(), matrix, targets).adjoint();
+    }
+
+    fn matrix2_to_dmatrix(matrix: &Matrix2<Complex<f64>>) -> DMatrix<Complex<f64>> {
+        DMatrix::from_iterator(2, 2, matrix.iter().cloned())
+    }
+
+    /// Applies a single-qubit gate to the specified target qubit
+    pub fn apply_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        self.check_targets(&[target])?;
+        self.conjugate_by(&Self::matrix2_to_dmatrix(&gate.matrix()), &[target]);
+        Ok(())
+    }
+
+    /// Applies a single-qubit gate to `target` when `control` is |1⟩
+    pub fn apply_controlled_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        control: usize,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        self.check_targets(&[control, target])?;
+
+        let mut matrix = DMatrix::identity(4, 4);
+        matrix
+            .slice_mut((2, 2), (2, 2))
+            .copy_from(&Self::matrix2_to_dmatrix(&gate.matrix()));
+        self.conjugate_by(&matrix, &[control, target]);
+        Ok(())
+    }
+
+    /// Applies a k-qubit gate to the listed target qubits
+    pub fn apply_multi_qubit_gate<G: MultiQubitGate>(
+        &mut self,
+        gate: G,
+        targets: &[usize],
+    ) -> Result<(), QuantumError> {
+        if targets.len() != gate.n_qubits() {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("{} gate targets", gate.name()),
+                expected: gate.n_qubits(),
+                found: targets.len(),
+            });
+        }
+        self.check_targets(targets)?;
+
+        let matrix = gate.matrix();
+        check_size(&matrix, targets.len(), gate.name())?;
+        self.conjugate_by(&matrix, targets);
+        Ok(())
+    }
+
+    /// Probability that measuring `target` gives |1⟩
+    fn probability_one(&self, target: usize) -> f64 {
+        (0..self.rho.nrows())
+            .filter(|i| i & (1 << target) != 0)
+            .map(|i| self.rho[(i, i)].re)
+            .sum()
+    }
+
+    /// Measures the specified qubit and collapses ρ according to the outcome
+    pub fn measure(&mut self, target: usize) -> Result<bool, QuantumError> {
+        self.measure_with(target, &mut rand::thread_rng())
+    }
+
+    /// Measures the specified qubit, drawing the outcome from `rng`
+    pub fn measure_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<bool, QuantumError> {
+        self.check_targets(&[target])?;
+
+        let prob_one = self.probability_one(target);
+        let random: f64 = rng.gen();
+        let result = random < prob_one;
+
+        // Keep only the block where the target qubit matches the outcome
+        let norm = if result { prob_one } else { 1.0 - prob_one };
+        let keep = |i: usize| (i & (1 << target) != 0) == result;
+        let dim = self.rho.nrows();
+        for i in 0..dim {
+            for j in 0..dim {
+                self.rho[(i, j)] = if keep(i) && keep(j) {
+                    self.rho[(i, j)] / Complex::new(norm, 0.0)
+                } else {
+                    Complex::new(0.0, 0.0)
+                };
+            }
+        }
+        Ok(result)
+    }
+
+    /// Resets one qubit to |0⟩ without recording the outcome
+    ///
+    /// Implemented as the channel ρ → K0 ρ K0† + K1 ρ K1† with
+    /// K0 = |0⟩⟨0| and K1 = |0⟩⟨1|.
+    pub fn reset_qubit(&mut self, target: usize) -> Result<(), QuantumError> {
+        self.check_targets(&[target])?;
+
+        let bit = 1 << target;
+        let dim = self.rho.nrows();
+        let mut result = DMatrix::from_element(dim, dim, Complex::new(0.0, 0.0));
+        for i in (0..dim).filter(|i| i & bit == 0) {
+            for j in (0..dim).filter(|j| j & bit == 0) {
+                result[(i, j)] = self.rho[(i, j)] + self.rho[(i | bit, j | bit)];
+            }
+        }
+        self.rho = result;
+        Ok(())
+    }
+
+    /// Returns Tr(ρ²)
+    pub fn purity(&self) -> f64 {
+        // Tr(ρ²) = Σ_ij |ρ_ij|² for Hermitian ρ
+        self.rho.iter().map(|x| x.norm_sqr()).sum()
+    }
+
+    /// Returns the current density matrix
+    pub fn get_density_matrix(&self) -> &DMatrix<Complex<f64>> {
+        &self.rho
+    }
+
+    /// Verifies that ρ is Hermitian with unit trace
+    pub fn verify_state(&self) -> bool {
+        let trace = self.rho.trace();
+        let hermitian = (&self.rho - self.rho.adjoint())
+            .iter()
+            .all(|x| x.norm_sqr() < 1e-20);
+        (trace.re - 1.0).abs() < 1e-10 && trace.im.abs() < 1e-10 && hermitian
+    }
+
+    /// Returns the number of qubits in the circuit
+    pub fn n_qubits(&self) -> usize {
+        self.n_qubits
+    }
+
+    /// Resets the whole register to |00...0⟩⟨00...0|
+    pub fn reset(&mut self) {
+        self.rho.fill(Complex::new(0.0, 0.0));
+        self.rho[(0, 0)] = Complex::new(1.0, 0.0);
+    }
+
+    /// Returns the probability of measuring a specific basis state
+    pub fn get_probability(&self, basis_state: usize) -> Result<f64, QuantumError> {
+        if basis_state >= self.rho.nrows() {
+            return Err(QuantumError::BasisStateOutOfRange {
+                state: basis_state,
+                dimension: self.rho.nrows(),
+            });
+        }
+        Ok(self.rho[(basis_state, basis_state)].re)
+    }
+}
+
+impl Backend for DensityMatrixCircuit {
+    fn n_qubits(&self) -> usize {
+        DensityMatrixCircuit::n_qubits(self)
+    }
+
+    fn reset(&mut self) {
+        DensityMatrixCircuit::reset(self)
+    }
+
+    fn apply(
+        &mut self,
+        instruction: &Instruction,
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<bool>, QuantumError> {
+        match instruction {
+            Instruction::Gate { gate, qubits } => {
+                self.check_targets(qubits)?;
+                self.conjugate_by(&gate.matrix()?, qubits);
+                Ok(None)
+            }
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => {
+                self.check_targets(&[*control, *target])?;
+                let mut matrix = DMatrix::identity(4, 4);
+                matrix.slice_mut((2, 2), (2, 2)).copy_from(&gate.matrix()?);
+                self.conjugate_by(&matrix, &[*control, *target]);
+                Ok(None)
+            }
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                ..
+            } => {
+                // Controls lead, so they are the most significant bits
+                let qubits = instruction.qubits();
+                self.check_targets(&qubits)?;
+                let matrix = controlled_matrix(&gate.matrix()?, controls.len(), *control_state);
+                self.conjugate_by(&matrix, &qubits);
+                Ok(None)
+            }
+            Instruction::Measure { qubit, .. } => self.measure_with(*qubit, rng).map(Some),
+            Instruction::Reset { qubit } => {
+                self.reset_qubit(*qubit)?;
+                Ok(None)
+            }
+            Instruction::Barrier { .. } => Ok(None),
+            Instruction::Conditional { .. } => Err(QuantumError::Unsupported(
+                "applying a conditional outside Backend::execute".to_string(),
+            )),
+        }
+    }
+
+    fn probabilities(&self) -> Option<Vec<f64>> {
+        Some(self.rho.diagonal().iter().map(|x| x.re).collect())
+    }
+}
+
+impl NoisyBackend for DensityMatrixCircuit {
+    /// Applies ρ → Σ K_i ρ K_i† exactly, so `rng` is not used
+    fn apply_channel(
+        &mut self,
+        channel: &KrausChannel,
+        qubits: &[usize],
+        _rng: &mut dyn RngCore,
+    ) -> Result<(), String> {
+        if qubits.len() != channel.n_qubits() {
+            return Err(format!(
+                "{}-qubit channel applied to {} qubits",
+                channel.n_qubits(),
+                qubits.len()
+            ));
+        }
+        self.check_targets(qubits)?;
+
+        let dim = self.rho.nrows();
+        let mut result = DMatrix::from_element(dim, dim, Complex::new(0.0, 0.0));
+        for operator in channel.operators() {
+            let left = Self::left_multiply(&self.rho, operator, qubits);
+            result += Self::left_multiply(&left.adjoint(), operator, qubits).adjoint();
+        }
+        self.rho = result;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::QuantumCircuit;
+    use crate::gates::{CNOTGate, HadamardGate, XGate};
+    use crate::ir::Circuit;
+    use approx::assert_relative_eq;
+
+    fn bell() -> DensityMatrixCircuit {
+        let mut circuit = DensityMatrixCircuit::new(2);
+        circuit.apply_gate(HadamardGate, 0).unwrap();
+        circuit.apply_multi_qubit_gate(CNOTGate, &[0, 1]).unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_new_circuit() {
+        let circuit = DensityMatrixCircuit::new(2);
+        assert!(circuit.verify_state());
+        assert_relative_eq!(circuit.purity(), 1.0);
+        assert_relative_eq!(circuit.get_probability(0).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_matches_state_vector() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .ry(0.7, 1)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .controlled(crate::ir::Gate::T, 1, 2)
+            .unwrap()
+            .iswap(1, 0)
+            .unwrap()
+            .multi_controlled_on(crate::ir::Gate::H, &[0, 2], 0b10, &[1])
+            .unwrap()
+            .ccx(1, 2, 0)
+            .unwrap();
+
+        let mut pure = QuantumCircuit::new(3).unwrap();
+        pure.run(&circuit).unwrap();
+        let mut mixed = DensityMatrixCircuit::new(3);
+        mixed.run(&circuit).unwrap();
+
+        let expected = DensityMatrixCircuit::from_state_vector(pure.get_state()).unwrap();
+        assert!((mixed.get_density_matrix() - expected.get_density_matrix()).norm() < 1e-10);
+        assert!(mixed.verify_state());
+    }
+
+    #[test]
+    fn test_measure_collapses_bell_state() {
+        let mut circuit = bell();
+        let result = circuit.measure(0).unwrap();
+        let expected = if result { 0b11 } else { 0b00 };
+        assert_relative_eq!(
+            circuit.get_probability(expected).unwrap(),
+            1.0,
+            epsilon = 1e-10
+        );
+        assert_relative_eq!(circuit.purity(), 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_reset_qubit_leaves_partner_mixed() {
+        let mut circuit = bell();
+        circuit.reset_qubit(0).unwrap();
+        assert!(circuit.verify_state());
+        assert_relative_eq!(circuit.purity(), 0.5, epsilon = 1e-10);
+        assert_relative_eq!(circuit.get_probability(0b00).unwrap(), 0.5, epsilon = 1e-10);
+        assert_relative_eq!(circuit.get_probability(0b10).unwrap(), 0.5, epsilon = 1e-10);
+
+        let mut flipped = DensityMatrixCircuit::new(1);
+        flipped.apply_gate(XGate, 0).unwrap();
+        flipped.reset_qubit(0).unwrap();
+        assert_relative_eq!(flipped.get_probability(0).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        let mut circuit = DensityMatrixCircuit::new(2);
+        assert!(circuit.apply_gate(XGate, 2).is_err());
+        assert!(circuit.apply_controlled_gate(XGate, 1, 1).is_err());
+        assert!(circuit.reset_qubit(5).is_err());
+        assert!(DensityMatrixCircuit::from_state_vector(&DVector::zeros(3)).is_err());
+    }
+}
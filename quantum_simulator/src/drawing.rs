@@ -0,0 +1,968 @@
+/*
+This file draws circuits as text diagrams and SVG images.
+
+Key concepts:
+1. Layout:
+   - Every qubit, and every classical bit of a circuit that measures or reads
+     bits, gets its own horizontal wire
+   - Instructions are packed left to right into columns: an instruction goes
+     into the first column where every wire it spans is free, so independent
+     gates share a column
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_circuit
+
+2. Symbols:
+   - Gates are boxes labelled with `QuantumGate::name()` and their angles,
+     with multiples of π written as fractions
+   - ● and ○ are controls on |1⟩ and |0⟩, × marks both ends of a SWAP and ░
+     is a barrier
+   - A measurement is an M box joined to the classical bit it writes (╩) by a
+     double line; a conditioned instruction is joined to the bits it reads,
+     with ● or ○ giving the value each bit must hold
+
+3. Text Output:
+   - `Display` draws with Unicode box-drawing characters and the alternate
+     form `{:#}` sticks to ASCII, for terminals and logs that need it
+   - Diagrams wider than the line width are cut into blocks stacked
+     vertically, with » and « marking where the wires continue
+
+4. SVG Output:
+   - The same layout as a scalable vector image, for course notes and web pages
+   - SVG images are never wrapped
+   - Learn more: https://developer.mozilla.org/en-US/docs/Web/SVG
+*/
+
+use crate::ir::{Circuit, Gate, Instruction};
+use crate::parameter::Angle;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Default maximum width of a text diagram, in characters
+const DEFAULT_LINE_WIDTH: usize = 80;
+
+/// SVG width of one text column, in pixels
+const SVG_UNIT: f64 = 10.0;
+/// SVG distance between wires, in pixels
+const SVG_ROW_HEIGHT: f64 = 40.0;
+
+/// What a column draws on one wire
+#[derive(Debug, Clone, PartialEq)]
+enum Symbol {
+    Wire,
+    Box(String),
+    /// A control on |1⟩ (true) or |0⟩ (false)
+    Control(bool),
+    Swap,
+    Barrier,
+    /// A classical bit written by a measurement
+    Target,
+    /// A classical bit read by a condition, with the value it must hold
+    Bit(bool),
+}
+
+/// A vertical connection between wires `from` < `to`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Link {
+    from: usize,
+    to: usize,
+    classical: bool,
+}
+
+/// One column of the diagram
+#[derive(Debug, Clone)]
+struct Column {
+    symbols: Vec<Symbol>,
+    links: Vec<Link>,
+}
+
+impl Column {
+    /// Kind of the link crossing the gap below wire `row` (true if classical)
+    fn link_below(&self, row: usize) -> Option<bool> {
+        self.links
+            .iter()
+            .find(|link| link.from <= row && row < link.to)
+            .map(|link| link.classical)
+    }
+
+    /// Width of the column in characters, always odd so symbols centre
+    fn width(&self) -> usize {
+        self.symbols
+            .iter()
+            .map(|symbol| match symbol {
+                Symbol::Box(label) => box_width(label),
+                _ => 1,
+            })
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+/// Width of the box around `label`, rounded up to an odd number
+fn box_width(label: &str) -> usize {
+    (label.chars().count() + 4) | 1
+}
+
+/// Wires and columns shared by the text and SVG renderers
+struct Layout {
+    names: Vec<String>,
+    n_qubits: usize,
+    columns: Vec<Column>,
+}
+
+impl Layout {
+    fn new(circuit: &Circuit, ascii: bool) -> Self {
+        let n_qubits = circuit.n_qubits();
+        let reads_clbits = circuit.instructions().iter().any(|instruction| {
+            matches!(
+                instruction,
+                Instruction::Measure { .. } | Instruction::Conditional { .. }
+            )
+        });
+        let n_clbits = if reads_clbits { circuit.n_clbits() } else { 0 };
+
+        let mut names: Vec<String> = (0..n_qubits).map(|q| format!("q[{}]", q)).collect();
+        names.extend((0..n_clbits).map(|clbit| clbit_name(circuit, clbit)));
+
+        let mut columns: Vec<Column> = Vec::new();
+        let mut next_free = vec![0; names.len()];
+        for instruction in circuit.instructions() {
+            let mut symbols = Vec::new();
+            let mut links = Vec::new();
+            place(instruction, n_qubits, &mut symbols, &mut links);
+            if ascii {
+                for (_, symbol) in symbols.iter_mut() {
+                    if let Symbol::Box(label) = symbol {
+                        *label = label.replace('π', "pi").replace('⟩', ">");
+                    }
+                }
+            }
+
+            let rows = symbols
+                .iter()
+                .map(|&(row, _)| row)
+                .chain(links.iter().flat_map(|link| [link.from, link.to]));
+            let (low, high) = rows.fold((usize::MAX, 0), |(low, high), row| {
+                (low.min(row), high.max(row))
+            });
+            if low > high {
+                continue;
+            }
+
+            let index = next_free[low..=high].iter().copied().max().unwrap_or(0);
+            if index == columns.len() {
+                columns.push(Column {
+                    symbols: vec![Symbol::Wire; names.len()],
+                    links: Vec::new(),
+                });
+            }
+            for (row, symbol) in symbols {
+                columns[index].symbols[row] = symbol;
+            }
+            columns[index].links.extend(links);
+            next_free[low..=high].fill(index + 1);
+        }
+
+        Layout {
+            names,
+            n_qubits,
+            columns,
+        }
+    }
+}
+
+/// Name of a classical wire: `register[i]` if a register holds the bit
+fn clbit_name(circuit: &Circuit, clbit: usize) -> String {
+    circuit
+        .registers()
+        .iter()
+        .find(|register| register.clbits().contains(&clbit))
+        .map(|register| format!("{}[{}]", register.name(), clbit - register.start()))
+        .unwrap_or_else(|| format!("c[{}]", clbit))
+}
+
+/// Records the symbols and links that draw `instruction`
+fn place(
+    instruction: &Instruction,
+    n_qubits: usize,
+    symbols: &mut Vec<(usize, Symbol)>,
+    links: &mut Vec<Link>,
+) {
+    match instruction {
+        Instruction::Gate { gate, qubits } => place_gate(gate, qubits, symbols),
+        Instruction::Controlled {
+            gate,
+            control,
+            target,
+        } => {
+            symbols.push((*control, Symbol::Control(true)));
+            place_gate(gate, &[*target], symbols);
+        }
+        Instruction::MultiControlled {
+            gate,
+            controls,
+            control_state,
+            targets,
+        } => {
+            for (i, &control) in controls.iter().enumerate() {
+                let on_one = i >= 64 || control_state >> i & 1 == 1;
+                symbols.push((control, Symbol::Control(on_one)));
+            }
+            place_gate(gate, targets, symbols);
+        }
+        Instruction::Measure { qubit, clbit } => {
+            symbols.push((*qubit, Symbol::Box("M".to_string())));
+            symbols.push((n_qubits + clbit, Symbol::Target));
+            links.push(Link {
+                from: *qubit,
+                to: n_qubits + clbit,
+                classical: true,
+            });
+        }
+        Instruction::Reset { qubit } => symbols.push((*qubit, Symbol::Box("|0⟩".to_string()))),
+        Instruction::Barrier { qubits } => {
+            symbols.extend(qubits.iter().map(|&q| (q, Symbol::Barrier)));
+        }
+        Instruction::Conditional {
+            clbits,
+            value,
+            instruction,
+        } => {
+            place(instruction, n_qubits, symbols, links);
+            for (i, &clbit) in clbits.iter().enumerate() {
+                let row = n_qubits + clbit;
+                if symbols.iter().all(|&(other, _)| other != row) {
+                    symbols.push((row, Symbol::Bit(i < 64 && value >> i & 1 == 1)));
+                }
+            }
+            if let (Some(lowest), Some(deepest)) =
+                (instruction.qubits().into_iter().max(), clbits.iter().max())
+            {
+                links.push(Link {
+                    from: lowest,
+                    to: n_qubits + deepest,
+                    classical: true,
+                });
+            }
+            return;
+        }
+    }
+
+    let qubits = instruction.qubits();
+    if qubits.len() > 1 && !matches!(instruction, Instruction::Barrier { .. }) {
+        links.push(Link {
+            from: *qubits.iter().min().unwrap(),
+            to: *qubits.iter().max().unwrap(),
+            classical: false,
+        });
+    }
+}
+
+/// Records the symbols of `gate` on `qubits`; controlled two-qubit gates
+/// draw their controls as dots
+fn place_gate(gate: &Gate, qubits: &[usize], symbols: &mut Vec<(usize, Symbol)>) {
+    match (gate, qubits) {
+        (Gate::CNOT, &[control, target]) => {
+            symbols.push((control, Symbol::Control(true)));
+            symbols.push((target, Symbol::Box(gate_label(&Gate::X))));
+        }
+        (Gate::CZ, &[a, b]) => {
+            symbols.push((a, Symbol::Control(true)));
+            symbols.push((b, Symbol::Control(true)));
+        }
+        (Gate::CPhase(phi), &[control, target]) => {
+            symbols.push((control, Symbol::Control(true)));
+            symbols.push((
+                target,
+                Symbol::Box(gate_label(&Gate::PhaseShift(phi.clone()))),
+            ));
+        }
+        (Gate::Swap, &[a, b]) => {
+            symbols.push((a, Symbol::Swap));
+            symbols.push((b, Symbol::Swap));
+        }
+        _ => symbols.extend(qubits.iter().map(|&q| (q, Symbol::Box(gate_label(gate))))),
+    }
+}
+
+/// `name(angle, ...)` for a gate
+fn gate_label(gate: &Gate) -> String {
+    let angles = gate.angles();
+    if angles.is_empty() {
+        return gate.name().to_string();
+    }
+    let angles: Vec<String> = angles.into_iter().map(format_angle).collect();
+    format!("{}({})", gate.name(), angles.join(", "))
+}
+
+/// Writes simple multiples of π as fractions and other values to 3 decimals
+fn format_angle(angle: &Angle) -> String {
+    let Some(value) = angle.value() else {
+        return angle.to_string();
+    };
+    for denominator in [1, 2, 3, 4, 6, 8] {
+        let numerator = value * denominator as f64 / PI;
+        if numerator.abs() >= 0.5 && (numerator - numerator.round()).abs() < 1e-9 {
+            let numerator = match numerator.round() as i64 {
+                1 => "π".to_string(),
+                -1 => "-π".to_string(),
+                n => format!("{}π", n),
+            };
+            return match denominator {
+                1 => numerator,
+                d => format!("{}/{}", numerator, d),
+            };
+        }
+    }
+    let decimals = format!("{:.3}", value);
+    let trimmed = decimals.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Characters used by a text diagram
+struct Glyphs {
+    wire: char,
+    classical_wire: char,
+    vertical: char,
+    classical_vertical: char,
+    /// A quantum link crossing a quantum wire
+    cross: char,
+    /// A classical link crossing a quantum wire
+    classical_cross: char,
+    /// A classical link crossing a classical wire
+    double_cross: char,
+    corners: [char; 4],
+    box_left: char,
+    box_right: char,
+    link_up: char,
+    link_down: char,
+    classical_link_up: char,
+    classical_link_down: char,
+    control: char,
+    open_control: char,
+    swap: char,
+    barrier: char,
+    target: char,
+    continues: char,
+    continued: char,
+}
+
+const UNICODE: Glyphs = Glyphs {
+    wire: '─',
+    classical_wire: '═',
+    vertical: '│',
+    classical_vertical: '║',
+    cross: '┼',
+    classical_cross: '╫',
+    double_cross: '╬',
+    corners: ['┌', '┐', '└', '┘'],
+    box_left: '┤',
+    box_right: '├',
+    link_up: '┴',
+    link_down: '┬',
+    classical_link_up: '╨',
+    classical_link_down: '╥',
+    control: '●',
+    open_control: '○',
+    swap: '×',
+    barrier: '░',
+    target: '╩',
+    continues: '»',
+    continued: '«',
+};
+
+const ASCII: Glyphs = Glyphs {
+    wire: '-',
+    classical_wire: '=',
+    vertical: '|',
+    classical_vertical: '|',
+    cross: '+',
+    classical_cross: '+',
+    double_cross: '+',
+    corners: ['+', '+', '+', '+'],
+    box_left: '|',
+    box_right: '|',
+    link_up: '+',
+    link_down: '+',
+    classical_link_up: '+',
+    classical_link_down: '+',
+    control: '*',
+    open_control: 'o',
+    swap: 'x',
+    barrier: '#',
+    target: 'v',
+    continues: '>',
+    continued: '<',
+};
+
+/// Draws circuits as text diagrams or SVG images
+///
+/// `Display` for `Circuit` uses the default drawer; this builder changes the
+/// line width or restricts the output to ASCII.
+#[derive(Debug, Clone)]
+pub struct CircuitDrawer {
+    line_width: usize,
+    ascii: bool,
+}
+
+impl Default for CircuitDrawer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitDrawer {
+    /// Creates a Unicode drawer wrapping at 80 characters
+    pub fn new() -> Self {
+        CircuitDrawer {
+            line_width: DEFAULT_LINE_WIDTH,
+            ascii: false,
+        }
+    }
+
+    /// Sets the width at which text diagrams wrap; 0 never wraps
+    ///
+    /// A column wider than the line width still gets a block of its own.
+    pub fn with_line_width(mut self, line_width: usize) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Restricts text diagrams to ASCII characters
+    pub fn with_ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    fn glyphs(&self) -> &'static Glyphs {
+        if self.ascii {
+            &ASCII
+        } else {
+            &UNICODE
+        }
+    }
+
+    /// Draws `circuit` as a text diagram, three lines per wire at most
+    pub fn text(&self, circuit: &Circuit) -> String {
+        let layout = Layout::new(circuit, self.ascii);
+        let name_width = layout
+            .names
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        // Name, ": ", and a wire or continuation mark at both ends
+        let margin = name_width + 4;
+
+        let mut blocks: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut used = margin;
+        for (index, column) in layout.columns.iter().enumerate() {
+            let width = column.width() + 2;
+            let current = blocks.last_mut().unwrap();
+            if self.line_width > 0 && !current.is_empty() && used + width > self.line_width {
+                blocks.push(vec![index]);
+                used = margin + width;
+            } else {
+                current.push(index);
+                used += width;
+            }
+        }
+
+        let n_blocks = blocks.len();
+        let mut rendered = Vec::with_capacity(n_blocks);
+        for (b, block) in blocks.into_iter().enumerate() {
+            let mut lines = Vec::new();
+            for (row, name) in layout.names.iter().enumerate() {
+                let mut text = [
+                    " ".repeat(name_width + 2),
+                    format!("{:<width$}: ", name, width = name_width),
+                    " ".repeat(name_width + 2),
+                ];
+                let wire = self.wire(row < layout.n_qubits);
+                let start = if b > 0 { self.glyphs().continued } else { wire };
+                let end = if b + 1 < n_blocks {
+                    self.glyphs().continues
+                } else {
+                    wire
+                };
+
+                text[0].push(' ');
+                text[1].push(start);
+                text[2].push(' ');
+                for &index in &block {
+                    let [top, middle, bottom] =
+                        self.cell(&layout.columns[index], row, layout.n_qubits);
+                    text[0].push_str(&format!(" {} ", top));
+                    text[1].push_str(&format!("{}{}{}", wire, middle, wire));
+                    text[2].push_str(&format!(" {} ", bottom));
+                }
+                text[0].push(' ');
+                text[1].push(end);
+                text[2].push(' ');
+                lines.extend(text);
+            }
+            // Lines with nothing but padding only add height
+            let lines: Vec<&str> = lines
+                .iter()
+                .map(|line| line.trim_end())
+                .filter(|line| !line.is_empty())
+                .collect();
+            rendered.push(lines.join("\n"));
+        }
+        rendered.join("\n\n")
+    }
+
+    fn wire(&self, quantum: bool) -> char {
+        if quantum {
+            self.glyphs().wire
+        } else {
+            self.glyphs().classical_wire
+        }
+    }
+
+    /// Top, middle and bottom lines of `column` on wire `row`
+    fn cell(&self, column: &Column, row: usize, n_qubits: usize) -> [String; 3] {
+        let g = self.glyphs();
+        let width = column.width();
+        let wire = self.wire(row < n_qubits);
+        let above = row.checked_sub(1).and_then(|r| column.link_below(r));
+        let below = column.link_below(row);
+        let vertical = |classical: bool| {
+            if classical {
+                g.classical_vertical
+            } else {
+                g.vertical
+            }
+        };
+        let centred = |fill: char, centre: Option<char>| -> String {
+            (0..width)
+                .map(|i| match centre {
+                    Some(c) if i == width / 2 => c,
+                    _ => fill,
+                })
+                .collect()
+        };
+
+        let symbol = match &column.symbols[row] {
+            Symbol::Box(label) => return self.boxed(label, width, wire, above, below),
+            Symbol::Barrier => {
+                return [
+                    centred(' ', Some(g.barrier)),
+                    centred(wire, Some(g.barrier)),
+                    centred(' ', Some(g.barrier)),
+                ]
+            }
+            Symbol::Wire => match above.or(below) {
+                None => wire,
+                Some(false) => g.cross,
+                Some(true) if row < n_qubits => g.classical_cross,
+                Some(true) => g.double_cross,
+            },
+            Symbol::Control(true) | Symbol::Bit(true) => g.control,
+            Symbol::Control(false) | Symbol::Bit(false) => g.open_control,
+            Symbol::Swap => g.swap,
+            Symbol::Target => g.target,
+        };
+        [
+            centred(' ', above.map(vertical)),
+            centred(wire, Some(symbol)),
+            centred(' ', below.map(vertical)),
+        ]
+    }
+
+    /// A gate box centred in a column of `width` characters
+    fn boxed(
+        &self,
+        label: &str,
+        width: usize,
+        wire: char,
+        above: Option<bool>,
+        below: Option<bool>,
+    ) -> [String; 3] {
+        let g = self.glyphs();
+        let inner = box_width(label);
+        let pad = (width - inner) / 2;
+        let border = |left: char, right: char, link: Option<char>| {
+            let mut line = " ".repeat(pad);
+            line.push(left);
+            for i in 1..inner - 1 {
+                line.push(match link {
+                    Some(c) if i == inner / 2 => c,
+                    _ => g.wire,
+                });
+            }
+            line.push(right);
+            line.push_str(&" ".repeat(pad));
+            line
+        };
+
+        let top = border(
+            g.corners[0],
+            g.corners[1],
+            above.map(|classical| {
+                if classical {
+                    g.classical_link_up
+                } else {
+                    g.link_up
+                }
+            }),
+        );
+        let bottom = border(
+            g.corners[2],
+            g.corners[3],
+            below.map(|classical| {
+                if classical {
+                    g.classical_link_down
+                } else {
+                    g.link_down
+                }
+            }),
+        );
+        let wires: String = std::iter::repeat_n(wire, pad).collect();
+        let middle = format!(
+            "{}{} {:<fill$} {}{}",
+            wires,
+            g.box_left,
+            label,
+            g.box_right,
+            wires,
+            fill = inner - 4
+        );
+        [top, middle, bottom]
+    }
+
+    /// Draws `circuit` as a standalone SVG image
+    pub fn svg(&self, circuit: &Circuit) -> String {
+        let layout = Layout::new(circuit, false);
+        let name_width = layout
+            .names
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let left = (name_width + 2) as f64 * SVG_UNIT;
+        let mut centres = Vec::with_capacity(layout.columns.len());
+        let mut x = left + SVG_UNIT;
+        for column in &layout.columns {
+            let width = (column.width() + 2) as f64 * SVG_UNIT;
+            centres.push(x + width / 2.0);
+            x += width;
+        }
+        let width = x + SVG_UNIT;
+        let height = (layout.names.len() + 1) as f64 * SVG_ROW_HEIGHT;
+        let y = |row: usize| (row as f64 + 1.0) * SVG_ROW_HEIGHT;
+
+        let mut svg = format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" ",
+                "viewBox=\"0 0 {w} {h}\" font-family=\"monospace\" font-size=\"14\">\n",
+                "<rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n"
+            ),
+            w = width,
+            h = height
+        );
+
+        for (row, name) in layout.names.iter().enumerate() {
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" dominant-baseline=\"middle\">{}</text>\n",
+                SVG_UNIT / 2.0,
+                y(row),
+                escape(name)
+            ));
+            if row < layout.n_qubits {
+                svg.push_str(&line(left, y(row), x, y(row)));
+            } else {
+                svg.push_str(&line(left, y(row) - 2.0, x, y(row) - 2.0));
+                svg.push_str(&line(left, y(row) + 2.0, x, y(row) + 2.0));
+            }
+        }
+
+        for (column, &cx) in layout.columns.iter().zip(&centres) {
+            for link in &column.links {
+                if link.classical {
+                    svg.push_str(&line(cx - 2.0, y(link.from), cx - 2.0, y(link.to)));
+                    svg.push_str(&line(cx + 2.0, y(link.from), cx + 2.0, y(link.to)));
+                } else {
+                    svg.push_str(&line(cx, y(link.from), cx, y(link.to)));
+                }
+            }
+
+            for (row, symbol) in column.symbols.iter().enumerate() {
+                let cy = y(row);
+                match symbol {
+                    Symbol::Wire => {}
+                    Symbol::Box(label) => {
+                        let half = (label.chars().count() + 2) as f64 * SVG_UNIT / 2.0;
+                        svg.push_str(&format!(
+                            concat!(
+                                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" ",
+                                "fill=\"white\" stroke=\"black\"/>\n",
+                                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" ",
+                                "dominant-baseline=\"middle\">{}</text>\n"
+                            ),
+                            cx - half,
+                            cy - 15.0,
+                            2.0 * half,
+                            30.0,
+                            cx,
+                            cy,
+                            escape(label)
+                        ));
+                    }
+                    Symbol::Control(filled) | Symbol::Bit(filled) => {
+                        let radius = if row < layout.n_qubits { 5.0 } else { 4.0 };
+                        let fill = if *filled { "black" } else { "white" };
+                        svg.push_str(&format!(
+                            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"black\"/>\n",
+                            cx, cy, radius, fill
+                        ));
+                    }
+                    Symbol::Swap => {
+                        svg.push_str(&line(cx - 6.0, cy - 6.0, cx + 6.0, cy + 6.0));
+                        svg.push_str(&line(cx - 6.0, cy + 6.0, cx + 6.0, cy - 6.0));
+                    }
+                    Symbol::Barrier => {
+                        svg.push_str(&format!(
+                            concat!(
+                                "<rect x=\"{}\" y=\"{}\" width=\"10\" height=\"{}\" ",
+                                "fill=\"#dddddd\" stroke=\"#888888\" stroke-dasharray=\"4 2\"/>\n"
+                            ),
+                            cx - 5.0,
+                            cy - SVG_ROW_HEIGHT / 2.0,
+                            SVG_ROW_HEIGHT
+                        ));
+                    }
+                    Symbol::Target => {
+                        svg.push_str(&format!(
+                            "<polygon points=\"{},{} {},{} {},{}\" fill=\"black\"/>\n",
+                            cx - 6.0,
+                            cy - 8.0,
+                            cx + 6.0,
+                            cy - 8.0,
+                            cx,
+                            cy
+                        ));
+                    }
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// An SVG line segment
+fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> String {
+    format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>\n",
+        x1, y1, x2, y2
+    )
+}
+
+/// Escapes text for use in SVG markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl fmt::Display for Circuit {
+    /// Draws the circuit with Unicode box-drawing characters, or with ASCII
+    /// only for `{:#}`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let drawer = CircuitDrawer::new().with_ascii(f.alternate());
+        f.write_str(&drawer.text(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameter::Parameter;
+
+    fn bell() -> Circuit {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .measure_all()
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_ascii_diagram() {
+        let expected = [
+            "        +-----------+               +---+",
+            "q[0]: --| Hadamard  |-------*-------| M |---------",
+            "        +-----------+       |       +-+-+",
+            "                       +----+----+    |    +---+",
+            "q[1]: -----------------| Pauli-X |----+----| M |--",
+            "                       +---------+    |    +-+-+",
+            "                                      |      |",
+            "c[0]: ================================v======+====",
+            "                                             |",
+            "                                             |",
+            "c[1]: =======================================v====",
+        ]
+        .join("\n");
+        assert_eq!(format!("{:#}", bell()), expected);
+    }
+
+    #[test]
+    fn test_unicode_diagram() {
+        let text = bell().to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[1],
+            "q[0]: ──┤ Hadamard  ├───────●───────┤ M ├─────────"
+        );
+        assert_eq!(lines[2].trim(), "└───────────┘       │       └─╥─┘");
+        assert_eq!(
+            lines[10],
+            "c[1]: ═══════════════════════════════════════╩════"
+        );
+        assert_eq!(
+            CircuitDrawer::new().with_ascii(true).text(&bell()),
+            format!("{:#}", bell())
+        );
+
+        // Wires of an empty circuit are still drawn
+        assert_eq!(Circuit::new(2).unwrap().to_string(), "q[0]: ──\nq[1]: ──");
+    }
+
+    #[test]
+    fn test_independent_gates_share_columns() {
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .cx(2, 3)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .x(1)
+            .unwrap();
+        let layout = Layout::new(&circuit, false);
+        // cx(0, 2) spans q[1], so x(1) cannot join its column
+        assert_eq!(layout.columns.len(), 3);
+        assert_eq!(layout.columns[0].symbols[3], Symbol::Box("Pauli-X".into()));
+        assert_eq!(layout.columns[1].symbols[1], Symbol::Wire);
+        assert_eq!(layout.columns[1].link_below(1), Some(false));
+        assert_eq!(layout.columns[2].symbols[1], Symbol::Box("Pauli-X".into()));
+        // No classical wires without measurements
+        assert_eq!(layout.names.len(), 4);
+
+        let text = circuit.to_string();
+        assert!(text.contains("──┼──"));
+    }
+
+    #[test]
+    fn test_symbols_and_labels() {
+        let gamma = Parameter::new("gamma");
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .rx(PI / 2.0, 0)
+            .unwrap()
+            .u3(-3.0 * PI / 4.0, 0.5, 2.0 * PI / 3.0, 1)
+            .unwrap()
+            .rz(&gamma * 2.0, 2)
+            .unwrap()
+            .multi_controlled_on(Gate::Z, &[0, 1], 0b01, &[2])
+            .unwrap()
+            .swap(0, 2)
+            .unwrap()
+            .barrier(&[])
+            .unwrap()
+            .reset(1)
+            .unwrap();
+        let text = circuit.to_string();
+        for expected in ["RX(π/2)", "U3(-3π/4, 0.5, 2π/3)", "RZ(2*gamma)", "|0⟩"] {
+            assert!(
+                text.contains(expected),
+                "{} missing from\n{}",
+                expected,
+                text
+            );
+        }
+        for glyph in ['●', '○', '×', '░'] {
+            assert!(text.contains(glyph), "{} missing from\n{}", glyph, text);
+        }
+
+        let ascii = format!("{:#}", circuit);
+        assert!(ascii.is_ascii());
+        assert!(ascii.contains("RX(pi/2)") && ascii.contains("|0>"));
+    }
+
+    #[test]
+    fn test_conditions_on_registers() {
+        let mut circuit = Circuit::with_clbits(2, 0).unwrap();
+        circuit.add_register("m", 2).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .measure_register(&[0, 1], "m")
+            .unwrap()
+            .c_if(
+                "m",
+                0b01,
+                Instruction::Gate {
+                    gate: Gate::X,
+                    qubits: vec![1],
+                },
+            )
+            .unwrap();
+        let text = circuit.to_string();
+        let target = text.lines().find(|line| line.starts_with("q[1]")).unwrap();
+        let condition_column = target[..target.find("Pauli-X").unwrap()].chars().count();
+        let bits: Vec<&str> = text.lines().filter(|l| l.starts_with("m[")).collect();
+        assert_eq!(bits.len(), 2);
+        // m[0] must read 1 and m[1] must read 0
+        let centre = |line: &str| line.chars().nth(condition_column + 3).unwrap();
+        assert_eq!(centre(bits[0]), '●');
+        assert_eq!(centre(bits[1]), '○');
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let mut circuit = Circuit::new(2).unwrap();
+        for i in 0..30 {
+            circuit.ry(0.1 * i as f64, i % 2).unwrap();
+        }
+        let text = CircuitDrawer::new().with_line_width(60).text(&circuit);
+        assert!(text.lines().all(|line| line.chars().count() <= 60));
+        let blocks = text.split("\n\n").count();
+        assert!(blocks > 2);
+        assert_eq!(text.matches('»').count(), 2 * (blocks - 1));
+        assert_eq!(text.matches('«').count(), 2 * (blocks - 1));
+        // Every gate is still drawn once
+        assert_eq!(text.matches("RY(").count(), 30);
+
+        let unwrapped = CircuitDrawer::new().with_line_width(0).text(&circuit);
+        assert_eq!(unwrapped.lines().count(), 6);
+    }
+
+    #[test]
+    fn test_svg() {
+        let mut circuit = bell();
+        circuit.add_gate(Gate::Rotation(0.25.into()), &[1]).unwrap();
+        circuit.swap(0, 1).unwrap();
+        let svg = CircuitDrawer::new().svg(&circuit);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 1 + 5);
+        assert!(svg.contains(">Hadamard</text>") && svg.contains(">Rotation(0.25)</text>"));
+        assert!(svg.contains("<circle"));
+
+        let mut reset = Circuit::new(1).unwrap();
+        reset.reset(0).unwrap();
+        assert!(CircuitDrawer::new().svg(&reset).contains(">|0⟩</text>"));
+        assert_eq!(escape("a<b & \"c\">"), "a&lt;b &amp; &quot;c&quot;&gt;");
+    }
+}
@@ -0,0 +1,441 @@
+/*
+This file implements partial traces and entanglement measures for
+state-vector simulations.
+
+Key concepts:
+1. Reduced Density Matrices:
+   - Tracing out the rest of the register from |ψ⟩⟨ψ| leaves the state
+     ρ_A = Tr_B |ψ⟩⟨ψ| seen by an observer holding only the qubits in A
+   - ρ_A is mixed exactly when A is entangled with the rest
+   - Learn more: https://en.wikipedia.org/wiki/Partial_trace
+
+2. Entanglement Entropy:
+   - Von Neumann entropy S(ρ) = -Tr(ρ log₂ ρ), in bits
+   - Rényi entropy S_α(ρ) = log₂(Tr ρ^α) / (1 - α), which tends to the von
+     Neumann entropy as α → 1
+   - For a pure state both are the same for A and its complement
+   - Learn more: https://en.wikipedia.org/wiki/Entropy_of_entanglement
+
+3. Concurrence:
+   - Wootters' measure of two-qubit entanglement, valid for mixed states:
+     C = max(0, λ1 - λ2 - λ3 - λ4), where λi are the decreasing square roots
+     of the eigenvalues of ρ (Y⊗Y) ρ* (Y⊗Y)
+   - 0 for separable states and 1 for Bell states
+   - Reference: https://arxiv.org/abs/quant-ph/9709029
+
+4. Schmidt Decomposition:
+   - Any pure state splits as |ψ⟩ = Σ_i s_i |a_i⟩|b_i⟩ with orthonormal
+     |a_i⟩, |b_i⟩ and s_i > 0, read off the SVD of the amplitudes arranged as
+     a matrix
+   - The Schmidt rank (number of terms) is 1 exactly for product states
+   - Learn more: https://en.wikipedia.org/wiki/Schmidt_decomposition
+
+A subsystem listed as `qubits` is ordered like a register of its own: qubits[i]
+becomes bit i of the reduced index, as qubit k is bit k of the full index.
+*/
+
+use crate::circuit::QuantumCircuit;
+use nalgebra::{Complex, DMatrix, DVector};
+
+/// Eigenvalues and Schmidt coefficients below this are treated as zero
+const ZERO_TOLERANCE: f64 = 1e-12;
+
+/// |ψ⟩ = Σ_i s_i |a_i⟩|b_i⟩ across a bipartition of the register
+#[derive(Debug, Clone)]
+pub struct SchmidtDecomposition {
+    /// Schmidt coefficients s_i, largest first
+    pub coefficients: Vec<f64>,
+    /// States |a_i⟩ of the listed qubits
+    pub states_a: Vec<DVector<Complex<f64>>>,
+    /// States |b_i⟩ of the remaining qubits, in increasing qubit order
+    pub states_b: Vec<DVector<Complex<f64>>>,
+}
+
+impl SchmidtDecomposition {
+    /// Number of nonzero Schmidt coefficients
+    pub fn rank(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// Von Neumann entropy -Σ s_i² log₂ s_i² of either side, in bits
+    pub fn entropy(&self) -> f64 {
+        entropy_of(self.coefficients.iter().map(|s| s * s))
+    }
+}
+
+impl QuantumCircuit {
+    /// Arranges the amplitudes as a matrix with one row per basis state of
+    /// `qubits` and one column per basis state of the other qubits
+    fn bipartition(&self, qubits: &[usize]) -> Result<DMatrix<Complex<f64>>, String> {
+        let n = self.n_qubits();
+        if qubits.is_empty() {
+            return Err("The subsystem needs at least one qubit".to_string());
+        }
+        for (i, &q) in qubits.iter().enumerate() {
+            if q >= n {
+                return Err(format!(
+                    "Qubit {} is out of range for circuit with {} qubits",
+                    q, n
+                ));
+            }
+            if qubits[..i].contains(&q) {
+                return Err(format!("Qubit {} is listed more than once", q));
+            }
+        }
+
+        let rest: Vec<usize> = (0..n).filter(|q| !qubits.contains(q)).collect();
+        let spread = |local: usize, positions: &[usize]| {
+            positions
+                .iter()
+                .enumerate()
+                .filter(|(bit, _)| local >> bit & 1 == 1)
+                .fold(0, |acc, (_, &q)| acc | 1 << q)
+        };
+        let state = self.get_state();
+        Ok(DMatrix::from_fn(
+            1 << qubits.len(),
+            1 << rest.len(),
+            |a, b| state[spread(a, qubits) | spread(b, &rest)],
+        ))
+    }
+
+    /// Returns ρ_A = Tr_B |ψ⟩⟨ψ| for the subsystem A made of `qubits`
+    ///
+    /// qubits[i] is bit i of the reduced index, so listing every qubit in
+    /// increasing order gives |ψ⟩⟨ψ| itself.
+    pub fn reduced_density_matrix(
+        &self,
+        qubits: &[usize],
+    ) -> Result<DMatrix<Complex<f64>>, String> {
+        let matrix = self.bipartition(qubits)?;
+        Ok(&matrix * matrix.adjoint())
+    }
+
+    /// Von Neumann entropy of `qubits` with the rest of the register, in bits
+    pub fn entanglement_entropy(&self, qubits: &[usize]) -> Result<f64, String> {
+        von_neumann_entropy(&self.reduced_density_matrix(qubits)?)
+    }
+
+    /// Rényi-α entropy of `qubits` with the rest of the register, in bits
+    pub fn renyi_entropy(&self, qubits: &[usize], alpha: f64) -> Result<f64, String> {
+        renyi_entropy(&self.reduced_density_matrix(qubits)?, alpha)
+    }
+
+    /// Concurrence of the (generally mixed) two-qubit state of `a` and `b`
+    pub fn concurrence(&self, a: usize, b: usize) -> Result<f64, String> {
+        concurrence(&self.reduced_density_matrix(&[a, b])?)
+    }
+
+    /// Schmidt decomposition across `qubits` and the rest of the register
+    pub fn schmidt_decomposition(&self, qubits: &[usize]) -> Result<SchmidtDecomposition, String> {
+        let matrix = self.bipartition(qubits)?;
+        let svd = matrix.svd(true, true);
+        let (u, v_t) = (svd.u.unwrap(), svd.v_t.unwrap());
+
+        let rank = svd
+            .singular_values
+            .iter()
+            .take_while(|&&s| s > ZERO_TOLERANCE)
+            .count();
+        Ok(SchmidtDecomposition {
+            coefficients: svd.singular_values.iter().take(rank).copied().collect(),
+            states_a: (0..rank).map(|i| u.column(i).into_owned()).collect(),
+            states_b: (0..rank)
+                .map(|i| v_t.row(i).adjoint().into_owned())
+                .collect(),
+        })
+    }
+}
+
+/// Checks that `rho` is a square Hermitian matrix with unit trace
+pub(crate) fn check_density_matrix(rho: &DMatrix<Complex<f64>>) -> Result<(), String> {
+    if !rho.is_square() || !rho.nrows().is_power_of_two() {
+        return Err(format!(
+            "A {}×{} matrix is not a density matrix of qubits",
+            rho.nrows(),
+            rho.ncols()
+        ));
+    }
+    let hermitian = (rho - rho.adjoint()).iter().all(|x| x.norm_sqr() < 1e-16);
+    let trace = rho.trace();
+    if !hermitian || (trace.re - 1.0).abs() > 1e-8 || trace.im.abs() > 1e-8 {
+        return Err("Density matrices must be Hermitian with unit trace".to_string());
+    }
+    Ok(())
+}
+
+/// Eigenvalues of a density matrix, with round-off negatives clamped to 0
+fn spectrum(rho: &DMatrix<Complex<f64>>) -> Result<Vec<f64>, String> {
+    check_density_matrix(rho)?;
+    Ok(rho
+        .clone()
+        .symmetric_eigenvalues()
+        .iter()
+        .map(|&p| p.max(0.0))
+        .collect())
+}
+
+/// -Σ p log₂ p over the nonzero probabilities
+fn entropy_of(probabilities: impl Iterator<Item = f64>) -> f64 {
+    -probabilities
+        .filter(|&p| p > ZERO_TOLERANCE)
+        .map(|p| p * p.log2())
+        .sum::<f64>()
+}
+
+/// Von Neumann entropy S(ρ) = -Tr(ρ log₂ ρ), in bits
+pub fn von_neumann_entropy(rho: &DMatrix<Complex<f64>>) -> Result<f64, String> {
+    Ok(entropy_of(spectrum(rho)?.into_iter()))
+}
+
+/// Rényi entropy S_α(ρ) = log₂(Tr ρ^α) / (1 - α), in bits
+///
+/// α = 1 gives the von Neumann entropy; α must be positive.
+pub fn renyi_entropy(rho: &DMatrix<Complex<f64>>, alpha: f64) -> Result<f64, String> {
+    if !alpha.is_finite() || alpha <= 0.0 {
+        return Err(format!(
+            "Rényi order must be positive and finite, got {}",
+            alpha
+        ));
+    }
+    if (alpha - 1.0).abs() < 1e-12 {
+        return von_neumann_entropy(rho);
+    }
+    let sum: f64 = spectrum(rho)?
+        .into_iter()
+        .filter(|&p| p > ZERO_TOLERANCE)
+        .map(|p| p.powf(alpha))
+        .sum();
+    Ok(sum.log2() / (1.0 - alpha))
+}
+
+/// Wootters concurrence of a two-qubit density matrix
+pub fn concurrence(rho: &DMatrix<Complex<f64>>) -> Result<f64, String> {
+    if rho.nrows() != 4 {
+        return Err(format!(
+            "Concurrence needs a two-qubit (4×4) density matrix, got {}×{}",
+            rho.nrows(),
+            rho.ncols()
+        ));
+    }
+    check_density_matrix(rho)?;
+
+    // ρ̃ = (Y⊗Y) ρ* (Y⊗Y); Y⊗Y is the anti-diagonal matrix (-1, 1, 1, -1)
+    let yy = DMatrix::from_fn(4, 4, |i, j| {
+        let sign = if i == 0 || i == 3 { -1.0 } else { 1.0 };
+        Complex::new(if i + j == 3 { sign } else { 0.0 }, 0.0)
+    });
+    let flipped = &yy * rho.conjugate() * &yy;
+
+    // The λi are the eigenvalues of the Hermitian √(√ρ ρ̃ √ρ)
+    let sqrt_rho = hermitian_sqrt(rho);
+    let mut lambdas: Vec<f64> = (&sqrt_rho * flipped * &sqrt_rho)
+        .symmetric_eigenvalues()
+        .iter()
+        .map(|&x| x.max(0.0).sqrt())
+        .collect();
+    lambdas.sort_by(|a, b| b.total_cmp(a));
+    Ok((lambdas[0] - lambdas[1] - lambdas[2] - lambdas[3]).max(0.0))
+}
+
+/// Square root of a positive semidefinite Hermitian matrix
+pub(crate) fn hermitian_sqrt(matrix: &DMatrix<Complex<f64>>) -> DMatrix<Complex<f64>> {
+    let eigen = matrix.clone().symmetric_eigen();
+    let roots = DMatrix::from_diagonal(
+        &eigen
+            .eigenvalues
+            .map(|x| Complex::new(x.max(0.0).sqrt(), 0.0)),
+    );
+    &eigen.eigenvectors * roots * eigen.eigenvectors.adjoint()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::ir::{Circuit, Gate};
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    fn run(circuit: &Circuit) -> QuantumCircuit {
+        let mut backend = QuantumCircuit::new(circuit.n_qubits()).unwrap();
+        backend.run(circuit).unwrap();
+        backend
+    }
+
+    fn bell() -> QuantumCircuit {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.h(0).unwrap().cx(0, 1).unwrap();
+        run(&circuit)
+    }
+
+    #[test]
+    fn test_reduced_density_matrix() {
+        let rho = bell().reduced_density_matrix(&[0]).unwrap();
+        assert_relative_eq!(rho[(0, 0)].re, 0.5, epsilon = 1e-12);
+        assert_relative_eq!(rho[(1, 1)].re, 0.5, epsilon = 1e-12);
+        assert_relative_eq!(rho[(0, 1)].norm_sqr(), 0.0, epsilon = 1e-24);
+
+        // |q2 q1 q0⟩ = |001⟩; listing [1, 0] puts q0 in bit 1 of the reduced index
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit.x(0).unwrap().h(2).unwrap();
+        let state = run(&circuit);
+        let rho = state.reduced_density_matrix(&[1, 0]).unwrap();
+        assert_relative_eq!(rho[(0b10, 0b10)].re, 1.0, epsilon = 1e-12);
+
+        let full = state.reduced_density_matrix(&[0, 1, 2]).unwrap();
+        let psi = state.get_state();
+        assert_relative_eq!((full - psi * psi.adjoint()).norm(), 0.0, epsilon = 1e-12);
+
+        assert!(state.reduced_density_matrix(&[]).is_err());
+        assert!(state.reduced_density_matrix(&[3]).is_err());
+        assert!(state.reduced_density_matrix(&[1, 1]).is_err());
+    }
+
+    #[test]
+    fn test_entropies() {
+        let bell = bell();
+        assert_relative_eq!(
+            bell.entanglement_entropy(&[0]).unwrap(),
+            1.0,
+            epsilon = 1e-10
+        );
+        assert_relative_eq!(bell.renyi_entropy(&[1], 2.0).unwrap(), 1.0, epsilon = 1e-10);
+        assert_relative_eq!(
+            bell.entanglement_entropy(&[0, 1]).unwrap(),
+            0.0,
+            epsilon = 1e-10
+        );
+
+        // cos θ|00⟩ + sin θ|11⟩ has entropy H(cos²θ)
+        let theta = 0.3;
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.ry(2.0 * theta, 0).unwrap().cx(0, 1).unwrap();
+        let state = run(&circuit);
+        let (p, q) = (theta.cos().powi(2), theta.sin().powi(2));
+        let shannon = -(p * p.log2() + q * q.log2());
+        assert_relative_eq!(
+            state.entanglement_entropy(&[1]).unwrap(),
+            shannon,
+            epsilon = 1e-10
+        );
+        assert_relative_eq!(
+            state.renyi_entropy(&[0], 1.0).unwrap(),
+            shannon,
+            epsilon = 1e-10
+        );
+        let renyi2 = -(p * p + q * q).log2();
+        assert_relative_eq!(
+            state.renyi_entropy(&[0], 2.0).unwrap(),
+            renyi2,
+            epsilon = 1e-10
+        );
+        // Rényi entropies decrease with α
+        assert!(state.renyi_entropy(&[0], 0.5).unwrap() > shannon);
+
+        assert!(state.renyi_entropy(&[0], 0.0).is_err());
+        assert!(state.renyi_entropy(&[0], f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_concurrence() {
+        assert_relative_eq!(bell().concurrence(0, 1).unwrap(), 1.0, epsilon = 1e-10);
+
+        let theta = 0.3;
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.ry(2.0 * theta, 0).unwrap().cx(0, 1).unwrap();
+        assert_relative_eq!(
+            run(&circuit).concurrence(1, 0).unwrap(),
+            (2.0 * theta).sin(),
+            epsilon = 1e-8
+        );
+
+        let mut product = Circuit::new(2).unwrap();
+        product.h(0).unwrap().ry(0.7, 1).unwrap();
+        assert_relative_eq!(
+            run(&product).concurrence(0, 1).unwrap(),
+            0.0,
+            epsilon = 1e-7
+        );
+
+        // Pairs of a GHZ state are classically correlated only, while pairs
+        // of a W state keep concurrence 2/3
+        let mut ghz = Circuit::new(3).unwrap();
+        ghz.h(0).unwrap().cx(0, 1).unwrap().cx(1, 2).unwrap();
+        assert_relative_eq!(run(&ghz).concurrence(0, 2).unwrap(), 0.0, epsilon = 1e-7);
+
+        let mut w = Circuit::new(3).unwrap();
+        let first = 2.0 * (1.0 / 3.0_f64.sqrt()).acos();
+        w.ry(first, 0)
+            .unwrap()
+            .controlled(Gate::RY((PI / 2.0).into()), 0, 1)
+            .unwrap()
+            .cx(1, 2)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .x(0)
+            .unwrap();
+        let w = run(&w);
+        for q in 0..3 {
+            assert_relative_eq!(
+                w.get_probability(1 << q).unwrap(),
+                1.0 / 3.0,
+                epsilon = 1e-10
+            );
+        }
+        assert_relative_eq!(w.concurrence(1, 2).unwrap(), 2.0 / 3.0, epsilon = 1e-7);
+
+        let rho = w.reduced_density_matrix(&[0]).unwrap();
+        assert!(concurrence(&rho).is_err());
+        assert!(w.concurrence(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_schmidt_decomposition() {
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .u3(0.3, 1.2, -0.4, 0)
+            .unwrap()
+            .ry(1.1, 1)
+            .unwrap()
+            .h(2)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .controlled(Gate::PhaseShift(0.8.into()), 1, 3)
+            .unwrap()
+            .ry(0.5, 3)
+            .unwrap()
+            .cx(3, 0)
+            .unwrap();
+        let state = run(&circuit);
+        let schmidt = state.schmidt_decomposition(&[2, 0]).unwrap();
+        assert!(schmidt.rank() > 1 && schmidt.rank() <= 4);
+        let norm: f64 = schmidt.coefficients.iter().map(|s| s * s).sum();
+        assert_relative_eq!(norm, 1.0, epsilon = 1e-10);
+        assert_relative_eq!(
+            schmidt.entropy(),
+            state.entanglement_entropy(&[0, 2]).unwrap(),
+            epsilon = 1e-10
+        );
+
+        // Rebuild |ψ⟩ from Σ s_i |a_i⟩|b_i⟩; A holds q2 (bit 0) and q0 (bit 1),
+        // B holds q1 (bit 0) and q3 (bit 1)
+        let psi = state.get_state();
+        for index in 0..16 {
+            let bit = |q: usize| index >> q & 1;
+            let a = bit(2) | bit(0) << 1;
+            let b = bit(1) | bit(3) << 1;
+            let amplitude: Complex<f64> = (0..schmidt.rank())
+                .map(|i| schmidt.states_a[i][a] * schmidt.states_b[i][b] * schmidt.coefficients[i])
+                .sum();
+            assert_relative_eq!((amplitude - psi[index]).norm_sqr(), 0.0, epsilon = 1e-20);
+        }
+
+        let mut product = Circuit::new(2).unwrap();
+        product.h(0).unwrap().x(1).unwrap();
+        assert_eq!(run(&product).schmidt_decomposition(&[1]).unwrap().rank(), 1);
+        assert_eq!(bell().schmidt_decomposition(&[0]).unwrap().rank(), 2);
+    }
+}
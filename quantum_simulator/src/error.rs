@@ -0,0 +1,103 @@
+/*
+This file defines the structured error type of the state-vector simulator.
+
+Key concepts:
+1. Typed Failures:
+   - `QuantumCircuit`, the other simulators and the `Backend` trait report
+     what went wrong as a `QuantumError` variant, so callers can match on
+     the failure (say, retry with fewer qubits) instead of parsing a message
+   - Variants carry the offending values; `Display` renders the message
+
+2. Interoperability:
+   - `QuantumError` implements `std::error::Error`, so it works with `?` and
+     `Box<dyn Error>`
+   - Modules that still report errors as `String` convert with `?` through
+     `From<QuantumError> for String`
+*/
+
+use std::error::Error;
+use std::fmt;
+
+/// What went wrong in a state-vector operation
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuantumError {
+    /// A register cannot hold this many qubits (zero, or more than can be
+    /// indexed)
+    InvalidQubitCount(usize),
+    /// A qubit index is not below the register size
+    QubitOutOfRange { qubit: usize, n_qubits: usize },
+    /// A qubit appears twice in the qubits of one operation
+    DuplicateQubit(usize),
+    /// A gate's matrix is not unitary
+    NonUnitary(String),
+    /// Two sizes that must agree do not
+    DimensionMismatch {
+        what: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A basis state index is not below the state dimension
+    BasisStateOutOfRange { state: usize, dimension: usize },
+    /// A control state has bits beyond the number of controls, or there are
+    /// more than 64 controls
+    InvalidControlState {
+        control_state: u64,
+        n_controls: usize,
+    },
+    /// A name (of a custom gate, say) is not a valid identifier or is reserved
+    InvalidName(String),
+    /// Angles still refer to these parameters, so there is no matrix to apply
+    UnboundParameters(Vec<String>),
+    /// The backend cannot run the requested operation
+    Unsupported(String),
+}
+
+impl fmt::Display for QuantumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuantumError::InvalidQubitCount(n) => {
+                write!(f, "cannot create a register of {} qubits", n)
+            }
+            QuantumError::QubitOutOfRange { qubit, n_qubits } => write!(
+                f,
+                "qubit {} is out of range for circuit with {} qubits",
+                qubit, n_qubits
+            ),
+            QuantumError::DuplicateQubit(qubit) => {
+                write!(f, "qubit {} is listed more than once", qubit)
+            }
+            QuantumError::NonUnitary(gate) => write!(f, "{} gate is not unitary", gate),
+            QuantumError::DimensionMismatch {
+                what,
+                expected,
+                found,
+            } => write!(f, "{}: expected {}, found {}", what, expected, found),
+            QuantumError::BasisStateOutOfRange { state, dimension } => write!(
+                f,
+                "basis state {} is out of range for dimension {}",
+                state, dimension
+            ),
+            QuantumError::InvalidControlState {
+                control_state,
+                n_controls,
+            } => write!(
+                f,
+                "control state {} does not fit in {} controls (at most 64)",
+                control_state, n_controls
+            ),
+            QuantumError::InvalidName(name) => write!(f, "'{}' is not a valid name", name),
+            QuantumError::UnboundParameters(names) => {
+                write!(f, "unbound parameters: {}", names.join(", "))
+            }
+            QuantumError::Unsupported(what) => write!(f, "{} is not supported", what),
+        }
+    }
+}
+
+impl Error for QuantumError {}
+
+impl From<QuantumError> for String {
+    fn from(error: QuantumError) -> String {
+        error.to_string()
+    }
+}
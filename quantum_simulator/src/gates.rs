@@ -3,8 +3,11 @@ This code implements fundamental quantum gates and operations for a quantum circ
 
 Key concepts implemented:
 1. Quantum Gates: Mathematical representations of quantum operations
-   - Single-qubit gates (X, Y, Z, H, S, T)
-   - Two-qubit gates (CNOT)
+   - Single-qubit gates (X, Y, Z, H, S, T, SX)
+   - Two-qubit gates (CNOT, CZ, SWAP, iSWAP, controlled-phase)
+   - Three-qubit gates (Toffoli, Fredkin)
+   - Custom gates built from any 2^k × 2^k unitary matrix, checked for
+     unitarity when they are created
    Learn more: https://qiskit.org/textbook/ch-states/single-qubit-gates.html
 
 2. Complex Linear Algebra
@@ -18,6 +21,8 @@ Key concepts implemented:
    - Hadamard (H): Creates superposition states
    - Phase (S) and T gates: Important for quantum algorithms
    - Rotation gates: Arbitrary rotations on Bloch sphere
+   - RX, RY, RZ, U3 and phase-shift gates: Standard parameterized rotations
+     used by variational algorithms
    Reference: https://en.wikipedia.org/wiki/Quantum_logic_gate
 
 4. Testing:
@@ -30,6 +35,8 @@ Implementation follows standard quantum computing conventions:
 - States are normalized complex vectors
 - Gates are unitary matrices
 - CNOT implements controlled operations
+- Multi-qubit gate matrices list their qubits most-significant first, so
+  the first qubit passed to a multi-qubit gate is the leftmost in |ab⟩
 - All operations preserve quantum mechanical properties
 
 For mathematical background:
@@ -37,7 +44,9 @@ For mathematical background:
 - Linear Algebra: https://arxiv.org/abs/quant-ph/0001066
 */
 
-use nalgebra::{Complex, DVector, Matrix2};
+use crate::error::QuantumError;
+use crate::qasm::is_reserved_gate_name;
+use nalgebra::{Complex, DMatrix, DVector, Matrix2};
 use std::f64::consts::PI;
 
 /// Trait defining the interface for quantum gates
@@ -47,6 +56,37 @@ pub trait QuantumGate {
     fn name(&self) -> &'static str;
 }
 
+/// Trait for gates acting on any number of qubits
+///
+/// `matrix` returns the 2^k × 2^k unitary for `k = n_qubits()`. The first
+/// qubit the gate is applied to is the most significant bit of the row and
+/// column index, which matches the textbook form of CNOT, SWAP, etc.
+pub trait MultiQubitGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>>;
+    fn n_qubits(&self) -> usize;
+    fn name(&self) -> &str;
+}
+
+/// Largest entry of U†U − I, in magnitude, that still counts as unitary
+const UNITARY_TOLERANCE: f64 = 1e-10;
+
+/// Returns true if `matrix` is square and U†U = I within `UNITARY_TOLERANCE`
+pub(crate) fn is_unitary(matrix: &DMatrix<Complex<f64>>) -> bool {
+    if matrix.nrows() != matrix.ncols() {
+        return false;
+    }
+    let product = matrix.adjoint() * matrix;
+    let identity = DMatrix::<Complex<f64>>::identity(matrix.nrows(), matrix.ncols());
+    (product - identity)
+        .iter()
+        .all(|x| x.norm_sqr() <= UNITARY_TOLERANCE * UNITARY_TOLERANCE)
+}
+
+/// Helper function to build a square complex matrix from real entries
+fn real_matrix(dim: usize, entries: &[f64]) -> DMatrix<Complex<f64>> {
+    DMatrix::from_row_iterator(dim, dim, entries.iter().map(|&x| Complex::new(x, 0.0)))
+}
+
 /// Helper function to apply a 2x2 matrix to a quantum state
 fn apply_matrix(matrix: &Matrix2<Complex<f64>>, state: &mut DVector<Complex<f64>>) {
     if state.len() != 2 {
@@ -196,6 +236,29 @@ impl QuantumGate for TGate {
     }
 }
 
+// SX Gate (square root of X)
+#[derive(Debug, Clone, Copy)]
+pub struct SXGate;
+impl QuantumGate for SXGate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let matrix = self.matrix();
+        apply_matrix(&matrix, state);
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        Matrix2::new(
+            Complex::new(0.5, 0.5),
+            Complex::new(0.5, -0.5),
+            Complex::new(0.5, -0.5),
+            Complex::new(0.5, 0.5),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "SX"
+    }
+}
+
 // Rotation Gate
 #[derive(Debug, Clone)]
 pub struct RotationGate {
@@ -228,27 +291,509 @@ impl QuantumGate for RotationGate {
     }
 }
 
+// RX Gate (rotation about the X axis by theta)
+#[derive(Debug, Clone)]
+pub struct RXGate {
+    theta: f64,
+}
+
+impl RXGate {
+    pub fn new(theta: f64) -> Self {
+        Self { theta }
+    }
+}
+
+impl QuantumGate for RXGate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let matrix = self.matrix();
+        apply_matrix(&matrix, state);
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        let (sin, cos) = (self.theta / 2.0).sin_cos();
+        Matrix2::new(
+            Complex::new(cos, 0.0),
+            Complex::new(0.0, -sin),
+            Complex::new(0.0, -sin),
+            Complex::new(cos, 0.0),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "RX"
+    }
+}
+
+// RY Gate (rotation about the Y axis by theta)
+#[derive(Debug, Clone)]
+pub struct RYGate {
+    theta: f64,
+}
+
+impl RYGate {
+    pub fn new(theta: f64) -> Self {
+        Self { theta }
+    }
+}
+
+impl QuantumGate for RYGate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let matrix = self.matrix();
+        apply_matrix(&matrix, state);
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        let (sin, cos) = (self.theta / 2.0).sin_cos();
+        Matrix2::new(
+            Complex::new(cos, 0.0),
+            Complex::new(-sin, 0.0),
+            Complex::new(sin, 0.0),
+            Complex::new(cos, 0.0),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "RY"
+    }
+}
+
+// RZ Gate (rotation about the Z axis by theta)
+#[derive(Debug, Clone)]
+pub struct RZGate {
+    theta: f64,
+}
+
+impl RZGate {
+    pub fn new(theta: f64) -> Self {
+        Self { theta }
+    }
+}
+
+impl QuantumGate for RZGate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let matrix = self.matrix();
+        apply_matrix(&matrix, state);
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        let half = self.theta / 2.0;
+        Matrix2::new(
+            Complex::new(half.cos(), -half.sin()),
+            Complex::new(0.0, 0.0),
+            Complex::new(0.0, 0.0),
+            Complex::new(half.cos(), half.sin()),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "RZ"
+    }
+}
+
+// Phase Shift Gate (applies e^{iλ} to |1⟩; S and T are the λ = π/2, π/4 cases)
+#[derive(Debug, Clone)]
+pub struct PhaseShiftGate {
+    lambda: f64,
+}
+
+impl PhaseShiftGate {
+    pub fn new(lambda: f64) -> Self {
+        Self { lambda }
+    }
+}
+
+impl QuantumGate for PhaseShiftGate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let matrix = self.matrix();
+        apply_matrix(&matrix, state);
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        Matrix2::new(
+            Complex::new(1.0, 0.0),
+            Complex::new(0.0, 0.0),
+            Complex::new(0.0, 0.0),
+            Complex::new(self.lambda.cos(), self.lambda.sin()),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "P"
+    }
+}
+
+// U3 Gate (general single-qubit rotation with Euler angles theta, phi, lambda)
+#[derive(Debug, Clone)]
+pub struct U3Gate {
+    theta: f64,
+    phi: f64,
+    lambda: f64,
+}
+
+impl U3Gate {
+    pub fn new(theta: f64, phi: f64, lambda: f64) -> Self {
+        Self { theta, phi, lambda }
+    }
+}
+
+impl QuantumGate for U3Gate {
+    fn apply(&self, state: &mut DVector<Complex<f64>>) {
+        let matrix = self.matrix();
+        apply_matrix(&matrix, state);
+    }
+
+    fn matrix(&self) -> Matrix2<Complex<f64>> {
+        let (sin, cos) = (self.theta / 2.0).sin_cos();
+        let phase = |angle: f64| Complex::new(angle.cos(), angle.sin());
+        Matrix2::new(
+            Complex::new(cos, 0.0),
+            -phase(self.lambda) * sin,
+            phase(self.phi) * sin,
+            phase(self.phi + self.lambda) * cos,
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "U3"
+    }
+}
+
+/// Splits a single-qubit unitary into a global phase and U3 angles
+///
+/// Returns `(alpha, theta, phi, lambda)` with
+/// `matrix = e^{iα} · U3(θ, φ, λ)`. When θ is 0 or π only φ + λ (or φ − λ)
+/// is determined, and the free angle is set to zero.
+pub fn u3_decomposition(matrix: &Matrix2<Complex<f64>>) -> (f64, f64, f64, f64) {
+    let (u00, u01, u10, u11) = (
+        matrix[(0, 0)],
+        matrix[(0, 1)],
+        matrix[(1, 0)],
+        matrix[(1, 1)],
+    );
+    let abs = |z: Complex<f64>| z.norm_sqr().sqrt();
+    let arg = |z: Complex<f64>| z.im.atan2(z.re);
+    let theta = 2.0 * abs(u10).atan2(abs(u00));
+    let eps = 1e-12;
+
+    if abs(u10) < eps {
+        // Diagonal: only φ + λ matters
+        let alpha = arg(u00);
+        (alpha, theta, 0.0, arg(u11) - alpha)
+    } else if abs(u00) < eps {
+        // Anti-diagonal: only φ − λ matters
+        let alpha = arg(-u01);
+        (alpha, theta, arg(u10) - alpha, 0.0)
+    } else {
+        let alpha = arg(u00);
+        (alpha, theta, arg(u10) - alpha, arg(-u01) - alpha)
+    }
+}
+
 // CNOT Gate (Controlled-NOT)
 #[derive(Debug, Clone, Copy)]
 pub struct CNOTGate;
 
 impl CNOTGate {
+    /// Flips `target` on every basis state of an n-qubit register where
+    /// `control` is |1⟩
     pub fn apply_controlled(
         &self,
         state: &mut DVector<Complex<f64>>,
         control: usize,
         target: usize,
     ) {
-        if state.len() != 4 {
-            panic!("CNOT gate requires a 2-qubit state (4-dimensional vector)");
+        let n = state.len();
+        if !n.is_power_of_two() || (1 << control) >= n || (1 << target) >= n {
+            panic!("Control and target qubits must lie inside the state vector");
+        }
+        if control == target {
+            panic!("Control and target qubits must be different");
         }
 
-        let mut new_state = state.clone();
-        if control == 1 {
-            new_state[2] = state[3];
-            new_state[3] = state[2];
+        for i in 0..n {
+            if (i & (1 << control)) != 0 && (i & (1 << target)) == 0 {
+                state.swap_rows(i, i | (1 << target));
+            }
         }
-        *state = new_state;
+    }
+}
+
+impl MultiQubitGate for CNOTGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        real_matrix(
+            4,
+            &[
+                1.0, 0.0, 0.0, 0.0, //
+                0.0, 1.0, 0.0, 0.0, //
+                0.0, 0.0, 0.0, 1.0, //
+                0.0, 0.0, 1.0, 0.0,
+            ],
+        )
+    }
+
+    fn n_qubits(&self) -> usize {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "CNOT"
+    }
+}
+
+// Controlled-Z Gate
+#[derive(Debug, Clone, Copy)]
+pub struct CZGate;
+impl MultiQubitGate for CZGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        real_matrix(
+            4,
+            &[
+                1.0, 0.0, 0.0, 0.0, //
+                0.0, 1.0, 0.0, 0.0, //
+                0.0, 0.0, 1.0, 0.0, //
+                0.0, 0.0, 0.0, -1.0,
+            ],
+        )
+    }
+
+    fn n_qubits(&self) -> usize {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "CZ"
+    }
+}
+
+// SWAP Gate
+#[derive(Debug, Clone, Copy)]
+pub struct SwapGate;
+impl MultiQubitGate for SwapGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        real_matrix(
+            4,
+            &[
+                1.0, 0.0, 0.0, 0.0, //
+                0.0, 0.0, 1.0, 0.0, //
+                0.0, 1.0, 0.0, 0.0, //
+                0.0, 0.0, 0.0, 1.0,
+            ],
+        )
+    }
+
+    fn n_qubits(&self) -> usize {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "SWAP"
+    }
+}
+
+// iSWAP Gate (swaps |01⟩ and |10⟩ with a phase of i)
+#[derive(Debug, Clone, Copy)]
+pub struct ISwapGate;
+impl MultiQubitGate for ISwapGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        let mut matrix = DMatrix::from_element(4, 4, Complex::new(0.0, 0.0));
+        matrix[(0, 0)] = Complex::new(1.0, 0.0);
+        matrix[(1, 2)] = Complex::new(0.0, 1.0);
+        matrix[(2, 1)] = Complex::new(0.0, 1.0);
+        matrix[(3, 3)] = Complex::new(1.0, 0.0);
+        matrix
+    }
+
+    fn n_qubits(&self) -> usize {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "iSWAP"
+    }
+}
+
+// Controlled-Phase Gate (applies e^{iφ} to |11⟩)
+#[derive(Debug, Clone)]
+pub struct CPhaseGate {
+    phi: f64,
+}
+
+impl CPhaseGate {
+    pub fn new(phi: f64) -> Self {
+        Self { phi }
+    }
+}
+
+impl MultiQubitGate for CPhaseGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        let mut matrix = DMatrix::identity(4, 4);
+        matrix[(3, 3)] = Complex::new(self.phi.cos(), self.phi.sin());
+        matrix
+    }
+
+    fn n_qubits(&self) -> usize {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "CPhase"
+    }
+}
+
+/// Embeds `matrix` in a gate that applies it only when the controls hold
+/// `control_state`
+///
+/// The controls are the leading qubits of the result (controls[0] most
+/// significant), followed by the qubits of `matrix`. Bit i of
+/// `control_state` is the value required of control i.
+pub(crate) fn controlled_matrix(
+    matrix: &DMatrix<Complex<f64>>,
+    n_controls: usize,
+    control_state: u64,
+) -> DMatrix<Complex<f64>> {
+    let block = matrix.nrows();
+    let mut result = DMatrix::identity(block << n_controls, block << n_controls);
+    let local = (0..n_controls)
+        .filter(|i| control_state & (1 << i) != 0)
+        .fold(0, |acc, i| acc | (1 << (n_controls - 1 - i)));
+    result
+        .slice_mut((local * block, local * block), (block, block))
+        .copy_from(matrix);
+    result
+}
+
+/// Checks that `matrix` is the 2^k × 2^k matrix of a gate on `n_qubits` qubits
+pub(crate) fn check_size(
+    matrix: &DMatrix<Complex<f64>>,
+    n_qubits: usize,
+    name: &str,
+) -> Result<(), QuantumError> {
+    let dim = 1 << n_qubits;
+    if matrix.nrows() != dim || matrix.ncols() != dim {
+        return Err(QuantumError::DimensionMismatch {
+            what: format!("rows and columns of the {} gate matrix", name),
+            expected: dim,
+            found: if matrix.nrows() != dim {
+                matrix.nrows()
+            } else {
+                matrix.ncols()
+            },
+        });
+    }
+    Ok(())
+}
+
+// Toffoli Gate (Controlled-Controlled-NOT)
+#[derive(Debug, Clone, Copy)]
+pub struct ToffoliGate;
+
+impl MultiQubitGate for ToffoliGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        controlled_matrix(&real_matrix(2, &[0.0, 1.0, 1.0, 0.0]), 2, 0b11)
+    }
+
+    fn n_qubits(&self) -> usize {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "Toffoli"
+    }
+}
+
+// Fredkin Gate (Controlled-SWAP)
+#[derive(Debug, Clone, Copy)]
+pub struct FredkinGate;
+
+impl MultiQubitGate for FredkinGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        controlled_matrix(&SwapGate.matrix(), 1, 1)
+    }
+
+    fn n_qubits(&self) -> usize {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "Fredkin"
+    }
+}
+
+/// A gate defined by a user-supplied unitary matrix
+///
+/// The matrix follows the `MultiQubitGate` convention: the first qubit the
+/// gate is applied to is the most significant bit of the index. The name
+/// labels the gate in diagrams and becomes its `gate` name in OpenQASM, so
+/// it must be an identifier that OpenQASM does not already use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomGate {
+    name: String,
+    matrix: DMatrix<Complex<f64>>,
+}
+
+impl CustomGate {
+    /// Creates a gate from a 2^k × 2^k matrix, k ≥ 1
+    ///
+    /// Fails if the name is not an identifier or is taken by OpenQASM (a
+    /// keyword or type such as `measure` or `float`, a gate of qelib1.inc /
+    /// stdgates.inc, or the exported registers `q` and `c`), the matrix is
+    /// not square with a power-of-two dimension, or U†U differs from the
+    /// identity by more than `1e-10` in any entry.
+    pub fn from_matrix(name: &str, matrix: DMatrix<Complex<f64>>) -> Result<Self, QuantumError> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid || is_reserved_gate_name(name) {
+            return Err(QuantumError::InvalidName(name.to_string()));
+        }
+        let dim = matrix.nrows();
+        if matrix.ncols() != dim {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("columns of the {} matrix", name),
+                expected: dim,
+                found: matrix.ncols(),
+            });
+        }
+        if dim < 2 || !dim.is_power_of_two() {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("dimension of the {} matrix (a power of two)", name),
+                expected: dim.next_power_of_two().max(2),
+                found: dim,
+            });
+        }
+        if !is_unitary(&matrix) {
+            return Err(QuantumError::NonUnitary(name.to_string()));
+        }
+        Ok(CustomGate {
+            name: name.to_string(),
+            matrix,
+        })
+    }
+
+    /// Returns U†, named `<name>_dg` (or `<name>` again if this is already
+    /// an adjoint and `<name>` is not reserved, so `x_dg` becomes `x_dg_dg`)
+    pub fn adjoint(&self) -> CustomGate {
+        let name = match self.name.strip_suffix("_dg") {
+            Some(base) if !is_reserved_gate_name(base) => base.to_string(),
+            _ => format!("{}_dg", self.name),
+        };
+        CustomGate {
+            name,
+            matrix: self.matrix.adjoint(),
+        }
+    }
+}
+
+impl MultiQubitGate for CustomGate {
+    fn matrix(&self) -> DMatrix<Complex<f64>> {
+        self.matrix.clone()
+    }
+
+    fn n_qubits(&self) -> usize {
+        self.matrix.nrows().trailing_zeros() as usize
+    }
+
+    fn name(&self) -> &str {
+        &self.name
     }
 }
 
@@ -320,4 +865,183 @@ mod tests {
         let gate = XGate;
         gate.apply(&mut state);
     }
+
+    #[test]
+    fn test_rotation_gates_at_pi() {
+        // RX(π) = -iX, RY(π) = [[0, -1], [1, 0]], RZ(π) = diag(-i, i)
+        let rx = RXGate::new(PI).matrix();
+        assert_relative_eq!(rx[(1, 0)].im, -1.0, epsilon = 1e-10);
+        let ry = RYGate::new(PI).matrix();
+        assert_relative_eq!(ry[(1, 0)].re, 1.0, epsilon = 1e-10);
+        let rz = RZGate::new(PI).matrix();
+        assert_relative_eq!(rz[(0, 0)].im, -1.0, epsilon = 1e-10);
+        assert_relative_eq!(rz[(1, 1)].im, 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_u3_matches_named_gates() {
+        // U3(π/2, 0, π) = H and U3(0, 0, λ) = P(λ)
+        let h = U3Gate::new(PI / 2.0, 0.0, PI).matrix();
+        assert!((h - HadamardGate.matrix()).norm() < 1e-10);
+        let p = U3Gate::new(0.0, 0.0, PI / 4.0).matrix();
+        assert!((p - TGate.matrix()).norm() < 1e-10);
+        assert!((PhaseShiftGate::new(PI / 2.0).matrix() - PhaseGate.matrix()).norm() < 1e-10);
+    }
+
+    #[test]
+    fn test_u3_decomposition_round_trip() {
+        let gates: Vec<Box<dyn QuantumGate>> = vec![
+            Box::new(XGate),
+            Box::new(YGate),
+            Box::new(ZGate),
+            Box::new(HadamardGate),
+            Box::new(TGate),
+            Box::new(RotationGate::new(0.4)),
+            Box::new(RXGate::new(-1.3)),
+            Box::new(U3Gate::new(0.7, 2.1, -0.4)),
+        ];
+        for gate in gates {
+            let (alpha, theta, phi, lambda) = u3_decomposition(&gate.matrix());
+            let rebuilt =
+                U3Gate::new(theta, phi, lambda).matrix() * Complex::new(alpha.cos(), alpha.sin());
+            assert!(
+                (rebuilt - gate.matrix()).norm() < 1e-10,
+                "{} was not rebuilt",
+                gate.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_cnot_on_larger_register() {
+        // |q2 q1 q0⟩ = |011⟩, control q1, target q2 -> |111⟩
+        let mut state = DVector::from_element(8, Complex::new(0.0, 0.0));
+        state[0b011] = Complex::new(1.0, 0.0);
+        CNOTGate.apply_controlled(&mut state, 1, 2);
+        assert_relative_eq!(state[0b111].re, 1.0, epsilon = 1e-10);
+        assert_relative_eq!(state[0b011].norm_sqr(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_two_qubit_gates_are_unitary() {
+        let gates: Vec<Box<dyn MultiQubitGate>> = vec![
+            Box::new(CNOTGate),
+            Box::new(CZGate),
+            Box::new(SwapGate),
+            Box::new(ISwapGate),
+            Box::new(CPhaseGate::new(0.7)),
+        ];
+        for gate in gates {
+            let m = gate.matrix();
+            assert_eq!(m.nrows(), 1 << gate.n_qubits());
+            let product = m.adjoint() * &m;
+            let identity = DMatrix::<Complex<f64>>::identity(4, 4);
+            assert!(
+                (product - identity).norm() < 1e-10,
+                "{} is not unitary",
+                gate.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_toffoli_and_fredkin_matrices() {
+        let toffoli = ToffoliGate.matrix();
+        for i in 0..8 {
+            // |ab c⟩ with a as the most significant bit: flip c only for a = b = 1
+            let expected = if i >= 6 { i ^ 1 } else { i };
+            assert_relative_eq!(toffoli[(expected, i)].re, 1.0);
+        }
+
+        let fredkin = FredkinGate.matrix();
+        for i in 0..8 {
+            let expected = match i {
+                0b101 => 0b110,
+                0b110 => 0b101,
+                _ => i,
+            };
+            assert_relative_eq!(fredkin[(expected, i)].re, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_controlled_matrix_with_negative_control() {
+        // X on the last qubit when control 0 is |0⟩ and control 1 is |1⟩
+        let matrix = controlled_matrix(&real_matrix(2, &[0.0, 1.0, 1.0, 0.0]), 2, 0b10);
+        // controls[0] is the most significant bit, so the active block is |01 t⟩
+        assert_relative_eq!(matrix[(0b011, 0b010)].re, 1.0);
+        assert_relative_eq!(matrix[(0b111, 0b111)].re, 1.0);
+    }
+
+    #[test]
+    fn test_custom_gate_validation() {
+        let sqrt_x = DMatrix::from_row_slice(
+            2,
+            2,
+            &[
+                Complex::new(0.5, 0.5),
+                Complex::new(0.5, -0.5),
+                Complex::new(0.5, -0.5),
+                Complex::new(0.5, 0.5),
+            ],
+        );
+        let gate = CustomGate::from_matrix("sqrt_x", sqrt_x.clone()).unwrap();
+        assert_eq!(gate.n_qubits(), 1);
+        assert_eq!(gate.name(), "sqrt_x");
+        assert!((&sqrt_x * &sqrt_x - real_matrix(2, &[0.0, 1.0, 1.0, 0.0])).norm() < 1e-12);
+
+        let adjoint = gate.adjoint();
+        assert_eq!(adjoint.name(), "sqrt_x_dg");
+        assert_eq!(adjoint.adjoint(), gate);
+        for name in ["x_dg", "s_dg", "measure_dg"] {
+            let named = CustomGate::from_matrix(name, sqrt_x.clone()).unwrap();
+            let adjoint = named.adjoint();
+            assert_eq!(adjoint.name(), format!("{}_dg", name));
+            assert!(!is_reserved_gate_name(adjoint.name()));
+        }
+
+        let toffoli = CustomGate::from_matrix("toff", ToffoliGate.matrix()).unwrap();
+        assert_eq!(toffoli.n_qubits(), 3);
+
+        assert_eq!(
+            CustomGate::from_matrix("bad", real_matrix(2, &[1.0, 1.0, 0.0, 1.0])),
+            Err(QuantumError::NonUnitary("bad".to_string()))
+        );
+        // Slightly off, beyond the tolerance
+        let scaled = sqrt_x * Complex::new(1.0 + 1e-8, 0.0);
+        assert!(CustomGate::from_matrix("scaled", scaled).is_err());
+        assert!(matches!(
+            CustomGate::from_matrix("three", DMatrix::identity(3, 3)),
+            Err(QuantumError::DimensionMismatch { found: 3, .. })
+        ));
+        assert!(matches!(
+            CustomGate::from_matrix("wide", DMatrix::identity(2, 4)),
+            Err(QuantumError::DimensionMismatch { .. })
+        ));
+        assert_eq!(
+            CustomGate::from_matrix("2x", DMatrix::identity(2, 2)),
+            Err(QuantumError::InvalidName("2x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_custom_gate_names_must_not_be_reserved() {
+        let keywords = [
+            "measure", "if", "OPENQASM", "def", "let", "const", "box", "delay", "return", "end",
+            "in", "true", "false",
+        ];
+        let types = ["int", "uint", "float", "bool", "angle", "bit", "qubit"];
+        let builtins = ["pi", "sqrt", "arccos", "popcount"];
+        let gates = ["U", "CX", "h", "cu", "iswap"];
+        let registers = ["q", "c"];
+        for names in [&keywords[..], &types, &builtins, &gates, &registers] {
+            for &reserved in names {
+                assert_eq!(
+                    CustomGate::from_matrix(reserved, DMatrix::identity(2, 2)),
+                    Err(QuantumError::InvalidName(reserved.to_string()))
+                );
+            }
+        }
+        assert!(CustomGate::from_matrix("qq", DMatrix::identity(2, 2)).is_ok());
+    }
 }
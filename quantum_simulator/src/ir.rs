@@ -0,0 +1,1546 @@
+/*
+This file implements a recorded circuit: a list of gate instructions that can be
+built once and executed many times.
+
+Key concepts:
+1. Gates as data:
+   - `Gate` names every gate from gates.rs together with its angles
+   - Angles may be symbolic `Parameter`s (see parameter.rs)
+   - `Gate::Custom` carries a user-defined unitary (`CustomGate`), which can
+     be controlled, inverted and exported like the built-in gates
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_circuit
+
+2. Circuit templates:
+   - A `Circuit` records which gate acts on which qubits without touching a
+     state vector
+   - `bind()` substitutes parameter values and returns a new circuit, so a
+     variational ansatz is built once and evaluated for many angle vectors
+   - Reference: https://arxiv.org/abs/1304.3061 (variational eigensolver)
+
+3. Instructions:
+   - Gates, singly-controlled gates, measurements, resets and barriers
+   - Measurements write into classical bits, and `Conditional` instructions
+     only run when a group of classical bits holds a given value
+   - Classical bits can be grouped into named registers, so feed-forward such
+     as teleportation reads `if (m == 1)` instead of listing bit indices
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_teleportation
+   - Because the circuit is plain data it can be inspected, cloned, inverted
+     and rewritten before anything is simulated
+
+4. Execution:
+   - Circuits are executed by a `Backend` (see backend.rs); the state-vector
+     `QuantumCircuit` is the first one
+   - Every angle must be bound before execution
+
+5. Unitaries and equivalence:
+   - `unitary()` builds the full 2^n × 2^n matrix by running the circuit on
+     every basis state, so it is only practical for small circuits
+   - `equivalent()` compares two circuits up to a global phase e^{iα}, which
+     no measurement can observe
+   - Learn more: https://en.wikipedia.org/wiki/Unitary_matrix
+*/
+
+use crate::backend::Backend;
+use crate::circuit::QuantumCircuit;
+use crate::error::QuantumError;
+use crate::gates::{
+    CNOTGate, CPhaseGate, CZGate, CustomGate, HadamardGate, ISwapGate, MultiQubitGate, PhaseGate,
+    PhaseShiftGate, QuantumGate, RXGate, RYGate, RZGate, RotationGate, SXGate, SwapGate, TGate,
+    U3Gate, XGate, YGate, ZGate,
+};
+use crate::parameter::{Angle, Parameter};
+use crate::qasm::is_reserved_register_name;
+use nalgebra::{Complex, DMatrix, Matrix2};
+use std::collections::{BTreeSet, HashMap};
+
+/// A gate from gates.rs together with its (possibly symbolic) angles
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gate {
+    X,
+    Y,
+    Z,
+    H,
+    S,
+    T,
+    SX,
+    Rotation(Angle),
+    RX(Angle),
+    RY(Angle),
+    RZ(Angle),
+    PhaseShift(Angle),
+    U3(Angle, Angle, Angle),
+    CNOT,
+    CZ,
+    Swap,
+    ISwap,
+    CPhase(Angle),
+    /// A user-defined unitary on any number of qubits
+    Custom(CustomGate),
+}
+
+impl From<CustomGate> for Gate {
+    fn from(gate: CustomGate) -> Self {
+        Gate::Custom(gate)
+    }
+}
+
+impl Gate {
+    /// Number of qubits the gate acts on
+    pub fn n_qubits(&self) -> usize {
+        match self {
+            Gate::CNOT | Gate::CZ | Gate::Swap | Gate::ISwap | Gate::CPhase(_) => 2,
+            Gate::Custom(gate) => gate.n_qubits(),
+            _ => 1,
+        }
+    }
+
+    /// Name of the gate, matching `QuantumGate::name` / `MultiQubitGate::name`
+    pub fn name(&self) -> &str {
+        match self {
+            Gate::X => XGate.name(),
+            Gate::Y => YGate.name(),
+            Gate::Z => ZGate.name(),
+            Gate::H => HadamardGate.name(),
+            Gate::S => PhaseGate.name(),
+            Gate::T => TGate.name(),
+            Gate::SX => SXGate.name(),
+            Gate::Rotation(_) => "Rotation",
+            Gate::RX(_) => "RX",
+            Gate::RY(_) => "RY",
+            Gate::RZ(_) => "RZ",
+            Gate::PhaseShift(_) => "P",
+            Gate::U3(..) => "U3",
+            Gate::CNOT => CNOTGate.name(),
+            Gate::CZ => CZGate.name(),
+            Gate::Swap => SwapGate.name(),
+            Gate::ISwap => ISwapGate.name(),
+            Gate::CPhase(_) => "CPhase",
+            Gate::Custom(gate) => gate.name(),
+        }
+    }
+
+    /// Angles of the gate in declaration order
+    pub fn angles(&self) -> Vec<&Angle> {
+        match self {
+            Gate::Rotation(a)
+            | Gate::RX(a)
+            | Gate::RY(a)
+            | Gate::RZ(a)
+            | Gate::PhaseShift(a)
+            | Gate::CPhase(a) => vec![a],
+            Gate::U3(theta, phi, lambda) => vec![theta, phi, lambda],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns a copy of the gate with every angle passed through `f`
+    pub fn map_angles<F: Fn(&Angle) -> Angle>(&self, f: F) -> Gate {
+        match self {
+            Gate::Rotation(a) => Gate::Rotation(f(a)),
+            Gate::RX(a) => Gate::RX(f(a)),
+            Gate::RY(a) => Gate::RY(f(a)),
+            Gate::RZ(a) => Gate::RZ(f(a)),
+            Gate::PhaseShift(a) => Gate::PhaseShift(f(a)),
+            Gate::CPhase(a) => Gate::CPhase(f(a)),
+            Gate::U3(theta, phi, lambda) => Gate::U3(f(theta), f(phi), f(lambda)),
+            other => other.clone(),
+        }
+    }
+
+    /// Returns true if any angle still refers to an unbound parameter
+    pub fn is_parameterized(&self) -> bool {
+        self.angles().iter().any(|a| a.parameter().is_some())
+    }
+
+    /// Returns the 2^k × 2^k unitary of the gate
+    pub fn matrix(&self) -> Result<DMatrix<Complex<f64>>, QuantumError> {
+        if self.n_qubits() == 1 {
+            let m = self.matrix2()?;
+            return Ok(DMatrix::from_iterator(2, 2, m.iter().cloned()));
+        }
+        Ok(match self {
+            Gate::CNOT => CNOTGate.matrix(),
+            Gate::CZ => CZGate.matrix(),
+            Gate::Swap => SwapGate.matrix(),
+            Gate::ISwap => ISwapGate.matrix(),
+            Gate::CPhase(phi) => CPhaseGate::new(phi.resolve()?).matrix(),
+            Gate::Custom(gate) => gate.matrix(),
+            _ => unreachable!("single-qubit gates are handled above"),
+        })
+    }
+
+    /// Returns the 2 × 2 unitary of a single-qubit gate
+    pub fn matrix2(&self) -> Result<Matrix2<Complex<f64>>, QuantumError> {
+        Ok(match self {
+            Gate::X => XGate.matrix(),
+            Gate::Y => YGate.matrix(),
+            Gate::Z => ZGate.matrix(),
+            Gate::H => HadamardGate.matrix(),
+            Gate::S => PhaseGate.matrix(),
+            Gate::T => TGate.matrix(),
+            Gate::SX => SXGate.matrix(),
+            Gate::Rotation(theta) => RotationGate::new(theta.resolve()?).matrix(),
+            Gate::RX(theta) => RXGate::new(theta.resolve()?).matrix(),
+            Gate::RY(theta) => RYGate::new(theta.resolve()?).matrix(),
+            Gate::RZ(theta) => RZGate::new(theta.resolve()?).matrix(),
+            Gate::PhaseShift(lambda) => PhaseShiftGate::new(lambda.resolve()?).matrix(),
+            Gate::U3(theta, phi, lambda) => {
+                U3Gate::new(theta.resolve()?, phi.resolve()?, lambda.resolve()?).matrix()
+            }
+            Gate::Custom(gate) if gate.n_qubits() == 1 => {
+                Matrix2::from_iterator(gate.matrix().iter().cloned())
+            }
+            _ => {
+                return Err(QuantumError::DimensionMismatch {
+                    what: format!("qubits of the {} gate", self.name()),
+                    expected: 1,
+                    found: self.n_qubits(),
+                })
+            }
+        })
+    }
+
+    /// Returns the inverse gate, if it belongs to the `Gate` set
+    ///
+    /// S and T invert to phase shifts and custom gates to their adjoint; SX
+    /// and iSWAP have no inverse in the set and return `None` (see
+    /// `Instruction::inverse`).
+    pub fn inverse(&self) -> Option<Gate> {
+        let pi = std::f64::consts::PI;
+        Some(match self {
+            Gate::X | Gate::Y | Gate::Z | Gate::H => self.clone(),
+            Gate::CNOT | Gate::CZ | Gate::Swap => self.clone(),
+            Gate::S => Gate::PhaseShift(Angle::Value(-pi / 2.0)),
+            Gate::T => Gate::PhaseShift(Angle::Value(-pi / 4.0)),
+            Gate::U3(theta, phi, lambda) => Gate::U3(-theta.clone(), -lambda.clone(), -phi.clone()),
+            Gate::Custom(gate) => Gate::Custom(gate.adjoint()),
+            Gate::SX | Gate::ISwap => return None,
+            _ => self.map_angles(|a| -a.clone()),
+        })
+    }
+}
+
+/// A single step of a recorded circuit
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// A gate applied to an ordered list of qubits
+    Gate { gate: Gate, qubits: Vec<usize> },
+    /// A single-qubit gate applied to `target` when `control` is |1⟩
+    Controlled {
+        gate: Gate,
+        control: usize,
+        target: usize,
+    },
+    /// `gate` applied to `targets` when the controls hold `control_state`
+    ///
+    /// Bit i of `control_state` is the value required of `controls[i]`; a 0
+    /// bit is a negative (control-on-|0⟩) control.
+    MultiControlled {
+        gate: Gate,
+        controls: Vec<usize>,
+        control_state: u64,
+        targets: Vec<usize>,
+    },
+    /// A computational-basis measurement of `qubit` stored in `clbit`
+    Measure { qubit: usize, clbit: usize },
+    /// Returns `qubit` to |0⟩, whatever state it was in
+    Reset { qubit: usize },
+    /// A marker that passes must not move gates across
+    Barrier { qubits: Vec<usize> },
+    /// Runs `instruction` only if the classical bits hold `value`
+    ///
+    /// `clbits[0]` is the least significant bit of the compared integer. The
+    /// integer is a `u64`, so a condition covers at most 64 bits.
+    Conditional {
+        clbits: Vec<usize>,
+        value: u64,
+        instruction: Box<Instruction>,
+    },
+}
+
+impl Instruction {
+    /// Qubits touched by the instruction
+    pub fn qubits(&self) -> Vec<usize> {
+        match self {
+            Instruction::Gate { qubits, .. } | Instruction::Barrier { qubits } => qubits.clone(),
+            Instruction::Controlled {
+                control, target, ..
+            } => vec![*control, *target],
+            Instruction::MultiControlled {
+                controls, targets, ..
+            } => controls.iter().chain(targets).copied().collect(),
+            Instruction::Measure { qubit, .. } | Instruction::Reset { qubit } => vec![*qubit],
+            Instruction::Conditional { instruction, .. } => instruction.qubits(),
+        }
+    }
+
+    /// Returns the gate of a gate or controlled instruction
+    pub fn gate(&self) -> Option<&Gate> {
+        match self {
+            Instruction::Gate { gate, .. }
+            | Instruction::Controlled { gate, .. }
+            | Instruction::MultiControlled { gate, .. } => Some(gate),
+            Instruction::Conditional { instruction, .. } => instruction.gate(),
+            _ => None,
+        }
+    }
+
+    /// Returns the instructions that undo this one
+    ///
+    /// Fails for measurements, resets and conditionals.
+    pub fn inverse(&self) -> Result<Vec<Instruction>, String> {
+        match self {
+            Instruction::Gate { gate, qubits } => match gate.inverse() {
+                Some(inverse) => Ok(vec![Instruction::Gate {
+                    gate: inverse,
+                    qubits: qubits.clone(),
+                }]),
+                // SX† = SX · SX · SX
+                None if *gate == Gate::SX => Ok(vec![self.clone(), self.clone(), self.clone()]),
+                // iSWAP† = (Z ⊗ Z) · iSWAP
+                None => Ok(vec![
+                    Instruction::Gate {
+                        gate: Gate::ISwap,
+                        qubits: qubits.clone(),
+                    },
+                    Instruction::Gate {
+                        gate: Gate::Z,
+                        qubits: vec![qubits[0]],
+                    },
+                    Instruction::Gate {
+                        gate: Gate::Z,
+                        qubits: vec![qubits[1]],
+                    },
+                ]),
+            },
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => {
+                let uncontrolled = Instruction::Gate {
+                    gate: gate.clone(),
+                    qubits: vec![*target],
+                };
+                Ok(uncontrolled
+                    .inverse()?
+                    .into_iter()
+                    .map(|inverse| match inverse {
+                        Instruction::Gate { gate, .. } => Instruction::Controlled {
+                            gate,
+                            control: *control,
+                            target: *target,
+                        },
+                        _ => unreachable!("gates invert to gates"),
+                    })
+                    .collect())
+            }
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                targets,
+            } => {
+                let uncontrolled = Instruction::Gate {
+                    gate: gate.clone(),
+                    qubits: targets.clone(),
+                };
+                Ok(uncontrolled
+                    .inverse()?
+                    .into_iter()
+                    .map(|inverse| match inverse {
+                        Instruction::Gate { gate, qubits } => Instruction::MultiControlled {
+                            gate,
+                            controls: controls.clone(),
+                            control_state: *control_state,
+                            targets: qubits,
+                        },
+                        _ => unreachable!("gates invert to gates"),
+                    })
+                    .collect())
+            }
+            Instruction::Measure { .. } => Err("Measurements cannot be inverted".to_string()),
+            Instruction::Reset { .. } => Err("Resets cannot be inverted".to_string()),
+            Instruction::Conditional { .. } => {
+                Err("Classically controlled instructions cannot be inverted".to_string())
+            }
+            Instruction::Barrier { .. } => Ok(vec![self.clone()]),
+        }
+    }
+
+    /// Returns a copy with every gate angle passed through `f`
+    pub fn map_angles(&self, f: &dyn Fn(&Angle) -> Angle) -> Instruction {
+        match self {
+            Instruction::Gate { gate, qubits } => Instruction::Gate {
+                gate: gate.map_angles(f),
+                qubits: qubits.clone(),
+            },
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => Instruction::Controlled {
+                gate: gate.map_angles(f),
+                control: *control,
+                target: *target,
+            },
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                targets,
+            } => Instruction::MultiControlled {
+                gate: gate.map_angles(f),
+                controls: controls.clone(),
+                control_state: *control_state,
+                targets: targets.clone(),
+            },
+            Instruction::Conditional {
+                clbits,
+                value,
+                instruction,
+            } => Instruction::Conditional {
+                clbits: clbits.clone(),
+                value: *value,
+                instruction: Box::new(instruction.map_angles(f)),
+            },
+            other => other.clone(),
+        }
+    }
+}
+
+/// Largest circuit `Circuit::unitary()` will expand (a 4096 × 4096 matrix)
+const MAX_UNITARY_QUBITS: usize = 12;
+
+/// A named, contiguous group of classical bits
+///
+/// Bit i of the register is classical bit `start() + i`, and the register
+/// reads as an integer with bit 0 least significant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassicalRegister {
+    name: String,
+    start: usize,
+    size: usize,
+}
+
+impl ClassicalRegister {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Index of the register's first classical bit
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Number of bits in the register
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Classical bit indices of the register, least significant first
+    pub fn clbits(&self) -> Vec<usize> {
+        (self.start..self.start + self.size).collect()
+    }
+}
+
+/// An ordered list of instructions on fixed numbers of qubits and classical bits
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    n_qubits: usize,
+    n_clbits: usize,
+    registers: Vec<ClassicalRegister>,
+    instructions: Vec<Instruction>,
+}
+
+impl Circuit {
+    /// Creates an empty circuit on `n_qubits` qubits with one classical bit per qubit
+    pub fn new(n_qubits: usize) -> Result<Self, QuantumError> {
+        Self::with_clbits(n_qubits, n_qubits)
+    }
+
+    /// Creates an empty circuit with explicit qubit and classical bit counts
+    pub fn with_clbits(n_qubits: usize, n_clbits: usize) -> Result<Self, QuantumError> {
+        if n_qubits == 0 {
+            return Err(QuantumError::InvalidQubitCount(n_qubits));
+        }
+
+        Ok(Circuit {
+            n_qubits,
+            n_clbits,
+            registers: Vec::new(),
+            instructions: Vec::new(),
+        })
+    }
+
+    /// Returns an empty circuit with the same qubits, classical bits and
+    /// named registers
+    pub fn empty_like(&self) -> Circuit {
+        self.empty_with_qubits(self.n_qubits)
+    }
+
+    /// Like `empty_like`, on `n_qubits` qubits instead
+    pub(crate) fn empty_with_qubits(&self, n_qubits: usize) -> Circuit {
+        Circuit {
+            n_qubits,
+            n_clbits: self.n_clbits,
+            registers: self.registers.clone(),
+            instructions: Vec::new(),
+        }
+    }
+
+    /// Returns the number of qubits in the circuit
+    pub fn n_qubits(&self) -> usize {
+        self.n_qubits
+    }
+
+    /// Returns the number of classical bits in the circuit
+    pub fn n_clbits(&self) -> usize {
+        self.n_clbits
+    }
+
+    /// Appends a register of `size` new classical bits called `name`
+    ///
+    /// The new bits come after every existing classical bit. The name must be
+    /// an identifier that OpenQASM export can declare, so keywords, standard
+    /// gate names and the quantum register `q` are rejected.
+    pub fn add_register(&mut self, name: &str, size: usize) -> Result<&mut Self, String> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid || is_reserved_register_name(name) {
+            return Err(format!("'{}' is not a valid register name", name));
+        }
+        if self.register(name).is_ok() {
+            return Err(format!("Register '{}' already exists", name));
+        }
+        if size == 0 {
+            return Err("Registers need at least one bit".to_string());
+        }
+
+        self.registers.push(ClassicalRegister {
+            name: name.to_string(),
+            start: self.n_clbits,
+            size,
+        });
+        self.n_clbits += size;
+        Ok(self)
+    }
+
+    /// Returns the classical register called `name`
+    pub fn register(&self, name: &str) -> Result<&ClassicalRegister, String> {
+        self.registers
+            .iter()
+            .find(|r| r.name == name)
+            .ok_or_else(|| format!("Circuit has no register named '{}'", name))
+    }
+
+    /// Returns the named classical registers in the order they were added
+    pub fn registers(&self) -> &[ClassicalRegister] {
+        &self.registers
+    }
+
+    /// Returns the recorded instructions in execution order
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the number of instructions, barriers included
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Returns true if nothing has been recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    fn check_qubits(&self, qubits: &[usize]) -> Result<(), String> {
+        for (i, &q) in qubits.iter().enumerate() {
+            if q >= self.n_qubits {
+                return Err(format!(
+                    "Qubit {} is out of range for circuit with {} qubits",
+                    q, self.n_qubits
+                ));
+            }
+            if qubits[..i].contains(&q) {
+                return Err(format!("Qubit {} is listed more than once", q));
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends an instruction after validating its qubits
+    pub fn push(&mut self, instruction: Instruction) -> Result<&mut Self, String> {
+        self.validate(&instruction)?;
+        self.instructions.push(instruction);
+        Ok(self)
+    }
+
+    /// Checks an instruction against the circuit's qubits and classical bits
+    ///
+    /// The instruction inside a `Conditional` gets the same checks.
+    fn validate(&self, instruction: &Instruction) -> Result<(), String> {
+        let out_of_range = |clbit: usize| {
+            format!(
+                "Classical bit {} is out of range for circuit with {} classical bits",
+                clbit, self.n_clbits
+            )
+        };
+        match instruction {
+            Instruction::Gate { gate, qubits } if qubits.len() != gate.n_qubits() => {
+                return Err(format!(
+                    "{} gate acts on {} qubits but {} were given",
+                    gate.name(),
+                    gate.n_qubits(),
+                    qubits.len()
+                ));
+            }
+            Instruction::Controlled { gate, .. } if gate.n_qubits() != 1 => {
+                return Err(format!(
+                    "Only single-qubit gates can be controlled, got {}",
+                    gate.name()
+                ));
+            }
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                targets,
+            } => {
+                if targets.len() != gate.n_qubits() {
+                    return Err(format!(
+                        "{} gate acts on {} qubits but {} targets were given",
+                        gate.name(),
+                        gate.n_qubits(),
+                        targets.len()
+                    ));
+                }
+                if controls.is_empty() || controls.len() > 64 {
+                    return Err("Multi-controlled gates need between 1 and 64 controls".to_string());
+                }
+                if controls.len() < 64 && *control_state >= 1 << controls.len() {
+                    return Err(format!(
+                        "Control state {} does not fit in {} controls",
+                        control_state,
+                        controls.len()
+                    ));
+                }
+            }
+            Instruction::Measure { clbit, .. } if *clbit >= self.n_clbits => {
+                return Err(out_of_range(*clbit));
+            }
+            Instruction::Conditional {
+                clbits,
+                value,
+                instruction,
+            } => {
+                if let Some(&clbit) = clbits.iter().find(|&&c| c >= self.n_clbits) {
+                    return Err(out_of_range(clbit));
+                }
+                if clbits.len() > 64 {
+                    return Err(format!(
+                        "Conditions compare at most 64 classical bits, got {}",
+                        clbits.len()
+                    ));
+                }
+                if clbits.len() < 64 && *value >= 1 << clbits.len() {
+                    return Err(format!(
+                        "Value {} does not fit in {} classical bits",
+                        value,
+                        clbits.len()
+                    ));
+                }
+                match instruction.as_ref() {
+                    Instruction::Conditional { .. } | Instruction::Barrier { .. } => {
+                        return Err(
+                            "Only gates, measurements and resets can be conditioned".to_string()
+                        );
+                    }
+                    inner => self.validate(inner)?,
+                }
+            }
+            _ => {}
+        }
+        self.check_qubits(&instruction.qubits())
+    }
+
+    /// Appends `gate` acting on `qubits`
+    pub fn add_gate(&mut self, gate: Gate, qubits: &[usize]) -> Result<&mut Self, String> {
+        self.push(Instruction::Gate {
+            gate,
+            qubits: qubits.to_vec(),
+        })
+    }
+
+    /// Appends a single-qubit `gate` on `target` controlled by `control`
+    pub fn controlled(
+        &mut self,
+        gate: Gate,
+        control: usize,
+        target: usize,
+    ) -> Result<&mut Self, String> {
+        self.push(Instruction::Controlled {
+            gate,
+            control,
+            target,
+        })
+    }
+
+    /// Appends `gate` on `targets`, applied when every control is |1⟩
+    pub fn multi_controlled(
+        &mut self,
+        gate: Gate,
+        controls: &[usize],
+        targets: &[usize],
+    ) -> Result<&mut Self, String> {
+        let all_ones = if controls.len() >= 64 {
+            u64::MAX
+        } else {
+            (1 << controls.len()) - 1
+        };
+        self.multi_controlled_on(gate, controls, all_ones, targets)
+    }
+
+    /// Appends `gate` on `targets`, applied when the controls hold
+    /// `control_state` (bit i for `controls[i]`)
+    pub fn multi_controlled_on(
+        &mut self,
+        gate: Gate,
+        controls: &[usize],
+        control_state: u64,
+        targets: &[usize],
+    ) -> Result<&mut Self, String> {
+        self.push(Instruction::MultiControlled {
+            gate,
+            controls: controls.to_vec(),
+            control_state,
+            targets: targets.to_vec(),
+        })
+    }
+
+    /// Appends a measurement of `qubit` into the classical bit with the same index
+    pub fn measure(&mut self, qubit: usize) -> Result<&mut Self, String> {
+        self.measure_into(qubit, qubit)
+    }
+
+    /// Appends a measurement of `qubit` into classical bit `clbit`
+    pub fn measure_into(&mut self, qubit: usize, clbit: usize) -> Result<&mut Self, String> {
+        self.push(Instruction::Measure { qubit, clbit })
+    }
+
+    /// Appends measurements of `qubits[i]` into bit i of `register`
+    pub fn measure_register(
+        &mut self,
+        qubits: &[usize],
+        register: &str,
+    ) -> Result<&mut Self, String> {
+        let clbits = self.register(register)?.clbits();
+        if qubits.len() != clbits.len() {
+            return Err(format!(
+                "Register '{}' has {} bits but {} qubits were given",
+                register,
+                clbits.len(),
+                qubits.len()
+            ));
+        }
+        for (&qubit, clbit) in qubits.iter().zip(clbits) {
+            self.measure_into(qubit, clbit)?;
+        }
+        Ok(self)
+    }
+
+    /// Appends a reset of `qubit` to |0⟩
+    pub fn reset(&mut self, qubit: usize) -> Result<&mut Self, String> {
+        self.push(Instruction::Reset { qubit })
+    }
+
+    /// Appends a measurement of every qubit into the classical bit with the same index
+    pub fn measure_all(&mut self) -> Result<&mut Self, String> {
+        for qubit in 0..self.n_qubits {
+            self.measure(qubit)?;
+        }
+        Ok(self)
+    }
+
+    /// Appends `instruction`, to be run only when `clbits` hold `value`
+    pub fn conditional(
+        &mut self,
+        clbits: &[usize],
+        value: u64,
+        instruction: Instruction,
+    ) -> Result<&mut Self, String> {
+        self.push(Instruction::Conditional {
+            clbits: clbits.to_vec(),
+            value,
+            instruction: Box::new(instruction),
+        })
+    }
+
+    /// Appends `instruction`, to be run only when `register` holds `value`
+    pub fn c_if(
+        &mut self,
+        register: &str,
+        value: u64,
+        instruction: Instruction,
+    ) -> Result<&mut Self, String> {
+        let clbits = self.register(register)?.clbits();
+        self.conditional(&clbits, value, instruction)
+    }
+
+    /// Appends a barrier across `qubits`, or across all qubits if empty
+    pub fn barrier(&mut self, qubits: &[usize]) -> Result<&mut Self, String> {
+        let qubits = if qubits.is_empty() {
+            (0..self.n_qubits).collect()
+        } else {
+            qubits.to_vec()
+        };
+        self.push(Instruction::Barrier { qubits })
+    }
+
+    pub fn x(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::X, &[q])
+    }
+
+    pub fn y(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::Y, &[q])
+    }
+
+    pub fn z(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::Z, &[q])
+    }
+
+    pub fn h(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::H, &[q])
+    }
+
+    pub fn s(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::S, &[q])
+    }
+
+    pub fn sx(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::SX, &[q])
+    }
+
+    pub fn t(&mut self, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::T, &[q])
+    }
+
+    pub fn rx<A: Into<Angle>>(&mut self, theta: A, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::RX(theta.into()), &[q])
+    }
+
+    pub fn ry<A: Into<Angle>>(&mut self, theta: A, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::RY(theta.into()), &[q])
+    }
+
+    pub fn rz<A: Into<Angle>>(&mut self, theta: A, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::RZ(theta.into()), &[q])
+    }
+
+    pub fn p<A: Into<Angle>>(&mut self, lambda: A, q: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::PhaseShift(lambda.into()), &[q])
+    }
+
+    pub fn u3<A: Into<Angle>, B: Into<Angle>, C: Into<Angle>>(
+        &mut self,
+        theta: A,
+        phi: B,
+        lambda: C,
+        q: usize,
+    ) -> Result<&mut Self, String> {
+        self.add_gate(Gate::U3(theta.into(), phi.into(), lambda.into()), &[q])
+    }
+
+    pub fn cx(&mut self, control: usize, target: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::CNOT, &[control, target])
+    }
+
+    pub fn cz(&mut self, a: usize, b: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::CZ, &[a, b])
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::Swap, &[a, b])
+    }
+
+    pub fn iswap(&mut self, a: usize, b: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::ISwap, &[a, b])
+    }
+
+    pub fn cp<A: Into<Angle>>(&mut self, phi: A, a: usize, b: usize) -> Result<&mut Self, String> {
+        self.add_gate(Gate::CPhase(phi.into()), &[a, b])
+    }
+
+    /// Appends a user-defined gate acting on `qubits`
+    pub fn custom(&mut self, gate: CustomGate, qubits: &[usize]) -> Result<&mut Self, String> {
+        self.add_gate(Gate::Custom(gate), qubits)
+    }
+
+    /// Toffoli gate: flips `target` when `a` and `b` are both |1⟩
+    pub fn ccx(&mut self, a: usize, b: usize, target: usize) -> Result<&mut Self, String> {
+        self.multi_controlled(Gate::X, &[a, b], &[target])
+    }
+
+    /// Fredkin gate: swaps `a` and `b` when `control` is |1⟩
+    pub fn cswap(&mut self, control: usize, a: usize, b: usize) -> Result<&mut Self, String> {
+        self.multi_controlled(Gate::Swap, &[control], &[a, b])
+    }
+
+    /// Flips `target` when every control is |1⟩
+    pub fn mcx(&mut self, controls: &[usize], target: usize) -> Result<&mut Self, String> {
+        self.multi_controlled(Gate::X, controls, &[target])
+    }
+
+    /// Appends every instruction of `other`, which must not be wider than `self`
+    pub fn append(&mut self, other: &Circuit) -> Result<&mut Self, String> {
+        if other.n_qubits > self.n_qubits || other.n_clbits > self.n_clbits {
+            return Err(format!(
+                "Cannot append a circuit with {} qubits and {} classical bits to one with {} and {}",
+                other.n_qubits, other.n_clbits, self.n_qubits, self.n_clbits
+            ));
+        }
+        self.instructions.extend(other.instructions.iter().cloned());
+        Ok(self)
+    }
+
+    /// Returns the circuit that undoes this one
+    ///
+    /// Fails if the circuit contains measurements, resets or conditionals,
+    /// which are not reversible.
+    pub fn inverse(&self) -> Result<Circuit, String> {
+        let mut instructions = Vec::with_capacity(self.instructions.len());
+        for inst in self.instructions.iter().rev() {
+            instructions.extend(inst.inverse()?);
+        }
+        Ok(Circuit {
+            n_qubits: self.n_qubits,
+            n_clbits: self.n_clbits,
+            registers: self.registers.clone(),
+            instructions,
+        })
+    }
+
+    /// Number of layers when every instruction waits for the qubits it touches
+    ///
+    /// Barriers synchronise their qubits but do not add a layer.
+    pub fn depth(&self) -> usize {
+        let mut layer = vec![0; self.n_qubits];
+        for inst in &self.instructions {
+            let qubits = inst.qubits();
+            let start = qubits.iter().map(|&q| layer[q]).max().unwrap_or(0);
+            let end = match inst {
+                Instruction::Barrier { .. } => start,
+                _ => start + 1,
+            };
+            for q in qubits {
+                layer[q] = end;
+            }
+        }
+        layer.into_iter().max().unwrap_or(0)
+    }
+
+    /// Returns the unbound parameters of the circuit, sorted by name
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let set: BTreeSet<Parameter> = self
+            .instructions
+            .iter()
+            .filter_map(|inst| inst.gate())
+            .flat_map(|gate| gate.angles())
+            .filter_map(|angle| angle.parameter().cloned())
+            .collect();
+        set.into_iter().collect()
+    }
+
+    /// Returns true if every angle in the circuit has a value
+    pub fn is_bound(&self) -> bool {
+        self.instructions
+            .iter()
+            .filter_map(|inst| inst.gate())
+            .all(|gate| !gate.is_parameterized())
+    }
+
+    /// Returns a copy of the circuit with the given parameters replaced by values
+    ///
+    /// Parameters that are not listed stay symbolic; naming a parameter that
+    /// does not appear in the circuit is an error.
+    pub fn bind(&self, values: &[(&str, f64)]) -> Result<Circuit, String> {
+        let known = self.parameters();
+        let mut map = HashMap::new();
+        for &(name, value) in values {
+            if !known.iter().any(|p| p.name() == name) {
+                return Err(format!("Circuit has no parameter named '{}'", name));
+            }
+            map.insert(name.to_string(), value);
+        }
+
+        let instructions = self
+            .instructions
+            .iter()
+            .map(|inst| inst.map_angles(&|a| a.bind(&map)))
+            .collect();
+        Ok(Circuit {
+            n_qubits: self.n_qubits,
+            n_clbits: self.n_clbits,
+            registers: self.registers.clone(),
+            instructions,
+        })
+    }
+
+    /// Returns the 2^n × 2^n matrix the circuit implements
+    ///
+    /// Column j is the state the circuit produces from basis state |j⟩, with
+    /// qubit k as bit k of the index. Fails for unbound circuits, for
+    /// measurements, resets and conditionals, which are not unitary, and for
+    /// circuits wider than 12 qubits.
+    pub fn unitary(&self) -> Result<DMatrix<Complex<f64>>, String> {
+        if self.n_qubits > MAX_UNITARY_QUBITS {
+            return Err(format!(
+                "Unitary of a {}-qubit circuit is too large; the limit is {} qubits",
+                self.n_qubits, MAX_UNITARY_QUBITS
+            ));
+        }
+        if !self.is_bound() {
+            return Err("Circuit has unbound parameters".to_string());
+        }
+        if self.instructions.iter().any(|inst| {
+            matches!(
+                inst,
+                Instruction::Measure { .. }
+                    | Instruction::Reset { .. }
+                    | Instruction::Conditional { .. }
+            )
+        }) {
+            return Err(
+                "Circuits with measurements, resets or conditionals have no unitary".to_string(),
+            );
+        }
+
+        let dim = 1 << self.n_qubits;
+        let mut matrix = DMatrix::zeros(dim, dim);
+        let mut backend = QuantumCircuit::new(self.n_qubits)?;
+        for column in 0..dim {
+            let state = backend.state_mut();
+            state.fill(Complex::new(0.0, 0.0));
+            state[column] = Complex::new(1.0, 0.0);
+            backend.execute(self)?;
+            matrix.set_column(column, backend.get_state());
+        }
+        Ok(matrix)
+    }
+}
+
+/// Returns true if `a` and `b` implement the same unitary up to a global phase
+///
+/// The phase is fitted from tr(U_a† U_b), and the circuits are equivalent when
+/// no entry of U_b − e^{iα} U_a exceeds `tolerance` in magnitude.
+pub fn equivalent(a: &Circuit, b: &Circuit, tolerance: f64) -> Result<bool, String> {
+    if a.n_qubits() != b.n_qubits() {
+        return Err(format!(
+            "Cannot compare circuits on {} and {} qubits",
+            a.n_qubits(),
+            b.n_qubits()
+        ));
+    }
+    let (ua, ub) = (a.unitary()?, b.unitary()?);
+
+    let overlap = ua.ad_mul(&ub).trace();
+    let magnitude = overlap.norm_sqr().sqrt();
+    if magnitude < f64::EPSILON {
+        return Ok(false);
+    }
+    let phase = overlap / magnitude;
+    Ok(ua
+        .iter()
+        .zip(ub.iter())
+        .all(|(x, y)| (x * phase - y).norm_sqr() <= tolerance * tolerance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_template_evaluated_with_different_values() {
+        let theta = Parameter::new("theta0");
+        let mut template = Circuit::new(2).unwrap();
+        template.ry(&theta, 0).unwrap().cx(0, 1).unwrap();
+        assert_eq!(template.parameters(), vec![theta]);
+
+        let mut state = QuantumCircuit::new(2).unwrap();
+        for &value in &[0.0, 0.3, std::f64::consts::PI] {
+            let bound = template.bind(&[("theta0", value)]).unwrap();
+            assert!(bound.is_bound());
+
+            state.run(&bound).unwrap();
+            let p11 = state.get_probability(0b11).unwrap();
+            assert_relative_eq!(p11, (value / 2.0).sin().powi(2), epsilon = 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_partial_binding_and_errors() {
+        let mut template = Circuit::new(1).unwrap();
+        template
+            .rx(Parameter::new("a"), 0)
+            .unwrap()
+            .rz(Parameter::new("b") * 2.0, 0)
+            .unwrap();
+
+        let partial = template.bind(&[("a", 0.1)]).unwrap();
+        assert_eq!(partial.parameters(), vec![Parameter::new("b")]);
+        assert!(QuantumCircuit::new(1).unwrap().run(&partial).is_err());
+        assert!(template.bind(&[("missing", 1.0)]).is_err());
+
+        let bound = partial.bind(&[("b", 0.5)]).unwrap();
+        assert_eq!(
+            bound.instructions()[1].gate(),
+            Some(&Gate::RZ(Angle::Value(1.0)))
+        );
+    }
+
+    #[test]
+    fn test_add_gate_validates_qubits() {
+        let mut circuit = Circuit::new(2).unwrap();
+        assert!(circuit.h(2).is_err());
+        assert!(circuit.cx(1, 1).is_err());
+        assert!(circuit.add_gate(Gate::CZ, &[0]).is_err());
+        assert!(circuit.controlled(Gate::CNOT, 0, 1).is_err());
+        assert!(circuit.is_empty());
+    }
+
+    #[test]
+    fn test_circuits_need_qubits() {
+        assert_eq!(Circuit::new(0), Err(QuantumError::InvalidQubitCount(0)));
+        assert_eq!(
+            Circuit::with_clbits(0, 2),
+            Err(QuantumError::InvalidQubitCount(0))
+        );
+        assert!(Circuit::with_clbits(1, 0).is_ok());
+    }
+
+    #[test]
+    fn test_conditionals_validate_their_instruction() {
+        let mut circuit = Circuit::new(2).unwrap();
+        // Wrong arity
+        let empty = Instruction::Gate {
+            gate: Gate::H,
+            qubits: vec![],
+        };
+        assert!(circuit.conditional(&[0], 0, empty).is_err());
+        let controlled_cnot = Instruction::Controlled {
+            gate: Gate::CNOT,
+            control: 0,
+            target: 1,
+        };
+        assert!(circuit.conditional(&[0], 1, controlled_cnot).is_err());
+        // Out of range and overlapping qubits
+        let out_of_range = Instruction::Gate {
+            gate: Gate::X,
+            qubits: vec![2],
+        };
+        assert!(circuit.conditional(&[0], 1, out_of_range).is_err());
+        let overlap = Instruction::Controlled {
+            gate: Gate::X,
+            control: 1,
+            target: 1,
+        };
+        assert!(circuit.conditional(&[0], 1, overlap).is_err());
+        circuit.add_register("m", 1).unwrap();
+        let bad_measure = Instruction::Measure { qubit: 0, clbit: 3 };
+        assert!(circuit.c_if("m", 1, bad_measure).is_err());
+        // The value is a u64, so at most 64 bits can be compared
+        let mut wide = Circuit::with_clbits(1, 65).unwrap();
+        let x = Instruction::Gate {
+            gate: Gate::X,
+            qubits: vec![0],
+        };
+        let all: Vec<usize> = (0..65).collect();
+        assert!(wide.conditional(&all, 1, x.clone()).is_err());
+        assert!(wide.conditional(&all[..64], u64::MAX, x).is_ok());
+        assert!(circuit.is_empty());
+    }
+
+    #[test]
+    fn test_gate_matrix_requires_bound_angles() {
+        assert!(Gate::RX(Parameter::new("t").into()).matrix().is_err());
+        let m = Gate::CPhase(Angle::Value(0.0)).matrix().unwrap();
+        assert_eq!(m, DMatrix::identity(4, 4));
+    }
+
+    #[test]
+    fn test_inverse_restores_initial_state() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .t(1)
+            .unwrap()
+            .s(2)
+            .unwrap()
+            .u3(0.3, 0.2, 0.1, 1)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .iswap(1, 2)
+            .unwrap()
+            .controlled(Gate::RY(0.4.into()), 2, 0)
+            .unwrap()
+            .barrier(&[])
+            .unwrap();
+
+        let mut round_trip = circuit.clone();
+        round_trip.append(&circuit.inverse().unwrap()).unwrap();
+
+        let mut state = QuantumCircuit::new(3).unwrap();
+        state.run(&round_trip).unwrap();
+        assert_relative_eq!(state.get_probability(0).unwrap(), 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_inverse_rejects_irreversible_instructions() {
+        let mut circuit = Circuit::new(1).unwrap();
+        circuit.h(0).unwrap().measure(0).unwrap();
+        assert!(circuit.inverse().is_err());
+
+        let mut reset = Circuit::new(1).unwrap();
+        reset.h(0).unwrap().reset(0).unwrap();
+        assert!(reset.inverse().is_err());
+
+        let mut conditional = Circuit::new(1).unwrap();
+        let x = Instruction::Gate {
+            gate: Gate::X,
+            qubits: vec![0],
+        };
+        conditional.conditional(&[0], 1, x).unwrap();
+        assert!(conditional.inverse().is_err());
+    }
+
+    #[test]
+    fn test_depth() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .x(2)
+            .unwrap();
+        assert_eq!(circuit.depth(), 2);
+        circuit.barrier(&[]).unwrap().x(2).unwrap();
+        assert_eq!(circuit.depth(), 3);
+    }
+
+    #[test]
+    fn test_multi_controlled_instructions() {
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .x(0)
+            .unwrap()
+            .mcx(&[0, 1], 2)
+            .unwrap()
+            .multi_controlled_on(Gate::X, &[0, 1], 0b01, &[3])
+            .unwrap()
+            .cswap(3, 1, 2)
+            .unwrap();
+        assert_eq!(circuit.instructions()[2].qubits(), vec![0, 1, 3]);
+
+        // Only the negatively controlled X fires, then the swap moves q1 and q2
+        // (both |0⟩), leaving |1001⟩
+        let mut backend = QuantumCircuit::new(4).unwrap();
+        backend.run(&circuit).unwrap();
+        assert_relative_eq!(backend.get_probability(0b1001).unwrap(), 1.0);
+
+        assert!(circuit.mcx(&[], 1).is_err());
+        assert!(circuit.mcx(&[0, 1], 1).is_err());
+        assert!(circuit.multi_controlled_on(Gate::X, &[0], 2, &[1]).is_err());
+        assert!(circuit.multi_controlled(Gate::Swap, &[0], &[1]).is_err());
+    }
+
+    #[test]
+    fn test_multi_controlled_inverse() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .multi_controlled_on(Gate::ISwap, &[0], 0, &[1, 2])
+            .unwrap()
+            .multi_controlled(Gate::T, &[0, 1], &[2])
+            .unwrap();
+        let mut roundtrip = circuit.clone();
+        roundtrip.append(&circuit.inverse().unwrap()).unwrap();
+
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        backend.run(&roundtrip).unwrap();
+        assert_relative_eq!(backend.get_probability(0).unwrap(), 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_unitary_of_small_circuits() {
+        let mut hadamard = Circuit::new(2).unwrap();
+        hadamard.h(1).unwrap();
+        let u = hadamard.unitary().unwrap();
+        let h = 1.0 / 2.0_f64.sqrt();
+        // H on qubit 1 mixes |00⟩ (index 0) with |10⟩ (index 2)
+        assert_relative_eq!(u[(0, 0)].re, h, epsilon = 1e-12);
+        assert_relative_eq!(u[(2, 0)].re, h, epsilon = 1e-12);
+        assert_relative_eq!(u[(2, 2)].re, -h, epsilon = 1e-12);
+        assert_relative_eq!(u[(1, 0)].norm_sqr(), 0.0);
+
+        let mut cx = Circuit::new(2).unwrap();
+        cx.cx(0, 1).unwrap();
+        let u = cx.unitary().unwrap();
+        for (column, row) in [(0, 0), (1, 3), (2, 2), (3, 1)] {
+            assert_relative_eq!(u[(row, column)].re, 1.0);
+        }
+
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .u3(0.3, 1.2, -0.7, 0)
+            .unwrap()
+            .ccx(0, 2, 1)
+            .unwrap()
+            .iswap(1, 2)
+            .unwrap();
+        let u = circuit.unitary().unwrap();
+        let identity = DMatrix::<Complex<f64>>::identity(8, 8);
+        assert_relative_eq!((u.adjoint() * &u - identity).norm(), 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_gate_identities() {
+        let circuit = |build: &dyn Fn(&mut Circuit)| {
+            let mut circuit = Circuit::new(2).unwrap();
+            build(&mut circuit);
+            circuit
+        };
+        let identities: Vec<(Circuit, Circuit)> = vec![
+            (
+                circuit(&|c| {
+                    c.h(0).unwrap().x(0).unwrap().h(0).unwrap();
+                }),
+                circuit(&|c| {
+                    c.z(0).unwrap();
+                }),
+            ),
+            (
+                circuit(&|c| {
+                    c.sx(1).unwrap().sx(1).unwrap();
+                }),
+                circuit(&|c| {
+                    c.x(1).unwrap();
+                }),
+            ),
+            (
+                circuit(&|c| {
+                    c.h(0).unwrap().h(1).unwrap().cx(1, 0).unwrap();
+                    c.h(0).unwrap().h(1).unwrap();
+                }),
+                circuit(&|c| {
+                    c.cx(0, 1).unwrap();
+                }),
+            ),
+            (
+                circuit(&|c| {
+                    c.cx(0, 1).unwrap().cx(1, 0).unwrap().cx(0, 1).unwrap();
+                }),
+                circuit(&|c| {
+                    c.swap(0, 1).unwrap();
+                }),
+            ),
+            // RZ and P differ only by the global phase e^{-iθ/2}
+            (
+                circuit(&|c| {
+                    c.rz(0.8, 0).unwrap();
+                }),
+                circuit(&|c| {
+                    c.p(0.8, 0).unwrap();
+                }),
+            ),
+        ];
+        for (a, b) in &identities {
+            assert!(equivalent(a, b, 1e-10).unwrap());
+        }
+
+        let (mut s, mut t) = (Circuit::new(1).unwrap(), Circuit::new(1).unwrap());
+        s.s(0).unwrap();
+        t.t(0).unwrap();
+        assert!(!equivalent(&s, &t, 1e-6).unwrap());
+        // A relative phase between qubits is not a global phase
+        let (mut cz, mut z) = (Circuit::new(2).unwrap(), Circuit::new(2).unwrap());
+        cz.cz(0, 1).unwrap();
+        z.z(1).unwrap();
+        assert!(!equivalent(&cz, &z, 1e-6).unwrap());
+    }
+
+    #[test]
+    fn test_circuit_times_inverse_is_identity() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .sx(1)
+            .unwrap()
+            .cp(0.4, 0, 2)
+            .unwrap()
+            .cswap(2, 0, 1)
+            .unwrap()
+            .controlled(Gate::RY(1.3.into()), 1, 2)
+            .unwrap();
+        let mut roundtrip = circuit.clone();
+        roundtrip.append(&circuit.inverse().unwrap()).unwrap();
+        assert!(equivalent(&roundtrip, &Circuit::new(3).unwrap(), 1e-10).unwrap());
+    }
+
+    #[test]
+    fn test_custom_gates() {
+        let mut block = Circuit::new(3).unwrap();
+        block
+            .h(0)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .u3(0.4, 1.1, -0.6, 1)
+            .unwrap()
+            .cp(0.9, 1, 2)
+            .unwrap();
+        // `unitary()` makes qubit 0 the least significant bit, while a custom
+        // gate's first qubit is the most significant
+        let gate = CustomGate::from_matrix("block", block.unitary().unwrap()).unwrap();
+        let mut custom = Circuit::new(3).unwrap();
+        custom.custom(gate.clone(), &[2, 1, 0]).unwrap();
+        assert!(equivalent(&block, &custom, 1e-10).unwrap());
+        assert!(custom.custom(gate.clone(), &[0, 1]).is_err());
+
+        let mut direct = QuantumCircuit::new(3).unwrap();
+        direct.apply_multi_qubit_gate(gate, &[2, 1, 0]).unwrap();
+        let mut recorded = QuantumCircuit::new(3).unwrap();
+        recorded.run(&block).unwrap();
+        assert_relative_eq!(
+            direct.get_state().dotc(recorded.get_state()).norm_sqr(),
+            1.0,
+            epsilon = 1e-10
+        );
+
+        // Inverting uses the adjoint
+        let inverse = custom.inverse().unwrap();
+        assert_eq!(inverse.instructions()[0].gate().unwrap().name(), "block_dg");
+        let mut roundtrip = custom.clone();
+        roundtrip.append(&inverse).unwrap();
+        assert!(equivalent(&roundtrip, &Circuit::new(3).unwrap(), 1e-10).unwrap());
+
+        // Controlled custom gates match their built-in twins
+        let x = CustomGate::from_matrix("my_x", Gate::X.matrix().unwrap()).unwrap();
+        let swap = CustomGate::from_matrix("my_swap", SwapGate.matrix()).unwrap();
+        let (mut a, mut b) = (Circuit::new(3).unwrap(), Circuit::new(3).unwrap());
+        a.h(0)
+            .unwrap()
+            .ry(0.7, 1)
+            .unwrap()
+            .controlled(x.clone().into(), 0, 2)
+            .unwrap()
+            .multi_controlled_on(x.into(), &[0, 1], 0b01, &[2])
+            .unwrap()
+            .multi_controlled(swap.into(), &[0], &[1, 2])
+            .unwrap();
+        b.h(0)
+            .unwrap()
+            .ry(0.7, 1)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .multi_controlled_on(Gate::X, &[0, 1], 0b01, &[2])
+            .unwrap()
+            .cswap(0, 1, 2)
+            .unwrap();
+        assert!(equivalent(&a, &b, 1e-10).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_unitaries() {
+        let mut measured = Circuit::new(1).unwrap();
+        measured.h(0).unwrap().measure(0).unwrap();
+        assert!(measured.unitary().is_err());
+
+        let mut symbolic = Circuit::new(1).unwrap();
+        symbolic.rx(Parameter::new("a"), 0).unwrap();
+        assert!(symbolic.unitary().is_err());
+
+        assert!(Circuit::new(13).unwrap().unitary().is_err());
+        assert!(equivalent(&Circuit::new(1).unwrap(), &Circuit::new(2).unwrap(), 1e-10).is_err());
+    }
+
+    #[test]
+    fn test_classical_registers() {
+        let mut circuit = Circuit::with_clbits(3, 1).unwrap();
+        circuit
+            .add_register("syndrome", 2)
+            .unwrap()
+            .add_register("out", 1)
+            .unwrap();
+        assert_eq!(circuit.n_clbits(), 4);
+        let syndrome = circuit.register("syndrome").unwrap();
+        assert_eq!((syndrome.start(), syndrome.size()), (1, 2));
+        assert_eq!(syndrome.clbits(), vec![1, 2]);
+        assert_eq!(circuit.registers().len(), 2);
+
+        circuit
+            .x(1)
+            .unwrap()
+            .measure_register(&[0, 1], "syndrome")
+            .unwrap()
+            .c_if("syndrome", 0b10, Instruction::Reset { qubit: 1 })
+            .unwrap()
+            .measure_register(&[1], "out")
+            .unwrap();
+        assert_eq!(
+            circuit.instructions()[3],
+            Instruction::Conditional {
+                clbits: vec![1, 2],
+                value: 0b10,
+                instruction: Box::new(Instruction::Reset { qubit: 1 }),
+            }
+        );
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        assert_eq!(
+            backend.run(&circuit).unwrap(),
+            vec![false, false, true, false]
+        );
+
+        assert!(circuit.add_register("out", 1).is_err());
+        assert!(circuit.add_register("2bits", 2).is_err());
+        for reserved in ["measure", "if", "qreg", "float", "h", "q"] {
+            assert!(circuit.add_register(reserved, 1).is_err());
+        }
+        assert!(circuit.add_register("c", 1).is_ok());
+        assert!(circuit.add_register("empty", 0).is_err());
+        assert!(circuit.register("missing").is_err());
+        assert!(circuit.measure_register(&[0], "syndrome").is_err());
+        assert!(circuit
+            .c_if("syndrome", 4, Instruction::Reset { qubit: 0 })
+            .is_err());
+        assert!(circuit
+            .c_if("missing", 0, Instruction::Reset { qubit: 0 })
+            .is_err());
+    }
+
+    #[test]
+    fn test_reset_instruction() {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.x(0).unwrap().reset(0).unwrap().measure(0).unwrap();
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert_eq!(backend.run(&circuit).unwrap(), vec![false, false]);
+
+        assert_eq!(circuit.instructions()[1].qubits(), vec![0]);
+        assert!(circuit.inverse().is_err());
+        assert!(circuit.reset(2).is_err());
+
+        let mut reset_only = Circuit::new(1).unwrap();
+        reset_only.reset(0).unwrap();
+        assert!(reset_only.unitary().is_err());
+    }
+}
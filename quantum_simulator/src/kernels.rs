@@ -0,0 +1,428 @@
+/*
+This file implements the in-place state-vector kernels behind QuantumCircuit.
+
+Key concepts:
+1. Strided Pair Iteration:
+   - A gate on qubit t mixes amplitude pairs (i, i + 2^t) where bit t of i is 0
+   - Chunking the state into blocks of 2^(t+1) amplitudes and splitting each
+     block in half yields every pair exactly once, in order, with no
+     allocation and no index tests
+
+2. Specialized Paths:
+   - Diagonal gates (Z, S, T, RZ, P, CZ, CPhase, ...) only rescale amplitudes
+   - Permutation gates (X, Y, CNOT, SWAP, iSWAP, Toffoli, ...) only move
+     amplitudes, with a phase, so each group costs O(2^k) instead of O(4^k)
+   - Everything else falls back to a dense 2^k × 2^k multiply per group
+
+3. Controls:
+   - Controlled gates are the target gate applied where
+     `index & control_mask == control_value`, so they share the same kernels
+
+4. Parallelism:
+   - Above `PARALLEL_THRESHOLD` amplitudes the disjoint blocks are processed
+     with rayon; below it the thread overhead outweighs the work
+   - Learn more: https://docs.rs/rayon
+*/
+
+use nalgebra::{Complex, DMatrix, Matrix2};
+use rayon::prelude::*;
+
+type Amplitude = Complex<f64>;
+
+/// States with fewer amplitudes than this are updated on one thread
+const PARALLEL_THRESHOLD: usize = 1 << 14;
+
+/// Number of amplitudes handed to one rayon task
+const CHUNK: usize = 1 << 12;
+
+/// Calls `f(i, a_i, a_j)` for every pair j = i + 2^target with bit `target` of i clear
+fn for_each_pair<F>(state: &mut [Amplitude], target: usize, f: F)
+where
+    F: Fn(usize, &mut Amplitude, &mut Amplitude) + Sync,
+{
+    let stride = 1 << target;
+    let visit = |base: usize, lo: &mut [Amplitude], hi: &mut [Amplitude]| {
+        for (i, (a, b)) in lo.iter_mut().zip(hi.iter_mut()).enumerate() {
+            f(base + i, a, b);
+        }
+    };
+    let serial = |start: usize, chunk: &mut [Amplitude]| {
+        for (block, chunk) in chunk.chunks_mut(2 * stride).enumerate() {
+            let (lo, hi) = chunk.split_at_mut(stride);
+            visit(start + block * 2 * stride, lo, hi);
+        }
+    };
+
+    if state.len() < PARALLEL_THRESHOLD {
+        serial(0, state);
+    } else if 2 * stride <= CHUNK {
+        state
+            .par_chunks_mut(CHUNK)
+            .enumerate()
+            .for_each(|(i, chunk)| serial(i * CHUNK, chunk));
+    } else {
+        // Few large blocks, so the halves of each block are split further
+        let size = CHUNK / 2;
+        state
+            .par_chunks_mut(2 * stride)
+            .enumerate()
+            .for_each(|(block, chunk)| {
+                let (lo, hi) = chunk.split_at_mut(stride);
+                lo.par_chunks_mut(size)
+                    .zip(hi.par_chunks_mut(size))
+                    .enumerate()
+                    .for_each(|(j, (lo, hi))| visit(block * 2 * stride + j * size, lo, hi));
+            });
+    }
+}
+
+/// Calls `f(i, a_i)` for every amplitude
+fn for_each_amplitude<F>(state: &mut [Amplitude], f: F)
+where
+    F: Fn(usize, &mut Amplitude) + Sync,
+{
+    let serial = |start: usize, chunk: &mut [Amplitude]| {
+        for (i, a) in chunk.iter_mut().enumerate() {
+            f(start + i, a);
+        }
+    };
+
+    if state.len() < PARALLEL_THRESHOLD {
+        serial(0, state);
+    } else {
+        state
+            .par_chunks_mut(CHUNK)
+            .enumerate()
+            .for_each(|(i, chunk)| serial(i * CHUNK, chunk));
+    }
+}
+
+/// Spreads the bits of `group` over the positions not in `sorted_targets`,
+/// leaving a zero at every target position
+fn deposit(mut group: usize, sorted_targets: &[usize]) -> usize {
+    for &t in sorted_targets {
+        let low = group & ((1 << t) - 1);
+        group = low | ((group ^ low) << 1);
+    }
+    group
+}
+
+/// Applies a 2×2 matrix to `target` wherever `index & control_mask == control_value`
+pub(crate) fn apply_single_qubit(
+    state: &mut [Amplitude],
+    matrix: &Matrix2<Amplitude>,
+    target: usize,
+    control_mask: usize,
+    control_value: usize,
+) {
+    let zero = Complex::new(0.0, 0.0);
+    let one = Complex::new(1.0, 0.0);
+    let (m00, m01, m10, m11) = (
+        matrix[(0, 0)],
+        matrix[(0, 1)],
+        matrix[(1, 0)],
+        matrix[(1, 1)],
+    );
+    let active = |i: usize| i & control_mask == control_value;
+
+    if m01 == zero && m10 == zero {
+        if m00 == one && m11 == one {
+            return;
+        }
+        for_each_pair(state, target, |i, a, b| {
+            if active(i) {
+                if m00 != one {
+                    *a *= m00;
+                }
+                *b *= m11;
+            }
+        });
+    } else if m00 == zero && m11 == zero {
+        for_each_pair(state, target, |i, a, b| {
+            if active(i) {
+                let (x, y) = (*a, *b);
+                *a = m01 * y;
+                *b = m10 * x;
+            }
+        });
+    } else {
+        for_each_pair(state, target, |i, a, b| {
+            if active(i) {
+                let (x, y) = (*a, *b);
+                *a = m00 * x + m01 * y;
+                *b = m10 * x + m11 * y;
+            }
+        });
+    }
+}
+
+/// The shape of a 2^k × 2^k gate matrix, used to pick a kernel
+enum Structure {
+    /// Diagonal entries
+    Diagonal(Vec<Amplitude>),
+    /// Column c is `phase` times basis vector `row`: (row, phase) per column
+    Permutation(Vec<(usize, Amplitude)>),
+    Dense,
+}
+
+fn structure(matrix: &DMatrix<Amplitude>) -> Structure {
+    let zero = Complex::new(0.0, 0.0);
+    let dim = matrix.nrows();
+
+    if (0..dim).all(|c| (0..dim).all(|r| r == c || matrix[(r, c)] == zero)) {
+        return Structure::Diagonal((0..dim).map(|i| matrix[(i, i)]).collect());
+    }
+
+    let mut permutation = Vec::with_capacity(dim);
+    for c in 0..dim {
+        let mut nonzero = (0..dim).filter(|&r| matrix[(r, c)] != zero);
+        match (nonzero.next(), nonzero.next()) {
+            (Some(r), None) => permutation.push((r, matrix[(r, c)])),
+            _ => return Structure::Dense,
+        }
+    }
+    Structure::Permutation(permutation)
+}
+
+/// Applies a 2^k × 2^k matrix to `targets` (`targets[0]` is the most
+/// significant bit of the matrix index) wherever
+/// `index & control_mask == control_value`
+///
+/// Qubit indices are assumed to be valid and distinct.
+pub(crate) fn apply_multi_qubit(
+    state: &mut [Amplitude],
+    matrix: &DMatrix<Amplitude>,
+    targets: &[usize],
+    control_mask: usize,
+    control_value: usize,
+) {
+    let k = targets.len();
+    let dim = 1 << k;
+    // Offsets of each local basis state |b_0 ... b_{k-1}⟩ in the full register
+    let offsets: Vec<usize> = (0..dim)
+        .map(|local| {
+            targets
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| local & (1 << (k - 1 - j)) != 0)
+                .fold(0, |acc, (_, &t)| acc | (1 << t))
+        })
+        .collect();
+    let active = |i: usize| i & control_mask == control_value;
+
+    let permutation = match structure(matrix) {
+        Structure::Diagonal(diagonal) => {
+            let one = Complex::new(1.0, 0.0);
+            for_each_amplitude(state, |i, a| {
+                if active(i) {
+                    let local = targets
+                        .iter()
+                        .fold(0, |acc, &t| (acc << 1) | ((i >> t) & 1));
+                    if diagonal[local] != one {
+                        *a *= diagonal[local];
+                    }
+                }
+            });
+            return;
+        }
+        Structure::Permutation(permutation) => Some(permutation),
+        Structure::Dense => None,
+    };
+
+    // Every group {base | offset} lies inside one aligned block of
+    // 2^(highest target + 1) amplitudes, so aligned chunks of whole blocks
+    // can be updated independently
+    let mut sorted = targets.to_vec();
+    sorted.sort_unstable();
+    let block_size = 1 << (sorted[k - 1] + 1);
+
+    let update_chunk = |start: usize, chunk: &mut [Amplitude]| {
+        let mut input = vec![Complex::new(0.0, 0.0); dim];
+        for group in 0..chunk.len() >> k {
+            let base = deposit(group, &sorted);
+            if !active(start + base) {
+                continue;
+            }
+            for (local, offset) in offsets.iter().enumerate() {
+                input[local] = chunk[base | offset];
+            }
+            match &permutation {
+                Some(permutation) => {
+                    for (c, &(r, phase)) in permutation.iter().enumerate() {
+                        chunk[base | offsets[r]] = phase * input[c];
+                    }
+                }
+                None => {
+                    for (r, offset) in offsets.iter().enumerate() {
+                        chunk[base | offset] = (0..dim).map(|c| matrix[(r, c)] * input[c]).sum();
+                    }
+                }
+            }
+        }
+    };
+
+    if state.len() < PARALLEL_THRESHOLD {
+        update_chunk(0, state);
+    } else {
+        let size = block_size.max(CHUNK);
+        state
+            .par_chunks_mut(size)
+            .enumerate()
+            .for_each(|(i, chunk)| update_chunk(i * size, chunk));
+    }
+}
+
+/// Probability that `target` reads 1
+pub(crate) fn probability_of_one(state: &[Amplitude], target: usize) -> f64 {
+    let mask = 1 << target;
+    if state.len() < PARALLEL_THRESHOLD {
+        state
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i & mask != 0)
+            .map(|(_, a)| a.norm_sqr())
+            .sum()
+    } else {
+        state
+            .par_iter()
+            .enumerate()
+            .with_min_len(CHUNK)
+            .filter(|(i, _)| i & mask != 0)
+            .map(|(_, a)| a.norm_sqr())
+            .sum()
+    }
+}
+
+/// Projects `target` onto `outcome` and rescales the surviving amplitudes by `1 / norm`
+pub(crate) fn collapse(state: &mut [Amplitude], target: usize, outcome: bool, norm: f64) {
+    let scale = 1.0 / norm;
+    for_each_pair(state, target, |_, a, b| {
+        if outcome {
+            *a = Complex::new(0.0, 0.0);
+            *b *= scale;
+        } else {
+            *a *= scale;
+            *b = Complex::new(0.0, 0.0);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gates::{CNOTGate, CPhaseGate, HadamardGate, ISwapGate, MultiQubitGate, U3Gate};
+    use crate::gates::{QuantumGate, ToffoliGate, XGate};
+    use approx::assert_relative_eq;
+
+    /// Reference: each output amplitude as a sum over its group, straight
+    /// from the definition of a controlled operator
+    fn reference(
+        state: &[Amplitude],
+        matrix: &DMatrix<Amplitude>,
+        targets: &[usize],
+        control_mask: usize,
+        control_value: usize,
+    ) -> Vec<Amplitude> {
+        let k = targets.len();
+        let local = |i: usize| {
+            targets
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (j, &t)| acc | (((i >> t) & 1) << (k - 1 - j)))
+        };
+        let spread = |l: usize| {
+            targets
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (j, &t)| acc | (((l >> (k - 1 - j)) & 1) << t))
+        };
+        let target_mask: usize = targets.iter().map(|&t| 1 << t).sum();
+        (0..state.len())
+            .map(|r| {
+                if r & control_mask != control_value {
+                    return state[r];
+                }
+                (0..1 << k)
+                    .map(|l| matrix[(local(r), l)] * state[(r & !target_mask) | spread(l)])
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn random_state(n: usize) -> Vec<Amplitude> {
+        let raw: Vec<Amplitude> = (0..1 << n)
+            .map(|i| Complex::new((i as f64 * 0.37).sin(), (i as f64 * 0.91).cos()))
+            .collect();
+        let norm = raw.iter().map(|a| a.norm_sqr()).sum::<f64>().sqrt();
+        raw.into_iter().map(|a| a / norm).collect()
+    }
+
+    fn check(
+        n: usize,
+        matrix: &DMatrix<Amplitude>,
+        targets: &[usize],
+        control_mask: usize,
+        control_value: usize,
+    ) {
+        let state = random_state(n);
+        let expected = reference(&state, matrix, targets, control_mask, control_value);
+        let mut actual = state.clone();
+        if targets.len() == 1 {
+            let m = Matrix2::from_iterator(matrix.iter().cloned());
+            apply_single_qubit(&mut actual, &m, targets[0], control_mask, control_value);
+        } else {
+            apply_multi_qubit(&mut actual, matrix, targets, control_mask, control_value);
+        }
+        for (a, e) in actual.iter().zip(&expected) {
+            assert_relative_eq!(a.re, e.re, epsilon = 1e-12);
+            assert_relative_eq!(a.im, e.im, epsilon = 1e-12);
+        }
+    }
+
+    fn dense(gate: impl QuantumGate) -> DMatrix<Amplitude> {
+        DMatrix::from_iterator(2, 2, gate.matrix().iter().cloned())
+    }
+
+    #[test]
+    fn test_kernels_match_full_operator() {
+        let u3 = dense(U3Gate::new(0.3, 1.1, -0.4));
+        let single = [dense(HadamardGate), dense(XGate), u3.clone()];
+        let double = [
+            CNOTGate.matrix(),
+            CPhaseGate::new(0.7).matrix(),
+            ISwapGate.matrix(),
+            u3.kronecker(&dense(HadamardGate)),
+        ];
+        // Small states take the serial path, 15 qubits the parallel one
+        for n in [4, 15] {
+            for matrix in &single {
+                for target in [0, 2, n - 1] {
+                    check(n, matrix, &[target], 0, 0);
+                    check(
+                        n,
+                        matrix,
+                        &[target],
+                        0b1010 & !(1 << target),
+                        0b1000 & !(1 << target),
+                    );
+                }
+            }
+            for matrix in &double {
+                check(n, matrix, &[n - 1, 0], 0, 0);
+                check(n, matrix, &[1, 3], 0b100, 0);
+            }
+            check(n, &ToffoliGate.matrix(), &[2, 0, n - 1], 0b10, 0b10);
+        }
+    }
+
+    #[test]
+    fn test_measurement_kernels() {
+        let mut state = random_state(15);
+        let p = probability_of_one(&state, 14);
+        let expected: f64 = state[1 << 14..].iter().map(|a| a.norm_sqr()).sum();
+        assert_relative_eq!(p, expected, epsilon = 1e-12);
+
+        collapse(&mut state, 14, true, p.sqrt());
+        assert_relative_eq!(probability_of_one(&state, 14), 1.0, epsilon = 1e-12);
+    }
+}
@@ -1,7 +1,59 @@
+mod algorithms;
+mod backend;
 mod circuit;
+mod density;
+mod drawing;
+mod entanglement;
+mod error;
 mod gates;
+mod gradient;
+mod ir;
+mod kernels;
+mod metrics;
+mod mps;
+mod noise;
+mod observable;
+mod optimizer;
+mod parameter;
+mod passes;
+mod qaoa;
+mod qasm;
 mod schrodinger;
+mod stabilizer;
+mod transpiler;
+mod vqe;
 
+pub use algorithms::{
+    bernstein_vazirani, boolean_oracle, deutsch_jozsa, grover, grover_with_iterations, inverse_qft,
+    optimal_grover_iterations, phase_estimation, phase_oracle, qft, simon,
+};
+pub use backend::{Backend, Counts};
 pub use circuit::QuantumCircuit;
-pub use gates::{HadamardGate, QuantumGate, XGate};
+pub use density::DensityMatrixCircuit;
+pub use drawing::CircuitDrawer;
+pub use entanglement::{concurrence, renyi_entropy, von_neumann_entropy, SchmidtDecomposition};
+pub use error::QuantumError;
+pub use gates::{
+    u3_decomposition, CNOTGate, CPhaseGate, CZGate, CustomGate, FredkinGate, HadamardGate,
+    ISwapGate, MultiQubitGate, PhaseGate, PhaseShiftGate, QuantumGate, RXGate, RYGate, RZGate,
+    RotationGate, SXGate, SwapGate, TGate, ToffoliGate, U3Gate, XGate, YGate, ZGate,
+};
+pub use gradient::{adjoint_gradient, expectation_value, parameter_shift_gradient};
+pub use ir::{equivalent, Circuit, ClassicalRegister, Gate, Instruction};
+pub use metrics::{fidelity, hilbert_schmidt_inner_product, state_fidelity, trace_distance};
+pub use mps::MpsCircuit;
+pub use noise::{KrausChannel, NoiseModel, NoisyBackend, ReadoutError};
+pub use observable::{Pauli, PauliString, PauliSum};
+pub use optimizer::{
+    Adam, GradientDescent, NelderMead, Objective, OptimizationResult, Optimizer, Spsa,
+};
+pub use parameter::{Angle, Parameter};
+pub use passes::{CancelInverses, FuseSingleQubitGates, MergeRotations, Pass, PassManager};
+pub use qaoa::{Qaoa, QaoaResult, QaoaSolution};
+pub use qasm::{parse_qasm, to_qasm2, to_qasm3, QasmError, QasmErrorKind};
 pub use schrodinger::SchrodingerSolver;
+pub use stabilizer::StabilizerCircuit;
+pub use transpiler::{
+    BasisGates, CouplingMap, Entangler, SingleQubitBasis, TranspileReport, Transpiled, Transpiler,
+};
+pub use vqe::{GradientMethod, Vqe, VqeResult};
@@ -0,0 +1,340 @@
+/*
+This file implements distances between quantum states and Bloch-sphere
+coordinates of single qubits.
+
+Key concepts:
+1. Fidelity:
+   - For pure states F = |⟨ψ|φ⟩|², the probability of passing a test for |ψ⟩
+     when holding |φ⟩
+   - For mixed states Uhlmann's F(ρ, σ) = (Tr √(√ρ σ √ρ))², which reduces to
+     ⟨ψ|σ|ψ⟩ when ρ = |ψ⟩⟨ψ|
+   - Learn more: https://en.wikipedia.org/wiki/Fidelity_of_quantum_states
+
+2. Trace Distance:
+   - T(ρ, σ) = ½ Tr|ρ - σ| = ½ Σ|λ_i| over the eigenvalues of ρ - σ
+   - The best achievable probability of telling ρ and σ apart in one shot is
+     (1 + T) / 2; for pure states T = √(1 - F)
+   - Learn more: https://en.wikipedia.org/wiki/Trace_distance
+
+3. Hilbert–Schmidt Inner Product:
+   - ⟨A, B⟩ = Tr(A† B), the Frobenius inner product of operators
+   - ⟨ρ, ρ⟩ is the purity Tr(ρ²)
+
+4. Bloch Vectors:
+   - A qubit's reduced state is ρ = (I + x X + y Y + z Z) / 2 with
+     (x, y, z) = (⟨X⟩, ⟨Y⟩, ⟨Z⟩)
+   - Pure qubits lie on the unit sphere; a qubit entangled with the rest of
+     the register points inside it
+   - Learn more: https://en.wikipedia.org/wiki/Bloch_sphere
+*/
+
+use crate::circuit::QuantumCircuit;
+use crate::density::DensityMatrixCircuit;
+use crate::entanglement::{check_density_matrix, hermitian_sqrt};
+use nalgebra::{Complex, DMatrix, DVector};
+
+/// Checks that two operators act on the same space
+fn check_same_shape(a: &DMatrix<Complex<f64>>, b: &DMatrix<Complex<f64>>) -> Result<(), String> {
+    if a.shape() != b.shape() {
+        return Err(format!(
+            "Cannot compare a {}×{} matrix with a {}×{} matrix",
+            a.nrows(),
+            a.ncols(),
+            b.nrows(),
+            b.ncols()
+        ));
+    }
+    Ok(())
+}
+
+/// Fidelity |⟨ψ|φ⟩|² between two normalized state vectors
+pub fn state_fidelity(
+    psi: &DVector<Complex<f64>>,
+    phi: &DVector<Complex<f64>>,
+) -> Result<f64, String> {
+    if psi.len() != phi.len() {
+        return Err(format!(
+            "Cannot compare states of dimension {} and {}",
+            psi.len(),
+            phi.len()
+        ));
+    }
+    for state in [psi, phi] {
+        if (state.norm_squared() - 1.0).abs() > 1e-8 {
+            return Err("State vectors must be normalized".to_string());
+        }
+    }
+    Ok(psi.dotc(phi).norm_sqr().min(1.0))
+}
+
+/// Uhlmann fidelity (Tr √(√ρ σ √ρ))² between two density matrices
+pub fn fidelity(rho: &DMatrix<Complex<f64>>, sigma: &DMatrix<Complex<f64>>) -> Result<f64, String> {
+    check_same_shape(rho, sigma)?;
+    check_density_matrix(rho)?;
+    check_density_matrix(sigma)?;
+
+    let sqrt_rho = hermitian_sqrt(rho);
+    let root_trace: f64 = (&sqrt_rho * sigma * &sqrt_rho)
+        .symmetric_eigenvalues()
+        .iter()
+        .map(|&x| x.max(0.0).sqrt())
+        .sum();
+    Ok((root_trace * root_trace).min(1.0))
+}
+
+/// Trace distance ½ Tr|ρ - σ| between two density matrices
+pub fn trace_distance(
+    rho: &DMatrix<Complex<f64>>,
+    sigma: &DMatrix<Complex<f64>>,
+) -> Result<f64, String> {
+    check_same_shape(rho, sigma)?;
+    check_density_matrix(rho)?;
+    check_density_matrix(sigma)?;
+
+    let difference = rho - sigma;
+    let sum: f64 = difference
+        .symmetric_eigenvalues()
+        .iter()
+        .map(|x| x.abs())
+        .sum();
+    Ok((0.5 * sum).min(1.0))
+}
+
+/// Hilbert–Schmidt inner product Tr(A† B)
+pub fn hilbert_schmidt_inner_product(
+    a: &DMatrix<Complex<f64>>,
+    b: &DMatrix<Complex<f64>>,
+) -> Result<Complex<f64>, String> {
+    check_same_shape(a, b)?;
+    // Tr(A† B) = Σ_ij conj(A_ij) B_ij
+    Ok(a.iter().zip(b.iter()).map(|(x, y)| x.conj() * y).sum())
+}
+
+impl QuantumCircuit {
+    /// Fidelity |⟨ψ|φ⟩|² between this state and `other`
+    pub fn fidelity(&self, other: &QuantumCircuit) -> Result<f64, String> {
+        state_fidelity(self.get_state(), other.get_state())
+    }
+
+    /// Trace distance √(1 - F) between this pure state and `other`
+    pub fn trace_distance(&self, other: &QuantumCircuit) -> Result<f64, String> {
+        Ok((1.0 - self.fidelity(other)?).max(0.0).sqrt())
+    }
+
+    /// Bloch vector (⟨X⟩, ⟨Y⟩, ⟨Z⟩) of `qubit`'s reduced state
+    pub fn bloch_vector(&self, qubit: usize) -> Result<[f64; 3], String> {
+        let rho = self.reduced_density_matrix(&[qubit])?;
+        Ok([
+            2.0 * rho[(1, 0)].re,
+            2.0 * rho[(1, 0)].im,
+            rho[(0, 0)].re - rho[(1, 1)].re,
+        ])
+    }
+
+    /// Bloch vectors of every qubit, indexed by qubit
+    pub fn bloch_vectors(&self) -> Vec<[f64; 3]> {
+        (0..self.n_qubits())
+            .map(|q| self.bloch_vector(q).expect("qubit is in range"))
+            .collect()
+    }
+}
+
+impl DensityMatrixCircuit {
+    /// Uhlmann fidelity between this state and `other`
+    pub fn fidelity(&self, other: &DensityMatrixCircuit) -> Result<f64, String> {
+        fidelity(self.get_density_matrix(), other.get_density_matrix())
+    }
+
+    /// Trace distance between this state and `other`
+    pub fn trace_distance(&self, other: &DensityMatrixCircuit) -> Result<f64, String> {
+        trace_distance(self.get_density_matrix(), other.get_density_matrix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::ir::Circuit;
+    use crate::noise::{KrausChannel, NoisyBackend};
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    fn prepare(circuit: &Circuit) -> QuantumCircuit {
+        let mut backend = QuantumCircuit::new(circuit.n_qubits()).unwrap();
+        backend.run(circuit).unwrap();
+        backend
+    }
+
+    fn projector(state: &DVector<Complex<f64>>) -> DMatrix<Complex<f64>> {
+        state * state.adjoint()
+    }
+
+    #[test]
+    fn test_pure_state_metrics() {
+        let mut target = Circuit::new(2).unwrap();
+        target.h(0).unwrap().cx(0, 1).unwrap();
+        let target = prepare(&target);
+
+        // A student who forgot the CNOT prepares |+0⟩, with overlap 1/2
+        let mut attempt = Circuit::new(2).unwrap();
+        attempt.h(0).unwrap();
+        let attempt = prepare(&attempt);
+
+        assert_relative_eq!(target.fidelity(&target).unwrap(), 1.0, epsilon = 1e-12);
+        assert_relative_eq!(target.fidelity(&attempt).unwrap(), 0.25, epsilon = 1e-12);
+        assert_relative_eq!(
+            target.trace_distance(&attempt).unwrap(),
+            0.75_f64.sqrt(),
+            epsilon = 1e-12
+        );
+
+        // The mixed-state formulas agree on pure states
+        let (rho, sigma) = (
+            projector(target.get_state()),
+            projector(attempt.get_state()),
+        );
+        assert_relative_eq!(fidelity(&rho, &sigma).unwrap(), 0.25, epsilon = 1e-8);
+        assert_relative_eq!(
+            trace_distance(&rho, &sigma).unwrap(),
+            0.75_f64.sqrt(),
+            epsilon = 1e-10
+        );
+
+        // Global phases do not matter
+        let mut phased = Circuit::new(2).unwrap();
+        phased
+            .h(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .z(0)
+            .unwrap()
+            .z(1)
+            .unwrap();
+        let mut flipped = phased.clone();
+        flipped.x(0).unwrap().x(1).unwrap();
+        assert_relative_eq!(
+            prepare(&phased).fidelity(&prepare(&flipped)).unwrap(),
+            1.0,
+            epsilon = 1e-12
+        );
+
+        assert!(target.fidelity(&QuantumCircuit::new(3).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_mixed_state_metrics() {
+        let mut plus = DensityMatrixCircuit::new(1);
+        plus.apply_gate(crate::gates::HadamardGate, 0).unwrap();
+        let mut dephased = plus.clone();
+        dephased
+            .apply_channel(
+                &KrausChannel::phase_damping(1.0).unwrap(),
+                &[0],
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+
+        // Fully dephasing |+⟩ leaves I/2
+        assert_relative_eq!(plus.fidelity(&dephased).unwrap(), 0.5, epsilon = 1e-8);
+        assert_relative_eq!(
+            plus.trace_distance(&dephased).unwrap(),
+            0.5,
+            epsilon = 1e-10
+        );
+        assert_relative_eq!(dephased.fidelity(&dephased).unwrap(), 1.0, epsilon = 1e-8);
+
+        // Classical distributions reduce to the Bhattacharyya coefficient and
+        // total variation distance
+        let diagonal = |p: &[f64]| {
+            DMatrix::from_diagonal(&DVector::from_iterator(
+                p.len(),
+                p.iter().map(|&x| Complex::new(x, 0.0)),
+            ))
+        };
+        let (rho, sigma) = (diagonal(&[0.7, 0.2, 0.1, 0.0]), diagonal(&[0.25; 4]));
+        let bhattacharyya: f64 = [0.7_f64, 0.2, 0.1, 0.0]
+            .iter()
+            .map(|p| (p * 0.25).sqrt())
+            .sum();
+        assert_relative_eq!(
+            fidelity(&rho, &sigma).unwrap(),
+            bhattacharyya * bhattacharyya,
+            epsilon = 1e-8
+        );
+        assert_relative_eq!(trace_distance(&rho, &sigma).unwrap(), 0.45, epsilon = 1e-10);
+
+        assert!(fidelity(&rho, &diagonal(&[0.5, 0.5])).is_err());
+        assert!(trace_distance(&rho, &diagonal(&[0.5; 4])).is_err());
+    }
+
+    #[test]
+    fn test_hilbert_schmidt_inner_product() {
+        let mut dephased = DensityMatrixCircuit::new(2);
+        dephased.apply_gate(crate::gates::HadamardGate, 0).unwrap();
+        dephased
+            .apply_channel(
+                &KrausChannel::depolarizing(0.3).unwrap(),
+                &[0],
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+        let rho = dephased.get_density_matrix();
+        let purity = hilbert_schmidt_inner_product(rho, rho).unwrap();
+        assert_relative_eq!(purity.re, dephased.purity(), epsilon = 1e-12);
+        assert_relative_eq!(purity.im, 0.0, epsilon = 1e-12);
+
+        // The overlap of two projectors is Tr(|ψ⟩⟨ψ|φ⟩⟨φ|) = |⟨ψ|φ⟩|²
+        let mut a = Circuit::new(1).unwrap();
+        a.ry(0.8, 0).unwrap();
+        let mut b = Circuit::new(1).unwrap();
+        b.rx(-1.3, 0).unwrap();
+        let (a, b) = (prepare(&a), prepare(&b));
+        let overlap =
+            hilbert_schmidt_inner_product(&projector(a.get_state()), &projector(b.get_state()))
+                .unwrap();
+        assert_relative_eq!(overlap.re, a.fidelity(&b).unwrap(), epsilon = 1e-12);
+
+        assert!(hilbert_schmidt_inner_product(rho, &DMatrix::identity(2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_bloch_vectors() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .s(1)
+            .unwrap()
+            .u3(0.6, 0.4, 0.0, 2)
+            .unwrap();
+        let vectors = prepare(&circuit).bloch_vectors();
+        let expected = [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [
+                0.6_f64.sin() * 0.4_f64.cos(),
+                0.6_f64.sin() * 0.4_f64.sin(),
+                0.6_f64.cos(),
+            ],
+        ];
+        for (vector, expected) in vectors.iter().zip(expected) {
+            for axis in 0..3 {
+                assert_relative_eq!(vector[axis], expected[axis], epsilon = 1e-12);
+            }
+        }
+
+        // Halves of a Bell pair sit at the centre of the sphere
+        let mut bell = Circuit::new(2).unwrap();
+        bell.ry(PI / 2.0, 0).unwrap().cx(0, 1).unwrap();
+        let bell = prepare(&bell);
+        for q in 0..2 {
+            let [x, y, z] = bell.bloch_vector(q).unwrap();
+            assert_relative_eq!(x * x + y * y + z * z, 0.0, epsilon = 1e-12);
+        }
+        assert!(bell.bloch_vector(2).is_err());
+    }
+}
@@ -0,0 +1,723 @@
+/*
+This file implements a matrix product state (MPS) simulator for circuits that
+create little entanglement.
+
+Key concepts:
+1. Matrix Product States:
+   - The amplitude of |s_0 s_1 ... s_{n-1}⟩ is the matrix product
+     A_0[s_0] A_1[s_1] ... A_{n-1}[s_{n-1}], with 1 × χ and χ × 1 matrices
+     at the ends
+   - The bond dimension χ between two sites grows with the entanglement
+     across that cut, so product states need χ = 1 and GHZ states χ = 2
+   - Memory is O(n χ²) instead of 2^n amplitudes
+   - Learn more: https://en.wikipedia.org/wiki/Matrix_product_state
+
+2. Canonical Form:
+   - Sites left of the orthogonality center are left-isometric and sites to
+     its right are right-isometric, so the norm of the state is the norm of
+     the center tensor alone
+   - The center is moved one site at a time with QR decompositions
+
+3. Gates:
+   - A single-qubit gate mixes the two matrices of one site
+   - A k-qubit gate is applied to k neighbouring sites, which are then split
+     apart again with SVDs; targets that are not neighbours are first moved
+     next to each other with SWAPs and moved back afterwards
+   - Reference: https://arxiv.org/abs/quant-ph/0301063 (Vidal)
+
+4. Truncation:
+   - Singular values below the truncation threshold, and all but the largest
+     `max_bond_dimension`, are discarded after every SVD
+   - The discarded weight Σ s_i² is accumulated in `truncation_error()`; the
+     fidelity with the exact state is roughly 1 minus that sum
+   - Reference: https://arxiv.org/abs/1008.3477 (Schollwöck)
+
+Qubit k is site k of the chain and, as in `QuantumCircuit`, bit k of the
+basis-state index.
+*/
+
+use crate::backend::{Backend, Counts};
+use crate::error::QuantumError;
+use crate::gates::{check_size, controlled_matrix, MultiQubitGate, QuantumGate, SwapGate, XGate};
+use crate::ir::Instruction;
+use crate::observable::{Pauli, PauliString};
+use nalgebra::{Complex, DMatrix, DVector};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+/// Bond dimension used when none is configured
+const DEFAULT_MAX_BOND_DIMENSION: usize = 64;
+
+/// Singular values below this are dropped when none is configured
+const DEFAULT_TRUNCATION_THRESHOLD: f64 = 1e-12;
+
+type Tensor = [DMatrix<Complex<f64>>; 2];
+
+#[derive(Debug, Clone)]
+pub struct MpsCircuit {
+    /// A_k[s] for each site k and physical index s
+    sites: Vec<Tensor>,
+    /// Site holding the orthogonality center
+    center: usize,
+    max_bond_dimension: usize,
+    truncation_threshold: f64,
+    truncation_error: f64,
+}
+
+impl MpsCircuit {
+    /// Creates an MPS for `n_qubits` qubits in the |00...0⟩ state
+    pub fn new(n_qubits: usize) -> Self {
+        if n_qubits == 0 {
+            panic!("Number of qubits must be greater than 0");
+        }
+
+        let mut mps = MpsCircuit {
+            sites: Vec::with_capacity(n_qubits),
+            center: 0,
+            max_bond_dimension: DEFAULT_MAX_BOND_DIMENSION,
+            truncation_threshold: DEFAULT_TRUNCATION_THRESHOLD,
+            truncation_error: 0.0,
+        };
+        mps.sites.resize(n_qubits, product_site(false));
+        mps
+    }
+
+    /// Caps the bond dimension kept after each SVD (at least 1)
+    pub fn with_max_bond_dimension(mut self, max_bond_dimension: usize) -> Self {
+        self.max_bond_dimension = max_bond_dimension.max(1);
+        self
+    }
+
+    /// Drops singular values below `threshold` after each SVD
+    pub fn with_truncation_threshold(mut self, threshold: f64) -> Self {
+        self.truncation_threshold = threshold;
+        self
+    }
+
+    /// Checks that the targets are in range and distinct
+    fn check_targets(&self, targets: &[usize]) -> Result<(), QuantumError> {
+        for (i, &target) in targets.iter().enumerate() {
+            if target >= self.sites.len() {
+                return Err(QuantumError::QubitOutOfRange {
+                    qubit: target,
+                    n_qubits: self.sites.len(),
+                });
+            }
+            if targets[..i].contains(&target) {
+                return Err(QuantumError::DuplicateQubit(target));
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the orthogonality center one site to the right
+    fn move_center_right(&mut self) {
+        let k = self.center;
+        let [a0, a1] = &self.sites[k];
+        let rows = a0.nrows();
+
+        // Stack A[0] over A[1] and keep the isometric Q on this site
+        let mut stacked = DMatrix::zeros(2 * rows, a0.ncols());
+        stacked.rows_mut(0, rows).copy_from(a0);
+        stacked.rows_mut(rows, rows).copy_from(a1);
+        let qr = stacked.qr();
+        let (q, r) = (qr.q(), qr.r());
+        self.sites[k] = [
+            q.rows(0, rows).into_owned(),
+            q.rows(rows, rows).into_owned(),
+        ];
+        let next = &mut self.sites[k + 1];
+        *next = [&r * &next[0], &r * &next[1]];
+        self.center = k + 1;
+    }
+
+    /// Moves the orthogonality center one site to the left
+    fn move_center_left(&mut self) {
+        let k = self.center;
+        let [a0, a1] = &self.sites[k];
+        let cols = a0.ncols();
+
+        // [A[0] A[1]] = L Q with orthonormal rows, from the QR of its adjoint
+        let mut joined = DMatrix::zeros(a0.nrows(), 2 * cols);
+        joined.columns_mut(0, cols).copy_from(a0);
+        joined.columns_mut(cols, cols).copy_from(a1);
+        let qr = joined.adjoint().qr();
+        let (q, l) = (qr.q().adjoint(), qr.r().adjoint());
+        self.sites[k] = [
+            q.columns(0, cols).into_owned(),
+            q.columns(cols, cols).into_owned(),
+        ];
+        let previous = &mut self.sites[k - 1];
+        *previous = [&previous[0] * &l, &previous[1] * &l];
+        self.center = k - 1;
+    }
+
+    fn move_center_to(&mut self, site: usize) {
+        while self.center < site {
+            self.move_center_right();
+        }
+        while self.center > site {
+            self.move_center_left();
+        }
+    }
+
+    /// Applies `matrix` to the `k` neighbouring sites starting at `first`,
+    /// with `first` as the most significant bit of its index
+    fn apply_to_block(&mut self, matrix: &DMatrix<Complex<f64>>, first: usize, k: usize) {
+        if k == 1 {
+            let [a0, a1] = &self.sites[first];
+            self.sites[first] = [
+                a0 * matrix[(0, 0)] + a1 * matrix[(0, 1)],
+                a0 * matrix[(1, 0)] + a1 * matrix[(1, 1)],
+            ];
+            return;
+        }
+
+        // Contract the block, containing the center, into θ[s] (χ_left × χ_right)
+        self.move_center_to(first);
+        let mut theta: Vec<DMatrix<Complex<f64>>> = self.sites[first].to_vec();
+        for site in &self.sites[first + 1..first + k] {
+            theta = theta
+                .iter()
+                .flat_map(|t| [t * &site[0], t * &site[1]])
+                .collect();
+        }
+        let theta: Vec<DMatrix<Complex<f64>>> = (0..1 << k)
+            .map(|s| {
+                (0..1 << k).fold(
+                    DMatrix::zeros(theta[0].nrows(), theta[0].ncols()),
+                    |acc, t| acc + &theta[t] * matrix[(s, t)],
+                )
+            })
+            .collect();
+
+        // Split off one site at a time, leaving the center on the last one
+        let mut rest = theta;
+        for j in 0..k - 1 {
+            let half = rest.len() / 2;
+            let (rows, cols) = (rest[0].nrows(), rest[0].ncols());
+            let mut joined = DMatrix::zeros(2 * rows, half * cols);
+            for (index, block) in rest.iter().enumerate() {
+                let (s, r) = (index / half, index % half);
+                joined
+                    .slice_mut((s * rows, r * cols), (rows, cols))
+                    .copy_from(block);
+            }
+
+            let (u, sv) = self.truncated_svd(joined);
+            let bond = u.ncols();
+            self.sites[first + j] = [
+                u.rows(0, rows).into_owned(),
+                u.rows(rows, rows).into_owned(),
+            ];
+            rest = (0..half)
+                .map(|r| sv.columns(r * cols, cols).into_owned())
+                .collect();
+            debug_assert_eq!(rest[0].nrows(), bond);
+        }
+        self.sites[first + k - 1] = [rest[0].clone(), rest[1].clone()];
+        self.center = first + k - 1;
+    }
+
+    /// Returns (U, S V†) for `matrix` after truncating small singular values,
+    /// renormalized to the weight before truncation
+    fn truncated_svd(
+        &mut self,
+        matrix: DMatrix<Complex<f64>>,
+    ) -> (DMatrix<Complex<f64>>, DMatrix<Complex<f64>>) {
+        let svd = matrix.svd(true, true);
+        let (u, v_t) = (svd.u.unwrap(), svd.v_t.unwrap());
+        let values = svd.singular_values;
+
+        let total: f64 = values.iter().map(|s| s * s).sum();
+        let keep = values
+            .iter()
+            .take(self.max_bond_dimension)
+            .take_while(|&&s| s >= self.truncation_threshold)
+            .count()
+            .max(1);
+        let kept: f64 = values.iter().take(keep).map(|s| s * s).sum();
+        if total > 0.0 {
+            self.truncation_error += (total - kept) / total;
+        }
+
+        let scale = if kept > 0.0 {
+            (total / kept).sqrt()
+        } else {
+            1.0
+        };
+        let mut sv = v_t.rows(0, keep).into_owned();
+        for (i, mut row) in sv.row_iter_mut().enumerate() {
+            row *= Complex::new(values[i] * scale, 0.0);
+        }
+        (u.columns(0, keep).into_owned(), sv)
+    }
+
+    /// Applies `matrix` to arbitrary `targets` (targets[0] is the most
+    /// significant bit of its index, as in `MultiQubitGate`)
+    fn apply_matrix(
+        &mut self,
+        matrix: &DMatrix<Complex<f64>>,
+        targets: &[usize],
+        name: &str,
+    ) -> Result<(), QuantumError> {
+        self.check_targets(targets)?;
+        let k = targets.len();
+        check_size(matrix, targets.len(), name)?;
+
+        // Bubble targets[j] left into site first + j, remembering the swaps
+        let first = *targets.iter().min().unwrap();
+        let mut order: Vec<usize> = (0..self.sites.len()).collect();
+        let mut swaps = Vec::new();
+        for (j, &target) in targets.iter().enumerate() {
+            let mut position = order.iter().position(|&q| q == target).unwrap();
+            while position > first + j {
+                self.swap_sites(position - 1);
+                order.swap(position - 1, position);
+                swaps.push(position - 1);
+                position -= 1;
+            }
+        }
+
+        self.apply_to_block(matrix, first, k);
+        for &site in swaps.iter().rev() {
+            self.swap_sites(site);
+        }
+        Ok(())
+    }
+
+    /// Swaps the qubits on sites `site` and `site + 1`
+    fn swap_sites(&mut self, site: usize) {
+        self.apply_to_block(&SwapGate.matrix(), site, 2);
+    }
+
+    /// Applies a single-qubit gate to the specified target qubit
+    pub fn apply_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        let matrix = DMatrix::from_iterator(2, 2, gate.matrix().iter().cloned());
+        self.apply_matrix(&matrix, &[target], gate.name())
+    }
+
+    /// Applies a single-qubit gate to `target` when `control` is |1⟩
+    pub fn apply_controlled_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        control: usize,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        let matrix = DMatrix::from_iterator(2, 2, gate.matrix().iter().cloned());
+        self.apply_matrix(
+            &controlled_matrix(&matrix, 1, 1),
+            &[control, target],
+            gate.name(),
+        )
+    }
+
+    /// Applies a k-qubit gate to the listed target qubits
+    pub fn apply_multi_qubit_gate<G: MultiQubitGate>(
+        &mut self,
+        gate: G,
+        targets: &[usize],
+    ) -> Result<(), QuantumError> {
+        if targets.len() != gate.n_qubits() {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("{} gate targets", gate.name()),
+                expected: gate.n_qubits(),
+                found: targets.len(),
+            });
+        }
+        self.apply_matrix(&gate.matrix(), targets, gate.name())
+    }
+
+    /// Measures the specified qubit and collapses the state according to the outcome
+    pub fn measure(&mut self, target: usize) -> Result<bool, QuantumError> {
+        self.measure_with(target, &mut rand::thread_rng())
+    }
+
+    /// Measures the specified qubit, drawing the outcome from `rng`
+    pub fn measure_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<bool, QuantumError> {
+        self.check_targets(&[target])?;
+
+        // With the center on the target, P(s) is the weight of A[s] alone
+        self.move_center_to(target);
+        let [a0, a1] = &self.sites[target];
+        let (w0, w1) = (a0.norm_squared(), a1.norm_squared());
+        let random: f64 = rng.gen();
+        let result = random * (w0 + w1) < w1;
+
+        let (keep, norm) = if result { (1, w1) } else { (0, w0) };
+        let site = &mut self.sites[target];
+        site[keep] /= Complex::new(norm.sqrt(), 0.0);
+        site[1 - keep].fill(Complex::new(0.0, 0.0));
+        Ok(result)
+    }
+
+    /// Resets one qubit to |0⟩ by measuring it and flipping it on outcome 1
+    pub fn reset_qubit(&mut self, target: usize) -> Result<(), QuantumError> {
+        self.reset_qubit_with(target, &mut rand::thread_rng())
+    }
+
+    /// Like `reset_qubit`, drawing the discarded outcome from `rng`
+    pub fn reset_qubit_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<(), QuantumError> {
+        if self.measure_with(target, rng)? {
+            self.apply_gate(XGate, target)?;
+        }
+        Ok(())
+    }
+
+    /// Draws `shots` samples of every qubit, keyed like `Backend::run_shots`
+    ///
+    /// Each shot measures a copy of the state site by site, so the state is
+    /// left untouched and the results are reproducible for a given `seed`.
+    pub fn sample(&self, shots: usize, seed: u64) -> Counts {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut counts = Counts::new();
+        for _ in 0..shots {
+            let mut copy = self.clone();
+            let bits: String = (0..self.sites.len())
+                .map(|q| copy.measure_with(q, &mut rng).unwrap())
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .map(|bit| if bit { '1' } else { '0' })
+                .collect();
+            *counts.entry(bits).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Amplitude ⟨s|ψ⟩ of a basis state, with qubit k as bit k of the index
+    pub fn amplitude(&self, basis_state: usize) -> Result<Complex<f64>, QuantumError> {
+        let n = self.sites.len();
+        if n < usize::BITS as usize && basis_state >> n != 0 {
+            return Err(QuantumError::BasisStateOutOfRange {
+                state: basis_state,
+                dimension: 1 << n,
+            });
+        }
+        let product =
+            self.sites
+                .iter()
+                .enumerate()
+                .fold(DMatrix::identity(1, 1), |acc, (q, site)| {
+                    let bit = q < usize::BITS as usize && basis_state >> q & 1 == 1;
+                    acc * &site[bit as usize]
+                });
+        Ok(product[(0, 0)])
+    }
+
+    /// Returns the probability of measuring a specific basis state
+    pub fn get_probability(&self, basis_state: usize) -> Result<f64, QuantumError> {
+        Ok(self.amplitude(basis_state)?.norm_sqr())
+    }
+
+    /// Expands the MPS into a dense state vector (only for small registers)
+    pub fn to_state_vector(&self) -> Result<DVector<Complex<f64>>, QuantumError> {
+        if self.sites.len() > 24 {
+            return Err(QuantumError::InvalidQubitCount(self.sites.len()));
+        }
+        (0..1 << self.sites.len())
+            .map(|i| self.amplitude(i))
+            .collect::<Result<Vec<_>, _>>()
+            .map(DVector::from_vec)
+    }
+
+    /// ⟨ψ|P|ψ⟩ for a Pauli string P, contracted site by site
+    pub(crate) fn pauli_expectation(&self, string: &PauliString) -> f64 {
+        let zero = Complex::new(0.0, 0.0);
+        let one = Complex::new(1.0, 0.0);
+        let i = Complex::new(0.0, 1.0);
+
+        // E ← Σ_st P[s][t] A[s]† E A[t]
+        let environment =
+            self.sites
+                .iter()
+                .enumerate()
+                .fold(DMatrix::identity(1, 1), |e, (q, site)| {
+                    let pauli = match string.get(q) {
+                        Pauli::I => [[one, zero], [zero, one]],
+                        Pauli::X => [[zero, one], [one, zero]],
+                        Pauli::Y => [[zero, -i], [i, zero]],
+                        Pauli::Z => [[one, zero], [zero, -one]],
+                    };
+                    let mut next = DMatrix::zeros(site[0].ncols(), site[0].ncols());
+                    for (s, row) in pauli.iter().enumerate() {
+                        for (t, &entry) in row.iter().enumerate() {
+                            if entry != zero {
+                                next += (site[s].adjoint() * &e * &site[t]) * entry;
+                            }
+                        }
+                    }
+                    next
+                });
+        environment[(0, 0)].re
+    }
+
+    /// Bond dimension of each of the n − 1 cuts between neighbouring qubits
+    pub fn bond_dimensions(&self) -> Vec<usize> {
+        self.sites[..self.sites.len() - 1]
+            .iter()
+            .map(|site| site[0].ncols())
+            .collect()
+    }
+
+    /// Total weight Σ s_i² of the singular values discarded so far
+    pub fn truncation_error(&self) -> f64 {
+        self.truncation_error
+    }
+
+    /// Returns the number of qubits in the circuit
+    pub fn n_qubits(&self) -> usize {
+        self.sites.len()
+    }
+
+    /// Resets the whole register to |00...0⟩ and clears the truncation error
+    pub fn reset(&mut self) {
+        self.sites.fill(product_site(false));
+        self.center = 0;
+        self.truncation_error = 0.0;
+    }
+}
+
+/// A site of a product state holding |0⟩ or |1⟩
+fn product_site(one: bool) -> Tensor {
+    let (zero, unit) = (
+        DMatrix::from_element(1, 1, Complex::new(0.0, 0.0)),
+        DMatrix::from_element(1, 1, Complex::new(1.0, 0.0)),
+    );
+    if one {
+        [zero, unit]
+    } else {
+        [unit, zero]
+    }
+}
+
+impl Backend for MpsCircuit {
+    fn n_qubits(&self) -> usize {
+        MpsCircuit::n_qubits(self)
+    }
+
+    fn reset(&mut self) {
+        MpsCircuit::reset(self)
+    }
+
+    fn apply(
+        &mut self,
+        instruction: &Instruction,
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<bool>, QuantumError> {
+        match instruction {
+            Instruction::Gate { gate, qubits } => {
+                self.apply_matrix(&gate.matrix()?, qubits, gate.name())?;
+                Ok(None)
+            }
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => {
+                let matrix = controlled_matrix(&gate.matrix()?, 1, 1);
+                self.apply_matrix(&matrix, &[*control, *target], gate.name())?;
+                Ok(None)
+            }
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                ..
+            } => {
+                // Controls lead, so they are the most significant bits
+                let matrix = controlled_matrix(&gate.matrix()?, controls.len(), *control_state);
+                self.apply_matrix(&matrix, &instruction.qubits(), gate.name())?;
+                Ok(None)
+            }
+            Instruction::Measure { qubit, .. } => self.measure_with(*qubit, rng).map(Some),
+            Instruction::Reset { qubit } => {
+                self.reset_qubit_with(*qubit, rng)?;
+                Ok(None)
+            }
+            Instruction::Barrier { .. } => Ok(None),
+            Instruction::Conditional { .. } => Err(QuantumError::Unsupported(
+                "applying a conditional outside Backend::execute".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::QuantumCircuit;
+    use crate::gates::{CNOTGate, HadamardGate, RYGate, ToffoliGate};
+    use crate::ir::{Circuit, Gate};
+    use crate::observable::PauliSum;
+    use approx::assert_relative_eq;
+
+    fn sample_circuit() -> Circuit {
+        let mut circuit = Circuit::new(6).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .ry(0.7, 3)
+            .unwrap()
+            .u3(0.4, 1.1, -0.6, 5)
+            .unwrap()
+            .cx(0, 4)
+            .unwrap()
+            .cp(0.9, 5, 1)
+            .unwrap()
+            .iswap(3, 2)
+            .unwrap()
+            .ccx(4, 0, 2)
+            .unwrap()
+            .cswap(1, 5, 0)
+            .unwrap()
+            .controlled(Gate::RX(1.3.into()), 2, 3)
+            .unwrap()
+            .multi_controlled_on(Gate::H, &[5, 1], 0b01, &[4])
+            .unwrap()
+            .sx(1)
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_matches_state_vector() {
+        let circuit = sample_circuit();
+        let mut mps = MpsCircuit::new(6);
+        mps.run(&circuit).unwrap();
+        let mut dense = QuantumCircuit::new(6).unwrap();
+        dense.run(&circuit).unwrap();
+
+        let state = mps.to_state_vector().unwrap();
+        for (a, b) in state.iter().zip(dense.get_state().iter()) {
+            assert_relative_eq!((a - b).norm_sqr(), 0.0, epsilon = 1e-20);
+        }
+        assert_relative_eq!(mps.truncation_error(), 0.0, epsilon = 1e-20);
+
+        for observable in ["Z0", "X1X2", "0.5*Y3Z5 - 2*Z0Z4", "X0Y1Z2X3Y4Z5"] {
+            let observable: PauliSum = observable.parse().unwrap();
+            assert_relative_eq!(
+                mps.expectation(&observable).unwrap(),
+                dense.expectation(&observable).unwrap(),
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    #[test]
+    fn test_direct_gate_api() {
+        let mut mps = MpsCircuit::new(3);
+        mps.apply_gate(HadamardGate, 0).unwrap();
+        mps.apply_controlled_gate(RYGate::new(0.5), 0, 2).unwrap();
+        mps.apply_multi_qubit_gate(ToffoliGate, &[0, 2, 1]).unwrap();
+
+        let mut dense = QuantumCircuit::new(3).unwrap();
+        dense.apply_gate(HadamardGate, 0).unwrap();
+        dense.apply_controlled_gate(RYGate::new(0.5), 0, 2).unwrap();
+        dense
+            .apply_multi_qubit_gate(ToffoliGate, &[0, 2, 1])
+            .unwrap();
+        for i in 0..8 {
+            assert_relative_eq!(
+                mps.get_probability(i).unwrap(),
+                dense.get_probability(i).unwrap(),
+                epsilon = 1e-12
+            );
+        }
+
+        assert!(mps.apply_gate(HadamardGate, 3).is_err());
+        assert!(mps.apply_multi_qubit_gate(CNOTGate, &[1, 1]).is_err());
+        assert!(mps.apply_multi_qubit_gate(CNOTGate, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_large_ghz_state() {
+        let n = 100;
+        let mut circuit = Circuit::new(n).unwrap();
+        circuit.h(0).unwrap();
+        for q in 1..n {
+            circuit.cx(q - 1, q).unwrap();
+        }
+
+        let mut mps = MpsCircuit::new(n);
+        mps.run(&circuit).unwrap();
+        assert!(mps.bond_dimensions().iter().all(|&chi| chi == 2));
+        assert_relative_eq!(mps.truncation_error(), 0.0, epsilon = 1e-12);
+
+        let parity: PauliSum = "Z0Z99 + X0".parse().unwrap();
+        assert_relative_eq!(mps.expectation(&parity).unwrap(), 1.0, epsilon = 1e-10);
+
+        let counts = mps.sample(50, 3);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.values().sum::<usize>(), 50);
+        assert!(counts
+            .keys()
+            .all(|k| k == &"0".repeat(n) || k == &"1".repeat(n)));
+
+        let first = mps.measure(50).unwrap();
+        assert_eq!(mps.measure(0).unwrap(), first);
+        assert_eq!(mps.measure(99).unwrap(), first);
+    }
+
+    #[test]
+    fn test_truncation() {
+        // A brickwork circuit that builds up more entanglement than χ = 2 holds
+        let mut circuit = Circuit::new(8).unwrap();
+        for layer in 0..4 {
+            for q in 0..8 {
+                circuit.ry(0.3 + 0.17 * (q + layer) as f64, q).unwrap();
+            }
+            for q in (layer % 2..7).step_by(2) {
+                circuit.cx(q, q + 1).unwrap();
+            }
+        }
+        let mut dense = QuantumCircuit::new(8).unwrap();
+        dense.run(&circuit).unwrap();
+
+        let mut truncated = MpsCircuit::new(8).with_max_bond_dimension(2);
+        truncated.run(&circuit).unwrap();
+        assert!(truncated.bond_dimensions().iter().all(|&chi| chi <= 2));
+        let error = truncated.truncation_error();
+        assert!(error > 1e-4);
+
+        let state = truncated.to_state_vector().unwrap();
+        assert_relative_eq!(state.norm(), 1.0, epsilon = 1e-10);
+        let fidelity = state.dotc(dense.get_state()).norm_sqr();
+        assert!(fidelity < 1.0 - 1e-6);
+        assert!(fidelity > 1.0 - 2.0 * error);
+
+        let mut exact = MpsCircuit::new(8).with_truncation_threshold(0.0);
+        exact.run(&circuit).unwrap();
+        assert!(exact.bond_dimensions().iter().any(|&chi| chi > 2));
+        assert_relative_eq!(exact.truncation_error(), 0.0, epsilon = 1e-20);
+    }
+
+    #[test]
+    fn test_shots_on_backend() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .x(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .cx(1, 2)
+            .unwrap()
+            .measure_all()
+            .unwrap();
+        let counts = MpsCircuit::new(3).run_shots(&circuit, 100, 5).unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["001"] + counts["111"], 100);
+    }
+}
@@ -0,0 +1,609 @@
+/*
+This file implements quantum noise: Kraus channels, classical readout errors
+and noise models that attach them to a circuit's instructions.
+
+Key concepts:
+1. Kraus Channels:
+   - A channel maps ρ → Σ_i K_i ρ K_i†, where the Kraus operators satisfy
+     Σ_i K_i† K_i = I so that the trace is preserved
+   - Every physical (CPTP) process can be written this way
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_operation
+
+2. Standard Channels:
+   - Depolarizing: with probability p the qubit is replaced by I/2
+   - Bit flip / phase flip: X or Z is applied with probability p
+   - Amplitude damping: energy loss |1⟩ → |0⟩ with probability γ
+   - Phase damping: loss of coherence without energy loss
+   - Thermal relaxation: amplitude and phase damping for a gate of given
+     duration on a qubit with relaxation times T1 and T2
+   - Reference: Nielsen & Chuang, "Quantum Computation and Quantum
+     Information", section 8.3
+
+3. Noise Models:
+   - `NoiseModel` attaches channels to gate types (by `Gate::name()`) or to
+     qubits, and readout errors to measurements
+   - Channels are applied right after the instruction they belong to
+
+4. Simulation:
+   - `DensityMatrixCircuit` applies channels exactly
+   - `QuantumCircuit` samples one Kraus operator per channel with probability
+     ‖K_i ψ‖² (quantum trajectories); averaging many runs reproduces the
+     density-matrix result
+   - Learn more: https://en.wikipedia.org/wiki/Quantum_jump_method
+*/
+
+use crate::backend::{execute_with, Backend};
+use crate::circuit::QuantumCircuit;
+use crate::ir::{Circuit, Instruction};
+use nalgebra::{Complex, DMatrix};
+use rand::{Rng, RngCore};
+use std::collections::HashMap;
+
+/// A quantum channel given by its Kraus operators
+#[derive(Debug, Clone, PartialEq)]
+pub struct KrausChannel {
+    operators: Vec<DMatrix<Complex<f64>>>,
+    n_qubits: usize,
+}
+
+fn check_probability(name: &str, p: f64) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&p) {
+        return Err(format!("{} must lie in [0, 1], got {}", name, p));
+    }
+    Ok(())
+}
+
+fn matrix2(entries: [f64; 4]) -> DMatrix<Complex<f64>> {
+    DMatrix::from_row_slice(2, 2, &entries.map(|x| Complex::new(x, 0.0)))
+}
+
+/// The Pauli matrices I, X, Y, Z
+fn paulis() -> [DMatrix<Complex<f64>>; 4] {
+    let (zero, one, i) = (
+        Complex::new(0.0, 0.0),
+        Complex::new(1.0, 0.0),
+        Complex::new(0.0, 1.0),
+    );
+    [
+        matrix2([1.0, 0.0, 0.0, 1.0]),
+        matrix2([0.0, 1.0, 1.0, 0.0]),
+        DMatrix::from_row_slice(2, 2, &[zero, -i, i, zero]),
+        DMatrix::from_row_slice(2, 2, &[one, zero, zero, -one]),
+    ]
+}
+
+impl KrausChannel {
+    /// Creates a channel from its Kraus operators
+    ///
+    /// The operators must be square with the same power-of-two size and
+    /// satisfy Σ K_i† K_i = I.
+    pub fn new(operators: Vec<DMatrix<Complex<f64>>>) -> Result<Self, String> {
+        let dim = match operators.first() {
+            Some(first) => first.nrows(),
+            None => return Err("A channel needs at least one Kraus operator".to_string()),
+        };
+        if dim < 2 || !dim.is_power_of_two() {
+            return Err(format!("Kraus operator size {} is not a power of two", dim));
+        }
+        if operators
+            .iter()
+            .any(|k| k.nrows() != dim || k.ncols() != dim)
+        {
+            return Err(format!("Every Kraus operator must be {}x{}", dim, dim));
+        }
+
+        let completeness = operators
+            .iter()
+            .fold(DMatrix::zeros(dim, dim), |acc, k| acc + k.adjoint() * k);
+        if (completeness - DMatrix::identity(dim, dim)).norm() > 1e-10 {
+            return Err("Kraus operators do not preserve the trace".to_string());
+        }
+
+        Ok(KrausChannel {
+            operators,
+            n_qubits: dim.trailing_zeros() as usize,
+        })
+    }
+
+    /// Replaces one qubit by the maximally mixed state with probability `p`
+    ///
+    /// Kraus operators: √(1 - 3p/4) I, √(p/4) X, √(p/4) Y, √(p/4) Z
+    pub fn depolarizing(p: f64) -> Result<Self, String> {
+        Self::pauli_depolarizing(1, p)
+    }
+
+    /// Replaces two qubits by the maximally mixed state with probability `p`
+    pub fn two_qubit_depolarizing(p: f64) -> Result<Self, String> {
+        Self::pauli_depolarizing(2, p)
+    }
+
+    /// n-qubit depolarizing channel as a uniform mixture of Pauli strings
+    fn pauli_depolarizing(n_qubits: usize, p: f64) -> Result<Self, String> {
+        check_probability("Depolarizing probability", p)?;
+        let count = 1usize << (2 * n_qubits);
+        let paulis = paulis();
+
+        let operators = (0..count)
+            .map(|index| {
+                let weight = if index == 0 {
+                    1.0 - p * (count - 1) as f64 / count as f64
+                } else {
+                    p / count as f64
+                };
+                // Two bits of `index` select the Pauli on each qubit
+                let string = (0..n_qubits).fold(DMatrix::identity(1, 1), |acc, q| {
+                    acc.kronecker(&paulis[(index >> (2 * q)) & 3])
+                });
+                string * Complex::new(weight.sqrt(), 0.0)
+            })
+            .collect();
+        Self::new(operators)
+    }
+
+    /// Applies X with probability `p`
+    pub fn bit_flip(p: f64) -> Result<Self, String> {
+        check_probability("Bit flip probability", p)?;
+        let [identity, x, _, _] = paulis();
+        Self::new(vec![
+            identity * Complex::new((1.0 - p).sqrt(), 0.0),
+            x * Complex::new(p.sqrt(), 0.0),
+        ])
+    }
+
+    /// Applies Z with probability `p`
+    pub fn phase_flip(p: f64) -> Result<Self, String> {
+        check_probability("Phase flip probability", p)?;
+        let [identity, _, _, z] = paulis();
+        Self::new(vec![
+            identity * Complex::new((1.0 - p).sqrt(), 0.0),
+            z * Complex::new(p.sqrt(), 0.0),
+        ])
+    }
+
+    /// Decays |1⟩ to |0⟩ with probability `gamma`
+    ///
+    /// Kraus operators: [[1, 0], [0, √(1-γ)]] and [[0, √γ], [0, 0]]
+    pub fn amplitude_damping(gamma: f64) -> Result<Self, String> {
+        check_probability("Damping rate", gamma)?;
+        Self::new(vec![
+            matrix2([1.0, 0.0, 0.0, (1.0 - gamma).sqrt()]),
+            matrix2([0.0, gamma.sqrt(), 0.0, 0.0]),
+        ])
+    }
+
+    /// Shrinks the off-diagonal elements by √(1 - λ) without changing
+    /// populations
+    ///
+    /// Kraus operators: [[1, 0], [0, √(1-λ)]] and [[0, 0], [0, √λ]]
+    pub fn phase_damping(lambda: f64) -> Result<Self, String> {
+        check_probability("Damping rate", lambda)?;
+        Self::new(vec![
+            matrix2([1.0, 0.0, 0.0, (1.0 - lambda).sqrt()]),
+            matrix2([0.0, 0.0, 0.0, lambda.sqrt()]),
+        ])
+    }
+
+    /// Relaxation of a qubit with times `t1` and `t2` over a gate lasting
+    /// `time` (all in the same unit)
+    ///
+    /// Populations relax towards |0⟩ as e^{-t/T1} and coherences decay as
+    /// e^{-t/T2}. Physical qubits satisfy T2 ≤ 2·T1.
+    pub fn thermal_relaxation(t1: f64, t2: f64, time: f64) -> Result<Self, String> {
+        if t1 <= 0.0 || t2 <= 0.0 || time < 0.0 {
+            return Err("Relaxation times must be positive and time non-negative".to_string());
+        }
+        if t2 > 2.0 * t1 {
+            return Err(format!(
+                "T2 = {} exceeds the physical limit 2·T1 = {}",
+                t2,
+                2.0 * t1
+            ));
+        }
+
+        // Amplitude damping alone already decays coherences as e^{-t/2T1};
+        // phase damping supplies the rest
+        let gamma = 1.0 - (-time / t1).exp();
+        let lambda = 1.0 - (time / t1 - 2.0 * time / t2).exp();
+        Self::amplitude_damping(gamma)?.then(&Self::phase_damping(lambda.clamp(0.0, 1.0))?)
+    }
+
+    /// The channel that applies `self` and then `next`
+    pub fn then(&self, next: &KrausChannel) -> Result<Self, String> {
+        if self.n_qubits != next.n_qubits {
+            return Err(format!(
+                "Cannot compose a {}-qubit channel with a {}-qubit channel",
+                self.n_qubits, next.n_qubits
+            ));
+        }
+        let operators = next
+            .operators
+            .iter()
+            .flat_map(|b| self.operators.iter().map(move |a| b * a))
+            .filter(|k| k.norm() > 1e-15)
+            .collect();
+        Self::new(operators)
+    }
+
+    /// The Kraus operators of the channel
+    pub fn operators(&self) -> &[DMatrix<Complex<f64>>] {
+        &self.operators
+    }
+
+    /// Number of qubits the channel acts on
+    pub fn n_qubits(&self) -> usize {
+        self.n_qubits
+    }
+}
+
+/// Classical error on measurement results
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadoutError {
+    prob_1_given_0: f64,
+    prob_0_given_1: f64,
+}
+
+impl ReadoutError {
+    /// Creates a readout error that reports 1 for a |0⟩ outcome with
+    /// probability `prob_1_given_0` and 0 for a |1⟩ outcome with probability
+    /// `prob_0_given_1`
+    pub fn new(prob_1_given_0: f64, prob_0_given_1: f64) -> Result<Self, String> {
+        check_probability("Readout error probability", prob_1_given_0)?;
+        check_probability("Readout error probability", prob_0_given_1)?;
+        Ok(ReadoutError {
+            prob_1_given_0,
+            prob_0_given_1,
+        })
+    }
+
+    /// Creates a readout error that flips either outcome with probability `p`
+    pub fn symmetric(p: f64) -> Result<Self, String> {
+        Self::new(p, p)
+    }
+
+    /// Returns the reported bit for a measured bit
+    pub fn apply<R: Rng + ?Sized>(&self, outcome: bool, rng: &mut R) -> bool {
+        let flip = if outcome {
+            self.prob_0_given_1
+        } else {
+            self.prob_1_given_0
+        };
+        outcome ^ (rng.gen::<f64>() < flip)
+    }
+}
+
+/// Channels and readout errors attached to a circuit's instructions
+///
+/// Gate errors are keyed by `Gate::name()` (e.g. "Hadamard", "CNOT"); a
+/// controlled instruction is keyed by one "C" per control, a dash and the
+/// name of its target gate (e.g. "C-Hadamard", or "CC-Pauli-X" for
+/// Toffoli). A single-qubit channel attached to a multi-qubit gate is
+/// applied to each of its qubits.
+#[derive(Debug, Clone, Default)]
+pub struct NoiseModel {
+    gate_errors: HashMap<String, Vec<KrausChannel>>,
+    qubit_errors: HashMap<usize, Vec<KrausChannel>>,
+    readout_errors: HashMap<usize, ReadoutError>,
+    default_readout_error: Option<ReadoutError>,
+}
+
+impl NoiseModel {
+    /// Creates a noise model without any errors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `channel` after every gate with the given name
+    pub fn add_gate_error(&mut self, gate: &str, channel: KrausChannel) -> &mut Self {
+        self.gate_errors
+            .entry(gate.to_string())
+            .or_default()
+            .push(channel);
+        self
+    }
+
+    /// Applies a single-qubit `channel` to `qubit` after every gate acting on it
+    pub fn add_qubit_error(
+        &mut self,
+        qubit: usize,
+        channel: KrausChannel,
+    ) -> Result<&mut Self, String> {
+        if channel.n_qubits() != 1 {
+            return Err(format!(
+                "Qubit errors must be single-qubit channels, got {} qubits",
+                channel.n_qubits()
+            ));
+        }
+        self.qubit_errors.entry(qubit).or_default().push(channel);
+        Ok(self)
+    }
+
+    /// Applies `error` to measurements of every qubit without its own
+    /// readout error
+    pub fn set_readout_error(&mut self, error: ReadoutError) -> &mut Self {
+        self.default_readout_error = Some(error);
+        self
+    }
+
+    /// Applies `error` to measurements of `qubit`
+    pub fn add_readout_error(&mut self, qubit: usize, error: ReadoutError) -> &mut Self {
+        self.readout_errors.insert(qubit, error);
+        self
+    }
+
+    /// The readout error for measurements of `qubit`, if any
+    pub fn readout_error(&self, qubit: usize) -> Option<&ReadoutError> {
+        self.readout_errors
+            .get(&qubit)
+            .or(self.default_readout_error.as_ref())
+    }
+
+    /// Channels to apply after `instruction`, each with the qubits it acts on
+    pub fn channels_after(
+        &self,
+        instruction: &Instruction,
+    ) -> Result<Vec<(&KrausChannel, Vec<usize>)>, String> {
+        let (key, qubits) = match instruction {
+            Instruction::Gate { gate, qubits } => (gate.name().to_string(), qubits.clone()),
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => (format!("C-{}", gate.name()), vec![*control, *target]),
+            Instruction::MultiControlled { gate, controls, .. } => (
+                format!("{}-{}", "C".repeat(controls.len()), gate.name()),
+                instruction.qubits(),
+            ),
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut channels = Vec::new();
+        for channel in self.gate_errors.get(&key).into_iter().flatten() {
+            if channel.n_qubits() == qubits.len() {
+                channels.push((channel, qubits.clone()));
+            } else if channel.n_qubits() == 1 {
+                channels.extend(qubits.iter().map(|&q| (channel, vec![q])));
+            } else {
+                return Err(format!(
+                    "{}-qubit channel cannot follow {} on {} qubits",
+                    channel.n_qubits(),
+                    key,
+                    qubits.len()
+                ));
+            }
+        }
+        for &qubit in &qubits {
+            for channel in self.qubit_errors.get(&qubit).into_iter().flatten() {
+                channels.push((channel, vec![qubit]));
+            }
+        }
+        Ok(channels)
+    }
+}
+
+/// A backend that can also apply quantum channels
+pub trait NoisyBackend: Backend {
+    /// Applies `channel` to the listed qubits (qubits[0] is the most
+    /// significant bit of the Kraus operators)
+    ///
+    /// Backends that sample the channel draw from `rng`.
+    fn apply_channel(
+        &mut self,
+        channel: &KrausChannel,
+        qubits: &[usize],
+        rng: &mut dyn RngCore,
+    ) -> Result<(), String>;
+
+    /// Executes `circuit` on the current state with the errors of `noise`
+    fn execute_noisy(
+        &mut self,
+        circuit: &Circuit,
+        noise: &NoiseModel,
+    ) -> Result<Vec<bool>, String> {
+        self.execute_noisy_with_rng(circuit, noise, &mut rand::thread_rng())
+    }
+
+    /// Like `execute_noisy`, drawing every random choice from `rng`
+    fn execute_noisy_with_rng(
+        &mut self,
+        circuit: &Circuit,
+        noise: &NoiseModel,
+        rng: &mut dyn RngCore,
+    ) -> Result<Vec<bool>, String> {
+        execute_with(self, circuit, rng, |backend, instruction, outcome, rng| {
+            for (channel, qubits) in noise.channels_after(instruction)? {
+                backend.apply_channel(channel, &qubits, rng)?;
+            }
+            Ok(match (instruction, outcome) {
+                (Instruction::Measure { qubit, .. }, Some(bit)) => Some(
+                    noise
+                        .readout_error(*qubit)
+                        .map_or(bit, |error| error.apply(bit, rng)),
+                ),
+                _ => outcome,
+            })
+        })
+    }
+
+    /// Resets the backend and executes `circuit` with noise from |00...0⟩
+    fn run_noisy(&mut self, circuit: &Circuit, noise: &NoiseModel) -> Result<Vec<bool>, String> {
+        self.reset();
+        self.execute_noisy(circuit, noise)
+    }
+}
+
+impl NoisyBackend for QuantumCircuit {
+    /// Samples one Kraus operator K_i with probability ‖K_i ψ‖² and replaces
+    /// ψ by K_i ψ / ‖K_i ψ‖
+    fn apply_channel(
+        &mut self,
+        channel: &KrausChannel,
+        qubits: &[usize],
+        rng: &mut dyn RngCore,
+    ) -> Result<(), String> {
+        if qubits.len() != channel.n_qubits() {
+            return Err(format!(
+                "{}-qubit channel applied to {} qubits",
+                channel.n_qubits(),
+                qubits.len()
+            ));
+        }
+
+        let mut random: f64 = rng.gen();
+        let mut chosen = None;
+        for operator in channel.operators() {
+            let mut branch = self.clone();
+            // Kraus operators are not unitary, so skip the gate checks
+            branch.apply_controlled_matrix(operator, &[], 0, qubits)?;
+            let probability: f64 = branch.get_state().iter().map(|x| x.norm_sqr()).sum();
+            if probability < 1e-15 {
+                continue;
+            }
+            chosen = Some((branch, probability));
+            if random < probability {
+                break;
+            }
+            random -= probability;
+        }
+
+        let (mut branch, probability) =
+            chosen.ok_or_else(|| "Channel annihilated the state".to_string())?;
+        let scale = Complex::new(1.0 / probability.sqrt(), 0.0);
+        branch.state_mut().iter_mut().for_each(|x| *x *= scale);
+        *self = branch;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::density::DensityMatrixCircuit;
+    use approx::assert_relative_eq;
+
+    fn excited() -> Circuit {
+        let mut circuit = Circuit::new(1).unwrap();
+        circuit.x(0).unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_channels_preserve_trace() {
+        for channel in [
+            KrausChannel::depolarizing(0.3),
+            KrausChannel::two_qubit_depolarizing(0.3),
+            KrausChannel::bit_flip(0.1),
+            KrausChannel::phase_flip(0.1),
+            KrausChannel::amplitude_damping(0.2),
+            KrausChannel::phase_damping(0.4),
+            KrausChannel::thermal_relaxation(50.0, 70.0, 1.0),
+        ] {
+            let channel = channel.unwrap();
+            assert!(KrausChannel::new(channel.operators().to_vec()).is_ok());
+        }
+        assert_eq!(
+            KrausChannel::two_qubit_depolarizing(0.1)
+                .unwrap()
+                .n_qubits(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_invalid_channels() {
+        assert!(KrausChannel::depolarizing(1.5).is_err());
+        assert!(KrausChannel::amplitude_damping(-0.1).is_err());
+        assert!(KrausChannel::thermal_relaxation(10.0, 30.0, 1.0).is_err());
+        assert!(KrausChannel::new(vec![matrix2([1.0, 0.0, 0.0, 0.5])]).is_err());
+        assert!(KrausChannel::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_amplitude_damping_on_density_matrix() {
+        let mut noise = NoiseModel::new();
+        noise.add_gate_error("Pauli-X", KrausChannel::amplitude_damping(0.3).unwrap());
+
+        let mut backend = DensityMatrixCircuit::new(1);
+        backend.run_noisy(&excited(), &noise).unwrap();
+        assert_relative_eq!(backend.get_probability(1).unwrap(), 0.7, epsilon = 1e-10);
+        assert!(backend.verify_state());
+    }
+
+    #[test]
+    fn test_depolarizing_fully_mixes() {
+        let mut backend = DensityMatrixCircuit::new(1);
+        backend
+            .apply_channel(
+                &KrausChannel::depolarizing(1.0).unwrap(),
+                &[0],
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+        assert_relative_eq!(backend.purity(), 0.5, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_thermal_relaxation_rates() {
+        let (t1, t2, time) = (40.0, 30.0, 5.0);
+        let channel = KrausChannel::thermal_relaxation(t1, t2, time).unwrap();
+
+        // |+⟩ has both population in |1⟩ and coherence to decay
+        let mut circuit = Circuit::new(1).unwrap();
+        circuit.h(0).unwrap();
+        let mut backend = DensityMatrixCircuit::new(1);
+        backend.run(&circuit).unwrap();
+        backend
+            .apply_channel(&channel, &[0], &mut rand::thread_rng())
+            .unwrap();
+
+        let rho = backend.get_density_matrix();
+        assert_relative_eq!(rho[(1, 1)].re, 0.5 * (-time / t1).exp(), epsilon = 1e-10);
+        assert_relative_eq!(rho[(0, 1)].re, 0.5 * (-time / t2).exp(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_trajectories_match_density_matrix() {
+        let mut noise = NoiseModel::new();
+        noise
+            .add_qubit_error(0, KrausChannel::amplitude_damping(0.3).unwrap())
+            .unwrap();
+        let mut circuit = excited();
+        circuit.measure(0).unwrap();
+
+        let mut backend = QuantumCircuit::new(1).unwrap();
+        let shots = 4000;
+        let ones = (0..shots)
+            .filter(|_| backend.run_noisy(&circuit, &noise).unwrap()[0])
+            .count();
+        assert_relative_eq!(ones as f64 / shots as f64, 0.7, epsilon = 0.05);
+    }
+
+    #[test]
+    fn test_readout_error_flips_outcome() {
+        let mut noise = NoiseModel::new();
+        noise.set_readout_error(ReadoutError::new(1.0, 0.0).unwrap());
+        noise.add_readout_error(1, ReadoutError::symmetric(0.0).unwrap());
+
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.measure_all().unwrap();
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert_eq!(
+            backend.run_noisy(&circuit, &noise).unwrap(),
+            vec![true, false]
+        );
+    }
+
+    #[test]
+    fn test_channel_arity_checked() {
+        let mut noise = NoiseModel::new();
+        noise.add_gate_error(
+            "Pauli-X",
+            KrausChannel::two_qubit_depolarizing(0.1).unwrap(),
+        );
+        assert!(noise
+            .add_qubit_error(0, KrausChannel::two_qubit_depolarizing(0.1).unwrap())
+            .is_err());
+
+        let mut backend = QuantumCircuit::new(1).unwrap();
+        assert!(backend.run_noisy(&excited(), &noise).is_err());
+    }
+}
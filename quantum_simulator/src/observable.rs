@@ -0,0 +1,685 @@
+/*
+This file implements observables built from Pauli operators and their
+expectation values.
+
+Key concepts:
+1. Pauli Strings:
+   - A tensor product of I, X, Y and Z on individual qubits, written as
+     letter-index pairs such as `Z0Z1` or `X2`
+   - Every Pauli string is Hermitian with eigenvalues ±1
+   - Learn more: https://en.wikipedia.org/wiki/Pauli_group
+
+2. Pauli Sums:
+   - Real linear combinations like `0.5*Z0Z1 + 0.2*X2` describe Hamiltonians
+     and other observables
+   - Any Hermitian operator on n qubits can be written this way
+
+3. Exact Expectation Values:
+   - ⟨ψ|O|ψ⟩ is computed directly from the state vector, and Tr(ρO) from a
+     density matrix, without building the 2^n × 2^n matrix of O
+   - Matrix product states contract ⟨ψ|P|ψ⟩ site by site
+   - On a stabilizer tableau every Pauli string has expectation 0 or ±1
+
+4. Shot-Based Estimation:
+   - Measuring X or Y requires rotating the qubit into the Z basis first
+     (H for X, S† then H for Y)
+   - Terms that agree on every shared qubit are measured together from the
+     same shots (qubit-wise commuting groups)
+   - Each term's value is the average parity (±1) of its measured bits
+   - Reference: https://arxiv.org/abs/1704.05018
+*/
+
+use crate::backend::{Backend, Counts};
+use crate::circuit::QuantumCircuit;
+use crate::density::DensityMatrixCircuit;
+use crate::ir::{Circuit, Instruction};
+use crate::mps::MpsCircuit;
+use crate::stabilizer::StabilizerCircuit;
+use nalgebra::{Complex, DVector};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// A single-qubit Pauli operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+impl fmt::Display for Pauli {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = match self {
+            Pauli::I => "I",
+            Pauli::X => "X",
+            Pauli::Y => "Y",
+            Pauli::Z => "Z",
+        };
+        write!(f, "{}", letter)
+    }
+}
+
+/// A tensor product of Pauli operators, storing only the non-identity factors
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PauliString {
+    paulis: BTreeMap<usize, Pauli>,
+}
+
+impl PauliString {
+    /// The identity on every qubit
+    pub fn identity() -> Self {
+        Self::default()
+    }
+
+    /// Creates a Pauli string from (qubit, Pauli) pairs
+    pub fn from_paulis(paulis: &[(usize, Pauli)]) -> Result<Self, String> {
+        let mut string = Self::identity();
+        for &(qubit, pauli) in paulis {
+            if string.paulis.contains_key(&qubit) {
+                return Err(format!("Qubit {} appears more than once", qubit));
+            }
+            if pauli != Pauli::I {
+                string.paulis.insert(qubit, pauli);
+            }
+        }
+        Ok(string)
+    }
+
+    /// The Pauli acting on `qubit`
+    pub fn get(&self, qubit: usize) -> Pauli {
+        self.paulis.get(&qubit).copied().unwrap_or(Pauli::I)
+    }
+
+    /// The non-identity factors as (qubit, Pauli) pairs in qubit order
+    pub fn paulis(&self) -> impl Iterator<Item = (usize, Pauli)> + '_ {
+        self.paulis.iter().map(|(&q, &p)| (q, p))
+    }
+
+    /// Returns true if every factor is the identity
+    pub fn is_identity(&self) -> bool {
+        self.paulis.is_empty()
+    }
+
+    /// Smallest register the string fits in
+    pub fn min_qubits(&self) -> usize {
+        self.paulis.keys().next_back().map_or(0, |&q| q + 1)
+    }
+
+    /// Bit masks of the qubits carrying X or Y (flipped), Y or Z (phased)
+    /// and Y alone
+    fn masks(&self) -> (usize, usize, usize) {
+        let mask = |pick: &[Pauli]| {
+            self.paulis
+                .iter()
+                .filter(|(_, p)| pick.contains(p))
+                .fold(0, |acc, (&q, _)| acc | (1 << q))
+        };
+        (
+            mask(&[Pauli::X, Pauli::Y]),
+            mask(&[Pauli::Y, Pauli::Z]),
+            mask(&[Pauli::Y]),
+        )
+    }
+
+    /// Returns (j, c) such that P|i⟩ = c|j⟩
+    fn apply_to_basis(&self, i: usize) -> (usize, Complex<f64>) {
+        let (flip, phase, y) = self.masks();
+        // Y = iXZ: Z contributes (-1)^bit on Y and Z qubits, and each Y an extra i
+        let sign = if (i & phase).count_ones() & 1 == 0 {
+            1.0
+        } else {
+            -1.0
+        };
+        let i_power = match y.count_ones() % 4 {
+            0 => Complex::new(1.0, 0.0),
+            1 => Complex::new(0.0, 1.0),
+            2 => Complex::new(-1.0, 0.0),
+            _ => Complex::new(0.0, -1.0),
+        };
+        (i ^ flip, i_power * sign)
+    }
+
+    fn check_qubits(&self, n_qubits: usize) -> Result<(), String> {
+        if self.min_qubits() > n_qubits {
+            return Err(format!(
+                "{} acts on qubit {} but the register has {} qubits",
+                self,
+                self.min_qubits() - 1,
+                n_qubits
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PauliString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_identity() {
+            return write!(f, "I");
+        }
+        for (qubit, pauli) in self.paulis() {
+            write!(f, "{}{}", pauli, qubit)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PauliString {
+    type Err = String;
+
+    /// Parses letter-index pairs such as `Z0Z1`, `X0 Y3` or `I`
+    fn from_str(text: &str) -> Result<Self, String> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if chars == ['I'] {
+            return Ok(Self::identity());
+        }
+
+        let mut pairs = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let pauli = match chars[i] {
+                'I' => Pauli::I,
+                'X' => Pauli::X,
+                'Y' => Pauli::Y,
+                'Z' => Pauli::Z,
+                c => return Err(format!("Unexpected '{}' in Pauli string '{}'", c, text)),
+            };
+            let digits: String = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            if digits.is_empty() {
+                return Err(format!(
+                    "Missing qubit index after '{}' in '{}'",
+                    pauli, text
+                ));
+            }
+            let qubit = digits
+                .parse()
+                .map_err(|_| format!("Invalid qubit index '{}'", digits))?;
+            pairs.push((qubit, pauli));
+            i += 1 + digits.len();
+        }
+        if pairs.is_empty() {
+            return Err("Empty Pauli string".to_string());
+        }
+        Self::from_paulis(&pairs)
+    }
+}
+
+/// The Pauli measured on each qubit of a group of commuting terms
+type Basis = BTreeMap<usize, Pauli>;
+
+/// A real linear combination of Pauli strings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PauliSum {
+    terms: Vec<(f64, PauliString)>,
+}
+
+impl PauliSum {
+    /// The zero observable
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a sum from (coefficient, Pauli string) terms, merging repeats
+    pub fn from_terms(terms: Vec<(f64, PauliString)>) -> Self {
+        let mut sum = Self::new();
+        for (coefficient, string) in terms {
+            sum.add_term(coefficient, string);
+        }
+        sum
+    }
+
+    /// Adds `coefficient * string`, merging it with an existing equal string
+    pub fn add_term(&mut self, coefficient: f64, string: PauliString) -> &mut Self {
+        match self.terms.iter_mut().find(|(_, s)| *s == string) {
+            Some((existing, _)) => *existing += coefficient,
+            None => self.terms.push((coefficient, string)),
+        }
+        self
+    }
+
+    /// The terms in insertion order
+    pub fn terms(&self) -> &[(f64, PauliString)] {
+        &self.terms
+    }
+
+    /// Smallest register every term fits in
+    pub fn min_qubits(&self) -> usize {
+        self.terms
+            .iter()
+            .map(|(_, s)| s.min_qubits())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns O|ψ⟩
+    pub(crate) fn apply(&self, state: &DVector<Complex<f64>>) -> DVector<Complex<f64>> {
+        let mut result = DVector::from_element(state.len(), Complex::new(0.0, 0.0));
+        for (coefficient, string) in &self.terms {
+            for i in 0..state.len() {
+                let (j, phase) = string.apply_to_basis(i);
+                result[j] += phase * state[i] * *coefficient;
+            }
+        }
+        result
+    }
+
+    /// Estimates ⟨O⟩ for the state prepared by `circuit` from `shots`
+    /// measurements per group of qubit-wise commuting terms
+    ///
+    /// `circuit` must not contain measurements. Results are reproducible for
+    /// a given `seed`.
+    pub fn estimate<B: Backend>(
+        &self,
+        backend: &mut B,
+        circuit: &Circuit,
+        shots: usize,
+        seed: u64,
+    ) -> Result<f64, String> {
+        let n_qubits = circuit.n_qubits();
+        for (_, string) in &self.terms {
+            string.check_qubits(n_qubits)?;
+        }
+        if circuit
+            .instructions()
+            .iter()
+            .any(|i| matches!(i, Instruction::Measure { .. }))
+        {
+            return Err("The state preparation circuit must not measure".to_string());
+        }
+        if shots == 0 {
+            return Err("At least one shot is needed".to_string());
+        }
+
+        let mut value = 0.0;
+        let mut groups: Vec<(Basis, Vec<&(f64, PauliString)>)> = Vec::new();
+        for term in &self.terms {
+            if term.1.is_identity() {
+                value += term.0;
+                continue;
+            }
+            let compatible = |basis: &Basis| {
+                term.1
+                    .paulis()
+                    .all(|(q, p)| basis.get(&q).is_none_or(|&b| b == p))
+            };
+            match groups.iter_mut().find(|(basis, _)| compatible(basis)) {
+                Some((basis, members)) => {
+                    basis.extend(term.1.paulis());
+                    members.push(term);
+                }
+                None => groups.push((term.1.paulis.clone(), vec![term])),
+            }
+        }
+
+        for (index, (basis, members)) in groups.iter().enumerate() {
+            let mut measured = Circuit::with_clbits(n_qubits, n_qubits.max(circuit.n_clbits()))?;
+            measured.append(circuit)?;
+            for (&qubit, &pauli) in basis {
+                match pauli {
+                    Pauli::X => {
+                        measured.h(qubit)?;
+                    }
+                    Pauli::Y => {
+                        measured.p(-PI / 2.0, qubit)?.h(qubit)?;
+                    }
+                    _ => {}
+                }
+                measured.measure_into(qubit, qubit)?;
+            }
+
+            let counts = backend.run_shots(&measured, shots, seed.wrapping_add(index as u64))?;
+            for (coefficient, string) in members {
+                value += coefficient * parity_average(&counts, string, shots);
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Average of (-1)^(parity of the string's qubits) over the counts, where
+/// qubit q was measured into classical bit q
+fn parity_average(counts: &Counts, string: &PauliString, shots: usize) -> f64 {
+    let total: f64 = counts
+        .iter()
+        .map(|(bits, &count)| {
+            let bits = bits.as_bytes();
+            let ones = string
+                .paulis()
+                .filter(|(q, _)| bits[bits.len() - 1 - q] == b'1')
+                .count();
+            if ones % 2 == 0 {
+                count as f64
+            } else {
+                -(count as f64)
+            }
+        })
+        .sum();
+    total / shots as f64
+}
+
+impl fmt::Display for PauliSum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (i, (coefficient, string)) in self.terms.iter().enumerate() {
+            match (i, *coefficient < 0.0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            write!(f, "{}*{}", coefficient.abs(), string)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PauliSum {
+    type Err = String;
+
+    /// Parses sums such as `0.5*Z0Z1 + 0.2*X2 - Y0` or `1.5 * I`
+    fn from_str(text: &str) -> Result<Self, String> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if chars.is_empty() {
+            return Err("Empty observable".to_string());
+        }
+
+        // Split at '+' and '-' that start a term, leaving exponents such as
+        // 1e-3 intact
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let exponent = matches!(chars[i - 1], 'e' | 'E')
+                && i >= 2
+                && (chars[i - 2].is_ascii_digit() || chars[i - 2] == '.');
+            if matches!(chars[i], '+' | '-') && !exponent {
+                pieces.push(&chars[start..i]);
+                start = i;
+            }
+        }
+        pieces.push(&chars[start..]);
+
+        let mut sum = PauliSum::new();
+        for piece in pieces {
+            let (sign, body) = match piece.first() {
+                Some('-') => (-1.0, &piece[1..]),
+                Some('+') => (1.0, &piece[1..]),
+                _ => (1.0, piece),
+            };
+            let body: String = body.iter().collect();
+            if body.is_empty() {
+                return Err(format!("Missing term in '{}'", text));
+            }
+
+            let (coefficient, string) = match body.split_once('*') {
+                Some((number, string)) => (
+                    number
+                        .parse::<f64>()
+                        .map_err(|_| format!("Invalid coefficient '{}'", number))?,
+                    string.parse()?,
+                ),
+                None => match body.parse::<f64>() {
+                    Ok(number) => (number, PauliString::identity()),
+                    Err(_) => (1.0, body.parse()?),
+                },
+            };
+            sum.add_term(sign * coefficient, string);
+        }
+        Ok(sum)
+    }
+}
+
+impl From<PauliString> for PauliSum {
+    fn from(string: PauliString) -> Self {
+        PauliSum::from_terms(vec![(1.0, string)])
+    }
+}
+
+impl Mul<f64> for PauliString {
+    type Output = PauliSum;
+
+    fn mul(self, coefficient: f64) -> PauliSum {
+        PauliSum::from_terms(vec![(coefficient, self)])
+    }
+}
+
+impl Mul<f64> for PauliSum {
+    type Output = PauliSum;
+
+    fn mul(mut self, coefficient: f64) -> PauliSum {
+        for (c, _) in &mut self.terms {
+            *c *= coefficient;
+        }
+        self
+    }
+}
+
+impl Add for PauliSum {
+    type Output = PauliSum;
+
+    fn add(mut self, other: PauliSum) -> PauliSum {
+        for (coefficient, string) in other.terms {
+            self.add_term(coefficient, string);
+        }
+        self
+    }
+}
+
+impl Neg for PauliSum {
+    type Output = PauliSum;
+
+    fn neg(self) -> PauliSum {
+        self * -1.0
+    }
+}
+
+impl Sub for PauliSum {
+    type Output = PauliSum;
+
+    fn sub(self, other: PauliSum) -> PauliSum {
+        self + (-other)
+    }
+}
+
+impl QuantumCircuit {
+    /// Exact expectation value ⟨ψ|O|ψ⟩ of a Pauli sum
+    pub fn expectation(&self, observable: &PauliSum) -> Result<f64, String> {
+        let state = self.get_state();
+        let mut value = 0.0;
+        for (coefficient, string) in observable.terms() {
+            string.check_qubits(self.n_qubits())?;
+            let term: Complex<f64> = (0..state.len())
+                .map(|i| {
+                    let (j, phase) = string.apply_to_basis(i);
+                    state[j].conj() * phase * state[i]
+                })
+                .sum();
+            value += coefficient * term.re;
+        }
+        Ok(value)
+    }
+}
+
+impl DensityMatrixCircuit {
+    /// Exact expectation value Tr(ρO) of a Pauli sum
+    pub fn expectation(&self, observable: &PauliSum) -> Result<f64, String> {
+        let rho = self.get_density_matrix();
+        let mut value = 0.0;
+        for (coefficient, string) in observable.terms() {
+            string.check_qubits(self.n_qubits())?;
+            // ⟨i|ρP|i⟩ = c ρ_ij where P|i⟩ = c|j⟩
+            let term: Complex<f64> = (0..rho.nrows())
+                .map(|i| {
+                    let (j, phase) = string.apply_to_basis(i);
+                    phase * rho[(i, j)]
+                })
+                .sum();
+            value += coefficient * term.re;
+        }
+        Ok(value)
+    }
+}
+
+impl MpsCircuit {
+    /// Expectation value ⟨ψ|O|ψ⟩ of a Pauli sum, contracted in O(n χ³) time per term
+    pub fn expectation(&self, observable: &PauliSum) -> Result<f64, String> {
+        let mut value = 0.0;
+        for (coefficient, string) in observable.terms() {
+            string.check_qubits(self.n_qubits())?;
+            value += coefficient * self.pauli_expectation(string);
+        }
+        Ok(value)
+    }
+}
+
+impl StabilizerCircuit {
+    /// Exact expectation value ⟨ψ|O|ψ⟩ of a Pauli sum, in O(n²) time per term
+    pub fn expectation(&self, observable: &PauliSum) -> Result<f64, String> {
+        let mut value = 0.0;
+        for (coefficient, string) in observable.terms() {
+            string.check_qubits(self.n_qubits())?;
+            value += coefficient * self.pauli_expectation(string);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn bell() -> Circuit {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.h(0).unwrap().cx(0, 1).unwrap();
+        circuit
+    }
+
+    fn observable(text: &str) -> PauliSum {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let sum = observable("0.5*Z0Z1 + 0.2 * X2 - Y0 + 1.5e-1*I + 0.5*Z1Z0");
+        assert_eq!(sum.terms().len(), 4);
+        assert_eq!(sum.to_string(), "1*Z0Z1 + 0.2*X2 - 1*Y0 + 0.15*I");
+        assert_eq!(sum.min_qubits(), 3);
+
+        let string: PauliString = "X3 Z1".parse().unwrap();
+        assert_eq!(string.get(1), Pauli::Z);
+        assert_eq!(string.get(0), Pauli::I);
+        assert_eq!(string.to_string(), "Z1X3");
+
+        assert!("Z0Z0".parse::<PauliString>().is_err());
+        assert!("Q1".parse::<PauliString>().is_err());
+        assert!("Z".parse::<PauliString>().is_err());
+        assert!("0.5*".parse::<PauliSum>().is_err());
+    }
+
+    #[test]
+    fn test_bell_state_expectations() {
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        backend.run(&bell()).unwrap();
+
+        for (text, expected) in [
+            ("Z0Z1", 1.0),
+            ("X0X1", 1.0),
+            ("Y0Y1", -1.0),
+            ("Z0", 0.0),
+            ("X0Y1", 0.0),
+            ("0.5*Z0Z1 + 0.25*X0X1 - 2", -1.25),
+        ] {
+            assert_relative_eq!(
+                backend.expectation(&observable(text)).unwrap(),
+                expected,
+                epsilon = 1e-10
+            );
+        }
+        assert!(backend.expectation(&observable("Z2")).is_err());
+    }
+
+    #[test]
+    fn test_y_eigenstate() {
+        // S H |0⟩ = |+i⟩, the +1 eigenstate of Y
+        let mut circuit = Circuit::new(1).unwrap();
+        circuit.h(0).unwrap().s(0).unwrap();
+        let mut backend = QuantumCircuit::new(1).unwrap();
+        backend.run(&circuit).unwrap();
+        assert_relative_eq!(
+            backend.expectation(&observable("Y0")).unwrap(),
+            1.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn test_density_matrix_matches_state_vector() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .ry(0.3, 0)
+            .unwrap()
+            .rx(1.1, 1)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .cz(1, 2)
+            .unwrap();
+        let sum = observable("0.7*X0Y1Z2 - 0.3*Z0 + 1.2*Y1Y2 + X1");
+
+        let mut pure = QuantumCircuit::new(3).unwrap();
+        pure.run(&circuit).unwrap();
+        let mut mixed = DensityMatrixCircuit::new(3);
+        mixed.run(&circuit).unwrap();
+        assert_relative_eq!(
+            pure.expectation(&sum).unwrap(),
+            mixed.expectation(&sum).unwrap(),
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn test_shot_estimate_close_to_exact() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .ry(0.8, 0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .s(1)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap();
+        let sum = observable("0.5*Z0Z2 + 0.2*X0 - 0.7*Y1 + 0.3*Z0 + 1.0");
+
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        backend.run(&circuit).unwrap();
+        let exact = backend.expectation(&sum).unwrap();
+        let estimate = sum.estimate(&mut backend, &circuit, 20000, 11).unwrap();
+        assert_relative_eq!(estimate, exact, epsilon = 0.05);
+        assert_eq!(
+            sum.estimate(&mut backend, &circuit, 20000, 11).unwrap(),
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_estimate_rejects_measured_circuit() {
+        let mut circuit = bell();
+        circuit.measure(0).unwrap();
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert!(observable("Z0")
+            .estimate(&mut backend, &circuit, 100, 0)
+            .is_err());
+    }
+}
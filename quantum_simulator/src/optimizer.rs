@@ -0,0 +1,462 @@
+/*
+This file implements classical optimizers for variational quantum algorithms.
+
+Key concepts:
+1. Objectives:
+   - An `Objective` returns a value for a parameter vector and, when asked,
+     its gradient
+   - Gradient-free optimizers never call `gradient()`, so objectives that
+     can only be sampled still work with them
+
+2. Optimizers:
+   - Gradient descent: x ← x - η∇f
+   - Adam: gradient descent with running averages of the gradient and its
+     square (https://arxiv.org/abs/1412.6980)
+   - Nelder–Mead: a derivative-free simplex search
+     (https://en.wikipedia.org/wiki/Nelder%E2%80%93Mead_method)
+   - SPSA: estimates the gradient from two evaluations along a random
+     direction, robust to shot noise (https://www.jhuapl.edu/spsa/)
+
+3. Results:
+   - Every optimizer reports the final parameters and value, the number of
+     iterations and the objective value after each iteration
+*/
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A function to minimize
+pub trait Objective {
+    /// Value of the function at `x`
+    fn value(&mut self, x: &[f64]) -> Result<f64, String>;
+
+    /// Gradient of the function at `x`
+    fn gradient(&mut self, x: &[f64]) -> Result<Vec<f64>, String>;
+}
+
+/// Outcome of a minimization
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizationResult {
+    pub parameters: Vec<f64>,
+    pub value: f64,
+    pub iterations: usize,
+    /// Objective value after each iteration
+    pub history: Vec<f64>,
+}
+
+/// A classical minimization strategy
+pub trait Optimizer {
+    /// Minimizes `objective` starting from `initial`
+    fn minimize(
+        &mut self,
+        objective: &mut dyn Objective,
+        initial: &[f64],
+    ) -> Result<OptimizationResult, String>;
+}
+
+/// Plain gradient descent with a fixed learning rate
+#[derive(Debug, Clone)]
+pub struct GradientDescent {
+    learning_rate: f64,
+    max_iterations: usize,
+    tolerance: f64,
+}
+
+impl GradientDescent {
+    /// Creates a gradient descent that stops after `max_iterations` steps or
+    /// once the value changes by less than 1e-8
+    pub fn new(learning_rate: f64, max_iterations: usize) -> Self {
+        GradientDescent {
+            learning_rate,
+            max_iterations,
+            tolerance: 1e-8,
+        }
+    }
+
+    /// Sets the change in value below which the search stops
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Optimizer for GradientDescent {
+    fn minimize(
+        &mut self,
+        objective: &mut dyn Objective,
+        initial: &[f64],
+    ) -> Result<OptimizationResult, String> {
+        let mut x = initial.to_vec();
+        let mut value = objective.value(&x)?;
+        let mut history = Vec::new();
+
+        for iteration in 1..=self.max_iterations {
+            let gradient = objective.gradient(&x)?;
+            for (xi, gi) in x.iter_mut().zip(&gradient) {
+                *xi -= self.learning_rate * gi;
+            }
+            let next = objective.value(&x)?;
+            history.push(next);
+            let converged = (value - next).abs() < self.tolerance;
+            value = next;
+            if converged {
+                return Ok(OptimizationResult {
+                    parameters: x,
+                    value,
+                    iterations: iteration,
+                    history,
+                });
+            }
+        }
+
+        Ok(OptimizationResult {
+            parameters: x,
+            value,
+            iterations: self.max_iterations,
+            history,
+        })
+    }
+}
+
+/// Adam: gradient descent with adaptive per-parameter step sizes
+#[derive(Debug, Clone)]
+pub struct Adam {
+    learning_rate: f64,
+    beta1: f64,
+    beta2: f64,
+    epsilon: f64,
+    max_iterations: usize,
+    tolerance: f64,
+}
+
+impl Adam {
+    /// Creates an Adam optimizer with the usual β1 = 0.9 and β2 = 0.999
+    pub fn new(learning_rate: f64, max_iterations: usize) -> Self {
+        Adam {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            max_iterations,
+            tolerance: 1e-8,
+        }
+    }
+
+    /// Sets the decay rates of the first and second moment estimates
+    pub fn with_betas(mut self, beta1: f64, beta2: f64) -> Self {
+        self.beta1 = beta1;
+        self.beta2 = beta2;
+        self
+    }
+
+    /// Sets the change in value below which the search stops
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Optimizer for Adam {
+    fn minimize(
+        &mut self,
+        objective: &mut dyn Objective,
+        initial: &[f64],
+    ) -> Result<OptimizationResult, String> {
+        let mut x = initial.to_vec();
+        let mut m = vec![0.0; x.len()];
+        let mut v = vec![0.0; x.len()];
+        let mut value = objective.value(&x)?;
+        let mut history = Vec::new();
+
+        for iteration in 1..=self.max_iterations {
+            let gradient = objective.gradient(&x)?;
+            let t = iteration as i32;
+            for i in 0..x.len() {
+                m[i] = self.beta1 * m[i] + (1.0 - self.beta1) * gradient[i];
+                v[i] = self.beta2 * v[i] + (1.0 - self.beta2) * gradient[i] * gradient[i];
+                let m_hat = m[i] / (1.0 - self.beta1.powi(t));
+                let v_hat = v[i] / (1.0 - self.beta2.powi(t));
+                x[i] -= self.learning_rate * m_hat / (v_hat.sqrt() + self.epsilon);
+            }
+            let next = objective.value(&x)?;
+            history.push(next);
+            let converged = (value - next).abs() < self.tolerance;
+            value = next;
+            if converged {
+                return Ok(OptimizationResult {
+                    parameters: x,
+                    value,
+                    iterations: iteration,
+                    history,
+                });
+            }
+        }
+
+        Ok(OptimizationResult {
+            parameters: x,
+            value,
+            iterations: self.max_iterations,
+            history,
+        })
+    }
+}
+
+/// Nelder–Mead downhill simplex search
+#[derive(Debug, Clone)]
+pub struct NelderMead {
+    max_iterations: usize,
+    tolerance: f64,
+    initial_step: f64,
+}
+
+impl NelderMead {
+    /// Creates a simplex search that stops after `max_iterations` steps or
+    /// once the simplex values differ by less than 1e-10
+    pub fn new(max_iterations: usize) -> Self {
+        NelderMead {
+            max_iterations,
+            tolerance: 1e-10,
+            initial_step: 0.5,
+        }
+    }
+
+    /// Sets the spread of values across the simplex below which the search stops
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets the distance of the initial simplex vertices from the start point
+    pub fn with_initial_step(mut self, step: f64) -> Self {
+        self.initial_step = step;
+        self
+    }
+}
+
+impl Optimizer for NelderMead {
+    fn minimize(
+        &mut self,
+        objective: &mut dyn Objective,
+        initial: &[f64],
+    ) -> Result<OptimizationResult, String> {
+        let n = initial.len();
+        let mut simplex = vec![initial.to_vec()];
+        for i in 0..n {
+            let mut vertex = initial.to_vec();
+            vertex[i] += self.initial_step;
+            simplex.push(vertex);
+        }
+        let mut values = simplex
+            .iter()
+            .map(|x| objective.value(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut history = Vec::new();
+        let mut iterations = 0;
+
+        // Moves from the centroid c through the worst vertex w: c + t (w - c)
+        let along = |centroid: &[f64], worst: &[f64], t: f64| -> Vec<f64> {
+            centroid
+                .iter()
+                .zip(worst)
+                .map(|(c, w)| c + t * (w - c))
+                .collect()
+        };
+
+        while iterations < self.max_iterations {
+            let mut order: Vec<usize> = (0..=n).collect();
+            order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+            simplex = order.iter().map(|&i| simplex[i].clone()).collect();
+            values = order.iter().map(|&i| values[i]).collect();
+            if values[n] - values[0] < self.tolerance {
+                break;
+            }
+            iterations += 1;
+
+            let centroid: Vec<f64> = (0..n)
+                .map(|j| simplex[..n].iter().map(|x| x[j]).sum::<f64>() / n as f64)
+                .collect();
+            let reflected = along(&centroid, &simplex[n], -1.0);
+            let reflected_value = objective.value(&reflected)?;
+
+            if reflected_value < values[0] {
+                let expanded = along(&centroid, &simplex[n], -2.0);
+                let expanded_value = objective.value(&expanded)?;
+                if expanded_value < reflected_value {
+                    simplex[n] = expanded;
+                    values[n] = expanded_value;
+                } else {
+                    simplex[n] = reflected;
+                    values[n] = reflected_value;
+                }
+            } else if reflected_value < values[n - 1] {
+                simplex[n] = reflected;
+                values[n] = reflected_value;
+            } else {
+                let contracted = if reflected_value < values[n] {
+                    along(&centroid, &simplex[n], -0.5)
+                } else {
+                    along(&centroid, &simplex[n], 0.5)
+                };
+                let contracted_value = objective.value(&contracted)?;
+                if contracted_value < values[n].min(reflected_value) {
+                    simplex[n] = contracted;
+                    values[n] = contracted_value;
+                } else {
+                    // Shrink every vertex towards the best one
+                    for i in 1..=n {
+                        simplex[i] = along(&simplex[0], &simplex[i], 0.5);
+                        values[i] = objective.value(&simplex[i])?;
+                    }
+                }
+            }
+            history.push(values.iter().copied().fold(f64::INFINITY, f64::min));
+        }
+
+        let best = (0..=n)
+            .min_by(|&a, &b| values[a].total_cmp(&values[b]))
+            .unwrap();
+        Ok(OptimizationResult {
+            parameters: simplex[best].clone(),
+            value: values[best],
+            iterations,
+            history,
+        })
+    }
+}
+
+/// Simultaneous perturbation stochastic approximation
+#[derive(Debug, Clone)]
+pub struct Spsa {
+    a: f64,
+    c: f64,
+    alpha: f64,
+    gamma: f64,
+    max_iterations: usize,
+    seed: u64,
+}
+
+impl Spsa {
+    /// Creates an SPSA optimizer with step size a / k^0.602 and perturbation
+    /// size c / k^0.101 at iteration k
+    pub fn new(a: f64, c: f64, max_iterations: usize, seed: u64) -> Self {
+        Spsa {
+            a,
+            c,
+            alpha: 0.602,
+            gamma: 0.101,
+            max_iterations,
+            seed,
+        }
+    }
+
+    /// Sets the decay exponents of the step and perturbation sizes
+    pub fn with_exponents(mut self, alpha: f64, gamma: f64) -> Self {
+        self.alpha = alpha;
+        self.gamma = gamma;
+        self
+    }
+}
+
+impl Optimizer for Spsa {
+    fn minimize(
+        &mut self,
+        objective: &mut dyn Objective,
+        initial: &[f64],
+    ) -> Result<OptimizationResult, String> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut x = initial.to_vec();
+        let mut history = Vec::new();
+
+        for k in 1..=self.max_iterations {
+            let step = self.a / (k as f64).powf(self.alpha);
+            let size = self.c / (k as f64).powf(self.gamma);
+            let delta: Vec<f64> = (0..x.len())
+                .map(|_| if rng.gen::<bool>() { 1.0 } else { -1.0 })
+                .collect();
+
+            let plus: Vec<f64> = x.iter().zip(&delta).map(|(x, d)| x + size * d).collect();
+            let minus: Vec<f64> = x.iter().zip(&delta).map(|(x, d)| x - size * d).collect();
+            let difference = objective.value(&plus)? - objective.value(&minus)?;
+            // With ±1 perturbations, 1/Δ_i = Δ_i
+            for (xi, di) in x.iter_mut().zip(&delta) {
+                *xi -= step * difference / (2.0 * size) * di;
+            }
+            history.push(objective.value(&x)?);
+        }
+
+        let value = match history.last() {
+            Some(&value) => value,
+            None => objective.value(&x)?,
+        };
+        Ok(OptimizationResult {
+            parameters: x,
+            value,
+            iterations: self.max_iterations,
+            history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    /// (x - 1)² + 2(y + 2)², minimized at (1, -2)
+    struct Quadratic;
+
+    impl Objective for Quadratic {
+        fn value(&mut self, x: &[f64]) -> Result<f64, String> {
+            Ok((x[0] - 1.0).powi(2) + 2.0 * (x[1] + 2.0).powi(2))
+        }
+
+        fn gradient(&mut self, x: &[f64]) -> Result<Vec<f64>, String> {
+            Ok(vec![2.0 * (x[0] - 1.0), 4.0 * (x[1] + 2.0)])
+        }
+    }
+
+    fn check(result: OptimizationResult, epsilon: f64) {
+        assert_relative_eq!(result.parameters[0], 1.0, epsilon = epsilon);
+        assert_relative_eq!(result.parameters[1], -2.0, epsilon = epsilon);
+        assert_eq!(result.history.len(), result.iterations);
+    }
+
+    #[test]
+    fn test_gradient_descent() {
+        let result = GradientDescent::new(0.1, 500)
+            .with_tolerance(1e-14)
+            .minimize(&mut Quadratic, &[0.0, 0.0])
+            .unwrap();
+        check(result, 1e-5);
+    }
+
+    #[test]
+    fn test_adam() {
+        let result = Adam::new(0.05, 2000)
+            .with_tolerance(1e-14)
+            .minimize(&mut Quadratic, &[0.0, 0.0])
+            .unwrap();
+        check(result, 1e-3);
+    }
+
+    #[test]
+    fn test_nelder_mead() {
+        let result = NelderMead::new(500)
+            .minimize(&mut Quadratic, &[0.0, 0.0])
+            .unwrap();
+        check(result, 1e-4);
+    }
+
+    #[test]
+    fn test_spsa() {
+        let result = Spsa::new(0.2, 0.1, 1000, 5)
+            .minimize(&mut Quadratic, &[0.0, 0.0])
+            .unwrap();
+        check(result.clone(), 0.05);
+
+        let again = Spsa::new(0.2, 0.1, 1000, 5)
+            .minimize(&mut Quadratic, &[0.0, 0.0])
+            .unwrap();
+        assert_eq!(again, result);
+    }
+}
@@ -0,0 +1,208 @@
+/*
+This file implements symbolic circuit parameters for variational quantum algorithms.
+
+Key concepts:
+1. Parameters:
+   - A named placeholder (e.g. "theta0") for a rotation angle
+   - Lets one circuit template be evaluated for many angle vectors
+   - Learn more: https://en.wikipedia.org/wiki/Variational_quantum_eigensolver
+
+2. Angles:
+   - Either a concrete value in radians or `coefficient * parameter`
+   - The coefficient covers angles such as 2γ in QAOA layers and the sign
+     flip needed to invert a rotation
+
+3. Binding:
+   - Substitutes concrete values for parameter names
+   - Unbound angles cannot be turned into gate matrices
+*/
+
+use crate::error::QuantumError;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Mul, Neg};
+
+/// A named symbolic parameter
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Parameter {
+    name: String,
+}
+
+impl Parameter {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A gate angle that is either known or proportional to a parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Angle {
+    Value(f64),
+    Parameter {
+        parameter: Parameter,
+        coefficient: f64,
+    },
+}
+
+impl Angle {
+    /// Returns the angle in radians if it is bound
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            Angle::Value(value) => Some(*value),
+            Angle::Parameter { .. } => None,
+        }
+    }
+
+    /// Returns the parameter this angle depends on, if any
+    pub fn parameter(&self) -> Option<&Parameter> {
+        match self {
+            Angle::Value(_) => None,
+            Angle::Parameter { parameter, .. } => Some(parameter),
+        }
+    }
+
+    /// Substitutes a value for the parameter if it appears in `values`
+    pub fn bind(&self, values: &HashMap<String, f64>) -> Angle {
+        match self {
+            Angle::Parameter {
+                parameter,
+                coefficient,
+            } => match values.get(parameter.name()) {
+                Some(value) => Angle::Value(coefficient * value),
+                None => self.clone(),
+            },
+            Angle::Value(_) => self.clone(),
+        }
+    }
+
+    /// Returns the bound value or an error naming the missing parameter
+    pub fn resolve(&self) -> Result<f64, QuantumError> {
+        match self {
+            Angle::Value(value) => Ok(*value),
+            Angle::Parameter { parameter, .. } => {
+                Err(QuantumError::UnboundParameters(vec![parameter
+                    .name()
+                    .to_string()]))
+            }
+        }
+    }
+}
+
+impl From<f64> for Angle {
+    fn from(value: f64) -> Self {
+        Angle::Value(value)
+    }
+}
+
+impl From<Parameter> for Angle {
+    fn from(parameter: Parameter) -> Self {
+        Angle::Parameter {
+            parameter,
+            coefficient: 1.0,
+        }
+    }
+}
+
+impl From<&Parameter> for Angle {
+    fn from(parameter: &Parameter) -> Self {
+        Angle::from(parameter.clone())
+    }
+}
+
+impl Mul<f64> for Angle {
+    type Output = Angle;
+
+    fn mul(self, rhs: f64) -> Angle {
+        match self {
+            Angle::Value(value) => Angle::Value(value * rhs),
+            Angle::Parameter {
+                parameter,
+                coefficient,
+            } => Angle::Parameter {
+                parameter,
+                coefficient: coefficient * rhs,
+            },
+        }
+    }
+}
+
+impl Mul<f64> for Parameter {
+    type Output = Angle;
+
+    fn mul(self, rhs: f64) -> Angle {
+        Angle::from(self) * rhs
+    }
+}
+
+impl Mul<f64> for &Parameter {
+    type Output = Angle;
+
+    fn mul(self, rhs: f64) -> Angle {
+        Angle::from(self) * rhs
+    }
+}
+
+impl Neg for Angle {
+    type Output = Angle;
+
+    fn neg(self) -> Angle {
+        self * -1.0
+    }
+}
+
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Angle::Value(value) => write!(f, "{}", value),
+            Angle::Parameter {
+                parameter,
+                coefficient,
+            } => {
+                if *coefficient == 1.0 {
+                    write!(f, "{}", parameter)
+                } else if *coefficient == -1.0 {
+                    write!(f, "-{}", parameter)
+                } else {
+                    write!(f, "{}*{}", coefficient, parameter)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_scaled_parameter() {
+        let gamma = Parameter::new("gamma");
+        let angle = &gamma * 2.0;
+        assert_eq!(angle.value(), None);
+        assert!(angle.resolve().is_err());
+
+        let values = HashMap::from([("gamma".to_string(), 0.25)]);
+        assert_eq!(angle.bind(&values).value(), Some(0.5));
+        assert_eq!((-angle).bind(&values).value(), Some(-0.5));
+    }
+
+    #[test]
+    fn test_bind_ignores_other_parameters() {
+        let angle = Angle::from(Parameter::new("theta"));
+        let values = HashMap::from([("phi".to_string(), 1.0)]);
+        assert_eq!(angle.bind(&values), angle);
+        assert_eq!(angle.to_string(), "theta");
+    }
+}
@@ -0,0 +1,509 @@
+/*
+This file implements optimization passes that rewrite a recorded `Circuit`.
+
+Key concepts:
+1. Passes:
+   - A `Pass` takes a circuit and returns an equivalent one, usually shorter
+   - A `PassManager` runs a list of passes in order, so pipelines are built
+     from small rewrites that are easy to check one at a time
+
+2. Adjacency:
+   - Two instructions are adjacent when nothing between them touches any of
+     their qubits; instructions on other qubits commute with both
+   - Measurements, barriers and classically controlled instructions are never
+     rewritten and block every rewrite across them
+
+3. The Rewrites:
+   - `CancelInverses`: U · U† = I, e.g. H·H, X·X, CNOT·CNOT, S·S†
+   - `MergeRotations`: R(a) · R(b) = R(a + b) for rotations about the same
+     axis, dropping rotations that end up as the identity
+   - `FuseSingleQubitGates`: multiplies runs of bound single-qubit gates into
+     one 2×2 matrix and re-emits it as a single U3 (see `u3_decomposition`)
+   - Learn more: https://en.wikipedia.org/wiki/Peephole_optimization
+
+4. Correctness:
+   - Every pass preserves the circuit unitary up to a global phase
+*/
+
+use std::f64::consts::PI;
+
+use nalgebra::{Complex, Matrix2};
+
+use crate::gates::u3_decomposition;
+use crate::ir::{Circuit, Gate, Instruction};
+use crate::parameter::Angle;
+
+/// A rewrite of a whole circuit that preserves its unitary up to global phase
+pub trait Pass {
+    /// Short name used in error messages
+    fn name(&self) -> &'static str;
+
+    /// Returns the rewritten circuit
+    fn run(&self, circuit: &Circuit) -> Result<Circuit, String>;
+}
+
+/// Runs a sequence of passes
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    /// Creates a pass manager with no passes
+    pub fn new() -> Self {
+        PassManager { passes: Vec::new() }
+    }
+
+    /// Cancellation, rotation merging, fusion and a final cancellation
+    pub fn standard() -> Self {
+        let mut manager = PassManager::new();
+        manager
+            .add_pass(CancelInverses)
+            .add_pass(MergeRotations)
+            .add_pass(FuseSingleQubitGates)
+            .add_pass(CancelInverses);
+        manager
+    }
+
+    /// Appends a pass to the pipeline
+    pub fn add_pass<P: Pass + 'static>(&mut self, pass: P) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Names of the passes in the order they run
+    pub fn passes(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Runs every pass in order
+    pub fn run(&self, circuit: &Circuit) -> Result<Circuit, String> {
+        let mut current = circuit.clone();
+        for pass in &self.passes {
+            current = pass
+                .run(&current)
+                .map_err(|e| format!("{} pass: {}", pass.name(), e))?;
+        }
+        Ok(current)
+    }
+}
+
+/// Result of trying to combine an instruction with the one before it
+enum Combined {
+    /// The two instructions cancel
+    Identity,
+    /// The two instructions are replaced by one
+    Single(Instruction),
+}
+
+/// True for instructions a pass may rewrite
+fn is_unitary(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Gate { .. }
+            | Instruction::Controlled { .. }
+            | Instruction::MultiControlled { .. }
+    )
+}
+
+/// Walks the circuit combining each unitary instruction with the previous
+/// instruction on exactly the same qubits, when `combine` allows it
+///
+/// A single instruction for which `combine` would produce the identity on
+/// its own is not removed here; see `MergeRotations` for that.
+fn combine_adjacent<F>(circuit: &Circuit, combine: F) -> Result<Circuit, String>
+where
+    F: Fn(&Instruction, &Instruction) -> Option<Combined>,
+{
+    let mut output: Vec<Option<Instruction>> = Vec::new();
+    // Indices into `output` of the live instructions on each qubit, in order
+    let mut stacks: Vec<Vec<usize>> = vec![Vec::new(); circuit.n_qubits()];
+
+    for instruction in circuit.instructions() {
+        let qubits = instruction.qubits();
+        let previous = qubits
+            .first()
+            .and_then(|&q| stacks[q].last().copied())
+            .filter(|&p| qubits.iter().all(|&q| stacks[q].last() == Some(&p)))
+            .filter(|&p| {
+                let mut mine = qubits.clone();
+                let mut theirs = output[p].as_ref().unwrap().qubits();
+                mine.sort_unstable();
+                theirs.sort_unstable();
+                mine == theirs
+            });
+
+        let combined = match previous {
+            Some(p) if is_unitary(instruction) && is_unitary(output[p].as_ref().unwrap()) => {
+                combine(output[p].as_ref().unwrap(), instruction).map(|c| (p, c))
+            }
+            _ => None,
+        };
+
+        match combined {
+            Some((p, Combined::Identity)) => {
+                output[p] = None;
+                for &q in &qubits {
+                    stacks[q].pop();
+                }
+            }
+            Some((p, Combined::Single(merged))) => output[p] = Some(merged),
+            None => {
+                for &q in &qubits {
+                    stacks[q].push(output.len());
+                }
+                output.push(Some(instruction.clone()));
+            }
+        }
+    }
+
+    rebuild(circuit, output.into_iter().flatten())
+}
+
+/// A circuit with the same registers as `circuit` holding `instructions`
+fn rebuild<I: IntoIterator<Item = Instruction>>(
+    circuit: &Circuit,
+    instructions: I,
+) -> Result<Circuit, String> {
+    let mut result = circuit.empty_like();
+    for instruction in instructions {
+        result.push(instruction)?;
+    }
+    Ok(result)
+}
+
+/// Removes adjacent pairs of mutually inverse instructions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CancelInverses;
+
+impl Pass for CancelInverses {
+    fn name(&self) -> &'static str {
+        "CancelInverses"
+    }
+
+    fn run(&self, circuit: &Circuit) -> Result<Circuit, String> {
+        combine_adjacent(circuit, |first, second| {
+            let undoes = |a: &Instruction, b: &Instruction| {
+                a.inverse()
+                    .is_ok_and(|inverse| inverse.as_slice() == [b.clone()])
+            };
+            let symmetric = matches!(first.gate(), Some(Gate::CZ | Gate::Swap))
+                && first.gate() == second.gate()
+                && matches!(first, Instruction::Gate { .. })
+                && matches!(second, Instruction::Gate { .. });
+            (undoes(first, second) || undoes(second, first) || symmetric)
+                .then_some(Combined::Identity)
+        })
+    }
+}
+
+/// Sum of two angles, if it can be expressed as a single `Angle`
+fn add_angles(a: &Angle, b: &Angle) -> Option<Angle> {
+    match (a, b) {
+        (Angle::Value(x), Angle::Value(y)) => Some(Angle::Value(x + y)),
+        (
+            Angle::Parameter {
+                parameter: p,
+                coefficient: x,
+            },
+            Angle::Parameter {
+                parameter: q,
+                coefficient: y,
+            },
+        ) if p == q => Some(if x + y == 0.0 {
+            Angle::Value(0.0)
+        } else {
+            Angle::Parameter {
+                parameter: p.clone(),
+                coefficient: x + y,
+            }
+        }),
+        _ => None,
+    }
+}
+
+/// The single angle of a rotation gate and the period after which it is
+/// exactly the identity
+fn rotation(gate: &Gate) -> Option<(&Angle, f64)> {
+    match gate {
+        Gate::RX(a) | Gate::RY(a) | Gate::RZ(a) => Some((a, 4.0 * PI)),
+        Gate::Rotation(a) | Gate::PhaseShift(a) | Gate::CPhase(a) => Some((a, 2.0 * PI)),
+        _ => None,
+    }
+}
+
+/// True if the instruction is a rotation by a whole number of periods
+fn is_identity_rotation(instruction: &Instruction) -> bool {
+    let Some((angle, period)) = instruction.gate().and_then(rotation) else {
+        return false;
+    };
+    angle.value().is_some_and(|value| {
+        let turns = value / period;
+        (turns - turns.round()).abs() * period < 1e-12
+    })
+}
+
+/// Merges adjacent rotations about the same axis on the same qubits
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeRotations;
+
+impl Pass for MergeRotations {
+    fn name(&self) -> &'static str {
+        "MergeRotations"
+    }
+
+    fn run(&self, circuit: &Circuit) -> Result<Circuit, String> {
+        let merged = combine_adjacent(circuit, |first, second| {
+            let (a, _) = rotation(first.gate()?)?;
+            let (b, _) = rotation(second.gate()?)?;
+            // Same gate kind on the same qubits with the same controls; CPhase
+            // is symmetric, and the qubit sets already match
+            let zero = |_: &Angle| Angle::Value(0.0);
+            let both_cphase = matches!(
+                (first, second),
+                (
+                    Instruction::Gate {
+                        gate: Gate::CPhase(_),
+                        ..
+                    },
+                    Instruction::Gate {
+                        gate: Gate::CPhase(_),
+                        ..
+                    }
+                )
+            );
+            if !both_cphase && first.map_angles(&zero) != second.map_angles(&zero) {
+                return None;
+            }
+            let sum = add_angles(a, b)?;
+            let combined = first.map_angles(&|_| sum.clone());
+            Some(if is_identity_rotation(&combined) {
+                Combined::Identity
+            } else {
+                Combined::Single(combined)
+            })
+        })?;
+
+        let kept = merged
+            .instructions()
+            .iter()
+            .filter(|instruction| !is_identity_rotation(instruction))
+            .cloned();
+        rebuild(circuit, kept.collect::<Vec<_>>())
+    }
+}
+
+/// Replaces runs of bound single-qubit gates on a qubit by one U3 gate
+///
+/// Runs whose product is the identity up to a global phase are removed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FuseSingleQubitGates;
+
+impl FuseSingleQubitGates {
+    /// The instructions that replace a run, from its product `matrix`
+    fn emit(
+        run: Vec<Instruction>,
+        matrix: Matrix2<Complex<f64>>,
+        qubit: usize,
+    ) -> Vec<Instruction> {
+        if run.len() < 2 {
+            return run;
+        }
+        let (_, theta, phi, lambda) = u3_decomposition(&matrix);
+        let turns = |x: f64| {
+            let t = x / (2.0 * PI);
+            (t - t.round()).abs() * 2.0 * PI
+        };
+        if theta.abs() < 1e-12 && turns(phi + lambda) < 1e-12 {
+            return Vec::new();
+        }
+        vec![Instruction::Gate {
+            gate: Gate::U3(theta.into(), phi.into(), lambda.into()),
+            qubits: vec![qubit],
+        }]
+    }
+}
+
+impl Pass for FuseSingleQubitGates {
+    fn name(&self) -> &'static str {
+        "FuseSingleQubitGates"
+    }
+
+    fn run(&self, circuit: &Circuit) -> Result<Circuit, String> {
+        let identity = Matrix2::identity();
+        let mut runs: Vec<(Vec<Instruction>, Matrix2<Complex<f64>>)> =
+            vec![(Vec::new(), identity); circuit.n_qubits()];
+        let mut output = Vec::new();
+
+        for instruction in circuit.instructions() {
+            if let Instruction::Gate { gate, qubits } = instruction {
+                if qubits.len() == 1 && !gate.is_parameterized() {
+                    let (run, product) = &mut runs[qubits[0]];
+                    *product = gate.matrix2()? * *product;
+                    run.push(instruction.clone());
+                    continue;
+                }
+            }
+            for q in instruction.qubits() {
+                let (run, product) = std::mem::replace(&mut runs[q], (Vec::new(), identity));
+                output.extend(Self::emit(run, product, q));
+            }
+            output.push(instruction.clone());
+        }
+        for (q, (run, product)) in runs.into_iter().enumerate() {
+            output.extend(Self::emit(run, product, q));
+        }
+
+        rebuild(circuit, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::equivalent;
+    use crate::parameter::Parameter;
+
+    /// Checks that two circuits have the same unitary up to a global phase
+    fn assert_equivalent(a: &Circuit, b: &Circuit) {
+        assert!(equivalent(a, b, 1e-10).unwrap());
+    }
+
+    fn sample() -> Circuit {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .rz(0.25, 2)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .rz(0.5, 2)
+            .unwrap()
+            .s(1)
+            .unwrap()
+            .p(-PI / 2.0, 1)
+            .unwrap()
+            .x(2)
+            .unwrap()
+            .t(0)
+            .unwrap()
+            .h(0)
+            .unwrap()
+            .ry(0.2, 0)
+            .unwrap()
+            .cz(1, 2)
+            .unwrap()
+            .cz(2, 1)
+            .unwrap()
+            .cp(0.5, 0, 2)
+            .unwrap()
+            .cp(-0.5, 2, 0)
+            .unwrap()
+            .rx(PI, 1)
+            .unwrap()
+            .rx(PI, 1)
+            .unwrap()
+            .rx(2.0 * PI, 1)
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_cancel_inverses() {
+        let circuit = sample();
+        let optimized = CancelInverses.run(&circuit).unwrap();
+        assert_equivalent(&circuit, &optimized);
+        // H·H, CNOT·CNOT, S·S†, CZ·CZ
+        assert_eq!(optimized.len(), circuit.len() - 8);
+
+        // A barrier or a gate in between blocks cancellation
+        let mut blocked = Circuit::new(2).unwrap();
+        blocked.h(0).unwrap().barrier(&[0]).unwrap().h(0).unwrap();
+        blocked.cx(0, 1).unwrap().x(1).unwrap().cx(0, 1).unwrap();
+        assert_eq!(CancelInverses.run(&blocked).unwrap(), blocked);
+    }
+
+    #[test]
+    fn test_merge_rotations() {
+        let circuit = sample();
+        let optimized = MergeRotations.run(&circuit).unwrap();
+        assert_equivalent(&circuit, &optimized);
+        // The RZs merge, the CPhases cancel and the three RXs reach 4π
+        assert_eq!(optimized.len(), circuit.len() - 6);
+        assert!(optimized.instructions().contains(&Instruction::Gate {
+            gate: Gate::RZ(Angle::Value(0.75)),
+            qubits: vec![2],
+        }));
+
+        let theta = Parameter::new("theta");
+        let mut symbolic = Circuit::new(1).unwrap();
+        symbolic
+            .rz(&theta, 0)
+            .unwrap()
+            .rz(&theta * 2.0, 0)
+            .unwrap()
+            .rz(&theta * -3.0, 0)
+            .unwrap();
+        assert!(MergeRotations.run(&symbolic).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fuse_single_qubit_gates() {
+        let circuit = sample();
+        let optimized = FuseSingleQubitGates.run(&circuit).unwrap();
+        assert_equivalent(&circuit, &optimized);
+        assert!(optimized.len() < circuit.len());
+
+        // Runs never cross multi-qubit gates or parameters
+        let mut mixed = Circuit::new(2).unwrap();
+        mixed
+            .h(0)
+            .unwrap()
+            .t(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .h(0)
+            .unwrap();
+        mixed.rz(Parameter::new("a"), 1).unwrap().x(1).unwrap();
+        let fused = FuseSingleQubitGates.run(&mixed).unwrap();
+        assert_eq!(fused.len(), 5);
+        assert_equivalent(
+            &mixed.bind(&[("a", 0.3)]).unwrap(),
+            &fused.bind(&[("a", 0.3)]).unwrap(),
+        );
+
+        // H·X·H = Z, and Z·Z vanishes
+        let mut trivial = Circuit::new(1).unwrap();
+        trivial
+            .h(0)
+            .unwrap()
+            .x(0)
+            .unwrap()
+            .h(0)
+            .unwrap()
+            .z(0)
+            .unwrap();
+        assert!(FuseSingleQubitGates.run(&trivial).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pass_manager() {
+        let circuit = sample();
+        let manager = PassManager::standard();
+        assert_eq!(manager.passes().len(), 4);
+        let optimized = manager.run(&circuit).unwrap();
+        assert_equivalent(&circuit, &optimized);
+        // Only one U3 on q0 and one on q2 remain
+        assert_eq!(optimized.len(), 2);
+
+        let mut measured = Circuit::new(1).unwrap();
+        measured.h(0).unwrap().measure(0).unwrap().h(0).unwrap();
+        assert_eq!(manager.run(&measured).unwrap(), measured);
+    }
+}
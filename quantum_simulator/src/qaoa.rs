@@ -0,0 +1,406 @@
+/*
+This file implements the Quantum Approximate Optimization Algorithm (QAOA).
+
+Key concepts:
+1. Cost Hamiltonians:
+   - A binary optimization problem is written as an Ising Hamiltonian
+     C = c + Σ h_i Z_i + Σ J_ij Z_i Z_j, diagonal in the computational basis
+   - Bit b_i maps to the Z eigenvalue z_i = 1 - 2b_i, so the energy of a
+     basis state is the cost of the matching bitstring
+   - QUBO: minimize Σ Q_ij x_i x_j over x ∈ {0, 1}^n
+   - MaxCut: maximize the weight of edges across a partition, i.e. minimize
+     -Σ w_ij (1 - Z_i Z_j) / 2
+
+2. The Ansatz:
+   - Start in |+⟩^n, then alternate p times:
+     cost layer e^{-iγ_k C} and mixer layer e^{-iβ_k Σ X_i}
+   - e^{-iγ J Z_i Z_j} = CX(i, j) · RZ_j(2γJ) · CX(i, j)
+   - Learn more: https://arxiv.org/abs/1411.4028
+
+3. Approximation Ratio:
+   - r(x) = (C_max - C(x)) / (C_max - C_min), so 1 is optimal and 0 is worst
+   - For MaxCut C_max = 0 (nothing cut) and this is the usual cut / max cut
+   - C_min and C_max are found by brute force, which is fine at the sizes a
+     state-vector simulator can reach anyway
+*/
+
+use std::collections::BTreeMap;
+
+use nalgebra::DMatrix;
+
+use crate::backend::Backend;
+use crate::circuit::QuantumCircuit;
+use crate::ir::Circuit;
+use crate::observable::{Pauli, PauliString, PauliSum};
+use crate::optimizer::Optimizer;
+use crate::parameter::Parameter;
+use crate::vqe::{GradientMethod, Vqe};
+
+/// A bitstring sampled from the optimized QAOA state
+#[derive(Debug, Clone, PartialEq)]
+pub struct QaoaSolution {
+    /// Qubit 0 is the rightmost character, as in `Counts`
+    pub bitstring: String,
+    pub probability: f64,
+    pub cost: f64,
+    pub approximation_ratio: f64,
+}
+
+/// Outcome of a QAOA run
+#[derive(Debug, Clone, PartialEq)]
+pub struct QaoaResult {
+    pub gammas: Vec<f64>,
+    pub betas: Vec<f64>,
+    /// Expected cost ⟨C⟩ of the optimized state
+    pub expected_cost: f64,
+    /// Approximation ratio of the expected cost
+    pub approximation_ratio: f64,
+    /// Most likely bitstrings, most probable first
+    pub solutions: Vec<QaoaSolution>,
+    pub iterations: usize,
+}
+
+/// A QAOA instance for a problem in Ising form
+#[derive(Debug, Clone)]
+pub struct Qaoa {
+    n_qubits: usize,
+    layers: usize,
+    offset: f64,
+    linear: Vec<f64>,
+    quadratic: BTreeMap<(usize, usize), f64>,
+    gradient: GradientMethod,
+    max_solutions: usize,
+}
+
+impl Qaoa {
+    fn empty(n_qubits: usize, layers: usize) -> Result<Self, String> {
+        if n_qubits == 0 {
+            return Err("QAOA needs at least one variable".to_string());
+        }
+        if layers == 0 {
+            return Err("QAOA needs at least one layer".to_string());
+        }
+        Ok(Qaoa {
+            n_qubits,
+            layers,
+            offset: 0.0,
+            linear: vec![0.0; n_qubits],
+            quadratic: BTreeMap::new(),
+            gradient: GradientMethod::Adjoint,
+            max_solutions: 8,
+        })
+    }
+
+    fn add_coupling(&mut self, i: usize, j: usize, value: f64) {
+        let key = (i.min(j), i.max(j));
+        *self.quadratic.entry(key).or_insert(0.0) += value;
+    }
+
+    /// Builds a MaxCut instance on `n_nodes` nodes from weighted edges `(u, v, w)`
+    pub fn max_cut(
+        n_nodes: usize,
+        edges: &[(usize, usize, f64)],
+        layers: usize,
+    ) -> Result<Self, String> {
+        let mut qaoa = Qaoa::empty(n_nodes, layers)?;
+        for &(u, v, w) in edges {
+            if u >= n_nodes || v >= n_nodes {
+                return Err(format!(
+                    "Edge ({}, {}) is out of range for {} nodes",
+                    u, v, n_nodes
+                ));
+            }
+            if u == v {
+                return Err(format!("Self-loop on node {} cannot be cut", u));
+            }
+            // -w (1 - Z_u Z_v) / 2
+            qaoa.offset -= w / 2.0;
+            qaoa.add_coupling(u, v, w / 2.0);
+        }
+        qaoa.check_terms()?;
+        Ok(qaoa)
+    }
+
+    /// Builds an instance minimizing xᵀQx over x ∈ {0, 1}^n
+    pub fn qubo(matrix: &DMatrix<f64>, layers: usize) -> Result<Self, String> {
+        if !matrix.is_square() {
+            return Err(format!(
+                "QUBO matrix must be square, got {}x{}",
+                matrix.nrows(),
+                matrix.ncols()
+            ));
+        }
+        let mut qaoa = Qaoa::empty(matrix.nrows(), layers)?;
+        for i in 0..matrix.nrows() {
+            for j in 0..matrix.ncols() {
+                let q = matrix[(i, j)];
+                if i == j {
+                    // x_i² = x_i = (1 - Z_i) / 2
+                    qaoa.offset += q / 2.0;
+                    qaoa.linear[i] -= q / 2.0;
+                } else {
+                    // x_i x_j = (1 - Z_i - Z_j + Z_i Z_j) / 4
+                    qaoa.offset += q / 4.0;
+                    qaoa.linear[i] -= q / 4.0;
+                    qaoa.linear[j] -= q / 4.0;
+                    qaoa.add_coupling(i, j, q / 4.0);
+                }
+            }
+        }
+        qaoa.check_terms()?;
+        Ok(qaoa)
+    }
+
+    fn check_terms(&mut self) -> Result<(), String> {
+        self.quadratic.retain(|_, j| *j != 0.0);
+        if self.quadratic.is_empty() && self.linear.iter().all(|&h| h == 0.0) {
+            return Err("The problem has a constant cost, nothing to optimize".to_string());
+        }
+        Ok(())
+    }
+
+    /// Chooses how gradients are computed for gradient-based optimizers
+    pub fn with_gradient(mut self, gradient: GradientMethod) -> Self {
+        self.gradient = gradient;
+        self
+    }
+
+    /// Sets how many of the most likely bitstrings `run` reports
+    pub fn with_max_solutions(mut self, max_solutions: usize) -> Self {
+        self.max_solutions = max_solutions;
+        self
+    }
+
+    pub fn n_qubits(&self) -> usize {
+        self.n_qubits
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    /// The cost Hamiltonian C as a sum of Pauli strings
+    pub fn hamiltonian(&self) -> PauliSum {
+        let mut hamiltonian = PauliSum::new();
+        if self.offset != 0.0 {
+            hamiltonian.add_term(self.offset, PauliString::identity());
+        }
+        for (i, &h) in self.linear.iter().enumerate() {
+            if h != 0.0 {
+                let z = PauliString::from_paulis(&[(i, Pauli::Z)]).unwrap();
+                hamiltonian.add_term(h, z);
+            }
+        }
+        for (&(i, j), &coupling) in &self.quadratic {
+            let zz = PauliString::from_paulis(&[(i, Pauli::Z), (j, Pauli::Z)]).unwrap();
+            hamiltonian.add_term(coupling, zz);
+        }
+        hamiltonian
+    }
+
+    /// Cost of the bitstring whose bit i is the value of variable i
+    pub fn cost(&self, bits: usize) -> f64 {
+        let z = |i: usize| if (bits >> i) & 1 == 1 { -1.0 } else { 1.0 };
+        let linear: f64 = self.linear.iter().enumerate().map(|(i, h)| h * z(i)).sum();
+        let quadratic: f64 = self
+            .quadratic
+            .iter()
+            .map(|(&(i, j), coupling)| coupling * z(i) * z(j))
+            .sum();
+        self.offset + linear + quadratic
+    }
+
+    /// The p-layer ansatz with parameters `gamma_k` and `beta_k`
+    pub fn circuit(&self) -> Circuit {
+        let mut circuit = Circuit::new(self.n_qubits).unwrap();
+        for q in 0..self.n_qubits {
+            circuit.h(q).unwrap();
+        }
+        for k in 0..self.layers {
+            let gamma = Parameter::new(&format!("gamma_{}", k));
+            let beta = Parameter::new(&format!("beta_{}", k));
+            for (&(i, j), &coupling) in &self.quadratic {
+                circuit
+                    .cx(i, j)
+                    .unwrap()
+                    .rz(&gamma * (2.0 * coupling), j)
+                    .unwrap()
+                    .cx(i, j)
+                    .unwrap();
+            }
+            for (i, &h) in self.linear.iter().enumerate() {
+                if h != 0.0 {
+                    circuit.rz(&gamma * (2.0 * h), i).unwrap();
+                }
+            }
+            for q in 0..self.n_qubits {
+                circuit.rx(&beta * 2.0, q).unwrap();
+            }
+        }
+        circuit
+    }
+
+    /// Optimizes the angles starting from `gammas` and `betas`, one per layer
+    pub fn run<O: Optimizer>(
+        &self,
+        optimizer: O,
+        gammas: &[f64],
+        betas: &[f64],
+    ) -> Result<QaoaResult, String> {
+        if gammas.len() != self.layers || betas.len() != self.layers {
+            return Err(format!(
+                "Expected {} gammas and betas, got {} and {}",
+                self.layers,
+                gammas.len(),
+                betas.len()
+            ));
+        }
+
+        let circuit = self.circuit();
+        let initial: Vec<f64> = circuit
+            .parameters()
+            .iter()
+            .map(|p| {
+                let (kind, k) = p.name().split_once('_').unwrap();
+                let k: usize = k.parse().unwrap();
+                if kind == "gamma" {
+                    gammas[k]
+                } else {
+                    betas[k]
+                }
+            })
+            .collect();
+
+        let mut vqe =
+            Vqe::new(circuit.clone(), self.hamiltonian(), optimizer)?.with_gradient(self.gradient);
+        let optimized = vqe.run(&initial)?;
+
+        let mut gammas = vec![0.0; self.layers];
+        let mut betas = vec![0.0; self.layers];
+        for (name, value) in &optimized.parameters {
+            let (kind, k) = name.split_once('_').unwrap();
+            let k: usize = k.parse().unwrap();
+            if kind == "gamma" {
+                gammas[k] = *value;
+            } else {
+                betas[k] = *value;
+            }
+        }
+
+        let values: Vec<(&str, f64)> = optimized
+            .parameters
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        let mut backend = QuantumCircuit::new(self.n_qubits)?;
+        backend.run(&circuit.bind(&values)?)?;
+        let probabilities = backend.probabilities().unwrap();
+
+        let costs: Vec<f64> = (0..probabilities.len()).map(|i| self.cost(i)).collect();
+        let best = costs.iter().copied().fold(f64::INFINITY, f64::min);
+        let worst = costs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let ratio = |cost: f64| {
+            if worst - best < 1e-12 {
+                1.0
+            } else {
+                (worst - cost) / (worst - best)
+            }
+        };
+
+        let mut order: Vec<usize> = (0..probabilities.len()).collect();
+        order.sort_by(|&a, &b| probabilities[b].total_cmp(&probabilities[a]));
+        let solutions = order
+            .into_iter()
+            .take(self.max_solutions)
+            .map(|i| QaoaSolution {
+                bitstring: format!("{:0width$b}", i, width = self.n_qubits),
+                probability: probabilities[i],
+                cost: costs[i],
+                approximation_ratio: ratio(costs[i]),
+            })
+            .collect();
+
+        Ok(QaoaResult {
+            gammas,
+            betas,
+            expected_cost: optimized.energy,
+            approximation_ratio: ratio(optimized.energy),
+            solutions,
+            iterations: optimized.iterations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{Adam, NelderMead};
+    use approx::assert_relative_eq;
+
+    /// Unweighted 4-node ring, whose maximum cut (4) separates alternate nodes
+    fn ring() -> Qaoa {
+        let edges = [(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 0, 1.0)];
+        Qaoa::max_cut(4, &edges, 2).unwrap()
+    }
+
+    #[test]
+    fn test_hamiltonian_matches_cost() {
+        let qaoa = ring();
+        let hamiltonian = qaoa.hamiltonian();
+        for bits in 0..16 {
+            let mut circuit = QuantumCircuit::new(4).unwrap();
+            for q in 0..4 {
+                if (bits >> q) & 1 == 1 {
+                    circuit.apply_gate(crate::gates::XGate, q).unwrap();
+                }
+            }
+            assert_relative_eq!(
+                circuit.expectation(&hamiltonian).unwrap(),
+                qaoa.cost(bits),
+                epsilon = 1e-12
+            );
+        }
+        assert_relative_eq!(qaoa.cost(0b0101), -4.0);
+        assert_relative_eq!(qaoa.cost(0b0011), -2.0);
+        assert_relative_eq!(qaoa.cost(0b0000), 0.0);
+        assert_eq!(qaoa.circuit().parameters().len(), 4);
+    }
+
+    #[test]
+    fn test_max_cut() {
+        let result = ring()
+            .run(NelderMead::new(1000), &[0.2, 0.4], &[0.4, 0.2])
+            .unwrap();
+        let top = &result.solutions[0];
+        assert!(top.bitstring == "0101" || top.bitstring == "1010");
+        assert_relative_eq!(top.approximation_ratio, 1.0);
+        assert!(result.approximation_ratio > 0.85);
+        assert_eq!(result.gammas.len(), 2);
+    }
+
+    #[test]
+    fn test_qubo() {
+        // Minimize -x0 - x1 + 3 x0 x1: choose exactly one variable, cost -1
+        let matrix = DMatrix::from_row_slice(2, 2, &[-1.0, 1.5, 1.5, -1.0]);
+        let qaoa = Qaoa::qubo(&matrix, 1).unwrap();
+        assert_relative_eq!(qaoa.cost(0b00), 0.0);
+        assert_relative_eq!(qaoa.cost(0b01), -1.0);
+        assert_relative_eq!(qaoa.cost(0b11), 1.0);
+
+        let result = qaoa.run(Adam::new(0.05, 300), &[0.3], &[0.3]).unwrap();
+        let top = &result.solutions[0];
+        assert!(top.bitstring == "01" || top.bitstring == "10");
+        assert_relative_eq!(top.cost, -1.0);
+        assert!(result.expected_cost < -0.5);
+    }
+
+    #[test]
+    fn test_invalid_qaoa() {
+        assert!(Qaoa::max_cut(2, &[(0, 2, 1.0)], 1).is_err());
+        assert!(Qaoa::max_cut(2, &[(1, 1, 1.0)], 1).is_err());
+        assert!(Qaoa::max_cut(2, &[], 1).is_err());
+        assert!(Qaoa::max_cut(2, &[(0, 1, 1.0)], 0).is_err());
+        assert!(Qaoa::qubo(&DMatrix::zeros(2, 3), 1).is_err());
+        assert!(ring().run(NelderMead::new(10), &[0.1], &[0.1]).is_err());
+    }
+}
@@ -0,0 +1,695 @@
+/*
+This file writes a recorded `Circuit` out as OpenQASM 2.0 or 3.0 source.
+
+Key concepts:
+1. Registers:
+   - Every qubit lives in one quantum register `q`, so qubit k is `q[k]`
+   - Named classical registers are declared as they are when they cover every
+     classical bit in order; otherwise a single register `c` is declared
+
+2. Gate Names:
+   - Gates map onto qelib1.inc (2.0) or stdgates.inc (3.0) names where one
+     exists, with `u1`/`cu1` in 2.0 and `p`/`cp` in 3.0
+   - The real rotation, iSWAP and custom gates have no standard name and are
+     written as `gate` blocks ahead of the body
+   - Specification: https://openqasm.com/
+
+3. Controls and Conditions:
+   - OpenQASM 3.0 writes any control with `ctrl @` / `negctrl @`; 2.0 falls
+     back to the controlled gates of qelib1.inc and decompositions
+   - A condition on a whole classical register becomes `if (c == value)`;
+     2.0 has no other form of `if`, so conditions on single bits need 3.0
+
+4. Parameters:
+   - OpenQASM 2.0 needs every angle bound
+   - OpenQASM 3.0 declares unbound parameters as `input float[64]` and writes
+     angles as expressions of them
+*/
+
+use crate::gates::u3_decomposition;
+use crate::ir::{Circuit, Gate, Instruction};
+use crate::parameter::Angle;
+use crate::transpiler::{decompose, Step};
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Version {
+    Qasm2,
+    Qasm3,
+}
+
+/// Gates without a name in qelib1.inc / stdgates.inc, emitted as `gate` blocks
+const ROTATION_DEFINITION: &str = "gate rotation(theta) a { ry(2*theta) a; }";
+const ISWAP_DEFINITION: &str = "gate iswap a, b { s a; s b; h a; cx a, b; cx b, a; h b; }";
+
+/// Serializes a circuit to OpenQASM 2.0 using `qelib1.inc`
+///
+/// All qubits are emitted as `qreg q[n]`. Classical bits keep their named
+/// registers when those cover every bit, and are otherwise emitted as
+/// `creg c[m]`. Every angle must be bound, and conditions must test a whole
+/// classical register, since OpenQASM 2.0 has no other form of `if`.
+///
+/// Custom gates become `gate` definitions built from `u3` and `cx`. Their
+/// global phase is dropped, which OpenQASM 2.0 cannot express.
+pub fn to_qasm2(circuit: &Circuit) -> Result<String, String> {
+    if !circuit.is_bound() {
+        return Err("OpenQASM 2.0 cannot express unbound parameters".to_string());
+    }
+    export(circuit, Version::Qasm2)
+}
+
+/// Serializes a circuit to OpenQASM 3.0 using `stdgates.inc`
+///
+/// Unbound parameters become `input float[64]` declarations and controlled
+/// gates use the `ctrl @` modifier. Custom gate definitions end with a
+/// `gphase`, so they stay exact under `ctrl @`.
+pub fn to_qasm3(circuit: &Circuit) -> Result<String, String> {
+    export(circuit, Version::Qasm3)
+}
+
+fn export(circuit: &Circuit, version: Version) -> Result<String, String> {
+    let mut body = String::new();
+    let mut definitions = BTreeSet::new();
+    for instruction in circuit.instructions() {
+        let line = instruction_line(circuit, instruction, version, &mut definitions)?;
+        body.push_str(&line);
+        body.push('\n');
+    }
+
+    let mut out = String::new();
+    match version {
+        Version::Qasm2 => {
+            out.push_str("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n");
+        }
+        Version::Qasm3 => {
+            out.push_str("OPENQASM 3.0;\ninclude \"stdgates.inc\";\n");
+        }
+    }
+    for definition in &definitions {
+        out.push_str(definition);
+        out.push('\n');
+    }
+    match version {
+        Version::Qasm2 => {
+            let _ = writeln!(out, "qreg q[{}];", circuit.n_qubits());
+            for (name, _, size) in classical_registers(circuit) {
+                let _ = writeln!(out, "creg {}[{}];", name, size);
+            }
+        }
+        Version::Qasm3 => {
+            for parameter in circuit.parameters() {
+                let _ = writeln!(out, "input float[64] {};", parameter);
+            }
+            let _ = writeln!(out, "qubit[{}] q;", circuit.n_qubits());
+            for (name, _, size) in classical_registers(circuit) {
+                let _ = writeln!(out, "bit[{}] {};", size, name);
+            }
+        }
+    }
+    out.push_str(&body);
+    Ok(out)
+}
+
+/// Classical registers to declare as (name, first bit, size)
+///
+/// The circuit's own registers are used when they cover every classical bit
+/// in order. `Circuit::add_register` keeps them from clashing with `q`.
+fn classical_registers(circuit: &Circuit) -> Vec<(&str, usize, usize)> {
+    let registers = circuit.registers();
+    let covered = registers.iter().map(|r| r.size()).sum::<usize>() == circuit.n_clbits()
+        && registers.first().is_none_or(|r| r.start() == 0);
+    if covered && !registers.is_empty() {
+        registers
+            .iter()
+            .map(|r| (r.name(), r.start(), r.size()))
+            .collect()
+    } else if circuit.n_clbits() > 0 {
+        vec![("c", 0, circuit.n_clbits())]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Name of classical bit `clbit` as `register[index]`
+fn clbit_name(circuit: &Circuit, clbit: usize) -> String {
+    classical_registers(circuit)
+        .into_iter()
+        .find(|&(_, start, size)| (start..start + size).contains(&clbit))
+        .map(|(name, start, _)| format!("{}[{}]", name, clbit - start))
+        .unwrap_or_else(|| format!("c[{}]", clbit))
+}
+
+fn instruction_line(
+    circuit: &Circuit,
+    instruction: &Instruction,
+    version: Version,
+    definitions: &mut BTreeSet<String>,
+) -> Result<String, String> {
+    let qubit_list = |qubits: &[usize]| {
+        qubits
+            .iter()
+            .map(|q| format!("q[{}]", q))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    Ok(match instruction {
+        Instruction::Gate { gate, qubits } => {
+            format!(
+                "{} {};",
+                gate_call(gate, version, definitions)?,
+                qubit_list(qubits)
+            )
+        }
+        Instruction::Controlled {
+            gate,
+            control,
+            target,
+        } => controlled_line(gate, *control, *target, version, definitions)?,
+        Instruction::MultiControlled {
+            gate,
+            controls,
+            control_state,
+            targets,
+        } => multi_controlled_line(
+            gate,
+            controls,
+            *control_state,
+            targets,
+            version,
+            definitions,
+        )?,
+        Instruction::Measure { qubit, clbit } => match version {
+            Version::Qasm2 => format!("measure q[{}] -> {};", qubit, clbit_name(circuit, *clbit)),
+            Version::Qasm3 => format!("{} = measure q[{}];", clbit_name(circuit, *clbit), qubit),
+        },
+        Instruction::Reset { qubit } => format!("reset q[{}];", qubit),
+        Instruction::Barrier { qubits } => format!("barrier {};", qubit_list(qubits)),
+        Instruction::Conditional {
+            clbits,
+            value,
+            instruction,
+        } => {
+            let inner = instruction_line(circuit, instruction, version, definitions)?;
+            let whole_register = classical_registers(circuit)
+                .into_iter()
+                .find(|&(_, start, size)| clbits.iter().copied().eq(start..start + size));
+            let condition = if let Some((name, _, _)) = whole_register {
+                format!("if ({} == {})", name, value)
+            } else if version == Version::Qasm3 {
+                let tests: Vec<String> = clbits
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &c)| format!("{} == {}", clbit_name(circuit, c), (value >> i) & 1))
+                    .collect();
+                format!("if ({})", tests.join(" && "))
+            } else {
+                return Err(
+                    "OpenQASM 2.0 can only condition on a whole classical register".to_string(),
+                );
+            };
+            // Decompositions span several statements, each needing the condition
+            inner
+                .lines()
+                .map(|line| format!("{} {}", condition, line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    })
+}
+
+/// Returns `name(params)` for a gate, registering any definition it needs
+fn gate_call(
+    gate: &Gate,
+    version: Version,
+    definitions: &mut BTreeSet<String>,
+) -> Result<String, String> {
+    let angles: Vec<String> = gate.angles().iter().map(|a| a.to_string()).collect();
+    let name = match (gate, version) {
+        (Gate::X, _) => "x",
+        (Gate::Y, _) => "y",
+        (Gate::Z, _) => "z",
+        (Gate::H, _) => "h",
+        (Gate::S, _) => "s",
+        (Gate::T, _) => "t",
+        (Gate::SX, _) => "sx",
+        (Gate::RX(_), _) => "rx",
+        (Gate::RY(_), _) => "ry",
+        (Gate::RZ(_), _) => "rz",
+        (Gate::PhaseShift(_), Version::Qasm2) => "u1",
+        (Gate::PhaseShift(_), Version::Qasm3) => "p",
+        (Gate::U3(..), Version::Qasm2) => "u3",
+        (Gate::U3(..), Version::Qasm3) => "U",
+        (Gate::CNOT, _) => "cx",
+        (Gate::CZ, _) => "cz",
+        (Gate::Swap, _) => "swap",
+        (Gate::CPhase(_), Version::Qasm2) => "cu1",
+        (Gate::CPhase(_), Version::Qasm3) => "cp",
+        (Gate::Rotation(_), _) => {
+            definitions.insert(ROTATION_DEFINITION.to_string());
+            "rotation"
+        }
+        (Gate::ISwap, _) => {
+            definitions.insert(ISWAP_DEFINITION.to_string());
+            "iswap"
+        }
+        (Gate::Custom(_), _) => {
+            let definition = custom_definition(gate, version)?;
+            let name = gate.name();
+            let header = format!("gate {} ", name);
+            if definitions
+                .iter()
+                .any(|d| d.starts_with(&header) && *d != definition)
+            {
+                return Err(format!("Two different custom gates are named '{}'", name));
+            }
+            definitions.insert(definition);
+            name
+        }
+    };
+
+    Ok(if angles.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}({})", name, angles.join(", "))
+    })
+}
+
+/// Returns a `gate` block defining a custom gate from single-qubit gates and
+/// CNOTs
+fn custom_definition(gate: &Gate, version: Version) -> Result<String, String> {
+    let name = gate.name();
+    let k = gate.n_qubits();
+    let steps = decompose(&Instruction::Gate {
+        gate: gate.clone(),
+        qubits: (0..k).collect(),
+    })?;
+    let arguments: Vec<String> = (0..k).map(|i| format!("a{}", i)).collect();
+    let mut out = format!("gate {} {} {{\n", name, arguments.join(", "));
+    let u3 = match version {
+        Version::Qasm2 => "u3",
+        Version::Qasm3 => "U",
+    };
+    let mut phase = 0.0;
+    for step in steps {
+        match step {
+            Step::One(q, matrix) => {
+                let (alpha, theta, phi, lambda) = u3_decomposition(&matrix);
+                phase += alpha;
+                let _ = writeln!(
+                    out,
+                    "  {}({}, {}, {}) {};",
+                    u3, theta, phi, lambda, arguments[q]
+                );
+            }
+            Step::Cx(a, b) => {
+                let _ = writeln!(out, "  cx {}, {};", arguments[a], arguments[b]);
+            }
+        }
+    }
+    if version == Version::Qasm3 && phase.abs() > 1e-12 {
+        let _ = writeln!(out, "  gphase({});", phase);
+    }
+    out.push('}');
+    Ok(out)
+}
+
+fn controlled_line(
+    gate: &Gate,
+    control: usize,
+    target: usize,
+    version: Version,
+    definitions: &mut BTreeSet<String>,
+) -> Result<String, String> {
+    let qubits = format!("q[{}], q[{}]", control, target);
+    if version == Version::Qasm3 {
+        let call = gate_call(gate, version, definitions)?;
+        return Ok(format!("ctrl @ {} {};", call, qubits));
+    }
+
+    let angle = |a: &Angle| a.to_string();
+    Ok(match gate {
+        Gate::X => format!("cx {};", qubits),
+        Gate::Y => format!("cy {};", qubits),
+        Gate::Z => format!("cz {};", qubits),
+        Gate::H => format!("ch {};", qubits),
+        Gate::S => format!("cu1({}) {};", PI / 2.0, qubits),
+        Gate::T => format!("cu1({}) {};", PI / 4.0, qubits),
+        Gate::RZ(theta) => format!("crz({}) {};", angle(theta), qubits),
+        Gate::PhaseShift(lambda) => format!("cu1({}) {};", angle(lambda), qubits),
+        Gate::U3(theta, phi, lambda) => format!(
+            "cu3({}, {}, {}) {};",
+            angle(theta),
+            angle(phi),
+            angle(lambda),
+            qubits
+        ),
+        // Anything else: controlled-U3 plus the global phase as a phase
+        // shift on the control
+        _ => {
+            let (alpha, theta, phi, lambda) = u3_decomposition(&gate.matrix2()?);
+            let mut line = format!("cu3({}, {}, {}) {};", theta, phi, lambda, qubits);
+            if alpha.abs() > 1e-12 {
+                let _ = write!(line, "\nu1({}) q[{}];", alpha, control);
+            }
+            line
+        }
+    })
+}
+
+fn multi_controlled_line(
+    gate: &Gate,
+    controls: &[usize],
+    control_state: u64,
+    targets: &[usize],
+    version: Version,
+    definitions: &mut BTreeSet<String>,
+) -> Result<String, String> {
+    let negative: Vec<usize> = controls
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| control_state & (1 << i) == 0)
+        .map(|(_, &c)| c)
+        .collect();
+    let qubits = controls
+        .iter()
+        .chain(targets)
+        .map(|q| format!("q[{}]", q))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if version == Version::Qasm3 {
+        let modifiers: String = (0..controls.len())
+            .map(|i| {
+                if control_state & (1 << i) != 0 {
+                    "ctrl @ "
+                } else {
+                    "negctrl @ "
+                }
+            })
+            .collect();
+        let call = gate_call(gate, version, definitions)?;
+        return Ok(format!("{}{} {};", modifiers, call, qubits));
+    }
+
+    // OpenQASM 2.0 has no negative controls: flip them around a positive one
+    let core = match (gate, controls.len()) {
+        (Gate::X, 2) => format!("ccx {};", qubits),
+        (Gate::Swap, 1) => format!("cswap {};", qubits),
+        (_, 1) if gate.n_qubits() == 1 => {
+            controlled_line(gate, controls[0], targets[0], version, definitions)?
+        }
+        _ => {
+            return Err(format!(
+                "OpenQASM 2.0 cannot express {} with {} controls",
+                gate.name(),
+                controls.len()
+            ))
+        }
+    };
+    let flips: Vec<String> = negative.iter().map(|q| format!("x q[{}];", q)).collect();
+    let mut lines = flips.clone();
+    lines.push(core);
+    lines.extend(flips);
+    Ok(lines.join("\n"))
+}
+
+impl Circuit {
+    /// Serializes the circuit to OpenQASM 2.0 (see `to_qasm2`)
+    pub fn to_qasm2(&self) -> Result<String, String> {
+        to_qasm2(self)
+    }
+
+    /// Serializes the circuit to OpenQASM 3.0 (see `to_qasm3`)
+    pub fn to_qasm3(&self) -> Result<String, String> {
+        to_qasm3(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::circuit::QuantumCircuit;
+    use crate::gates::{CustomGate, MultiQubitGate, SwapGate, ToffoliGate};
+    use crate::parameter::Parameter;
+    use crate::qasm::parse_qasm;
+    use approx::assert_relative_eq;
+    use nalgebra::Complex;
+
+    /// |⟨a|b⟩|² between the states two circuits prepare from |0...0⟩
+    fn overlap(a: &Circuit, b: &Circuit) -> f64 {
+        let mut sa = QuantumCircuit::new(a.n_qubits()).unwrap();
+        let mut sb = QuantumCircuit::new(b.n_qubits()).unwrap();
+        sa.run(a).unwrap();
+        sb.run(b).unwrap();
+        sa.get_state().dotc(sb.get_state()).norm_sqr()
+    }
+
+    fn sample_circuit() -> Circuit {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .t(1)
+            .unwrap()
+            .rx(0.3, 2)
+            .unwrap()
+            .u3(0.4, -1.2, 2.5, 0)
+            .unwrap()
+            .p(0.9, 1)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .cp(1.1, 1, 2)
+            .unwrap()
+            .iswap(0, 2)
+            .unwrap()
+            .add_gate(Gate::Rotation(0.6.into()), &[1])
+            .unwrap()
+            .controlled(Gate::RY(0.8.into()), 2, 0)
+            .unwrap()
+            .controlled(Gate::H, 0, 1)
+            .unwrap()
+            .barrier(&[])
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_qasm2_round_trip() {
+        let circuit = sample_circuit();
+        let text = circuit.to_qasm2().unwrap();
+        assert!(text.starts_with("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n"));
+        assert!(text.contains(ROTATION_DEFINITION));
+        assert!(text.contains(ISWAP_DEFINITION));
+
+        let parsed = parse_qasm(&text).unwrap();
+        assert_relative_eq!(overlap(&circuit, &parsed), 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_qasm2_measure_and_if() {
+        let mut circuit = Circuit::with_clbits(2, 0).unwrap();
+        circuit.add_register("c", 1).unwrap();
+        circuit.h(0).unwrap().measure_into(0, 0).unwrap();
+        circuit
+            .conditional(
+                &[0],
+                1,
+                Instruction::Gate {
+                    gate: Gate::X,
+                    qubits: vec![1],
+                },
+            )
+            .unwrap();
+        let text = circuit.to_qasm2().unwrap();
+        assert!(text.contains("creg c[1];"));
+        assert!(text.contains("measure q[0] -> c[0];"));
+        assert!(text.contains("if (c == 1) x q[1];"));
+        assert_eq!(parse_qasm(&text).unwrap(), circuit);
+    }
+
+    #[test]
+    fn test_named_registers_and_reset() {
+        let mut circuit = Circuit::with_clbits(2, 0).unwrap();
+        circuit
+            .add_register("m", 1)
+            .unwrap()
+            .add_register("out", 1)
+            .unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .measure_register(&[0], "m")
+            .unwrap()
+            .c_if("m", 1, Instruction::Reset { qubit: 1 })
+            .unwrap()
+            .reset(0)
+            .unwrap()
+            .measure_register(&[1], "out")
+            .unwrap();
+
+        let text = circuit.to_qasm2().unwrap();
+        assert!(text.contains("creg m[1];\ncreg out[1];"));
+        assert!(text.contains("measure q[0] -> m[0];"));
+        assert!(text.contains("if (m == 1) reset q[1];"));
+        assert!(text.contains("reset q[0];"));
+        assert!(text.contains("measure q[1] -> out[0];"));
+        assert_eq!(parse_qasm(&text).unwrap(), circuit);
+
+        let text = circuit.to_qasm3().unwrap();
+        assert!(text.contains("bit[1] m;\nbit[1] out;"));
+        assert!(text.contains("out[0] = measure q[1];"));
+
+        // A condition on part of a register has no OpenQASM 2.0 form
+        let mut partial = Circuit::with_clbits(2, 0).unwrap();
+        partial.add_register("c", 2).unwrap();
+        partial
+            .conditional(&[1], 1, Instruction::Reset { qubit: 0 })
+            .unwrap();
+        assert!(partial.to_qasm2().is_err());
+        assert!(partial
+            .to_qasm3()
+            .unwrap()
+            .contains("if (c[1] == 1) reset q[0];"));
+    }
+
+    #[test]
+    fn test_qasm2_rejects_unbound_parameters() {
+        let mut circuit = Circuit::new(1).unwrap();
+        circuit.rx(Parameter::new("theta"), 0).unwrap();
+        assert!(circuit.to_qasm2().is_err());
+    }
+
+    #[test]
+    fn test_qasm3_output() {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit
+            .rx(Parameter::new("theta") * 2.0, 0)
+            .unwrap()
+            .controlled(Gate::RY(0.5.into()), 0, 1)
+            .unwrap()
+            .measure(1)
+            .unwrap();
+        let text = circuit.to_qasm3().unwrap();
+        assert_eq!(
+            text,
+            "OPENQASM 3.0;\n\
+             include \"stdgates.inc\";\n\
+             input float[64] theta;\n\
+             qubit[2] q;\n\
+             bit[2] c;\n\
+             rx(2*theta) q[0];\n\
+             ctrl @ ry(0.5) q[0], q[1];\n\
+             c[1] = measure q[1];\n"
+        );
+    }
+
+    #[test]
+    fn test_multi_controlled_export() {
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .ccx(0, 1, 2)
+            .unwrap()
+            .cswap(2, 0, 3)
+            .unwrap()
+            .multi_controlled_on(Gate::RY(0.4.into()), &[3], 0, &[1])
+            .unwrap();
+
+        let text = circuit.to_qasm2().unwrap();
+        assert!(text.contains("ccx q[0], q[1], q[2];"));
+        assert!(text.contains("cswap q[2], q[0], q[3];"));
+        assert!(text.contains("x q[3];"));
+        let parsed = parse_qasm(&text).unwrap();
+        assert_relative_eq!(overlap(&circuit, &parsed), 1.0, epsilon = 1e-10);
+
+        let text = circuit.to_qasm3().unwrap();
+        assert!(text.contains("ctrl @ ctrl @ x q[0], q[1], q[2];"));
+        assert!(text.contains("negctrl @ ry(0.4) q[3], q[1];"));
+
+        let mut wide = Circuit::new(4).unwrap();
+        wide.mcx(&[0, 1, 2], 3).unwrap();
+        assert!(wide.to_qasm2().is_err());
+    }
+
+    #[test]
+    fn test_custom_gate_export() {
+        let mut block = Circuit::new(2).unwrap();
+        block.h(0).unwrap().cx(0, 1).unwrap().ry(0.8, 1).unwrap();
+        let phase = Complex::new(0.3_f64.cos(), 0.3_f64.sin());
+        let entangler =
+            CustomGate::from_matrix("entangler", block.unitary().unwrap() * phase).unwrap();
+        let sqrt_x = CustomGate::from_matrix("sqrt_x", Gate::SX.matrix().unwrap() * phase).unwrap();
+
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(2)
+            .unwrap()
+            .custom(entangler.clone(), &[0, 2])
+            .unwrap()
+            .custom(entangler.adjoint(), &[1, 0])
+            .unwrap()
+            .controlled(sqrt_x.clone().into(), 2, 1)
+            .unwrap()
+            .custom(entangler.clone(), &[2, 1])
+            .unwrap();
+
+        let text = circuit.to_qasm2().unwrap();
+        assert!(text.contains("gate entangler a0, a1 {\n"));
+        assert!(text.contains("gate entangler_dg a0, a1 {\n"));
+        assert!(text.contains("entangler q[0], q[2];"));
+        let parsed = parse_qasm(&text).unwrap();
+        assert_relative_eq!(overlap(&circuit, &parsed), 1.0, epsilon = 1e-10);
+
+        // OpenQASM 3.0 keeps the global phase, so the gate can be controlled
+        let text = circuit.to_qasm3().unwrap();
+        assert!(text.contains("gphase("));
+        assert!(text.contains("ctrl @ sqrt_x q[2], q[1];"));
+        assert_eq!(text.matches("gate entangler ").count(), 1);
+
+        // Names must not clash with each other; clashes with OpenQASM's own
+        // names are rejected by `CustomGate::from_matrix`
+        let mut clash = Circuit::new(2).unwrap();
+        clash
+            .custom(entangler, &[0, 1])
+            .unwrap()
+            .custom(
+                CustomGate::from_matrix("entangler", SwapGate.matrix()).unwrap(),
+                &[0, 1],
+            )
+            .unwrap();
+        assert!(clash.to_qasm2().is_err());
+        assert!(CustomGate::from_matrix("h", Gate::H.matrix().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_custom_gate_round_trip() {
+        // Names close to, but not equal to, OpenQASM keywords and gates
+        let phase = CustomGate::from_matrix("measure_z", Gate::S.matrix().unwrap()).unwrap();
+        let hh = CustomGate::from_matrix("hh", ToffoliGate.matrix()).unwrap();
+        let u2 = CustomGate::from_matrix("U2", Gate::RY(0.9.into()).matrix().unwrap()).unwrap();
+
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .custom(phase, &[1])
+            .unwrap()
+            .custom(hh, &[0, 1, 2])
+            .unwrap()
+            .custom(u2, &[2])
+            .unwrap();
+
+        let parsed = parse_qasm(&circuit.to_qasm2().unwrap()).unwrap();
+        assert_relative_eq!(overlap(&circuit, &parsed), 1.0, epsilon = 1e-10);
+    }
+}
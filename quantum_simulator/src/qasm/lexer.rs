@@ -0,0 +1,266 @@
+/*
+This file splits OpenQASM 2.0 source into tokens for parser.rs.
+
+Key concepts:
+1. Tokens:
+   - Identifiers, integers, reals, strings and punctuation, each tagged with
+     the line and column where it starts
+   - Integers that fit in a `u64` stay integers; other numbers (`0.5`, `1e-3`)
+     become reals
+
+2. Skipped Input:
+   - Whitespace, `//` line comments and `/* */` block comments produce no
+     tokens
+
+3. Errors:
+   - Unknown characters and unterminated comments or strings are reported as
+     a `QasmError` at the position where they start
+*/
+
+use super::{QasmError, QasmErrorKind};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum TokenKind {
+    Ident(String),
+    Int(u64),
+    Real(f64),
+    Str(String),
+    Semicolon,
+    Comma,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Arrow,
+    EqEq,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Ident(name) => write!(f, "'{}'", name),
+            TokenKind::Int(value) => write!(f, "'{}'", value),
+            TokenKind::Real(value) => write!(f, "'{}'", value),
+            TokenKind::Str(text) => write!(f, "\"{}\"", text),
+            TokenKind::Semicolon => write!(f, "';'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::LBracket => write!(f, "'['"),
+            TokenKind::RBracket => write!(f, "']'"),
+            TokenKind::LParen => write!(f, "'('"),
+            TokenKind::RParen => write!(f, "')'"),
+            TokenKind::LBrace => write!(f, "'{{'"),
+            TokenKind::RBrace => write!(f, "'}}'"),
+            TokenKind::Arrow => write!(f, "'->'"),
+            TokenKind::EqEq => write!(f, "'=='"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Caret => write!(f, "'^'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Splits OpenQASM source into tokens, dropping whitespace and comments
+pub(super) fn tokenize(source: &str) -> Result<Vec<Token>, QasmError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut column) = (0, 1, 1);
+
+    while i < chars.len() {
+        let c = chars[i];
+        let (start_line, start_column) = (line, column);
+        let mut advance = |i: &mut usize, n: usize| {
+            for _ in 0..n {
+                if chars[*i] == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+                *i += 1;
+            }
+        };
+
+        if c.is_whitespace() {
+            advance(&mut i, 1);
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, 1);
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            advance(&mut i, 2);
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                advance(&mut i, 1);
+            }
+            if i >= chars.len() {
+                return Err(QasmError::new(
+                    QasmErrorKind::UnexpectedEof {
+                        expected: "'*/'".to_string(),
+                    },
+                    start_line,
+                    start_column,
+                ));
+            }
+            advance(&mut i, 2);
+            continue;
+        }
+
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            let len = chars[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            let ident: String = chars[i..i + len].iter().collect();
+            advance(&mut i, len);
+            TokenKind::Ident(ident)
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let mut len = chars[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit() || **c == '.')
+                .count();
+            if matches!(chars.get(i + len), Some('e') | Some('E')) {
+                let sign = matches!(chars.get(i + len + 1), Some('+') | Some('-')) as usize;
+                if chars
+                    .get(i + len + 1 + sign)
+                    .is_some_and(char::is_ascii_digit)
+                {
+                    len += 1 + sign;
+                    len += chars[i + len..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_digit())
+                        .count();
+                }
+            }
+            let text: String = chars[i..i + len].iter().collect();
+            advance(&mut i, len);
+            if let Ok(value) = text.parse::<u64>() {
+                TokenKind::Int(value)
+            } else {
+                match text.parse::<f64>() {
+                    Ok(value) => TokenKind::Real(value),
+                    Err(_) => {
+                        return Err(QasmError::new(
+                            QasmErrorKind::UnexpectedToken {
+                                expected: "a number".to_string(),
+                                found: format!("'{}'", text),
+                            },
+                            start_line,
+                            start_column,
+                        ))
+                    }
+                }
+            }
+        } else if c == '"' {
+            let len = chars[i + 1..].iter().take_while(|c| **c != '"').count();
+            if i + 1 + len >= chars.len() {
+                return Err(QasmError::new(
+                    QasmErrorKind::UnexpectedEof {
+                        expected: "closing '\"'".to_string(),
+                    },
+                    start_line,
+                    start_column,
+                ));
+            }
+            let text: String = chars[i + 1..i + 1 + len].iter().collect();
+            advance(&mut i, len + 2);
+            TokenKind::Str(text)
+        } else {
+            let two = (c, chars.get(i + 1).copied());
+            let (kind, len) = match two {
+                ('-', Some('>')) => (TokenKind::Arrow, 2),
+                ('=', Some('=')) => (TokenKind::EqEq, 2),
+                (';', _) => (TokenKind::Semicolon, 1),
+                (',', _) => (TokenKind::Comma, 1),
+                ('[', _) => (TokenKind::LBracket, 1),
+                (']', _) => (TokenKind::RBracket, 1),
+                ('(', _) => (TokenKind::LParen, 1),
+                (')', _) => (TokenKind::RParen, 1),
+                ('{', _) => (TokenKind::LBrace, 1),
+                ('}', _) => (TokenKind::RBrace, 1),
+                ('+', _) => (TokenKind::Plus, 1),
+                ('-', _) => (TokenKind::Minus, 1),
+                ('*', _) => (TokenKind::Star, 1),
+                ('/', _) => (TokenKind::Slash, 1),
+                ('^', _) => (TokenKind::Caret, 1),
+                _ => {
+                    return Err(QasmError::new(
+                        QasmErrorKind::UnexpectedCharacter(c),
+                        start_line,
+                        start_column,
+                    ))
+                }
+            };
+            advance(&mut i, len);
+            kind
+        };
+
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_positions_and_comments() {
+        let tokens = tokenize("// header\nqreg q[2];\n/* skip */ h q[0];").unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Ident("qreg".to_string()));
+        assert_eq!((tokens[0].line, tokens[0].column), (2, 1));
+        assert_eq!(tokens[3].kind, TokenKind::Int(2));
+        let h = tokens
+            .iter()
+            .find(|t| t.kind == TokenKind::Ident("h".into()));
+        assert_eq!(h.map(|t| (t.line, t.column)), Some((3, 12)));
+    }
+
+    #[test]
+    fn test_tokenize_numbers() {
+        let tokens = tokenize("0.5 1e-3 .25 3").unwrap();
+        let kinds: Vec<_> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Real(0.5),
+                TokenKind::Real(1e-3),
+                TokenKind::Real(0.25),
+                TokenKind::Int(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unexpected_character() {
+        let err = tokenize("qreg q[2];\n  @").unwrap_err();
+        assert_eq!(err.kind, QasmErrorKind::UnexpectedCharacter('@'));
+        assert_eq!((err.line, err.column), (2, 3));
+    }
+}
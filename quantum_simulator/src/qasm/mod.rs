@@ -0,0 +1,290 @@
+/*
+This module reads OpenQASM 2.0 programs into a recorded `Circuit` and writes
+circuits back out as OpenQASM 2.0 or 3.0.
+
+Key concepts:
+1. OpenQASM:
+   - A plain-text circuit description shared by most quantum toolchains
+   - Specification: https://arxiv.org/abs/1707.03429
+
+2. Supported language:
+   - `qreg`/`creg` declarations (registers are laid out in declaration order)
+   - Every gate of `qelib1.inc`, plus the built-in `U` and `CX`
+   - User `gate` definitions, expanded at each call site
+   - `measure`, `reset`, `barrier` and `if (creg == value)`
+   - `creg` names are kept as the circuit's classical registers
+   - Register arguments broadcast over every index, e.g. `h q;`
+
+3. Gate mapping:
+   - Gates without a direct `Gate` counterpart are decomposed (e.g. `rzz`
+     into two CNOTs around a phase shift)
+   - `ccx`, `cswap`, `c3x`, `c3sqrtx` and `c4x` become multi-controlled
+     instructions; the relative-phase `rccx` and `rc3x` are spelled out as in
+     qelib1.inc
+   - `sxdg`, `u1`, `rz` and friends agree with their `Gate` counterparts up to a
+     global phase, which no measurement can observe
+
+4. Errors:
+   - Every parse failure is a `QasmError` carrying the line and column where
+     the offending token starts
+
+5. Export:
+   - `to_qasm2()` targets qelib1.inc and `to_qasm3()` targets stdgates.inc
+   - Gates with no standard name (the real `RotationGate`, iSWAP) are written
+     as `gate` definitions at the top of the program
+   - Custom gates are written as `gate` definitions of `u3`/`U` and `cx`
+     (exact two-level decomposition from transpiler.rs); OpenQASM 3.0 adds a
+     `gphase` so the definition keeps the gate's global phase
+   - OpenQASM 3.0 keeps unbound parameters as `input` declarations
+   - Multi-controlled gates use `ctrl @` / `negctrl @` in OpenQASM 3.0; in
+     2.0 only `ccx`, `cswap` and singly controlled gates can be written, with
+     negative controls wrapped in X gates
+*/
+
+mod export;
+mod lexer;
+mod parser;
+
+pub use export::{to_qasm2, to_qasm3};
+pub use parser::parse_qasm;
+
+use crate::ir::Circuit;
+use std::error::Error;
+use std::fmt;
+
+/// Keywords and literals of OpenQASM 2.0 and 3.0
+const KEYWORDS: &[&str] = &[
+    "OPENQASM",
+    "include",
+    "qreg",
+    "creg",
+    "gate",
+    "opaque",
+    "measure",
+    "reset",
+    "barrier",
+    "if",
+    "else",
+    "for",
+    "while",
+    "in",
+    "break",
+    "continue",
+    "end",
+    "return",
+    "def",
+    "defcal",
+    "defcalgrammar",
+    "cal",
+    "extern",
+    "let",
+    "const",
+    "mutable",
+    "readonly",
+    "input",
+    "output",
+    "box",
+    "delay",
+    "nop",
+    "pragma",
+    "switch",
+    "case",
+    "default",
+    "ctrl",
+    "negctrl",
+    "inv",
+    "pow",
+    "gphase",
+    "true",
+    "false",
+    "im",
+];
+
+/// Types of OpenQASM 3.0
+const TYPES: &[&str] = &[
+    "qubit", "bit", "bool", "int", "uint", "float", "angle", "complex", "duration", "stretch",
+    "array", "void",
+];
+
+/// Constants and functions built into OpenQASM 2.0 and 3.0
+const BUILTINS: &[&str] = &[
+    "pi",
+    "tau",
+    "euler",
+    "sin",
+    "cos",
+    "tan",
+    "arcsin",
+    "arccos",
+    "arctan",
+    "exp",
+    "ln",
+    "log",
+    "sqrt",
+    "floor",
+    "ceiling",
+    "mod",
+    "popcount",
+    "rotl",
+    "rotr",
+    "real",
+    "imag",
+    "sizeof",
+    "durationof",
+];
+
+/// Gates of stdgates.inc missing from qelib1.inc (`phase` and `cphase`), and
+/// the gates the exporter defines itself
+const OTHER_GATES: &[&str] = &["phase", "cphase", "rotation", "iswap"];
+
+/// The quantum register every exported program declares
+const QUANTUM_REGISTER: &str = "q";
+
+/// The classical register exported programs declare when the circuit's own
+/// registers cannot be kept
+const CLASSICAL_REGISTER: &str = "c";
+
+/// True if `name` is taken in OpenQASM itself: a keyword, type or built-in,
+/// or a gate of qelib1.inc, stdgates.inc or the exporter's own definitions
+pub(crate) fn is_reserved_name(name: &str) -> bool {
+    [KEYWORDS, TYPES, BUILTINS, OTHER_GATES]
+        .iter()
+        .any(|words| words.contains(&name))
+        || parser::is_standard_gate(name)
+}
+
+/// True if `name` cannot name a user gate in exported OpenQASM: a reserved
+/// name, or one of the registers the exporter declares
+pub(crate) fn is_reserved_gate_name(name: &str) -> bool {
+    is_reserved_name(name) || name == QUANTUM_REGISTER || name == CLASSICAL_REGISTER
+}
+
+/// True if `name` cannot name a classical register in exported OpenQASM: a
+/// reserved name, or the quantum register the exporter declares
+pub(crate) fn is_reserved_register_name(name: &str) -> bool {
+    is_reserved_name(name) || name == QUANTUM_REGISTER
+}
+
+/// What went wrong while reading an OpenQASM program
+#[derive(Debug, Clone, PartialEq)]
+pub enum QasmErrorKind {
+    UnexpectedCharacter(char),
+    UnexpectedToken {
+        expected: String,
+        found: String,
+    },
+    UnexpectedEof {
+        expected: String,
+    },
+    UnsupportedVersion(String),
+    UnknownInclude(String),
+    UndefinedRegister(String),
+    UndefinedIdentifier(String),
+    DuplicateDefinition(String),
+    IndexOutOfRange {
+        register: String,
+        index: usize,
+        size: usize,
+    },
+    UnknownGate(String),
+    WrongArgumentCount {
+        gate: String,
+        expected: usize,
+        found: usize,
+    },
+    WrongParameterCount {
+        gate: String,
+        expected: usize,
+        found: usize,
+    },
+    RegisterSizeMismatch,
+    Unsupported(String),
+    InvalidCircuit(String),
+}
+
+impl fmt::Display for QasmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QasmErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            QasmErrorKind::UnexpectedToken { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            QasmErrorKind::UnexpectedEof { expected } => {
+                write!(f, "expected {}, found end of input", expected)
+            }
+            QasmErrorKind::UnsupportedVersion(v) => write!(f, "unsupported OpenQASM version {}", v),
+            QasmErrorKind::UnknownInclude(file) => write!(f, "cannot include \"{}\"", file),
+            QasmErrorKind::UndefinedRegister(name) => write!(f, "undefined register '{}'", name),
+            QasmErrorKind::UndefinedIdentifier(name) => {
+                write!(f, "undefined identifier '{}'", name)
+            }
+            QasmErrorKind::DuplicateDefinition(name) => write!(f, "'{}' is already defined", name),
+            QasmErrorKind::IndexOutOfRange {
+                register,
+                index,
+                size,
+            } => write!(
+                f,
+                "index {} is out of range for register '{}' of size {}",
+                index, register, size
+            ),
+            QasmErrorKind::UnknownGate(name) => write!(f, "unknown gate '{}'", name),
+            QasmErrorKind::WrongArgumentCount {
+                gate,
+                expected,
+                found,
+            } => write!(
+                f,
+                "gate '{}' takes {} qubit arguments but {} were given",
+                gate, expected, found
+            ),
+            QasmErrorKind::WrongParameterCount {
+                gate,
+                expected,
+                found,
+            } => write!(
+                f,
+                "gate '{}' takes {} parameters but {} were given",
+                gate, expected, found
+            ),
+            QasmErrorKind::RegisterSizeMismatch => {
+                write!(f, "register arguments have different sizes")
+            }
+            QasmErrorKind::Unsupported(what) => write!(f, "{} is not supported", what),
+            QasmErrorKind::InvalidCircuit(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// An error in an OpenQASM program, located by 1-based line and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct QasmError {
+    pub kind: QasmErrorKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl QasmError {
+    pub(crate) fn new(kind: QasmErrorKind, line: usize, column: usize) -> Self {
+        Self { kind, line, column }
+    }
+}
+
+impl fmt::Display for QasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.kind
+        )
+    }
+}
+
+impl Error for QasmError {}
+
+impl Circuit {
+    /// Parses an OpenQASM 2.0 program
+    pub fn from_qasm(source: &str) -> Result<Circuit, QasmError> {
+        parse_qasm(source)
+    }
+}
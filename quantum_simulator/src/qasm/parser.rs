@@ -0,0 +1,1292 @@
+/*
+This file parses OpenQASM 2.0 source into a recorded `Circuit`.
+
+Key concepts:
+1. Recursive Descent:
+   - Statements are read one at a time from the token stream of lexer.rs
+   - Gate parameters are arithmetic expressions over `pi`, numbers and the
+     parameters of the enclosing `gate` definition
+   - Specification: https://arxiv.org/abs/1707.03429
+
+2. Registers:
+   - Quantum registers are concatenated into one qubit range, and classical
+     registers become the circuit's named registers, in declaration order
+   - A whole register as an argument broadcasts the statement over its bits
+
+3. Gate Definitions:
+   - `gate` bodies may only call gates defined before them, so recursion is
+     rejected when the body is read
+   - Calls to user gates are expanded into their bodies at the call site;
+     qelib1.inc gates map onto `Gate`s or short decompositions
+
+4. Errors:
+   - Every failure is a `QasmError` with the line and column of the token
+     that caused it
+*/
+
+use super::lexer::{tokenize, Token, TokenKind};
+use super::{QasmError, QasmErrorKind};
+use crate::ir::{Circuit, Gate, Instruction};
+use crate::parameter::Angle;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Parses an OpenQASM 2.0 program into a `Circuit`
+///
+/// Quantum registers are concatenated in declaration order, so with
+/// `qreg a[2]; qreg b[1];` the qubit `b[0]` becomes qubit 2. Classical
+/// registers are laid out the same way.
+pub fn parse_qasm(source: &str) -> Result<Circuit, QasmError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser::new(tokens);
+    parser.parse_program()?;
+    parser.into_circuit()
+}
+
+/// Arithmetic expression used for gate parameters
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Variable {
+        name: String,
+        line: usize,
+        column: usize,
+    },
+    Negate(Box<Expr>),
+    Binary(TokenKind, Box<Expr>, Box<Expr>),
+    Function(String, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, env: &HashMap<String, f64>) -> Result<f64, QasmError> {
+        Ok(match self {
+            Expr::Number(value) => *value,
+            Expr::Variable { name, line, column } => match env.get(name) {
+                Some(value) => *value,
+                None => {
+                    return Err(QasmError::new(
+                        QasmErrorKind::UndefinedIdentifier(name.clone()),
+                        *line,
+                        *column,
+                    ))
+                }
+            },
+            Expr::Negate(inner) => -inner.eval(env)?,
+            Expr::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(env)?, rhs.eval(env)?);
+                match op {
+                    TokenKind::Plus => a + b,
+                    TokenKind::Minus => a - b,
+                    TokenKind::Star => a * b,
+                    TokenKind::Slash => a / b,
+                    _ => a.powf(b),
+                }
+            }
+            Expr::Function(name, arg) => {
+                let x = arg.eval(env)?;
+                match name.as_str() {
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "tan" => x.tan(),
+                    "exp" => x.exp(),
+                    "ln" => x.ln(),
+                    _ => x.sqrt(),
+                }
+            }
+        })
+    }
+}
+
+/// One statement inside a `gate` body
+#[derive(Debug, Clone)]
+struct GateCall {
+    name: String,
+    params: Vec<Expr>,
+    args: Vec<String>,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Clone)]
+struct GateDefinition {
+    params: Vec<String>,
+    qubits: Vec<String>,
+    body: Vec<GateCall>,
+}
+
+/// A register reference such as `q` or `q[3]`
+struct Argument {
+    register: String,
+    index: Option<usize>,
+    line: usize,
+    column: usize,
+}
+
+struct Register {
+    name: String,
+    offset: usize,
+    size: usize,
+    line: usize,
+    column: usize,
+}
+
+/// (name, number of parameters, number of qubits) for qelib1.inc
+const QELIB1_GATES: &[(&str, usize, usize)] = &[
+    ("u3", 3, 1),
+    ("u2", 2, 1),
+    ("u1", 1, 1),
+    ("u", 3, 1),
+    ("p", 1, 1),
+    ("u0", 1, 1),
+    ("id", 0, 1),
+    ("x", 0, 1),
+    ("y", 0, 1),
+    ("z", 0, 1),
+    ("h", 0, 1),
+    ("s", 0, 1),
+    ("sdg", 0, 1),
+    ("t", 0, 1),
+    ("tdg", 0, 1),
+    ("sx", 0, 1),
+    ("sxdg", 0, 1),
+    ("rx", 1, 1),
+    ("ry", 1, 1),
+    ("rz", 1, 1),
+    ("cx", 0, 2),
+    ("cy", 0, 2),
+    ("cz", 0, 2),
+    ("ch", 0, 2),
+    ("swap", 0, 2),
+    ("crx", 1, 2),
+    ("cry", 1, 2),
+    ("crz", 1, 2),
+    ("cu1", 1, 2),
+    ("cp", 1, 2),
+    ("cu3", 3, 2),
+    ("cu", 4, 2),
+    ("csx", 0, 2),
+    ("rxx", 1, 2),
+    ("rzz", 1, 2),
+    ("ccx", 0, 3),
+    ("cswap", 0, 3),
+    ("rccx", 0, 3),
+    ("rc3x", 0, 4),
+    ("c3x", 0, 4),
+    ("c3sqrtx", 0, 4),
+    ("c4x", 0, 5),
+];
+
+/// Gates that exist without any include
+const BUILTIN_GATES: &[(&str, usize, usize)] = &[("U", 3, 1), ("CX", 0, 2)];
+
+/// True for the gates of qelib1.inc and the built-in `U` and `CX`
+pub(super) fn is_standard_gate(name: &str) -> bool {
+    QELIB1_GATES
+        .iter()
+        .chain(BUILTIN_GATES)
+        .any(|&(gate, _, _)| gate == name)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    qelib1: bool,
+    qregs: Vec<Register>,
+    cregs: Vec<Register>,
+    gates: HashMap<String, GateDefinition>,
+    instructions: Vec<(Instruction, usize, usize)>,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            qelib1: false,
+            qregs: Vec::new(),
+            cregs: Vec::new(),
+            gates: HashMap::new(),
+            instructions: Vec::new(),
+        }
+    }
+
+    fn into_circuit(self) -> Result<Circuit, QasmError> {
+        let n_qubits: usize = self.qregs.iter().map(|r| r.size).sum();
+        let n_clbits: usize = self.cregs.iter().map(|r| r.size).sum();
+        let mut circuit = Circuit::with_clbits(n_qubits, 0).map_err(|_| {
+            let (line, column) = self.end_position();
+            QasmError::new(
+                QasmErrorKind::InvalidCircuit("program declares no qubits".to_string()),
+                line,
+                column,
+            )
+        })?;
+        for register in &self.cregs {
+            circuit
+                .add_register(&register.name, register.size)
+                .map_err(|msg| {
+                    QasmError::new(
+                        QasmErrorKind::InvalidCircuit(msg),
+                        register.line,
+                        register.column,
+                    )
+                })?;
+        }
+        debug_assert_eq!(circuit.n_clbits(), n_clbits);
+        for (instruction, line, column) in self.instructions {
+            circuit
+                .push(instruction)
+                .map_err(|msg| QasmError::new(QasmErrorKind::InvalidCircuit(msg), line, column))?;
+        }
+        Ok(circuit)
+    }
+
+    // ----- token helpers -----
+
+    fn end_position(&self) -> (usize, usize) {
+        self.tokens
+            .last()
+            .map(|t| (t.line, t.column))
+            .unwrap_or((1, 1))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_is(&self, kind: &TokenKind) -> bool {
+        self.peek().is_some_and(|t| &t.kind == kind)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, QasmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => {
+                let (line, column) = self.end_position();
+                Err(QasmError::new(
+                    QasmErrorKind::UnexpectedEof {
+                        expected: expected.to_string(),
+                    },
+                    line,
+                    column,
+                ))
+            }
+        }
+    }
+
+    fn unexpected(token: &Token, expected: &str) -> QasmError {
+        QasmError::new(
+            QasmErrorKind::UnexpectedToken {
+                expected: expected.to_string(),
+                found: token.kind.to_string(),
+            },
+            token.line,
+            token.column,
+        )
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, QasmError> {
+        let expected = kind.to_string();
+        let token = self.next(&expected)?;
+        if token.kind != kind {
+            return Err(Self::unexpected(&token, &expected));
+        }
+        Ok(token)
+    }
+
+    fn expect_ident(&mut self) -> Result<(String, usize, usize), QasmError> {
+        let token = self.next("an identifier")?;
+        match token.kind {
+            TokenKind::Ident(name) => Ok((name, token.line, token.column)),
+            _ => Err(Self::unexpected(&token, "an identifier")),
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<u64, QasmError> {
+        let token = self.next("an integer")?;
+        match token.kind {
+            TokenKind::Int(value) => Ok(value),
+            _ => Err(Self::unexpected(&token, "an integer")),
+        }
+    }
+
+    // ----- program structure -----
+
+    fn parse_program(&mut self) -> Result<(), QasmError> {
+        let (keyword, line, column) = self.expect_ident()?;
+        if keyword != "OPENQASM" {
+            return Err(QasmError::new(
+                QasmErrorKind::UnexpectedToken {
+                    expected: "'OPENQASM'".to_string(),
+                    found: format!("'{}'", keyword),
+                },
+                line,
+                column,
+            ));
+        }
+        let version = self.next("a version number")?;
+        let supported = match version.kind {
+            TokenKind::Real(v) => v == 2.0,
+            TokenKind::Int(v) => v == 2,
+            _ => return Err(Self::unexpected(&version, "a version number")),
+        };
+        if !supported {
+            let text = match version.kind {
+                TokenKind::Real(v) => v.to_string(),
+                TokenKind::Int(v) => v.to_string(),
+                _ => unreachable!(),
+            };
+            return Err(QasmError::new(
+                QasmErrorKind::UnsupportedVersion(text),
+                version.line,
+                version.column,
+            ));
+        }
+        self.expect(TokenKind::Semicolon)?;
+
+        while self.peek().is_some() {
+            self.parse_statement()?;
+        }
+        Ok(())
+    }
+
+    fn parse_statement(&mut self) -> Result<(), QasmError> {
+        let (keyword, line, column) = self.expect_ident()?;
+        match keyword.as_str() {
+            "include" => {
+                let token = self.next("a file name")?;
+                match &token.kind {
+                    TokenKind::Str(file) if file == "qelib1.inc" => self.qelib1 = true,
+                    TokenKind::Str(file) => {
+                        return Err(QasmError::new(
+                            QasmErrorKind::UnknownInclude(file.clone()),
+                            token.line,
+                            token.column,
+                        ))
+                    }
+                    _ => return Err(Self::unexpected(&token, "a file name")),
+                }
+                self.expect(TokenKind::Semicolon)?;
+            }
+            "qreg" | "creg" => self.parse_register(keyword == "qreg")?,
+            "gate" => self.parse_gate_definition()?,
+            "if" => self.parse_if(line, column)?,
+            "opaque" => {
+                return Err(QasmError::new(
+                    QasmErrorKind::Unsupported(format!("'{}'", keyword)),
+                    line,
+                    column,
+                ))
+            }
+            _ => {
+                let instructions = self.parse_operation(keyword, line, column)?;
+                self.instructions.extend(
+                    instructions
+                        .into_iter()
+                        .map(|instruction| (instruction, line, column)),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_register(&mut self, quantum: bool) -> Result<(), QasmError> {
+        let (name, line, column) = self.expect_ident()?;
+        self.expect(TokenKind::LBracket)?;
+        let size = self.expect_int()? as usize;
+        self.expect(TokenKind::RBracket)?;
+        self.expect(TokenKind::Semicolon)?;
+
+        let taken = self.qregs.iter().chain(&self.cregs).any(|r| r.name == name);
+        if taken {
+            return Err(QasmError::new(
+                QasmErrorKind::DuplicateDefinition(name),
+                line,
+                column,
+            ));
+        }
+        let registers = if quantum {
+            &mut self.qregs
+        } else {
+            &mut self.cregs
+        };
+        let offset: usize = registers.iter().map(|r| r.size).sum();
+        if offset.checked_add(size).is_none() {
+            return Err(QasmError::new(
+                QasmErrorKind::InvalidCircuit(format!("register '{}' is too large", name)),
+                line,
+                column,
+            ));
+        }
+        registers.push(Register {
+            name,
+            offset,
+            size,
+            line,
+            column,
+        });
+        Ok(())
+    }
+
+    fn parse_if(&mut self, line: usize, column: usize) -> Result<(), QasmError> {
+        self.expect(TokenKind::LParen)?;
+        let (name, reg_line, reg_column) = self.expect_ident()?;
+        self.expect(TokenKind::EqEq)?;
+        let value = self.expect_int()?;
+        self.expect(TokenKind::RParen)?;
+
+        let register = self.cregs.iter().find(|r| r.name == name).ok_or_else(|| {
+            QasmError::new(QasmErrorKind::UndefinedRegister(name), reg_line, reg_column)
+        })?;
+        let clbits: Vec<usize> = (register.offset..register.offset + register.size).collect();
+
+        let (keyword, op_line, op_column) = self.expect_ident()?;
+        if keyword == "if" || keyword == "gate" || keyword == "qreg" || keyword == "creg" {
+            return Err(Self::unexpected(
+                &Token {
+                    kind: TokenKind::Ident(keyword),
+                    line: op_line,
+                    column: op_column,
+                },
+                "a quantum operation",
+            ));
+        }
+        for instruction in self.parse_operation(keyword, op_line, op_column)? {
+            let conditional = Instruction::Conditional {
+                clbits: clbits.clone(),
+                value,
+                instruction: Box::new(instruction),
+            };
+            self.instructions.push((conditional, line, column));
+        }
+        Ok(())
+    }
+
+    /// Parses `measure`, `barrier` or a gate call and returns its instructions
+    fn parse_operation(
+        &mut self,
+        keyword: String,
+        line: usize,
+        column: usize,
+    ) -> Result<Vec<Instruction>, QasmError> {
+        match keyword.as_str() {
+            "measure" => {
+                let qubit = self.parse_argument()?;
+                self.expect(TokenKind::Arrow)?;
+                let clbit = self.parse_argument()?;
+                self.expect(TokenKind::Semicolon)?;
+
+                let qubits = self.resolve(&qubit, true)?;
+                let clbits = self.resolve(&clbit, false)?;
+                if qubits.len() != clbits.len() {
+                    return Err(QasmError::new(
+                        QasmErrorKind::RegisterSizeMismatch,
+                        line,
+                        column,
+                    ));
+                }
+                Ok(qubits
+                    .into_iter()
+                    .zip(clbits)
+                    .map(|(qubit, clbit)| Instruction::Measure { qubit, clbit })
+                    .collect())
+            }
+            "reset" => {
+                let qubit = self.parse_argument()?;
+                self.expect(TokenKind::Semicolon)?;
+                Ok(self
+                    .resolve(&qubit, true)?
+                    .into_iter()
+                    .map(|qubit| Instruction::Reset { qubit })
+                    .collect())
+            }
+            "barrier" => {
+                let args = self.parse_argument_list()?;
+                self.expect(TokenKind::Semicolon)?;
+                let mut qubits = Vec::new();
+                for arg in &args {
+                    for q in self.resolve(arg, true)? {
+                        if !qubits.contains(&q) {
+                            qubits.push(q);
+                        }
+                    }
+                }
+                Ok(vec![Instruction::Barrier { qubits }])
+            }
+            _ => {
+                let params = if self.peek_is(&TokenKind::LParen) {
+                    self.parse_expression_list()?
+                } else {
+                    Vec::new()
+                };
+                let args = self.parse_argument_list()?;
+                self.expect(TokenKind::Semicolon)?;
+
+                let env = HashMap::from([("pi".to_string(), PI)]);
+                let values = params
+                    .iter()
+                    .map(|p| p.eval(&env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let resolved = args
+                    .iter()
+                    .map(|a| self.resolve(a, true))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Broadcast register arguments: every full register must have
+                // the same size and single qubits are repeated
+                let width = resolved.iter().map(Vec::len).max().unwrap_or(1);
+                let broadcast = args.iter().any(|a| a.index.is_none());
+                if resolved.iter().any(|r| r.len() != 1 && r.len() != width) {
+                    return Err(QasmError::new(
+                        QasmErrorKind::RegisterSizeMismatch,
+                        line,
+                        column,
+                    ));
+                }
+                let rounds = if broadcast { width } else { 1 };
+
+                let mut out = Vec::new();
+                for round in 0..rounds {
+                    let qubits: Vec<usize> = resolved
+                        .iter()
+                        .map(|r| if r.len() == 1 { r[0] } else { r[round] })
+                        .collect();
+                    self.expand_gate(&keyword, &values, &qubits, line, column, &mut out)?;
+                }
+                Ok(out)
+            }
+        }
+    }
+
+    fn parse_gate_definition(&mut self) -> Result<(), QasmError> {
+        let (name, line, column) = self.expect_ident()?;
+        if self.gates.contains_key(&name) || self.gate_signature(&name).is_some() {
+            return Err(QasmError::new(
+                QasmErrorKind::DuplicateDefinition(name),
+                line,
+                column,
+            ));
+        }
+
+        let mut params = Vec::new();
+        if self.peek_is(&TokenKind::LParen) {
+            self.expect(TokenKind::LParen)?;
+            if !self.peek_is(&TokenKind::RParen) {
+                loop {
+                    params.push(self.expect_ident()?.0);
+                    if !self.peek_is(&TokenKind::Comma) {
+                        break;
+                    }
+                    self.expect(TokenKind::Comma)?;
+                }
+            }
+            self.expect(TokenKind::RParen)?;
+        }
+
+        let mut qubits = vec![self.expect_ident()?.0];
+        while self.peek_is(&TokenKind::Comma) {
+            self.expect(TokenKind::Comma)?;
+            qubits.push(self.expect_ident()?.0);
+        }
+
+        self.expect(TokenKind::LBrace)?;
+        let mut body = Vec::new();
+        while !self.peek_is(&TokenKind::RBrace) {
+            let (gate, gate_line, gate_column) = self.expect_ident()?;
+            // Bodies may only call gates defined before this one, which also
+            // rules out recursion
+            if gate != "barrier" && self.gate_signature(&gate).is_none() {
+                return Err(QasmError::new(
+                    QasmErrorKind::UnknownGate(gate),
+                    gate_line,
+                    gate_column,
+                ));
+            }
+            let call_params = if gate != "barrier" && self.peek_is(&TokenKind::LParen) {
+                self.parse_expression_list()?
+            } else {
+                Vec::new()
+            };
+
+            let mut args = Vec::new();
+            loop {
+                let (arg, arg_line, arg_column) = self.expect_ident()?;
+                if !qubits.contains(&arg) {
+                    return Err(QasmError::new(
+                        QasmErrorKind::UndefinedIdentifier(arg),
+                        arg_line,
+                        arg_column,
+                    ));
+                }
+                args.push(arg);
+                if !self.peek_is(&TokenKind::Comma) {
+                    break;
+                }
+                self.expect(TokenKind::Comma)?;
+            }
+            self.expect(TokenKind::Semicolon)?;
+
+            if gate == "barrier" {
+                continue;
+            }
+            for param in &call_params {
+                Self::check_variables(param, &params)?;
+            }
+            body.push(GateCall {
+                name: gate,
+                params: call_params,
+                args,
+                line: gate_line,
+                column: gate_column,
+            });
+        }
+        self.expect(TokenKind::RBrace)?;
+
+        self.gates.insert(
+            name,
+            GateDefinition {
+                params,
+                qubits,
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    fn check_variables(expr: &Expr, params: &[String]) -> Result<(), QasmError> {
+        match expr {
+            Expr::Variable { name, line, column } => {
+                if name != "pi" && !params.contains(name) {
+                    return Err(QasmError::new(
+                        QasmErrorKind::UndefinedIdentifier(name.clone()),
+                        *line,
+                        *column,
+                    ));
+                }
+                Ok(())
+            }
+            Expr::Negate(inner) | Expr::Function(_, inner) => Self::check_variables(inner, params),
+            Expr::Binary(_, lhs, rhs) => {
+                Self::check_variables(lhs, params)?;
+                Self::check_variables(rhs, params)
+            }
+            Expr::Number(_) => Ok(()),
+        }
+    }
+
+    // ----- arguments -----
+
+    fn parse_argument(&mut self) -> Result<Argument, QasmError> {
+        let (register, line, column) = self.expect_ident()?;
+        let index = if self.peek_is(&TokenKind::LBracket) {
+            self.expect(TokenKind::LBracket)?;
+            let index = self.expect_int()? as usize;
+            self.expect(TokenKind::RBracket)?;
+            Some(index)
+        } else {
+            None
+        };
+        Ok(Argument {
+            register,
+            index,
+            line,
+            column,
+        })
+    }
+
+    fn parse_argument_list(&mut self) -> Result<Vec<Argument>, QasmError> {
+        let mut args = vec![self.parse_argument()?];
+        while self.peek_is(&TokenKind::Comma) {
+            self.expect(TokenKind::Comma)?;
+            args.push(self.parse_argument()?);
+        }
+        Ok(args)
+    }
+
+    fn resolve(&self, arg: &Argument, quantum: bool) -> Result<Vec<usize>, QasmError> {
+        let registers = if quantum { &self.qregs } else { &self.cregs };
+        let register = registers
+            .iter()
+            .find(|r| r.name == arg.register)
+            .ok_or_else(|| {
+                QasmError::new(
+                    QasmErrorKind::UndefinedRegister(arg.register.clone()),
+                    arg.line,
+                    arg.column,
+                )
+            })?;
+        match arg.index {
+            Some(index) if index >= register.size => Err(QasmError::new(
+                QasmErrorKind::IndexOutOfRange {
+                    register: register.name.clone(),
+                    index,
+                    size: register.size,
+                },
+                arg.line,
+                arg.column,
+            )),
+            Some(index) => Ok(vec![register.offset + index]),
+            None => Ok((register.offset..register.offset + register.size).collect()),
+        }
+    }
+
+    // ----- expressions -----
+
+    fn parse_expression_list(&mut self) -> Result<Vec<Expr>, QasmError> {
+        self.expect(TokenKind::LParen)?;
+        let mut exprs = Vec::new();
+        if !self.peek_is(&TokenKind::RParen) {
+            exprs.push(self.parse_expression()?);
+            while self.peek_is(&TokenKind::Comma) {
+                self.expect(TokenKind::Comma)?;
+                exprs.push(self.parse_expression()?);
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(exprs)
+    }
+
+    fn parse_expression(&mut self) -> Result<Expr, QasmError> {
+        let mut lhs = self.parse_term()?;
+        while self.peek_is(&TokenKind::Plus) || self.peek_is(&TokenKind::Minus) {
+            let op = self.next("an operator")?.kind;
+            let rhs = self.parse_term()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, QasmError> {
+        let mut lhs = self.parse_power()?;
+        while self.peek_is(&TokenKind::Star) || self.peek_is(&TokenKind::Slash) {
+            let op = self.next("an operator")?.kind;
+            let rhs = self.parse_power()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_power(&mut self) -> Result<Expr, QasmError> {
+        let base = self.parse_unary()?;
+        if self.peek_is(&TokenKind::Caret) {
+            self.expect(TokenKind::Caret)?;
+            let exponent = self.parse_power()?;
+            return Ok(Expr::Binary(
+                TokenKind::Caret,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QasmError> {
+        if self.peek_is(&TokenKind::Minus) {
+            self.expect(TokenKind::Minus)?;
+            return Ok(Expr::Negate(Box::new(self.parse_unary()?)));
+        }
+        if self.peek_is(&TokenKind::Plus) {
+            self.expect(TokenKind::Plus)?;
+            return self.parse_unary();
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QasmError> {
+        let token = self.next("an expression")?;
+        match token.kind {
+            TokenKind::Int(value) => Ok(Expr::Number(value as f64)),
+            TokenKind::Real(value) => Ok(Expr::Number(value)),
+            TokenKind::LParen => {
+                let inner = self.parse_expression()?;
+                self.expect(TokenKind::RParen)?;
+                Ok(inner)
+            }
+            TokenKind::Ident(name) => {
+                let is_function =
+                    matches!(name.as_str(), "sin" | "cos" | "tan" | "exp" | "ln" | "sqrt");
+                if is_function && self.peek_is(&TokenKind::LParen) {
+                    self.expect(TokenKind::LParen)?;
+                    let arg = self.parse_expression()?;
+                    self.expect(TokenKind::RParen)?;
+                    return Ok(Expr::Function(name, Box::new(arg)));
+                }
+                Ok(Expr::Variable {
+                    name,
+                    line: token.line,
+                    column: token.column,
+                })
+            }
+            _ => Err(Self::unexpected(&token, "an expression")),
+        }
+    }
+
+    // ----- gate expansion -----
+
+    /// Returns (parameters, qubits) for a gate visible at this point
+    fn gate_signature(&self, name: &str) -> Option<(usize, usize)> {
+        if let Some(def) = self.gates.get(name) {
+            return Some((def.params.len(), def.qubits.len()));
+        }
+        let qelib = if self.qelib1 { QELIB1_GATES } else { &[] };
+        BUILTIN_GATES
+            .iter()
+            .chain(qelib)
+            .find(|(gate, _, _)| *gate == name)
+            .map(|&(_, params, qubits)| (params, qubits))
+    }
+
+    fn expand_gate(
+        &self,
+        name: &str,
+        params: &[f64],
+        qubits: &[usize],
+        line: usize,
+        column: usize,
+        out: &mut Vec<Instruction>,
+    ) -> Result<(), QasmError> {
+        let (n_params, n_qubits) = self.gate_signature(name).ok_or_else(|| {
+            QasmError::new(QasmErrorKind::UnknownGate(name.to_string()), line, column)
+        })?;
+        if params.len() != n_params {
+            return Err(QasmError::new(
+                QasmErrorKind::WrongParameterCount {
+                    gate: name.to_string(),
+                    expected: n_params,
+                    found: params.len(),
+                },
+                line,
+                column,
+            ));
+        }
+        if qubits.len() != n_qubits {
+            return Err(QasmError::new(
+                QasmErrorKind::WrongArgumentCount {
+                    gate: name.to_string(),
+                    expected: n_qubits,
+                    found: qubits.len(),
+                },
+                line,
+                column,
+            ));
+        }
+
+        if let Some(def) = self.gates.get(name) {
+            let mut env: HashMap<String, f64> = def
+                .params
+                .iter()
+                .cloned()
+                .zip(params.iter().copied())
+                .collect();
+            env.insert("pi".to_string(), PI);
+            for call in &def.body {
+                let values = call
+                    .params
+                    .iter()
+                    .map(|p| p.eval(&env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let call_qubits: Vec<usize> = call
+                    .args
+                    .iter()
+                    .map(|arg| qubits[def.qubits.iter().position(|q| q == arg).unwrap()])
+                    .collect();
+                self.expand_gate(
+                    &call.name,
+                    &values,
+                    &call_qubits,
+                    call.line,
+                    call.column,
+                    out,
+                )?;
+            }
+            return Ok(());
+        }
+
+        let gate = |gate: Gate, qubits: &[usize]| Instruction::Gate {
+            gate,
+            qubits: qubits.to_vec(),
+        };
+        let controlled = |gate: Gate| Instruction::Controlled {
+            gate,
+            control: qubits[0],
+            target: qubits[1],
+        };
+        let multi_controlled = |gate: Gate, n_controls: usize| Instruction::MultiControlled {
+            gate,
+            controls: qubits[..n_controls].to_vec(),
+            control_state: (1 << n_controls) - 1,
+            targets: qubits[n_controls..].to_vec(),
+        };
+        let angle = |i: usize| Angle::Value(params[i]);
+        let q = qubits;
+
+        match name {
+            "U" | "u" | "u3" => out.push(gate(Gate::U3(angle(0), angle(1), angle(2)), q)),
+            "u2" => out.push(gate(
+                Gate::U3(Angle::Value(PI / 2.0), angle(0), angle(1)),
+                q,
+            )),
+            "u1" | "p" => out.push(gate(Gate::PhaseShift(angle(0)), q)),
+            "id" | "u0" => {}
+            "x" => out.push(gate(Gate::X, q)),
+            "y" => out.push(gate(Gate::Y, q)),
+            "z" => out.push(gate(Gate::Z, q)),
+            "h" => out.push(gate(Gate::H, q)),
+            "s" => out.push(gate(Gate::S, q)),
+            "sdg" => out.push(gate(Gate::PhaseShift(Angle::Value(-PI / 2.0)), q)),
+            "t" => out.push(gate(Gate::T, q)),
+            "tdg" => out.push(gate(Gate::PhaseShift(Angle::Value(-PI / 4.0)), q)),
+            "sx" => out.push(gate(Gate::SX, q)),
+            "sxdg" => out.push(gate(Gate::RX(Angle::Value(-PI / 2.0)), q)),
+            "rx" => out.push(gate(Gate::RX(angle(0)), q)),
+            "ry" => out.push(gate(Gate::RY(angle(0)), q)),
+            "rz" => out.push(gate(Gate::RZ(angle(0)), q)),
+            "CX" | "cx" => out.push(gate(Gate::CNOT, q)),
+            "cz" => out.push(gate(Gate::CZ, q)),
+            "swap" => out.push(gate(Gate::Swap, q)),
+            "cy" => out.push(controlled(Gate::Y)),
+            "ch" => out.push(controlled(Gate::H)),
+            "crz" => out.push(controlled(Gate::RZ(angle(0)))),
+            "cu3" => out.push(controlled(Gate::U3(angle(0), angle(1), angle(2)))),
+            "cu1" | "cp" => out.push(gate(Gate::CPhase(angle(0)), q)),
+            "rzz" => {
+                out.push(gate(Gate::CNOT, q));
+                out.push(gate(Gate::PhaseShift(angle(0)), &q[1..]));
+                out.push(gate(Gate::CNOT, q));
+            }
+            "ccx" => out.push(multi_controlled(Gate::X, 2)),
+            "cswap" => out.push(multi_controlled(Gate::Swap, 1)),
+            "crx" => out.push(controlled(Gate::RX(angle(0)))),
+            "cry" => out.push(controlled(Gate::RY(angle(0)))),
+            "csx" => out.push(controlled(Gate::SX)),
+            "cu" => {
+                // cu3 with the phase gamma on the controlled branch
+                out.push(gate(Gate::PhaseShift(angle(3)), &q[..1]));
+                out.push(controlled(Gate::U3(angle(0), angle(1), angle(2))));
+            }
+            "rxx" => {
+                out.extend(q.iter().map(|&a| gate(Gate::H, &[a])));
+                out.push(gate(Gate::CNOT, q));
+                out.push(gate(Gate::PhaseShift(angle(0)), &q[1..]));
+                out.push(gate(Gate::CNOT, q));
+                out.extend(q.iter().map(|&a| gate(Gate::H, &[a])));
+            }
+            "c3x" => out.push(multi_controlled(Gate::X, 3)),
+            "c3sqrtx" => out.push(multi_controlled(Gate::SX, 3)),
+            "c4x" => out.push(multi_controlled(Gate::X, 4)),
+            // Toffolis up to relative phases, spelled out as in qelib1.inc
+            "rccx" | "rc3x" => {
+                let target = q[q.len() - 1];
+                let h = || gate(Gate::H, &[target]);
+                let t =
+                    |sign: f64| gate(Gate::PhaseShift(Angle::Value(sign * PI / 4.0)), &[target]);
+                let cx = |control: usize| gate(Gate::CNOT, &[q[control], target]);
+                if name == "rccx" {
+                    out.extend([
+                        h(),
+                        t(1.0),
+                        cx(1),
+                        t(-1.0),
+                        cx(0),
+                        t(1.0),
+                        cx(1),
+                        t(-1.0),
+                        h(),
+                    ]);
+                } else {
+                    out.extend([h(), t(1.0), cx(2), t(-1.0), h(), cx(0), t(1.0), cx(1)]);
+                    out.extend([t(-1.0), cx(0), t(1.0), cx(1), t(-1.0), h(), t(1.0), cx(2)]);
+                    out.extend([t(-1.0), h()]);
+                }
+            }
+            _ => unreachable!("gate_signature only accepts known gates"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::circuit::QuantumCircuit;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_parse_bell_program() {
+        let source = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[2];
+            h q[0];
+            cx q[0], q[1];
+            barrier q;
+            measure q -> c;
+        "#;
+        let circuit = parse_qasm(source).unwrap();
+        assert_eq!(circuit.n_qubits(), 2);
+        assert_eq!(circuit.n_clbits(), 2);
+        assert_eq!(circuit.len(), 5);
+        assert_eq!(
+            circuit.instructions()[4],
+            Instruction::Measure { qubit: 1, clbit: 1 }
+        );
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        let bits = backend.run(&circuit).unwrap();
+        assert_eq!(bits[0], bits[1]);
+    }
+
+    #[test]
+    fn test_registers_are_concatenated_and_broadcast() {
+        let source = "OPENQASM 2.0; include \"qelib1.inc\"; qreg a[2]; qreg b[2]; cx a, b;";
+        let circuit = parse_qasm(source).unwrap();
+        assert_eq!(circuit.n_qubits(), 4);
+        let pairs: Vec<Vec<usize>> = circuit.instructions().iter().map(|i| i.qubits()).collect();
+        assert_eq!(pairs, vec![vec![0, 2], vec![1, 3]]);
+    }
+
+    #[test]
+    fn test_parameter_expressions() {
+        let source = "OPENQASM 2.0; include \"qelib1.inc\"; qreg q[1]; rz(-pi/2 + 2*0.25) q[0];";
+        let circuit = parse_qasm(source).unwrap();
+        assert_eq!(
+            circuit.instructions()[0].gate(),
+            Some(&Gate::RZ(Angle::Value(-PI / 2.0 + 0.5)))
+        );
+    }
+
+    #[test]
+    fn test_user_gate_definition_and_ccx() {
+        let source = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            gate flip2(theta) a, b { rx(theta) a; x b; }
+            qreg q[3];
+            flip2(pi) q[0], q[1];
+            ccx q[0], q[1], q[2];
+        "#;
+        let circuit = parse_qasm(source).unwrap();
+        let mut backend = QuantumCircuit::new(3).unwrap();
+        backend.run(&circuit).unwrap();
+        assert_relative_eq!(
+            backend.get_probability(0b111).unwrap(),
+            1.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn test_remaining_qelib1_gates_match_their_definitions() {
+        // (call, parameter names, qubit names, body from qelib1.inc)
+        let gates = [
+            (
+                "crx(0.7)",
+                "lambda",
+                "a, b",
+                "u1(pi/2) b; cx a,b; u3(-lambda/2,0,0) b; cx a,b; u3(lambda/2,-pi/2,0) b;",
+            ),
+            (
+                "cry(0.7)",
+                "lambda",
+                "a, b",
+                "ry(lambda/2) b; cx a,b; ry(-lambda/2) b; cx a,b;",
+            ),
+            ("csx", "", "a, b", "h b; cu1(pi/2) a,b; h b;"),
+            (
+                "cu(0.3, 0.5, -0.2, 0.9)",
+                "theta, phi, lambda, gamma",
+                "c, t",
+                "p(gamma) c; p((lambda+phi)/2) c; p((lambda-phi)/2) t; cx c,t; \
+                 u(-theta/2,0,-(phi+lambda)/2) t; cx c,t; u(theta/2,phi,0) t;",
+            ),
+            (
+                "rxx(0.7)",
+                "theta",
+                "a, b",
+                "u3(pi/2, theta, 0) a; h b; cx a,b; u1(-theta) b; cx a,b; h b; \
+                 u2(-pi, pi-theta) a;",
+            ),
+            (
+                "c3sqrtx",
+                "",
+                "a, b, c, d",
+                "h d; cu1(pi/8) a,d; h d; cx a,b; h d; cu1(-pi/8) b,d; h d; cx a,b; \
+                 h d; cu1(pi/8) b,d; h d; cx b,c; h d; cu1(-pi/8) c,d; h d; cx a,c; \
+                 h d; cu1(pi/8) c,d; h d; cx b,c; h d; cu1(-pi/8) c,d; h d; cx a,c; \
+                 h d; cu1(pi/8) c,d; h d;",
+            ),
+        ];
+        for (call, params, args, body) in gates {
+            let n = args.split(',').count();
+            let qubits: Vec<String> = (0..n).map(|i| format!("q[{}]", i)).collect();
+            let (name, values) = call.split_at(call.find('(').unwrap_or(call.len()));
+            let header = "OPENQASM 2.0;\ninclude \"qelib1.inc\";\n";
+            let direct = format!("{}qreg q[{}];\n{} {};", header, n, call, qubits.join(", "));
+            let params = if params.is_empty() {
+                String::new()
+            } else {
+                format!("({})", params)
+            };
+            let reference = format!(
+                "{}gate reference{} {} {{ {} }}\nqreg q[{}];\nreference{} {};",
+                header,
+                params,
+                args,
+                body,
+                n,
+                values,
+                qubits.join(", ")
+            );
+            let direct = parse_qasm(&direct).unwrap();
+            let reference = parse_qasm(&reference).unwrap();
+            assert!(
+                crate::ir::equivalent(&direct, &reference, 1e-9).unwrap(),
+                "{} differs from its qelib1.inc definition",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_qelib1_toffoli_variants() {
+        let header = "OPENQASM 2.0;\ninclude \"qelib1.inc\";\n";
+        let program = |body: &str| parse_qasm(&format!("{}{}", header, body)).unwrap();
+        let expected = |n: usize| {
+            let mut circuit = Circuit::new(n).unwrap();
+            let controls: Vec<usize> = (0..n - 1).collect();
+            circuit
+                .multi_controlled(Gate::X, &controls, &[n - 1])
+                .unwrap();
+            circuit.unitary().unwrap()
+        };
+        // c3x and c4x are exact; c3sqrtx applied twice is c3x
+        let c3x = program("qreg q[4];\nc3x q[0], q[1], q[2], q[3];");
+        assert!((c3x.unitary().unwrap() - expected(4)).norm() < 1e-12);
+        let c4x = program("qreg q[5];\nc4x q[0], q[1], q[2], q[3], q[4];");
+        assert!((c4x.unitary().unwrap() - expected(5)).norm() < 1e-12);
+        let twice =
+            program("qreg q[4];\nc3sqrtx q[0], q[1], q[2], q[3];\nc3sqrtx q[0], q[1], q[2], q[3];");
+        assert!((twice.unitary().unwrap() - expected(4)).norm() < 1e-12);
+
+        // rccx and rc3x permute basis states like Toffolis, up to phases
+        for (body, n) in [
+            ("qreg q[3];\nrccx q[0], q[1], q[2];", 3),
+            ("qreg q[4];\nrc3x q[0], q[1], q[2], q[3];", 4),
+        ] {
+            let unitary = program(body).unitary().unwrap();
+            let magnitudes = unitary.map(|x| x.norm_sqr());
+            assert!((magnitudes - expected(n).map(|x| x.norm_sqr())).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let source = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[1];
+            creg d[1];
+            x q[0];
+            measure q[0] -> c[0];
+            if (c == 1) x q[1];
+            measure q[1] -> d[0];
+        "#;
+        let circuit = parse_qasm(source).unwrap();
+        assert!(matches!(
+            circuit.instructions()[2],
+            Instruction::Conditional { value: 1, .. }
+        ));
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert_eq!(backend.run(&circuit).unwrap(), vec![true, true]);
+    }
+
+    #[test]
+    fn test_gate_bodies_only_call_earlier_gates() {
+        let err = parse_qasm("OPENQASM 2.0;\nqreg q[1];\ngate g a { g a; }\ng q[0];").unwrap_err();
+        assert_eq!(err.kind, QasmErrorKind::UnknownGate("g".to_string()));
+        assert_eq!((err.line, err.column), (3, 12));
+
+        let source = "OPENQASM 2.0;\nqreg q[1];\ngate f a { g a; }\ngate g a { U(0,0,0) a; }";
+        let err = parse_qasm(source).unwrap_err();
+        assert_eq!(err.kind, QasmErrorKind::UnknownGate("g".to_string()));
+        assert_eq!((err.line, err.column), (3, 12));
+
+        let source =
+            "OPENQASM 2.0;\nqreg q[1];\ngate g a { U(0,0,0) a; }\ngate f a { g a; }\nf q[0];";
+        assert_eq!(parse_qasm(source).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_errors_report_position() {
+        let err = parse_qasm("OPENQASM 2.0;\nqreg q[1];\nh q[0];").unwrap_err();
+        assert_eq!(err.kind, QasmErrorKind::UnknownGate("h".to_string()));
+        assert_eq!((err.line, err.column), (3, 1));
+
+        let err = parse_qasm("OPENQASM 2.0;\nqreg q[1];\nU(0,0,0) q[3];").unwrap_err();
+        assert!(matches!(
+            err.kind,
+            QasmErrorKind::IndexOutOfRange { index: 3, .. }
+        ));
+        assert_eq!((err.line, err.column), (3, 10));
+
+        let err = parse_qasm("OPENQASM 3.0;").unwrap_err();
+        assert_eq!(err.kind, QasmErrorKind::UnsupportedVersion("3".to_string()));
+
+        let err = parse_qasm("OPENQASM 2.0;\nqreg q[1];\n  opaque g q;").unwrap_err();
+        assert_eq!(err.kind, QasmErrorKind::Unsupported("'opaque'".to_string()));
+        assert_eq!((err.line, err.column), (3, 3));
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 3: 'opaque' is not supported"
+        );
+    }
+
+    #[test]
+    fn test_register_errors_point_at_the_declaration() {
+        let err = parse_qasm("OPENQASM 2.0;\nqreg q[1];\ncreg c[0];\nU(0,0,0) q[0];").unwrap_err();
+        assert!(matches!(err.kind, QasmErrorKind::InvalidCircuit(_)));
+        assert_eq!((err.line, err.column), (3, 6));
+
+        let source = "OPENQASM 2.0;\nqreg a[18446744073709551615];\n qreg b[1];";
+        let err = parse_qasm(source).unwrap_err();
+        assert!(matches!(err.kind, QasmErrorKind::InvalidCircuit(_)));
+        assert_eq!((err.line, err.column), (3, 7));
+    }
+
+    #[test]
+    fn test_missing_semicolon() {
+        let err = parse_qasm("OPENQASM 2.0;\nqreg q[1]\nqreg r[1];").unwrap_err();
+        assert!(matches!(err.kind, QasmErrorKind::UnexpectedToken { .. }));
+        assert_eq!((err.line, err.column), (3, 1));
+    }
+
+    #[test]
+    fn test_reset_and_named_registers() {
+        let source = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg m[1];
+            creg out[1];
+            x q;
+            measure q[0] -> m[0];
+            if (m == 1) reset q[1];
+            reset q[0];
+            measure q[1] -> out[0];
+        "#;
+        let circuit = parse_qasm(source).unwrap();
+        let names: Vec<&str> = circuit.registers().iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["m", "out"]);
+        assert_eq!(circuit.instructions()[4], Instruction::Reset { qubit: 0 });
+
+        let mut backend = QuantumCircuit::new(2).unwrap();
+        assert_eq!(backend.run(&circuit).unwrap(), vec![true, false]);
+        assert_relative_eq!(backend.get_probability(0).unwrap(), 1.0);
+    }
+}
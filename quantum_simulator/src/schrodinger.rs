@@ -49,7 +49,6 @@ error handling and test cases to verify physical correctness.
 */
 
 use nalgebra::{DMatrix, DVector};
-use std::f64::consts::PI;
 
 #[derive(Debug)]
 pub enum SchrodingerError {
@@ -62,7 +61,6 @@ pub struct SchrodingerSolver {
     grid_points: usize,
     dx: f64,
     potential: Option<Vec<f64>>,
-    length: f64, // Adding box length parameter
 }
 
 impl SchrodingerSolver {
@@ -83,7 +81,6 @@ impl SchrodingerSolver {
             grid_points,
             dx,
             potential: None,
-            length: (grid_points as f64) * dx,
         })
     }
 
@@ -192,6 +189,7 @@ impl SchrodingerSolver {
 mod tests {
     use super::*;
     use approx::assert_relative_eq;
+    use std::f64::consts::PI;
 
     #[test]
     fn test_particle_in_box() {
@@ -0,0 +1,670 @@
+/*
+This file implements a stabilizer (Clifford tableau) simulator that handles
+hundreds or thousands of qubits.
+
+Key concepts:
+1. Stabilizer States:
+   - A state is stabilized by an operator S when S|ψ⟩ = |ψ⟩
+   - States reachable from |00...0⟩ with Clifford gates are fixed by n
+     commuting Pauli strings, each ±1 times a product of I, X, Y and Z
+   - Storing those n strings takes O(n²) bits instead of 2^n amplitudes
+   - Learn more: https://en.wikipedia.org/wiki/Stabilizer_code
+
+2. Gottesman–Knill Theorem:
+   - Clifford gates map Pauli strings to Pauli strings (U P U† = ±Q), so a
+     gate only rewrites the tableau rows, in O(n) time
+   - H, S, CNOT and the Paulis generate the group; CZ, SWAP, iSWAP, SX and
+     rotations by multiples of π/2 are Clifford as well
+   - Non-Clifford gates such as T have no tableau update and are rejected
+   - Reference: https://arxiv.org/abs/quant-ph/9807006
+
+3. Tableau Layout:
+   - Following Aaronson and Gottesman, rows 0..n hold destabilizers, rows
+     n..2n hold stabilizers and row 2n is scratch space
+   - Each row stores one bit of X and one bit of Z per qubit (both set for
+     Y), packed 64 to a word, plus a sign bit
+   - Reference: https://arxiv.org/abs/quant-ph/0406196
+
+4. Measurement and Reset:
+   - Measuring Z on a qubit is random when some stabilizer anticommutes with
+     it, and otherwise determined by a product of stabilizers, in O(n²) time
+   - Reset measures the qubit and flips it back to |0⟩ on outcome 1
+
+Qubit indices follow the same convention as `QuantumCircuit`: qubit k is bit
+k of the basis-state index.
+*/
+
+use crate::backend::Backend;
+use crate::error::QuantumError;
+use crate::gates::{check_size, controlled_matrix, MultiQubitGate, QuantumGate, XGate};
+use crate::ir::Instruction;
+use crate::observable::{Pauli, PauliString};
+use nalgebra::{Complex, DMatrix};
+use rand::{Rng, RngCore};
+
+/// Tolerance when matching U P U† against a Pauli string
+const CLIFFORD_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone)]
+pub struct StabilizerCircuit {
+    n_qubits: usize,
+    /// 64-bit words per row
+    words: usize,
+    /// X bits of each row, `words` words per row
+    x: Vec<u64>,
+    /// Z bits of each row, `words` words per row
+    z: Vec<u64>,
+    /// Sign of each row, true for −
+    r: Vec<bool>,
+}
+
+impl StabilizerCircuit {
+    /// Creates a tableau for `n_qubits` qubits in the |00...0⟩ state
+    pub fn new(n_qubits: usize) -> Self {
+        if n_qubits == 0 {
+            panic!("Number of qubits must be greater than 0");
+        }
+
+        let words = n_qubits.div_ceil(64);
+        let rows = 2 * n_qubits + 1;
+        let mut tableau = StabilizerCircuit {
+            n_qubits,
+            words,
+            x: vec![0; rows * words],
+            z: vec![0; rows * words],
+            r: vec![false; rows],
+        };
+        tableau.reset();
+        tableau
+    }
+
+    fn get_x(&self, row: usize, qubit: usize) -> bool {
+        self.x[row * self.words + qubit / 64] >> (qubit % 64) & 1 == 1
+    }
+
+    fn get_z(&self, row: usize, qubit: usize) -> bool {
+        self.z[row * self.words + qubit / 64] >> (qubit % 64) & 1 == 1
+    }
+
+    fn set_bits(&mut self, row: usize, qubit: usize, x: bool, z: bool) {
+        let index = row * self.words + qubit / 64;
+        let bit = 1 << (qubit % 64);
+        self.x[index] = (self.x[index] & !bit) | if x { bit } else { 0 };
+        self.z[index] = (self.z[index] & !bit) | if z { bit } else { 0 };
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        let range = row * self.words..(row + 1) * self.words;
+        self.x[range.clone()].fill(0);
+        self.z[range].fill(0);
+        self.r[row] = false;
+    }
+
+    fn copy_row(&mut self, from: usize, to: usize) {
+        let words = self.words;
+        self.x
+            .copy_within(from * words..(from + 1) * words, to * words);
+        self.z
+            .copy_within(from * words..(from + 1) * words, to * words);
+        self.r[to] = self.r[from];
+    }
+
+    /// Replaces row h by the product of rows i and h, tracking the sign
+    ///
+    /// The phase exponent of i^k gained per qubit is Aaronson and Gottesman's
+    /// g function, summed over whole words with population counts.
+    fn rowsum(&mut self, h: usize, i: usize) {
+        let mut sum = 2 * (self.r[h] as i64 + self.r[i] as i64);
+        for w in 0..self.words {
+            let (x1, z1) = (self.x[i * self.words + w], self.z[i * self.words + w]);
+            let (x2, z2) = (self.x[h * self.words + w], self.z[h * self.words + w]);
+            // Cyclic products XY, YZ and ZX gain +i; the reverse orders gain −i
+            let plus = (x1 & z1 & z2 & !x2) | (x1 & !z1 & x2 & z2) | (!x1 & z1 & x2 & !z2);
+            let minus = (x1 & z1 & x2 & !z2) | (x1 & !z1 & z2 & !x2) | (!x1 & z1 & x2 & z2);
+            sum += plus.count_ones() as i64 - minus.count_ones() as i64;
+            self.x[h * self.words + w] ^= x1;
+            self.z[h * self.words + w] ^= z1;
+        }
+        self.r[h] = sum.rem_euclid(4) == 2;
+    }
+
+    /// Checks that the targets are in range and distinct
+    fn check_targets(&self, targets: &[usize]) -> Result<(), QuantumError> {
+        for (i, &target) in targets.iter().enumerate() {
+            if target >= self.n_qubits {
+                return Err(QuantumError::QubitOutOfRange {
+                    qubit: target,
+                    n_qubits: self.n_qubits,
+                });
+            }
+            if targets[..i].contains(&target) {
+                return Err(QuantumError::DuplicateQubit(target));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the Clifford unitary `matrix` to `targets` (targets[0] is the
+    /// most significant bit of its index, as in `MultiQubitGate`)
+    fn apply_clifford(
+        &mut self,
+        matrix: &DMatrix<Complex<f64>>,
+        targets: &[usize],
+        name: &str,
+    ) -> Result<(), QuantumError> {
+        self.check_targets(targets)?;
+        check_size(matrix, targets.len(), name)?;
+
+        let table = conjugation_table(matrix).ok_or_else(|| {
+            QuantumError::Unsupported(format!(
+                "non-Clifford {} gate on the stabilizer simulator",
+                name
+            ))
+        })?;
+        for row in 0..2 * self.n_qubits {
+            let local = targets.iter().enumerate().fold(0, |acc, (j, &q)| {
+                acc | (self.get_x(row, q) as usize) << (2 * j)
+                    | (self.get_z(row, q) as usize) << (2 * j + 1)
+            });
+            let (image, negative) = table[local];
+            for (j, &q) in targets.iter().enumerate() {
+                self.set_bits(
+                    row,
+                    q,
+                    image >> (2 * j) & 1 == 1,
+                    image >> (2 * j + 1) & 1 == 1,
+                );
+            }
+            self.r[row] ^= negative;
+        }
+        Ok(())
+    }
+
+    /// Applies a single-qubit Clifford gate to the specified target qubit
+    pub fn apply_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        let matrix = DMatrix::from_iterator(2, 2, gate.matrix().iter().cloned());
+        self.apply_clifford(&matrix, &[target], gate.name())
+    }
+
+    /// Applies a single-qubit gate to `target` when `control` is |1⟩
+    ///
+    /// Only controlled Paulis (CNOT, CY, CZ) are Clifford.
+    pub fn apply_controlled_gate<G: QuantumGate>(
+        &mut self,
+        gate: G,
+        control: usize,
+        target: usize,
+    ) -> Result<(), QuantumError> {
+        let matrix = DMatrix::from_iterator(2, 2, gate.matrix().iter().cloned());
+        self.apply_clifford(
+            &controlled_matrix(&matrix, 1, 1),
+            &[control, target],
+            gate.name(),
+        )
+    }
+
+    /// Applies a k-qubit Clifford gate to the listed target qubits
+    pub fn apply_multi_qubit_gate<G: MultiQubitGate>(
+        &mut self,
+        gate: G,
+        targets: &[usize],
+    ) -> Result<(), QuantumError> {
+        if targets.len() != gate.n_qubits() {
+            return Err(QuantumError::DimensionMismatch {
+                what: format!("{} gate targets", gate.name()),
+                expected: gate.n_qubits(),
+                found: targets.len(),
+            });
+        }
+        self.apply_clifford(&gate.matrix(), targets, gate.name())
+    }
+
+    /// Measures one qubit in the Z basis, letting `choose` pick the outcome
+    /// when it is random
+    ///
+    /// Returns the outcome and whether it was random.
+    fn measure_z(&mut self, target: usize, choose: impl FnOnce() -> bool) -> (bool, bool) {
+        let n = self.n_qubits;
+        let anticommuting = (n..2 * n).find(|&p| self.get_x(p, target));
+
+        match anticommuting {
+            Some(p) => {
+                for row in (0..2 * n).filter(|&row| row != p) {
+                    if self.get_x(row, target) {
+                        self.rowsum(row, p);
+                    }
+                }
+                // The old stabilizer becomes the destabilizer of ±Z_target
+                self.copy_row(p, p - n);
+                self.clear_row(p);
+                let outcome = choose();
+                self.set_bits(p, target, false, true);
+                self.r[p] = outcome;
+                (outcome, true)
+            }
+            None => {
+                // Z_target is ± the product of the stabilizers whose
+                // destabilizers anticommute with it
+                let scratch = 2 * n;
+                self.clear_row(scratch);
+                for i in 0..n {
+                    if self.get_x(i, target) {
+                        self.rowsum(scratch, i + n);
+                    }
+                }
+                (self.r[scratch], false)
+            }
+        }
+    }
+
+    /// Measures the specified qubit and updates the tableau according to the outcome
+    pub fn measure(&mut self, target: usize) -> Result<bool, QuantumError> {
+        self.measure_with(target, &mut rand::thread_rng())
+    }
+
+    /// Measures the specified qubit, drawing a random outcome from `rng`
+    pub fn measure_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<bool, QuantumError> {
+        self.check_targets(&[target])?;
+        Ok(self.measure_z(target, || rng.gen()).0)
+    }
+
+    /// Resets one qubit to |0⟩
+    ///
+    /// A stabilizer state stays pure, so the qubit is measured and flipped
+    /// when the outcome is 1; the discarded outcome is drawn at random.
+    pub fn reset_qubit(&mut self, target: usize) -> Result<(), QuantumError> {
+        self.reset_qubit_with(target, &mut rand::thread_rng())
+    }
+
+    /// Like `reset_qubit`, drawing the discarded outcome from `rng`
+    pub fn reset_qubit_with<R: Rng + ?Sized>(
+        &mut self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<(), QuantumError> {
+        if self.measure_with(target, rng)? {
+            self.apply_gate(XGate, target)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the stabilizer generators as (±1, Pauli string) pairs
+    pub fn stabilizers(&self) -> Vec<(f64, PauliString)> {
+        (self.n_qubits..2 * self.n_qubits)
+            .map(|row| {
+                let sign = if self.r[row] { -1.0 } else { 1.0 };
+                (sign, self.row_to_pauli_string(row))
+            })
+            .collect()
+    }
+
+    fn row_to_pauli_string(&self, row: usize) -> PauliString {
+        let paulis: Vec<(usize, Pauli)> = (0..self.n_qubits)
+            .filter_map(|q| match (self.get_x(row, q), self.get_z(row, q)) {
+                (false, false) => None,
+                (true, false) => Some((q, Pauli::X)),
+                (true, true) => Some((q, Pauli::Y)),
+                (false, true) => Some((q, Pauli::Z)),
+            })
+            .collect();
+        PauliString::from_paulis(&paulis).expect("tableau rows list each qubit once")
+    }
+
+    /// ⟨ψ|P|ψ⟩ for a Pauli string P, which is 0 or ±1 on a stabilizer state
+    pub(crate) fn pauli_expectation(&self, string: &PauliString) -> f64 {
+        let n = self.n_qubits;
+        let scratch = 2 * n;
+        let mut target = self.clone();
+        target.clear_row(scratch);
+        for (q, pauli) in string.paulis() {
+            let (x, z) = match pauli {
+                Pauli::I => (false, false),
+                Pauli::X => (true, false),
+                Pauli::Y => (true, true),
+                Pauli::Z => (false, true),
+            };
+            target.set_bits(scratch, q, x, z);
+        }
+
+        // P anticommutes with a stabilizer, so ⟨P⟩ = 0
+        let commutes = |a: &StabilizerCircuit, row: usize| {
+            let odd = (0..a.words).fold(0, |acc, w| {
+                let (x1, z1) = (a.x[row * a.words + w], a.z[row * a.words + w]);
+                let (x2, z2) = (a.x[scratch * a.words + w], a.z[scratch * a.words + w]);
+                acc ^ ((x1 & z2) ^ (z1 & x2)).count_ones()
+            });
+            odd % 2 == 0
+        };
+        if (n..2 * n).any(|row| !commutes(&target, row)) {
+            return 0.0;
+        }
+
+        // Otherwise ±P is the product of the stabilizers whose destabilizers
+        // anticommute with it; multiplying them into P leaves ±I
+        let flips: Vec<usize> = (0..n).filter(|&i| !commutes(&target, i)).collect();
+        for i in flips {
+            target.rowsum(scratch, i + n);
+        }
+        if target.r[scratch] {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the number of qubits in the circuit
+    pub fn n_qubits(&self) -> usize {
+        self.n_qubits
+    }
+
+    /// Resets the whole register to |00...0⟩
+    pub fn reset(&mut self) {
+        self.x.fill(0);
+        self.z.fill(0);
+        self.r.fill(false);
+        for q in 0..self.n_qubits {
+            self.set_bits(q, q, true, false);
+            self.set_bits(q + self.n_qubits, q, false, true);
+        }
+    }
+
+    /// Returns the probability of measuring a specific basis state
+    ///
+    /// The probability is 0 or 2^-k, where k is the number of qubits whose
+    /// outcome would be random, and takes O(n³) time to compute.
+    pub fn get_probability(&self, basis_state: usize) -> Result<f64, QuantumError> {
+        if self.n_qubits < usize::BITS as usize && basis_state >> self.n_qubits != 0 {
+            return Err(QuantumError::BasisStateOutOfRange {
+                state: basis_state,
+                dimension: 1 << self.n_qubits,
+            });
+        }
+
+        let mut copy = self.clone();
+        let mut probability = 1.0;
+        for q in 0..self.n_qubits {
+            let wanted = q < usize::BITS as usize && basis_state >> q & 1 == 1;
+            let (outcome, random) = copy.measure_z(q, || wanted);
+            if random {
+                probability /= 2.0;
+            } else if outcome != wanted {
+                return Ok(0.0);
+            }
+        }
+        Ok(probability)
+    }
+}
+
+/// Local Pauli string on k qubits with x_j at bit 2j and z_j at bit 2j + 1,
+/// as a matrix with qubit 0 as the most significant bit
+fn local_pauli(index: usize, k: usize) -> DMatrix<Complex<f64>> {
+    let zero = Complex::new(0.0, 0.0);
+    let one = Complex::new(1.0, 0.0);
+    let i = Complex::new(0.0, 1.0);
+    (0..k).fold(DMatrix::identity(1, 1), |acc, j| {
+        let single = match (index >> (2 * j) & 1, index >> (2 * j + 1) & 1) {
+            (0, 0) => DMatrix::from_row_slice(2, 2, &[one, zero, zero, one]),
+            (1, 0) => DMatrix::from_row_slice(2, 2, &[zero, one, one, zero]),
+            (1, 1) => DMatrix::from_row_slice(2, 2, &[zero, -i, i, zero]),
+            _ => DMatrix::from_row_slice(2, 2, &[one, zero, zero, -one]),
+        };
+        acc.kronecker(&single)
+    })
+}
+
+/// For each local Pauli string P, the index of Q and the sign in U P U† = ±Q
+///
+/// Returns `None` when some image is not a signed Pauli string, i.e. when U
+/// is not Clifford.
+fn conjugation_table(matrix: &DMatrix<Complex<f64>>) -> Option<Vec<(usize, bool)>> {
+    let k = matrix.nrows().trailing_zeros() as usize;
+    let dim = matrix.nrows() as f64;
+    let paulis: Vec<DMatrix<Complex<f64>>> = (0..1 << (2 * k)).map(|p| local_pauli(p, k)).collect();
+
+    paulis
+        .iter()
+        .map(|p| {
+            let image = matrix * p * matrix.adjoint();
+            // Pauli strings are orthogonal under tr(A†B), so ±Q is found by projection
+            paulis.iter().enumerate().find_map(|(q, candidate)| {
+                let overlap = (candidate * &image).trace() / dim;
+                ((overlap.re.abs() - 1.0).abs() < CLIFFORD_TOLERANCE
+                    && overlap.im.abs() < CLIFFORD_TOLERANCE)
+                    .then_some((q, overlap.re < 0.0))
+            })
+        })
+        .collect()
+}
+
+impl Backend for StabilizerCircuit {
+    fn n_qubits(&self) -> usize {
+        StabilizerCircuit::n_qubits(self)
+    }
+
+    fn reset(&mut self) {
+        StabilizerCircuit::reset(self)
+    }
+
+    fn apply(
+        &mut self,
+        instruction: &Instruction,
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<bool>, QuantumError> {
+        match instruction {
+            Instruction::Gate { gate, qubits } => {
+                self.apply_clifford(&gate.matrix()?, qubits, gate.name())?;
+                Ok(None)
+            }
+            Instruction::Controlled {
+                gate,
+                control,
+                target,
+            } => {
+                let matrix = controlled_matrix(&gate.matrix()?, 1, 1);
+                self.apply_clifford(&matrix, &[*control, *target], gate.name())?;
+                Ok(None)
+            }
+            Instruction::MultiControlled {
+                gate,
+                controls,
+                control_state,
+                ..
+            } => {
+                // Controls lead, so they are the most significant bits
+                let matrix = controlled_matrix(&gate.matrix()?, controls.len(), *control_state);
+                self.apply_clifford(&matrix, &instruction.qubits(), gate.name())?;
+                Ok(None)
+            }
+            Instruction::Measure { qubit, .. } => self.measure_with(*qubit, rng).map(Some),
+            Instruction::Reset { qubit } => {
+                self.reset_qubit_with(*qubit, rng)?;
+                Ok(None)
+            }
+            Instruction::Barrier { .. } => Ok(None),
+            Instruction::Conditional { .. } => Err(QuantumError::Unsupported(
+                "applying a conditional outside Backend::execute".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::QuantumCircuit;
+    use crate::gates::{
+        CNOTGate, CZGate, HadamardGate, ISwapGate, PhaseGate, RZGate, SXGate, TGate, YGate, ZGate,
+    };
+    use crate::ir::{Circuit, Gate};
+    use crate::observable::PauliSum;
+    use approx::assert_relative_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_bell_state() {
+        let mut tableau = StabilizerCircuit::new(2);
+        tableau.apply_gate(HadamardGate, 0).unwrap();
+        tableau.apply_multi_qubit_gate(CNOTGate, &[0, 1]).unwrap();
+
+        let stabilizers: Vec<String> = tableau
+            .stabilizers()
+            .iter()
+            .map(|(sign, s)| format!("{}{}", sign, s))
+            .collect();
+        assert_eq!(stabilizers, vec!["1X0X1", "1Z0Z1"]);
+        assert_relative_eq!(tableau.get_probability(0b00).unwrap(), 0.5);
+        assert_relative_eq!(tableau.get_probability(0b01).unwrap(), 0.0);
+        assert_relative_eq!(tableau.get_probability(0b11).unwrap(), 0.5);
+
+        for _ in 0..10 {
+            let mut copy = tableau.clone();
+            let first = copy.measure(0).unwrap();
+            assert_eq!(copy.measure(1).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn test_large_ghz_state() {
+        let n = 500;
+        let mut tableau = StabilizerCircuit::new(n);
+        tableau.apply_gate(HadamardGate, 0).unwrap();
+        for q in 1..n {
+            tableau
+                .apply_multi_qubit_gate(CNOTGate, &[q - 1, q])
+                .unwrap();
+        }
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let first = tableau.measure_with(0, &mut rng).unwrap();
+        for q in 1..n {
+            assert_eq!(tableau.measure_with(q, &mut rng).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn test_matches_state_vector() {
+        // A Clifford circuit using every supported gate kind
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .s(1)
+            .unwrap()
+            .h(1)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .sx(3)
+            .unwrap()
+            .cz(1, 3)
+            .unwrap()
+            .iswap(2, 1)
+            .unwrap()
+            .y(0)
+            .unwrap()
+            .swap(0, 3)
+            .unwrap()
+            .rz(PI / 2.0, 2)
+            .unwrap()
+            .controlled(Gate::Y, 1, 0)
+            .unwrap()
+            .multi_controlled_on(Gate::Z, &[2], 0, &[3])
+            .unwrap();
+
+        let mut tableau = StabilizerCircuit::new(4);
+        tableau.run(&circuit).unwrap();
+        let mut state = QuantumCircuit::new(4).unwrap();
+        state.run(&circuit).unwrap();
+
+        for (sign, string) in tableau.stabilizers() {
+            let observable = PauliSum::from_terms(vec![(sign, string)]);
+            assert_relative_eq!(
+                state.expectation(&observable).unwrap(),
+                1.0,
+                epsilon = 1e-10
+            );
+        }
+        for basis_state in 0..16 {
+            assert_relative_eq!(
+                tableau.get_probability(basis_state).unwrap(),
+                state.get_probability(basis_state).unwrap(),
+                epsilon = 1e-10
+            );
+        }
+        for term in ["X0", "Z1Z2", "Y0X1Z3", "X0X1X2X3", "Z0Y2"] {
+            let observable: PauliSum = term.parse().unwrap();
+            assert_relative_eq!(
+                tableau.expectation(&observable).unwrap(),
+                state.expectation(&observable).unwrap(),
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    #[test]
+    fn test_deterministic_measurement_and_reset() {
+        let mut tableau = StabilizerCircuit::new(3);
+        tableau.apply_gate(XGate, 1).unwrap();
+        tableau.apply_gate(YGate, 2).unwrap();
+        tableau.apply_gate(ZGate, 2).unwrap();
+        assert!(!tableau.measure(0).unwrap());
+        assert!(tableau.measure(1).unwrap());
+        assert!(tableau.measure(2).unwrap());
+
+        tableau.apply_gate(HadamardGate, 0).unwrap();
+        tableau.apply_multi_qubit_gate(CZGate, &[0, 1]).unwrap();
+        for q in 0..3 {
+            tableau.reset_qubit(q).unwrap();
+        }
+        assert_relative_eq!(tableau.get_probability(0).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_shots_on_backend() {
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .x(2)
+            .unwrap()
+            .measure_all()
+            .unwrap();
+        let counts = StabilizerCircuit::new(3)
+            .run_shots(&circuit, 200, 11)
+            .unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["100"] + counts["111"], 200);
+    }
+
+    #[test]
+    fn test_non_clifford_gates_are_rejected() {
+        let mut tableau = StabilizerCircuit::new(2);
+        assert!(tableau.apply_gate(TGate, 0).is_err());
+        assert!(tableau.apply_gate(RZGate::new(0.3), 0).is_err());
+        assert!(tableau.apply_controlled_gate(HadamardGate, 0, 1).is_err());
+        assert!(tableau.apply_gate(PhaseGate, 0).is_ok());
+        assert!(tableau.apply_gate(SXGate, 1).is_ok());
+        assert!(tableau.apply_multi_qubit_gate(ISwapGate, &[0, 1]).is_ok());
+        assert!(tableau.apply_gate(HadamardGate, 2).is_err());
+
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit.t(0).unwrap();
+        let error = tableau.run(&circuit).unwrap_err();
+        assert!(matches!(error, QuantumError::Unsupported(_)));
+        circuit = Circuit::new(3).unwrap();
+        circuit.ccx(0, 1, 2).unwrap();
+        assert!(StabilizerCircuit::new(3).run(&circuit).is_err());
+    }
+}
@@ -0,0 +1,1362 @@
+/*
+This file implements a transpiler that rewrites a `Circuit` for constrained
+hardware: a small set of native gates and limited qubit connectivity.
+
+Key concepts:
+1. Basis Translation:
+   - Every instruction is first broken into exact single-qubit unitaries and
+     CNOTs, with no global phase dropped, so controlled pieces stay correct
+   - Controlled gates use the A·X·B·X·C construction and multi-controlled
+     gates the recursive square-root construction of Barenco et al.
+     (https://arxiv.org/abs/quant-ph/9503016)
+   - Custom gates on several qubits are split into two-level unitaries,
+     each a multi-controlled single-qubit gate (Nielsen & Chuang §4.5.1)
+   - Single-qubit unitaries are then written in the target basis, e.g.
+     U3(θ, φ, λ) ≅ RZ(φ + π) · SX · RZ(θ + π) · SX · RZ(λ) for {RZ, SX, CX}
+
+2. Coupling Maps:
+   - Physical qubits can only interact along the edges of a coupling graph
+   - Edges are undirected: a CNOT may run either way along an edge
+
+3. Routing (SABRE):
+   - Gates whose qubits are adjacent run immediately; when none can, the
+     SWAP that most reduces the distance of the waiting gates (plus a
+     discounted look-ahead set) is inserted
+   - The initial layout is refined by routing the circuit forwards and
+     backwards, as in https://arxiv.org/abs/1809.02573
+
+4. Report:
+   - Depth and gate counts before and after, and the number of SWAPs
+*/
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::fmt;
+
+use nalgebra::{Complex, ComplexField, DMatrix, Matrix2};
+
+use crate::gates::{
+    u3_decomposition, HadamardGate, PhaseShiftGate, QuantumGate, RYGate, RZGate, XGate,
+};
+use crate::ir::{Circuit, Gate, Instruction};
+use crate::passes::{CancelInverses, MergeRotations, Pass};
+
+type Unitary2 = Matrix2<Complex<f64>>;
+
+/// Native single-qubit gates of a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingleQubitBasis {
+    /// RZ and SX, as on superconducting hardware
+    RzSx,
+    /// RZ and RY (ZYZ Euler angles)
+    RzRy,
+    /// A single general U3 gate
+    U3,
+}
+
+/// Native two-qubit gate of a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entangler {
+    CX,
+    CZ,
+}
+
+/// The gate set a transpiled circuit is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasisGates {
+    pub single_qubit: SingleQubitBasis,
+    pub entangler: Entangler,
+}
+
+impl BasisGates {
+    pub fn new(single_qubit: SingleQubitBasis, entangler: Entangler) -> Self {
+        BasisGates {
+            single_qubit,
+            entangler,
+        }
+    }
+
+    /// Gate names of the basis, matching `Gate::name`
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = match self.single_qubit {
+            SingleQubitBasis::RzSx => vec!["RZ", "SX"],
+            SingleQubitBasis::RzRy => vec!["RZ", "RY"],
+            SingleQubitBasis::U3 => vec!["U3"],
+        };
+        names.push(match self.entangler {
+            Entangler::CX => "CNOT",
+            Entangler::CZ => "CZ",
+        });
+        names
+    }
+}
+
+impl Default for BasisGates {
+    /// {RZ, SX, CX}
+    fn default() -> Self {
+        BasisGates::new(SingleQubitBasis::RzSx, Entangler::CX)
+    }
+}
+
+/// Which pairs of physical qubits can interact
+#[derive(Debug, Clone, PartialEq)]
+pub struct CouplingMap {
+    n_qubits: usize,
+    edges: Vec<(usize, usize)>,
+    distance: Vec<Vec<usize>>,
+}
+
+impl CouplingMap {
+    /// Creates a coupling map from undirected edges; the graph must be connected
+    pub fn new(n_qubits: usize, edges: &[(usize, usize)]) -> Result<Self, String> {
+        if n_qubits == 0 {
+            return Err("A coupling map needs at least one qubit".to_string());
+        }
+        let mut neighbours = vec![Vec::new(); n_qubits];
+        let mut unique = Vec::new();
+        for &(a, b) in edges {
+            if a >= n_qubits || b >= n_qubits {
+                return Err(format!(
+                    "Edge ({}, {}) is out of range for {} qubits",
+                    a, b, n_qubits
+                ));
+            }
+            if a == b {
+                return Err(format!("Qubit {} cannot be coupled to itself", a));
+            }
+            let edge = (a.min(b), a.max(b));
+            if !unique.contains(&edge) {
+                unique.push(edge);
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+            }
+        }
+
+        // Breadth-first search from every qubit
+        let mut distance = vec![vec![usize::MAX; n_qubits]; n_qubits];
+        for (source, row) in distance.iter_mut().enumerate() {
+            row[source] = 0;
+            let mut queue = VecDeque::from([source]);
+            while let Some(q) = queue.pop_front() {
+                for &next in &neighbours[q] {
+                    if row[next] == usize::MAX {
+                        row[next] = row[q] + 1;
+                        queue.push_back(next);
+                    }
+                }
+            }
+            if row.contains(&usize::MAX) {
+                return Err("The coupling map is not connected".to_string());
+            }
+        }
+
+        Ok(CouplingMap {
+            n_qubits,
+            edges: unique,
+            distance,
+        })
+    }
+
+    /// Qubits 0 - 1 - ... - (n-1)
+    pub fn line(n_qubits: usize) -> Result<Self, String> {
+        let edges: Vec<_> = (1..n_qubits).map(|q| (q - 1, q)).collect();
+        CouplingMap::new(n_qubits, &edges)
+    }
+
+    /// A line whose ends are also coupled
+    pub fn ring(n_qubits: usize) -> Result<Self, String> {
+        let mut edges: Vec<_> = (1..n_qubits).map(|q| (q - 1, q)).collect();
+        if n_qubits > 2 {
+            edges.push((n_qubits - 1, 0));
+        }
+        CouplingMap::new(n_qubits, &edges)
+    }
+
+    /// A rows × cols lattice, numbered row by row
+    pub fn grid(rows: usize, cols: usize) -> Result<Self, String> {
+        let mut edges = Vec::new();
+        for r in 0..rows {
+            for c in 0..cols {
+                let q = r * cols + c;
+                if c + 1 < cols {
+                    edges.push((q, q + 1));
+                }
+                if r + 1 < rows {
+                    edges.push((q, q + cols));
+                }
+            }
+        }
+        CouplingMap::new(rows * cols, &edges)
+    }
+
+    /// Every pair of qubits coupled
+    pub fn full(n_qubits: usize) -> Result<Self, String> {
+        let mut edges = Vec::new();
+        for a in 0..n_qubits {
+            for b in a + 1..n_qubits {
+                edges.push((a, b));
+            }
+        }
+        CouplingMap::new(n_qubits, &edges)
+    }
+
+    pub fn n_qubits(&self) -> usize {
+        self.n_qubits
+    }
+
+    /// Edges as (smaller, larger) pairs
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+
+    /// Length of the shortest path between two physical qubits
+    pub fn distance(&self, a: usize, b: usize) -> usize {
+        self.distance[a][b]
+    }
+
+    pub fn are_adjacent(&self, a: usize, b: usize) -> bool {
+        self.distance[a][b] == 1
+    }
+}
+
+/// Size and depth of a circuit before and after transpiling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranspileReport {
+    pub original_depth: usize,
+    pub depth: usize,
+    pub original_gate_count: usize,
+    pub gate_count: usize,
+    pub original_two_qubit_gates: usize,
+    pub two_qubit_gates: usize,
+    pub swaps: usize,
+}
+
+impl TranspileReport {
+    /// Transpiled depth divided by the original depth
+    pub fn depth_overhead(&self) -> f64 {
+        self.depth as f64 / self.original_depth.max(1) as f64
+    }
+
+    /// Transpiled gate count divided by the original gate count
+    pub fn gate_overhead(&self) -> f64 {
+        self.gate_count as f64 / self.original_gate_count.max(1) as f64
+    }
+}
+
+impl fmt::Display for TranspileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "depth {} -> {} ({:.2}x), gates {} -> {} ({:.2}x), two-qubit gates {} -> {}, swaps {}",
+            self.original_depth,
+            self.depth,
+            self.depth_overhead(),
+            self.original_gate_count,
+            self.gate_count,
+            self.gate_overhead(),
+            self.original_two_qubit_gates,
+            self.two_qubit_gates,
+            self.swaps
+        )
+    }
+}
+
+/// A transpiled circuit with the layouts needed to read its results
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transpiled {
+    /// The circuit on the physical qubits of the target
+    pub circuit: Circuit,
+    /// Physical qubit holding each logical qubit at the start
+    pub initial_layout: Vec<usize>,
+    /// Physical qubit holding each logical qubit at the end
+    pub final_layout: Vec<usize>,
+    pub report: TranspileReport,
+}
+
+/// Rewrites circuits into a basis gate set and, optionally, a coupling map
+#[derive(Debug, Clone)]
+pub struct Transpiler {
+    basis: BasisGates,
+    coupling_map: Option<CouplingMap>,
+    layout_iterations: usize,
+}
+
+impl Transpiler {
+    /// Creates a transpiler targeting `basis` with all-to-all connectivity
+    pub fn new(basis: BasisGates) -> Self {
+        Transpiler {
+            basis,
+            coupling_map: None,
+            layout_iterations: 1,
+        }
+    }
+
+    /// Restricts two-qubit gates to the edges of `coupling_map`
+    pub fn with_coupling_map(mut self, coupling_map: CouplingMap) -> Self {
+        self.coupling_map = Some(coupling_map);
+        self
+    }
+
+    /// Sets how many forward-backward routing rounds refine the initial layout
+    pub fn with_layout_iterations(mut self, iterations: usize) -> Self {
+        self.layout_iterations = iterations;
+        self
+    }
+
+    /// Translates `circuit` into the basis and routes it onto the coupling map
+    pub fn transpile(&self, circuit: &Circuit) -> Result<Transpiled, String> {
+        if !circuit.is_bound() {
+            return Err("Bind every parameter before transpiling".to_string());
+        }
+
+        let mut translated = circuit.empty_like();
+        for instruction in circuit.instructions() {
+            for step in self.translate(instruction)? {
+                translated.push(step)?;
+            }
+        }
+        let translated = MergeRotations.run(&CancelInverses.run(&translated)?)?;
+
+        let (routed, initial_layout, final_layout, swaps) = match &self.coupling_map {
+            None => {
+                let identity: Vec<usize> = (0..circuit.n_qubits()).collect();
+                (translated, identity.clone(), identity, 0)
+            }
+            Some(map) => {
+                if map.n_qubits() < circuit.n_qubits() {
+                    return Err(format!(
+                        "The circuit needs {} qubits but the coupling map has {}",
+                        circuit.n_qubits(),
+                        map.n_qubits()
+                    ));
+                }
+                self.route(&translated, map)?
+            }
+        };
+
+        let report = TranspileReport {
+            original_depth: circuit.depth(),
+            depth: routed.depth(),
+            original_gate_count: gate_count(circuit),
+            gate_count: gate_count(&routed),
+            original_two_qubit_gates: two_qubit_count(circuit),
+            two_qubit_gates: two_qubit_count(&routed),
+            swaps,
+        };
+        Ok(Transpiled {
+            circuit: routed,
+            initial_layout,
+            final_layout,
+            report,
+        })
+    }
+
+    /// Basis instructions equivalent to `instruction`, up to a global phase
+    fn translate(&self, instruction: &Instruction) -> Result<Vec<Instruction>, String> {
+        match instruction {
+            Instruction::Measure { .. }
+            | Instruction::Reset { .. }
+            | Instruction::Barrier { .. } => Ok(vec![instruction.clone()]),
+            Instruction::Conditional {
+                clbits,
+                value,
+                instruction,
+            } => Ok(self
+                .translate(instruction)?
+                .into_iter()
+                .map(|inner| Instruction::Conditional {
+                    clbits: clbits.clone(),
+                    value: *value,
+                    instruction: Box::new(inner),
+                })
+                .collect()),
+            _ => Ok(decompose(instruction)?
+                .into_iter()
+                .flat_map(|step| self.emit(step))
+                .collect()),
+        }
+    }
+
+    /// Writes one exact step in the basis gates
+    fn emit(&self, step: Step) -> Vec<Instruction> {
+        let single = |gate: Gate, q: usize| Instruction::Gate {
+            gate,
+            qubits: vec![q],
+        };
+        match step {
+            Step::Cx(control, target) => match self.basis.entangler {
+                Entangler::CX => vec![Instruction::Gate {
+                    gate: Gate::CNOT,
+                    qubits: vec![control, target],
+                }],
+                Entangler::CZ => {
+                    let h = self.emit(Step::One(target, HadamardGate.matrix()));
+                    let cz = Instruction::Gate {
+                        gate: Gate::CZ,
+                        qubits: vec![control, target],
+                    };
+                    h.iter()
+                        .cloned()
+                        .chain([cz])
+                        .chain(h.iter().cloned())
+                        .collect()
+                }
+            },
+            Step::One(q, matrix) => {
+                let (_, theta, phi, lambda) = u3_decomposition(&matrix);
+                let rz = |angle: f64| {
+                    let angle = wrap(angle);
+                    (angle.abs() > EPSILON).then(|| single(Gate::RZ(angle.into()), q))
+                };
+                if theta.abs() < EPSILON {
+                    return match self.basis.single_qubit {
+                        SingleQubitBasis::U3 => {
+                            let angle = wrap(phi + lambda);
+                            (angle.abs() > EPSILON)
+                                .then(|| single(Gate::U3(0.0.into(), 0.0.into(), angle.into()), q))
+                                .into_iter()
+                                .collect()
+                        }
+                        _ => rz(phi + lambda).into_iter().collect(),
+                    };
+                }
+                match self.basis.single_qubit {
+                    SingleQubitBasis::U3 => {
+                        vec![single(Gate::U3(theta.into(), phi.into(), lambda.into()), q)]
+                    }
+                    SingleQubitBasis::RzRy => rz(lambda)
+                        .into_iter()
+                        .chain([single(Gate::RY(theta.into()), q)])
+                        .chain(rz(phi))
+                        .collect(),
+                    SingleQubitBasis::RzSx if (theta - PI / 2.0).abs() < EPSILON => {
+                        rz(lambda - PI / 2.0)
+                            .into_iter()
+                            .chain([single(Gate::SX, q)])
+                            .chain(rz(phi + PI / 2.0))
+                            .collect()
+                    }
+                    SingleQubitBasis::RzSx => rz(lambda)
+                        .into_iter()
+                        .chain([single(Gate::SX, q)])
+                        .chain(rz(theta + PI))
+                        .chain([single(Gate::SX, q)])
+                        .chain(rz(phi + PI))
+                        .collect(),
+                }
+            }
+        }
+    }
+
+    /// Routes a translated circuit, returning it with its initial and final
+    /// layouts and the number of SWAPs inserted
+    fn route(
+        &self,
+        circuit: &Circuit,
+        map: &CouplingMap,
+    ) -> Result<(Circuit, Vec<usize>, Vec<usize>, usize), String> {
+        let n_logical = circuit.n_qubits();
+        let instructions = circuit.instructions();
+
+        // Refine the layout on the two-qubit interactions alone
+        let interactions: Vec<Instruction> = instructions
+            .iter()
+            .filter(|instruction| is_two_qubit(instruction))
+            .cloned()
+            .collect();
+        let reversed: Vec<Instruction> = interactions.iter().rev().cloned().collect();
+        let mut layout: Vec<usize> = (0..map.n_qubits()).collect();
+        for _ in 0..self.layout_iterations {
+            let forward = Router::new(map, layout).run(&interactions, false);
+            let backward = Router::new(map, forward.layout).run(&reversed, false);
+            layout = backward.layout;
+        }
+
+        let initial_layout = layout[..n_logical].to_vec();
+        let routed = Router::new(map, layout).run(instructions, true);
+
+        let mut result = circuit.empty_with_qubits(map.n_qubits());
+        for instruction in routed.output {
+            match instruction {
+                Routed::Swap(a, b) => {
+                    for (control, target) in [(a, b), (b, a), (a, b)] {
+                        for step in self.emit(Step::Cx(control, target)) {
+                            result.push(step)?;
+                        }
+                    }
+                }
+                Routed::Instruction(instruction) => {
+                    result.push(instruction)?;
+                }
+            }
+        }
+        Ok((
+            result,
+            initial_layout,
+            routed.layout[..n_logical].to_vec(),
+            routed.swaps,
+        ))
+    }
+}
+
+const EPSILON: f64 = 1e-10;
+
+/// Maps an angle into (-π, π]
+fn wrap(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(2.0 * PI);
+    if wrapped > PI {
+        wrapped - 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+fn gate_count(circuit: &Circuit) -> usize {
+    circuit
+        .instructions()
+        .iter()
+        .filter(|instruction| instruction.gate().is_some())
+        .count()
+}
+
+fn two_qubit_count(circuit: &Circuit) -> usize {
+    circuit
+        .instructions()
+        .iter()
+        .filter(|instruction| instruction.gate().is_some() && instruction.qubits().len() > 1)
+        .count()
+}
+
+/// True for instructions that need their two qubits to be adjacent
+fn is_two_qubit(instruction: &Instruction) -> bool {
+    instruction.gate().is_some() && instruction.qubits().len() == 2
+}
+
+/// An exact piece of a decomposed instruction
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Step {
+    One(usize, Unitary2),
+    Cx(usize, usize),
+}
+
+fn rz(theta: f64) -> Unitary2 {
+    RZGate::new(theta).matrix()
+}
+
+fn ry(theta: f64) -> Unitary2 {
+    RYGate::new(theta).matrix()
+}
+
+/// Breaks a gate instruction into single-qubit unitaries and CNOTs, exactly
+pub(crate) fn decompose(instruction: &Instruction) -> Result<Vec<Step>, String> {
+    match instruction {
+        Instruction::Gate { gate, qubits } => {
+            if qubits.len() == 1 {
+                return Ok(vec![Step::One(qubits[0], gate.matrix2()?)]);
+            }
+            let (a, b) = (qubits[0], qubits[1]);
+            let h = HadamardGate.matrix();
+            Ok(match gate {
+                Gate::CNOT => vec![Step::Cx(a, b)],
+                Gate::CZ => vec![Step::One(b, h), Step::Cx(a, b), Step::One(b, h)],
+                Gate::Swap => vec![Step::Cx(a, b), Step::Cx(b, a), Step::Cx(a, b)],
+                Gate::ISwap => vec![
+                    Step::One(b, h),
+                    Step::Cx(b, a),
+                    Step::Cx(a, b),
+                    Step::One(a, h),
+                    Step::One(a, Gate::S.matrix2()?),
+                    Step::One(b, Gate::S.matrix2()?),
+                ],
+                Gate::CPhase(phi) => {
+                    let phi = phi.resolve()?;
+                    let half = |sign: f64| PhaseShiftGate::new(sign * phi / 2.0).matrix();
+                    vec![
+                        Step::One(a, half(1.0)),
+                        Step::Cx(a, b),
+                        Step::One(b, half(-1.0)),
+                        Step::Cx(a, b),
+                        Step::One(b, half(1.0)),
+                    ]
+                }
+                Gate::Custom(_) => two_level(&gate.matrix()?, qubits),
+                _ => return Err(format!("Cannot decompose {} gate", gate.name())),
+            })
+        }
+        Instruction::Controlled {
+            gate,
+            control,
+            target,
+        } => Ok(controlled(&[*control], gate.matrix2()?, *target)),
+        Instruction::MultiControlled {
+            gate,
+            controls,
+            control_state,
+            targets,
+        } => {
+            // Negative controls: flip them to |1⟩ around the gate
+            let flips: Vec<Step> = controls
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| control_state & (1 << i) == 0)
+                .map(|(_, &c)| Step::One(c, XGate.matrix()))
+                .collect();
+            let body = Instruction::Gate {
+                gate: gate.clone(),
+                qubits: targets.clone(),
+            };
+            let mut steps = flips.clone();
+            for step in decompose(&body)? {
+                steps.extend(match step {
+                    Step::One(q, matrix) => controlled(controls, matrix, q),
+                    Step::Cx(a, b) => {
+                        let mut with_a = controls.clone();
+                        with_a.push(a);
+                        controlled(&with_a, XGate.matrix(), b)
+                    }
+                });
+            }
+            steps.extend(flips);
+            Ok(steps)
+        }
+        _ => Err("Only gate instructions can be decomposed".to_string()),
+    }
+}
+
+/// Exact steps applying a k-qubit unitary to `qubits` (`qubits[0]` most
+/// significant)
+///
+/// Column by column, the entries below the diagonal are folded into it by
+/// rotations between basis states that differ in one bit, so each rotation
+/// is a single-qubit gate on that bit controlled by every other qubit. This
+/// leaves V·U = D with D diagonal, so U = V† D is applied as D first and the
+/// rotations undone in reverse order.
+fn two_level(matrix: &DMatrix<Complex<f64>>, qubits: &[usize]) -> Vec<Step> {
+    let k = qubits.len();
+    let dim = 1 << k;
+    let mut u = matrix.clone();
+    // (bit, basis state with that bit clear, 2×2 unitary on the pair)
+    let mut rotations: Vec<(usize, usize, Unitary2)> = Vec::new();
+    for column in 0..dim {
+        for bit in 0..k {
+            let mask = 1 << bit;
+            for row in column..dim {
+                // Rows that agree with `column` below `bit` but not at it
+                if (row ^ column) & (mask - 1) != 0 || (row ^ column) & mask == 0 {
+                    continue;
+                }
+                let (keep, fold) = (row ^ mask, row);
+                let (a, b) = (u[(keep, column)], u[(fold, column)]);
+                if b.norm_sqr() < EPSILON * EPSILON {
+                    continue;
+                }
+                let norm = (a.norm_sqr() + b.norm_sqr()).sqrt();
+                // Maps (a, b) to (norm, 0) in the (keep, fold) basis
+                let g = Unitary2::new(a.conj(), b.conj(), -b, a) / Complex::new(norm, 0.0);
+                for j in 0..dim {
+                    let (x, y) = (u[(keep, j)], u[(fold, j)]);
+                    u[(keep, j)] = g[(0, 0)] * x + g[(0, 1)] * y;
+                    u[(fold, j)] = g[(1, 0)] * x + g[(1, 1)] * y;
+                }
+                let (low, ordered) = if keep & mask == 0 {
+                    (keep, g)
+                } else {
+                    (
+                        fold,
+                        Unitary2::new(g[(1, 1)], g[(1, 0)], g[(0, 1)], g[(0, 0)]),
+                    )
+                };
+                rotations.push((bit, low, ordered));
+            }
+        }
+    }
+
+    let one = Complex::new(1.0, 0.0);
+    let mut pieces: Vec<(usize, usize, Unitary2)> = (0..dim / 2)
+        .map(|pair| {
+            let (low, high) = (2 * pair, 2 * pair + 1);
+            let zero = Complex::new(0.0, 0.0);
+            (
+                0,
+                low,
+                Unitary2::new(u[(low, low)], zero, zero, u[(high, high)]),
+            )
+        })
+        .collect();
+    pieces.extend(
+        rotations
+            .into_iter()
+            .rev()
+            .map(|(bit, low, g)| (bit, low, g.adjoint())),
+    );
+
+    let qubit = |bit: usize| qubits[k - 1 - bit];
+    let mut steps = Vec::new();
+    for (bit, low, g) in pieces {
+        if (g - Unitary2::identity() * one).norm() < EPSILON {
+            continue;
+        }
+        let others: Vec<usize> = (0..k).filter(|&b| b != bit).collect();
+        let flips: Vec<Step> = others
+            .iter()
+            .filter(|&&b| low & (1 << b) == 0)
+            .map(|&b| Step::One(qubit(b), XGate.matrix()))
+            .collect();
+        let controls: Vec<usize> = others.iter().map(|&b| qubit(b)).collect();
+        steps.extend(flips.iter().cloned());
+        steps.extend(controlled(&controls, g, qubit(bit)));
+        steps.extend(flips);
+    }
+    steps
+}
+
+/// A square root of a 2×2 unitary
+///
+/// By Cayley–Hamilton, V = (U + sI) / √(tr U + 2s) squares to U for either
+/// s = ±√det U; the sign with the larger denominator is the stable one.
+fn sqrt_unitary(u: &Unitary2) -> Unitary2 {
+    let root = u.determinant().sqrt();
+    let trace = u.trace();
+    let s = if (trace + root * 2.0).norm_sqr() >= (trace - root * 2.0).norm_sqr() {
+        root
+    } else {
+        -root
+    };
+    (u + Unitary2::identity() * s) / (trace + s * 2.0).sqrt()
+}
+
+/// Exact steps applying `u` to `target` when every control is |1⟩
+fn controlled(controls: &[usize], u: Unitary2, target: usize) -> Vec<Step> {
+    match controls {
+        [] => vec![Step::One(target, u)],
+        [control] => {
+            // u = e^{iδ} RZ(φ) RY(θ) RZ(λ) = e^{iδ} A·X·B·X·C with A·B·C = I
+            let (alpha, theta, phi, lambda) = u3_decomposition(&u);
+            let delta = alpha + (phi + lambda) / 2.0;
+            let a = rz(phi) * ry(theta / 2.0);
+            let b = ry(-theta / 2.0) * rz(-(phi + lambda) / 2.0);
+            let c = rz((lambda - phi) / 2.0);
+            vec![
+                Step::One(target, c),
+                Step::Cx(*control, target),
+                Step::One(target, b),
+                Step::Cx(*control, target),
+                Step::One(target, a),
+                Step::One(*control, PhaseShiftGate::new(delta).matrix()),
+            ]
+        }
+        [rest @ .., last] => {
+            // C^n(U) = C^{n-1}(V) · C^{n-1}(X)→last · C(V†) · C^{n-1}(X)→last · C(V)
+            let v = sqrt_unitary(&u);
+            let x = XGate.matrix();
+            let mut steps = controlled(&[*last], v, target);
+            steps.extend(controlled(rest, x, *last));
+            steps.extend(controlled(&[*last], v.adjoint(), target));
+            steps.extend(controlled(rest, x, *last));
+            steps.extend(controlled(rest, v, target));
+            steps
+        }
+    }
+}
+
+/// An instruction of a routed circuit
+enum Routed {
+    Swap(usize, usize),
+    Instruction(Instruction),
+}
+
+struct RouteResult {
+    output: Vec<Routed>,
+    /// Physical qubit of every virtual qubit, ancillas included
+    layout: Vec<usize>,
+    swaps: usize,
+}
+
+/// SABRE routing state
+struct Router<'a> {
+    map: &'a CouplingMap,
+    /// Physical qubit of each virtual qubit; virtual qubits past the circuit
+    /// width are idle ancillas
+    layout: Vec<usize>,
+    decay: Vec<f64>,
+}
+
+const EXTENDED_SET_SIZE: usize = 20;
+const EXTENDED_SET_WEIGHT: f64 = 0.5;
+const DECAY_STEP: f64 = 0.001;
+const DECAY_RESET: usize = 5;
+
+impl<'a> Router<'a> {
+    fn new(map: &'a CouplingMap, layout: Vec<usize>) -> Self {
+        Router {
+            map,
+            decay: vec![1.0; map.n_qubits()],
+            layout,
+        }
+    }
+
+    fn physical(&self, instruction: &Instruction) -> Vec<usize> {
+        instruction
+            .qubits()
+            .iter()
+            .map(|&q| self.layout[q])
+            .collect()
+    }
+
+    fn executable(&self, instruction: &Instruction) -> bool {
+        if !is_two_qubit(instruction) {
+            return true;
+        }
+        let p = self.physical(instruction);
+        self.map.are_adjacent(p[0], p[1])
+    }
+
+    /// Sum of the distances between the qubits of each two-qubit instruction
+    fn cost(&self, instructions: &[&Instruction]) -> f64 {
+        instructions
+            .iter()
+            .map(|instruction| {
+                let p = self.physical(instruction);
+                self.map.distance(p[0], p[1]) as f64
+            })
+            .sum()
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        for physical in self.layout.iter_mut() {
+            if *physical == a {
+                *physical = b;
+            } else if *physical == b {
+                *physical = a;
+            }
+        }
+    }
+
+    /// Routes `instructions`, collecting the routed circuit only if `emit`
+    fn run(mut self, instructions: &[Instruction], emit: bool) -> RouteResult {
+        let n = instructions.len();
+
+        // Dependencies along qubit wires and classical bit wires
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut pending = vec![0; n];
+        let mut last_on_qubit: Vec<Option<usize>> = vec![None; self.layout.len()];
+        let mut last_on_clbit: Vec<Option<usize>> = Vec::new();
+        for (i, instruction) in instructions.iter().enumerate() {
+            let clbits = match instruction {
+                Instruction::Measure { clbit, .. } => vec![*clbit],
+                Instruction::Conditional { clbits, .. } => clbits.clone(),
+                _ => Vec::new(),
+            };
+            let mut predecessors: Vec<usize> = Vec::new();
+            for q in instruction.qubits() {
+                predecessors.extend(last_on_qubit[q].replace(i));
+            }
+            for c in clbits {
+                if last_on_clbit.len() <= c {
+                    last_on_clbit.resize(c + 1, None);
+                }
+                predecessors.extend(last_on_clbit[c].replace(i));
+            }
+            predecessors.sort_unstable();
+            predecessors.dedup();
+            for p in predecessors {
+                successors[p].push(i);
+                pending[i] += 1;
+            }
+        }
+
+        let mut front: Vec<usize> = (0..n).filter(|&i| pending[i] == 0).collect();
+        let mut output = Vec::new();
+        let mut swaps = 0;
+        let mut swaps_since_progress = 0;
+
+        while !front.is_empty() {
+            // Run everything that can run
+            let mut progressed = false;
+            let mut index = 0;
+            while index < front.len() {
+                let i = front[index];
+                if self.executable(&instructions[i]) {
+                    front.swap_remove(index);
+                    progressed = true;
+                    if emit {
+                        let layout = &self.layout;
+                        let mapped = remap(&instructions[i], &|q| layout[q]);
+                        output.push(Routed::Instruction(mapped));
+                    }
+                    for &s in &successors[i] {
+                        pending[s] -= 1;
+                        if pending[s] == 0 {
+                            front.push(s);
+                        }
+                    }
+                    index = 0;
+                } else {
+                    index += 1;
+                }
+            }
+            if progressed {
+                self.decay.fill(1.0);
+                swaps_since_progress = 0;
+            }
+            if front.is_empty() {
+                break;
+            }
+
+            let blocked: Vec<&Instruction> = front.iter().map(|&i| &instructions[i]).collect();
+            let (a, b) = if swaps_since_progress > 2 * self.map.n_qubits() {
+                // Escape a livelock: walk the first blocked gate's qubits together
+                let p = self.physical(blocked[0]);
+                let step = self
+                    .map
+                    .edges()
+                    .iter()
+                    .copied()
+                    .find(|&(x, y)| {
+                        (x == p[0] && self.map.distance(y, p[1]) < self.map.distance(p[0], p[1]))
+                            || (y == p[0]
+                                && self.map.distance(x, p[1]) < self.map.distance(p[0], p[1]))
+                    })
+                    .unwrap();
+                step
+            } else {
+                self.best_swap(&blocked, &successors, instructions, &front)
+            };
+
+            self.swap(a, b);
+            if emit {
+                output.push(Routed::Swap(a, b));
+            }
+            swaps += 1;
+            swaps_since_progress += 1;
+            self.decay[a] += DECAY_STEP;
+            self.decay[b] += DECAY_STEP;
+            if swaps % DECAY_RESET == 0 {
+                self.decay.fill(1.0);
+            }
+        }
+
+        RouteResult {
+            output,
+            layout: self.layout,
+            swaps,
+        }
+    }
+
+    /// The SWAP on an edge next to a blocked gate with the lowest SABRE score
+    fn best_swap(
+        &mut self,
+        blocked: &[&Instruction],
+        successors: &[Vec<usize>],
+        instructions: &[Instruction],
+        front: &[usize],
+    ) -> (usize, usize) {
+        // Look-ahead: the next two-qubit instructions after the front layer
+        let mut extended: Vec<&Instruction> = Vec::new();
+        let mut queue: VecDeque<usize> = front.iter().copied().collect();
+        let mut seen = vec![false; instructions.len()];
+        while let Some(i) = queue.pop_front() {
+            if extended.len() >= EXTENDED_SET_SIZE {
+                break;
+            }
+            for &s in &successors[i] {
+                if !seen[s] {
+                    seen[s] = true;
+                    if is_two_qubit(&instructions[s]) {
+                        extended.push(&instructions[s]);
+                    }
+                    queue.push_back(s);
+                }
+            }
+        }
+
+        let active: Vec<usize> = blocked.iter().flat_map(|i| self.physical(i)).collect();
+        let candidates: Vec<(usize, usize)> = self
+            .map
+            .edges()
+            .iter()
+            .copied()
+            .filter(|(a, b)| active.contains(a) || active.contains(b))
+            .collect();
+
+        let mut best = candidates[0];
+        let mut best_score = f64::INFINITY;
+        for (a, b) in candidates {
+            self.swap(a, b);
+            let mut score = self.cost(blocked) / blocked.len() as f64;
+            if !extended.is_empty() {
+                score += EXTENDED_SET_WEIGHT * self.cost(&extended) / extended.len() as f64;
+            }
+            score *= self.decay[a].max(self.decay[b]);
+            self.swap(a, b);
+            if score < best_score - 1e-12 {
+                best = (a, b);
+                best_score = score;
+            }
+        }
+        best
+    }
+}
+
+/// Returns a copy of `instruction` with every qubit passed through `map`
+pub(crate) fn remap(instruction: &Instruction, map: &dyn Fn(usize) -> usize) -> Instruction {
+    let all = |qubits: &[usize]| qubits.iter().map(|&q| map(q)).collect::<Vec<_>>();
+    match instruction {
+        Instruction::Gate { gate, qubits } => Instruction::Gate {
+            gate: gate.clone(),
+            qubits: all(qubits),
+        },
+        Instruction::Controlled {
+            gate,
+            control,
+            target,
+        } => Instruction::Controlled {
+            gate: gate.clone(),
+            control: map(*control),
+            target: map(*target),
+        },
+        Instruction::MultiControlled {
+            gate,
+            controls,
+            control_state,
+            targets,
+        } => Instruction::MultiControlled {
+            gate: gate.clone(),
+            controls: all(controls),
+            control_state: *control_state,
+            targets: all(targets),
+        },
+        Instruction::Measure { qubit, clbit } => Instruction::Measure {
+            qubit: map(*qubit),
+            clbit: *clbit,
+        },
+        Instruction::Reset { qubit } => Instruction::Reset { qubit: map(*qubit) },
+        Instruction::Barrier { qubits } => Instruction::Barrier {
+            qubits: all(qubits),
+        },
+        Instruction::Conditional {
+            clbits,
+            value,
+            instruction,
+        } => Instruction::Conditional {
+            clbits: clbits.clone(),
+            value: *value,
+            instruction: Box::new(remap(instruction, map)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::circuit::QuantumCircuit;
+    use crate::gates::CustomGate;
+    use crate::ir::equivalent;
+    use crate::parameter::Parameter;
+    use crate::passes::PassManager;
+    use crate::qasm::parse_qasm;
+    use approx::assert_relative_eq;
+    use nalgebra::DMatrix;
+
+    type Unitary = DMatrix<Complex<f64>>;
+
+    /// Columns of the unitary, computed by running `apply` on each basis state
+    fn columns<F: Fn(&mut QuantumCircuit)>(n_qubits: usize, apply: F) -> Unitary {
+        let dim = 1 << n_qubits;
+        let mut matrix = DMatrix::zeros(dim, dim);
+        let mut backend = QuantumCircuit::new(n_qubits).unwrap();
+        for column in 0..dim {
+            let state = backend.state_mut();
+            state.fill(Complex::new(0.0, 0.0));
+            state[column] = Complex::new(1.0, 0.0);
+            apply(&mut backend);
+            matrix.set_column(column, backend.get_state());
+        }
+        matrix
+    }
+
+    /// Checks `b = e^{iα} a` for some global phase α
+    fn assert_equal_up_to_phase(a: &Unitary, b: &Unitary) {
+        let (index, _) = a
+            .iter()
+            .enumerate()
+            .max_by(|x, y| x.1.norm_sqr().total_cmp(&y.1.norm_sqr()))
+            .unwrap();
+        let phase = b[index] / a[index];
+        assert_relative_eq!(phase.norm_sqr(), 1.0, epsilon = 1e-9);
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert_relative_eq!((x * phase - y).norm_sqr(), 0.0, epsilon = 1e-18);
+        }
+    }
+
+    /// A custom gate from a small entangling circuit, with a global phase
+    fn custom_gate(name: &str, n_qubits: usize) -> CustomGate {
+        let mut circuit = Circuit::new(n_qubits).unwrap();
+        for q in 0..n_qubits {
+            circuit.u3(0.3 + q as f64, -0.8 * q as f64, 1.4, q).unwrap();
+            if q > 0 {
+                circuit.cx(q - 1, q).unwrap();
+            }
+        }
+        let matrix = circuit.unitary().unwrap() * Complex::new(0.4_f64.cos(), 0.4_f64.sin());
+        CustomGate::from_matrix(name, matrix).unwrap()
+    }
+
+    /// A circuit using every kind of gate instruction
+    fn sample() -> Circuit {
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .t(1)
+            .unwrap()
+            .sx(2)
+            .unwrap()
+            .u3(0.3, -1.2, 2.5, 3)
+            .unwrap()
+            .cx(0, 2)
+            .unwrap()
+            .cz(1, 3)
+            .unwrap()
+            .swap(0, 3)
+            .unwrap()
+            .iswap(2, 1)
+            .unwrap()
+            .cp(0.7, 3, 1)
+            .unwrap()
+            .controlled(Gate::RY(0.4.into()), 2, 0)
+            .unwrap()
+            .ccx(0, 3, 1)
+            .unwrap()
+            .cswap(1, 2, 3)
+            .unwrap()
+            .multi_controlled_on(
+                Gate::U3(0.2.into(), 0.9.into(), (-0.3).into()),
+                &[0, 1, 2],
+                0b101,
+                &[3],
+            )
+            .unwrap()
+            .custom(custom_gate("three", 3), &[3, 0, 2])
+            .unwrap()
+            .controlled(custom_gate("one", 1).into(), 1, 3)
+            .unwrap()
+            .multi_controlled_on(custom_gate("two", 2).into(), &[2], 0, &[0, 1])
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn test_decompositions_are_exact() {
+        for instruction in sample().instructions() {
+            let n = 4;
+            let expected = columns(n, |backend| {
+                backend.apply(instruction, &mut rand::thread_rng()).unwrap();
+            });
+            let steps = decompose(instruction).unwrap();
+            let actual = columns(n, |backend| {
+                for step in &steps {
+                    match step {
+                        Step::One(q, m) => {
+                            let m = DMatrix::from_iterator(2, 2, m.iter().cloned());
+                            backend.apply_controlled_matrix(&m, &[], 0, &[*q]).unwrap();
+                        }
+                        Step::Cx(c, t) => {
+                            let x = DMatrix::from_iterator(2, 2, XGate.matrix().iter().cloned());
+                            backend
+                                .apply_controlled_matrix(&x, &[*c], 1, &[*t])
+                                .unwrap();
+                        }
+                    }
+                }
+            });
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert_relative_eq!((a - e).norm_sqr(), 0.0, epsilon = 1e-18);
+            }
+        }
+    }
+
+    #[test]
+    fn test_basis_translation() {
+        let circuit = sample();
+        for single_qubit in [
+            SingleQubitBasis::RzSx,
+            SingleQubitBasis::RzRy,
+            SingleQubitBasis::U3,
+        ] {
+            for entangler in [Entangler::CX, Entangler::CZ] {
+                let basis = BasisGates::new(single_qubit, entangler);
+                let transpiled = Transpiler::new(basis).transpile(&circuit).unwrap();
+                for instruction in transpiled.circuit.instructions() {
+                    let name = instruction.gate().unwrap().name();
+                    assert!(basis.names().contains(&name), "{} not in basis", name);
+                    assert!(matches!(instruction, Instruction::Gate { .. }));
+                }
+                assert!(equivalent(&circuit, &transpiled.circuit, 1e-9).unwrap());
+                assert_eq!(transpiled.report.swaps, 0);
+            }
+        }
+    }
+
+    /// Checks a routed circuit against the original, reading logical qubit i
+    /// from physical qubit `initial[i]` at the start and `final_[i]` at the end
+    fn assert_routed_equivalent(original: &Circuit, transpiled: &Transpiled) {
+        let place = |x: usize, layout: &[usize]| {
+            (0..layout.len()).fold(0, |acc, i| acc | (((x >> i) & 1) << layout[i]))
+        };
+        let logical = original.unitary().unwrap();
+        let physical = transpiled.circuit.unitary().unwrap();
+        let dim = 1 << original.n_qubits();
+        let mut expected = DMatrix::zeros(dim, dim);
+        let mut actual = DMatrix::zeros(dim, dim);
+        for x in 0..dim {
+            for y in 0..dim {
+                expected[(y, x)] = logical[(y, x)];
+                actual[(y, x)] = physical[(
+                    place(y, &transpiled.final_layout),
+                    place(x, &transpiled.initial_layout),
+                )];
+            }
+        }
+        assert_equal_up_to_phase(&expected, &actual);
+    }
+
+    #[test]
+    fn test_routing_on_a_line() {
+        let circuit = sample();
+        let map = CouplingMap::line(4).unwrap();
+        let transpiled = Transpiler::new(BasisGates::default())
+            .with_coupling_map(map.clone())
+            .transpile(&circuit)
+            .unwrap();
+
+        for instruction in transpiled.circuit.instructions() {
+            let qubits = instruction.qubits();
+            if qubits.len() == 2 {
+                assert!(map.are_adjacent(qubits[0], qubits[1]));
+            }
+        }
+        assert_routed_equivalent(&circuit, &transpiled);
+        assert!(transpiled.report.swaps > 0);
+        assert!(transpiled.report.depth_overhead() > 1.0);
+        assert!(transpiled.report.to_string().contains("swaps"));
+    }
+
+    #[test]
+    fn test_routing_with_spare_qubits() {
+        // Three logical qubits on a 2×3 grid, interacting all-to-all
+        let mut circuit = Circuit::new(3).unwrap();
+        circuit
+            .h(0)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .cx(1, 2)
+            .unwrap()
+            .cx(2, 0)
+            .unwrap()
+            .ry(0.3, 1)
+            .unwrap()
+            .cz(0, 1)
+            .unwrap();
+        let map = CouplingMap::grid(2, 3).unwrap();
+        let transpiled = Transpiler::new(BasisGates::default())
+            .with_coupling_map(map)
+            .transpile(&circuit)
+            .unwrap();
+        assert_eq!(transpiled.circuit.n_qubits(), 6);
+
+        // Spare qubits start and end in |0⟩, so the logical state can be read back
+        let mut backend = QuantumCircuit::new(6).unwrap();
+        backend.run(&transpiled.circuit).unwrap();
+        let mut reference = QuantumCircuit::new(3).unwrap();
+        reference.run(&circuit).unwrap();
+        let place = |x: usize| {
+            (0..3).fold(0, |acc, i| {
+                acc | (((x >> i) & 1) << transpiled.final_layout[i])
+            })
+        };
+        for x in 0..8 {
+            assert_relative_eq!(
+                backend.get_probability(place(x)).unwrap(),
+                reference.get_probability(x).unwrap(),
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    #[test]
+    fn test_measurements_follow_their_qubits() {
+        let mut circuit = Circuit::new(4).unwrap();
+        circuit
+            .x(0)
+            .unwrap()
+            .cx(0, 3)
+            .unwrap()
+            .measure_all()
+            .unwrap();
+        let transpiled = Transpiler::new(BasisGates::default())
+            .with_coupling_map(CouplingMap::line(4).unwrap())
+            .transpile(&circuit)
+            .unwrap();
+        let counts = QuantumCircuit::new(4)
+            .unwrap()
+            .run_shots(&transpiled.circuit, 20, 3)
+            .unwrap();
+        assert_eq!(counts.get("1001"), Some(&20));
+    }
+
+    #[test]
+    fn test_registers_survive_passes_and_transpiling() {
+        let source = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[3];
+            creg a[1];
+            creg b[2];
+            h q[0];
+            h q[0];
+            x q[0];
+            measure q[0] -> a[0];
+            if (a == 1) cx q[2], q[0];
+            measure q[0] -> b[0];
+            measure q[2] -> b[1];
+        "#;
+        let circuit = parse_qasm(source).unwrap();
+        let names = |c: &Circuit| -> Vec<String> {
+            c.registers().iter().map(|r| r.name().to_string()).collect()
+        };
+        assert_eq!(names(&circuit), ["a", "b"]);
+
+        let optimized = PassManager::standard().run(&circuit).unwrap();
+        let transpiled = Transpiler::new(BasisGates::default())
+            .with_coupling_map(CouplingMap::line(4).unwrap())
+            .transpile(&circuit)
+            .unwrap()
+            .circuit;
+        for rewritten in [optimized, transpiled] {
+            assert_eq!(rewritten.registers(), circuit.registers());
+            let text = rewritten.to_qasm2().unwrap();
+            assert!(text.contains("if (a == 1)"));
+            assert_eq!(names(&parse_qasm(&text).unwrap()), ["a", "b"]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_transpiles() {
+        let mut symbolic = Circuit::new(2).unwrap();
+        symbolic.rx(Parameter::new("a"), 0).unwrap();
+        let transpiler = Transpiler::new(BasisGates::default());
+        assert!(transpiler.transpile(&symbolic).is_err());
+
+        let small = transpiler.with_coupling_map(CouplingMap::line(2).unwrap());
+        assert!(small.transpile(&Circuit::new(3).unwrap()).is_err());
+
+        assert!(CouplingMap::new(3, &[(0, 1)]).is_err());
+        assert!(CouplingMap::new(2, &[(0, 0)]).is_err());
+        assert!(CouplingMap::new(2, &[(0, 2)]).is_err());
+        assert_eq!(CouplingMap::ring(4).unwrap().distance(0, 3), 1);
+        assert_eq!(CouplingMap::grid(2, 3).unwrap().distance(0, 5), 3);
+    }
+}
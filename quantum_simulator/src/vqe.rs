@@ -0,0 +1,201 @@
+/*
+This file implements the variational quantum eigensolver (VQE).
+
+Key concepts:
+1. Variational Principle:
+   - For any state |ψ(θ)⟩, ⟨ψ(θ)|H|ψ(θ)⟩ ≥ E_0, the ground-state energy
+   - Minimizing over the parameters of an ansatz circuit approaches E_0 from
+     above
+   - Learn more: https://en.wikipedia.org/wiki/Variational_quantum_eigensolver
+
+2. Hybrid Loop:
+   - The simulator evaluates ⟨H⟩ (and its gradient) for given parameters
+   - A classical `Optimizer` from optimizer.rs picks the next parameters
+
+3. Gradients:
+   - `GradientMethod::ParameterShift` mirrors what hardware can do
+   - `GradientMethod::Adjoint` is much faster on the state-vector simulator
+   - Reference: https://arxiv.org/abs/1304.3061
+*/
+
+use crate::gradient::{adjoint_gradient, expectation_value, parameter_shift_gradient};
+use crate::ir::Circuit;
+use crate::observable::PauliSum;
+use crate::optimizer::{Objective, Optimizer};
+
+/// How `Vqe` differentiates the energy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientMethod {
+    ParameterShift,
+    Adjoint,
+}
+
+/// Outcome of a VQE run
+#[derive(Debug, Clone, PartialEq)]
+pub struct VqeResult {
+    /// Lowest energy found
+    pub energy: f64,
+    /// Optimal value of each ansatz parameter, by name
+    pub parameters: Vec<(String, f64)>,
+    pub iterations: usize,
+    /// Energy after each optimizer iteration
+    pub history: Vec<f64>,
+}
+
+/// Minimizes ⟨H⟩ over the parameters of an ansatz circuit
+pub struct Vqe<O: Optimizer> {
+    ansatz: Circuit,
+    hamiltonian: PauliSum,
+    optimizer: O,
+    gradient: GradientMethod,
+}
+
+/// ⟨H⟩ as a function of the ansatz parameters
+struct Energy<'a> {
+    ansatz: &'a Circuit,
+    hamiltonian: &'a PauliSum,
+    gradient: GradientMethod,
+}
+
+impl Objective for Energy<'_> {
+    fn value(&mut self, x: &[f64]) -> Result<f64, String> {
+        expectation_value(self.ansatz, self.hamiltonian, x)
+    }
+
+    fn gradient(&mut self, x: &[f64]) -> Result<Vec<f64>, String> {
+        match self.gradient {
+            GradientMethod::ParameterShift => {
+                parameter_shift_gradient(self.ansatz, self.hamiltonian, x)
+            }
+            GradientMethod::Adjoint => adjoint_gradient(self.ansatz, self.hamiltonian, x),
+        }
+    }
+}
+
+impl<O: Optimizer> Vqe<O> {
+    /// Creates a VQE for `hamiltonian` over the parameters of `ansatz`,
+    /// differentiating with the adjoint method
+    pub fn new(ansatz: Circuit, hamiltonian: PauliSum, optimizer: O) -> Result<Self, String> {
+        if hamiltonian.min_qubits() > ansatz.n_qubits() {
+            return Err(format!(
+                "Hamiltonian acts on {} qubits but the ansatz has {}",
+                hamiltonian.min_qubits(),
+                ansatz.n_qubits()
+            ));
+        }
+        if ansatz.parameters().is_empty() {
+            return Err("The ansatz has no parameters to optimize".to_string());
+        }
+
+        Ok(Vqe {
+            ansatz,
+            hamiltonian,
+            optimizer,
+            gradient: GradientMethod::Adjoint,
+        })
+    }
+
+    /// Chooses how gradients are computed for gradient-based optimizers
+    pub fn with_gradient(mut self, gradient: GradientMethod) -> Self {
+        self.gradient = gradient;
+        self
+    }
+
+    /// Runs the optimizer from `initial`, ordered like `Circuit::parameters()`
+    pub fn run(&mut self, initial: &[f64]) -> Result<VqeResult, String> {
+        let mut energy = Energy {
+            ansatz: &self.ansatz,
+            hamiltonian: &self.hamiltonian,
+            gradient: self.gradient,
+        };
+        let result = self.optimizer.minimize(&mut energy, initial)?;
+
+        let parameters = self
+            .ansatz
+            .parameters()
+            .iter()
+            .map(|p| p.name().to_string())
+            .zip(result.parameters)
+            .collect();
+        Ok(VqeResult {
+            energy: result.value,
+            parameters,
+            iterations: result.iterations,
+            history: result.history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{Adam, GradientDescent, NelderMead, Spsa};
+    use crate::parameter::Parameter;
+    use approx::assert_relative_eq;
+
+    /// H = Z0Z1 + 0.5 X0 + 0.5 X1 (transverse-field Ising on two sites)
+    fn hamiltonian() -> PauliSum {
+        "Z0Z1 + 0.5*X0 + 0.5*X1".parse().unwrap()
+    }
+
+    /// Ground energy of `hamiltonian()`: -√(1 + 1) = -√2
+    fn ground_energy() -> f64 {
+        -(2.0_f64.sqrt())
+    }
+
+    fn ansatz() -> Circuit {
+        let mut circuit = Circuit::new(2).unwrap();
+        circuit
+            .ry(Parameter::new("a"), 0)
+            .unwrap()
+            .ry(Parameter::new("b"), 1)
+            .unwrap()
+            .cx(0, 1)
+            .unwrap()
+            .ry(Parameter::new("c"), 0)
+            .unwrap()
+            .ry(Parameter::new("d"), 1)
+            .unwrap();
+        circuit
+    }
+
+    const START: [f64; 4] = [0.1, -0.2, 0.3, 0.4];
+
+    #[test]
+    fn test_vqe_with_gradient_descent() {
+        let mut vqe = Vqe::new(ansatz(), hamiltonian(), GradientDescent::new(0.2, 500))
+            .unwrap()
+            .with_gradient(GradientMethod::ParameterShift);
+        let result = vqe.run(&START).unwrap();
+        assert_relative_eq!(result.energy, ground_energy(), epsilon = 1e-4);
+        assert_eq!(result.parameters[0].0, "a");
+    }
+
+    #[test]
+    fn test_vqe_with_adam() {
+        let mut vqe = Vqe::new(ansatz(), hamiltonian(), Adam::new(0.05, 1000)).unwrap();
+        let result = vqe.run(&START).unwrap();
+        assert_relative_eq!(result.energy, ground_energy(), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_vqe_with_nelder_mead() {
+        let mut vqe = Vqe::new(ansatz(), hamiltonian(), NelderMead::new(2000)).unwrap();
+        let result = vqe.run(&START).unwrap();
+        assert_relative_eq!(result.energy, ground_energy(), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_vqe_with_spsa() {
+        let mut vqe = Vqe::new(ansatz(), hamiltonian(), Spsa::new(0.3, 0.1, 1500, 1)).unwrap();
+        let result = vqe.run(&START).unwrap();
+        assert_relative_eq!(result.energy, ground_energy(), epsilon = 0.02);
+    }
+
+    #[test]
+    fn test_invalid_vqe() {
+        let wide: PauliSum = "Z3".parse().unwrap();
+        assert!(Vqe::new(ansatz(), wide, NelderMead::new(10)).is_err());
+        assert!(Vqe::new(Circuit::new(2).unwrap(), hamiltonian(), NelderMead::new(10)).is_err());
+    }
+}